@@ -1,5 +1,5 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use data_transform::{parse, Executor};
+use data_transform::parse;
 
 fn benchmark_parse(c: &mut Criterion) {
     c.bench_function("parse simple pipeline", |b| {
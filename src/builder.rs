@@ -0,0 +1,298 @@
+//! Build pipelines in Rust instead of DSL text. The builder produces the same
+//! AST the parser does, so the executor runs both the same way.
+//!
+//! ```no_run
+//! use data_transform::{col, Pipeline};
+//!
+//! let df = Pipeline::read("sales.csv")
+//!     .filter(col("amount").gt(100).and(col("region").eq("eu")))
+//!     .mutate("total", col("amount") * col("quantity"))
+//!     .select(["id", "total"])
+//!     .execute()?;
+//! # Ok::<(), data_transform::DtransformError>(())
+//! ```
+
+use polars::prelude::DataFrame;
+use std::ops::{Add, Div, Mul, Neg, Rem, Sub};
+
+use crate::error::Result;
+use crate::executor::Executor;
+use crate::parser::ast::*;
+
+/// A named column: `col("amount")`
+pub fn col(name: impl Into<String>) -> Expression {
+    Expression::Column(ColumnRef::Name(name.into()))
+}
+
+/// A positional column, 1-based like `$1`: `nth(1)`
+pub fn nth(position: usize) -> Expression {
+    Expression::Column(ColumnRef::Position(position))
+}
+
+/// A literal value: `lit(5)`, `lit("eu")`, `lit(true)`
+pub fn lit(value: impl Into<Literal>) -> Expression {
+    Expression::Literal(value.into())
+}
+
+/// A built-in function call: `call("round", [col("price"), lit(2)])`
+pub fn call(name: impl Into<String>, args: impl IntoIterator<Item = Expression>) -> Expression {
+    Expression::FunctionCall {
+        name: name.into(),
+        args: args.into_iter().collect(),
+    }
+}
+
+macro_rules! literal_from {
+    ($($type:ty => |$value:ident| $literal:expr;)*) => {$(
+        impl From<$type> for Literal {
+            fn from($value: $type) -> Self {
+                $literal
+            }
+        }
+
+        /// Plain values are literals, so `col("a").gt(5)` compares with the number 5
+        impl From<$type> for Expression {
+            fn from(value: $type) -> Self {
+                Expression::Literal(value.into())
+            }
+        }
+    )*};
+}
+
+literal_from! {
+    f64 => |value| Literal::Number(value);
+    i64 => |value| Literal::Number(value as f64);
+    i32 => |value| Literal::Number(value as f64);
+    bool => |value| Literal::Boolean(value);
+    &str => |value| Literal::String(value.to_string());
+    String => |value| Literal::String(value);
+}
+
+impl Expression {
+    fn binary(self, op: BinOp, right: impl Into<Expression>) -> Expression {
+        Expression::BinaryOp {
+            left: Box::new(self),
+            op,
+            right: Box::new(right.into()),
+        }
+    }
+
+    pub fn gt(self, other: impl Into<Expression>) -> Expression {
+        self.binary(BinOp::Gt, other)
+    }
+
+    pub fn lt(self, other: impl Into<Expression>) -> Expression {
+        self.binary(BinOp::Lt, other)
+    }
+
+    pub fn gt_eq(self, other: impl Into<Expression>) -> Expression {
+        self.binary(BinOp::Gte, other)
+    }
+
+    pub fn lt_eq(self, other: impl Into<Expression>) -> Expression {
+        self.binary(BinOp::Lte, other)
+    }
+
+    pub fn eq(self, other: impl Into<Expression>) -> Expression {
+        self.binary(BinOp::Eq, other)
+    }
+
+    pub fn neq(self, other: impl Into<Expression>) -> Expression {
+        self.binary(BinOp::Neq, other)
+    }
+
+    /// Null-aware equality, like `a is b`
+    pub fn is(self, other: impl Into<Expression>) -> Expression {
+        self.binary(BinOp::Is, other)
+    }
+
+    pub fn is_null(self) -> Expression {
+        self.binary(BinOp::Is, Expression::Literal(Literal::Null))
+    }
+
+    pub fn and(self, other: impl Into<Expression>) -> Expression {
+        self.binary(BinOp::And, other)
+    }
+
+    pub fn or(self, other: impl Into<Expression>) -> Expression {
+        self.binary(BinOp::Or, other)
+    }
+
+    pub fn pow(self, exponent: impl Into<Expression>) -> Expression {
+        self.binary(BinOp::Pow, exponent)
+    }
+
+    /// Membership in a list of values, like `a in ['x', 'y']`
+    pub fn is_in<T: Into<Literal>>(self, values: impl IntoIterator<Item = T>) -> Expression {
+        self.binary(BinOp::In, Expression::List(values.into_iter().map(Into::into).collect()))
+    }
+}
+
+macro_rules! arithmetic {
+    ($($trait:ident, $method:ident, $op:expr;)*) => {$(
+        impl<T: Into<Expression>> $trait<T> for Expression {
+            type Output = Expression;
+
+            fn $method(self, other: T) -> Expression {
+                self.binary($op, other)
+            }
+        }
+    )*};
+}
+
+arithmetic! {
+    Add, add, BinOp::Add;
+    Sub, sub, BinOp::Sub;
+    Mul, mul, BinOp::Mul;
+    Div, div, BinOp::Div;
+    Rem, rem, BinOp::Mod;
+}
+
+impl Neg for Expression {
+    type Output = Expression;
+
+    fn neg(self) -> Expression {
+        Expression::Negate(Box::new(self))
+    }
+}
+
+impl ReadOp {
+    /// read('path') with every option left to auto-detection
+    pub fn new(path: impl Into<String>) -> Self {
+        ReadOp {
+            path: path.into(),
+            format: None,
+            delimiter: None,
+            header: None,
+            skip_rows: None,
+            trim_whitespace: None,
+            include_filename: None,
+            quote_char: None,
+            comment: None,
+            null_values: Vec::new(),
+            encoding: None,
+            compression: None,
+            headers: Vec::new(),
+            timeout: None,
+            table: None,
+            query: None,
+            schema: None,
+        }
+    }
+}
+
+impl WriteOp {
+    /// write('path') with the format taken from the extension
+    pub fn new(path: impl Into<String>) -> Self {
+        WriteOp {
+            path: path.into(),
+            format: None,
+            header: None,
+            delimiter: None,
+            orient: None,
+            pretty: None,
+            formats: Vec::new(),
+            chunk_size: None,
+            flush: None,
+            progress: None,
+            compression: None,
+            table: None,
+            mode: None,
+            schema: None,
+        }
+    }
+}
+
+impl Pipeline {
+    /// A pipeline starting with `read(path)`; use [`Pipeline::read_with`] for read options
+    pub fn read(path: impl Into<String>) -> Self {
+        Self::read_with(ReadOp::new(path))
+    }
+
+    pub fn read_with(op: ReadOp) -> Self {
+        Pipeline {
+            source: Some(Source::Read(Box::new(op))),
+            operations: Vec::new(),
+        }
+    }
+
+    /// A pipeline starting from a variable stored in the executor
+    pub fn from_variable(name: impl Into<String>) -> Self {
+        Pipeline {
+            source: Some(Source::Variable(name.into())),
+            operations: Vec::new(),
+        }
+    }
+
+    /// Append any operation, for those without a shortcut below
+    pub fn then(mut self, op: Operation) -> Self {
+        self.operations.push(op);
+        self
+    }
+
+    pub fn filter(self, condition: Expression) -> Self {
+        self.then(Operation::Filter(FilterOp { condition }))
+    }
+
+    pub fn select<S: Into<String>>(self, columns: impl IntoIterator<Item = S>) -> Self {
+        let selectors = columns
+            .into_iter()
+            .map(|name| (ColumnSelector::Name(name.into()), None))
+            .collect();
+        self.then(Operation::Select(SelectOp { selectors }))
+    }
+
+    /// Add or replace one column
+    pub fn mutate(self, column: impl Into<String>, expression: impl Into<Expression>) -> Self {
+        self.then(Operation::Mutate(MutateOp {
+            assignments: vec![Assignment {
+                column: AssignmentTarget::Name(column.into()),
+                expression: expression.into(),
+            }],
+        }))
+    }
+
+    /// Sort by one column; chain `then(Operation::Sort(..))` for several
+    pub fn sort(self, column: impl Into<String>, descending: bool) -> Self {
+        self.then(Operation::Sort(SortOp {
+            columns: vec![SortColumn {
+                column: ColumnRef::Name(column.into()),
+                descending,
+                nulls_last: false,
+            }],
+            limit: None,
+            stable: true,
+            multithreaded: true,
+        }))
+    }
+
+    pub fn take(self, n: usize) -> Self {
+        self.then(Operation::Take(TakeOp { n }))
+    }
+
+    pub fn distinct(self) -> Self {
+        self.then(Operation::Distinct(DistinctOp { columns: None }))
+    }
+
+    pub fn write(self, path: impl Into<String>) -> Self {
+        self.then(Operation::Write(WriteOp::new(path)))
+    }
+
+    /// Run on a fresh executor
+    pub fn execute(self) -> Result<DataFrame> {
+        Executor::new().execute_pipeline(self)
+    }
+
+    /// Run on `executor`, which can hold variables and settings (params, verbose)
+    pub fn execute_with(self, executor: &mut Executor) -> Result<DataFrame> {
+        executor.execute_pipeline(self)
+    }
+}
+
+impl From<Pipeline> for Program {
+    fn from(pipeline: Pipeline) -> Self {
+        Program {
+            statements: vec![Statement::Pipeline(pipeline)],
+        }
+    }
+}
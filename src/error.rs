@@ -31,11 +31,26 @@ pub enum DtransformError {
 
     #[error("Readline error: {0}")]
     ReadlineError(String),
+
+    #[error("Expectation failed: {0}")]
+    ExpectationFailed(String),
 }
 
 pub type Result<T> = std::result::Result<T, DtransformError>;
 
 impl DtransformError {
+    /// Whether the error is a write to a pipe whose reader has gone, such as
+    /// `dt ... -o - | head`
+    pub fn is_broken_pipe(&self) -> bool {
+        match self {
+            DtransformError::IoError(e) => e.kind() == std::io::ErrorKind::BrokenPipe,
+            DtransformError::PolarsError(polars::error::PolarsError::IO { error, .. }) => {
+                error.kind() == std::io::ErrorKind::BrokenPipe
+            }
+            _ => false,
+        }
+    }
+
     pub fn display_friendly(&self) -> String {
         match self {
             DtransformError::ColumnNotFound(col) => {
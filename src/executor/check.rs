@@ -0,0 +1,149 @@
+use polars::prelude::*;
+use std::collections::HashMap;
+
+use super::{operation_name, Executor, PeekRows};
+use crate::error::{DtransformError, Result};
+use crate::parser::ast::*;
+
+/// Rows read from a stand-in to infer its column types
+const STAND_IN_ROWS: usize = 100;
+
+/// Files read in place of a program's inputs (dt check --against). Only the
+/// first rows are read, with the options of the read() they stand in for.
+#[derive(Debug, Clone, Default)]
+pub struct StandIns {
+    /// Stand-in by the path as written in read()
+    pub by_path: HashMap<String, String>,
+    /// Stand-in for every other read(); without one those read their own file
+    pub fallback: Option<String>,
+}
+
+/// A statement that would fail on data shaped like its stand-ins
+#[derive(Debug, Clone)]
+pub struct CheckIssue {
+    /// 1-based statement number within the program
+    pub statement: usize,
+    /// The stage that failed, named as in --explain: `read 'sales.csv'`, `filter`
+    pub stage: String,
+    pub message: String,
+}
+
+impl Executor {
+    /// Tables to check a program with: every read() takes the first rows of its stand-in
+    pub fn set_stand_ins(&mut self, stand_ins: StandIns) {
+        self.stand_ins = Some(stand_ins);
+    }
+
+    /// The empty table a read() gives while checking
+    pub(super) fn read_stand_in(&self, stand_ins: &StandIns, op: ReadOp) -> Result<DataFrame> {
+        let stand_in = match stand_ins.by_path.get(&op.path).or(stand_ins.fallback.as_ref()) {
+            Some(path) => ReadOp { path: path.clone(), ..op.clone() },
+            None if std::path::Path::new(&op.path).is_file() => op.clone(),
+            // Globs, URLs, databases and stdin would have to be read in full
+            None => {
+                return Err(DtransformError::InvalidOperation(format!(
+                    "no stand-in for '{}'; pass --against {}=FILE",
+                    op.path, op.path
+                )))
+            }
+        };
+        let (df, _) = self.peek_read(&stand_in, PeekRows::Head(STAND_IN_ROWS))?;
+        let df = if op.include_filename == Some(true) {
+            self.with_filename_column(df, &op.path)?
+        } else {
+            df
+        };
+        Ok(df.clear())
+    }
+
+    /// Run a program on empty tables, to find what would fail on real data before
+    /// any is read: unknown columns and variables, and operations that don't suit
+    /// a column's type. read() gives the columns of its stand-in (set_stand_ins),
+    /// nothing is written and expect() is skipped. Statements using a variable
+    /// whose statement failed are skipped. Variables are left as they were.
+    pub fn check_program(&mut self, program: &Program) -> Vec<CheckIssue> {
+        let saved = self.get_all_variables();
+        let mut issues = Vec::new();
+        // Tables assigned so far, as empty tables; None where the statement failed
+        let mut assigned: HashMap<String, Option<DataFrame>> = HashMap::new();
+
+        for (index, statement) in program.statements.iter().enumerate() {
+            let (name, pipeline) = match statement {
+                Statement::Assignment { name, pipeline } => (Some(name), pipeline),
+                Statement::Pipeline(pipeline) => (None, pipeline),
+            };
+            let df = match self.check_pipeline(pipeline, &assigned) {
+                Ok(df) => df,
+                Err((stage, error)) => {
+                    issues.push(CheckIssue { statement: index + 1, stage, message: error.to_string() });
+                    None
+                }
+            };
+
+            if let Some(name) = name {
+                // Operations such as lookup() and append() use the variables
+                match &df {
+                    Some(df) => self.set_variable(name.clone(), df.clone()),
+                    None => self.remove_variable(name),
+                }
+                assigned.insert(name.clone(), df);
+            }
+        }
+
+        self.restore_variables(saved);
+        issues
+    }
+
+    /// The empty table a pipeline leaves, None if it uses a failed variable, or
+    /// the stage that failed and why
+    fn check_pipeline(
+        &mut self,
+        pipeline: &Pipeline,
+        assigned: &HashMap<String, Option<DataFrame>>,
+    ) -> std::result::Result<Option<DataFrame>, (String, DtransformError)> {
+        self.current_file = match &pipeline.source {
+            Some(Source::Read(read_op)) => super::read_file_name(read_op),
+            _ => None,
+        };
+        let mut df = match &pipeline.source {
+            Some(Source::Read(read_op)) => self
+                .execute_read((**read_op).clone())
+                .map_err(|e| (format!("read '{}'", read_op.path), e))?,
+            Some(Source::Variable(var_name)) => match assigned.get(var_name) {
+                Some(Some(df)) => df.clone(),
+                Some(None) => return Ok(None),
+                None => self
+                    .get_variable(var_name)
+                    .map(|df| df.clear())
+                    .ok_or_else(|| (var_name.clone(), DtransformError::VariableNotFound(var_name.clone())))?,
+            },
+            Some(Source::Concat(concat_op)) => self
+                .execute_concat(None, concat_op.clone())
+                .map_err(|e| ("concat".to_string(), e))?,
+            None => {
+                return Err((
+                    "pipeline".to_string(),
+                    DtransformError::InvalidOperation(
+                        "Pipeline must start with a data source (read() or variable)".to_string(),
+                    ),
+                ))
+            }
+        };
+
+        for op in &pipeline.operations {
+            df = match op {
+                // Nothing to check or write without the data
+                Operation::Write(_) | Operation::Expect(_) => df,
+                op => {
+                    let stage = match op {
+                        Operation::Read(read_op) => format!("read '{}'", read_op.path),
+                        op => operation_name(op).to_string(),
+                    };
+                    self.execute_operation(df, op.clone()).map_err(|e| (stage, e))?
+                }
+            };
+        }
+
+        Ok(Some(df))
+    }
+}
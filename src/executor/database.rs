@@ -0,0 +1,431 @@
+use polars::prelude::*;
+
+use super::Executor;
+use crate::error::{DtransformError, Result};
+use crate::parser::ast::{BinOp, ColumnRef, ColumnSelector, Expression, Literal, Operation, ReadOp, WriteOp};
+
+/// One value of a query result
+#[derive(Debug, Clone)]
+pub(super) enum Value {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Text(String),
+}
+
+/// A column to write, typed like the table column it becomes
+pub(super) enum SqlColumn {
+    Integer(Vec<Option<i64>>),
+    Real(Vec<Option<f64>>),
+    Text(Vec<Option<String>>),
+}
+
+/// What write() does with a table that already exists
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum WriteMode {
+    Replace,
+    Append,
+    Fail,
+}
+
+/// A database read with read(..., table= | query=) and written with write(..., table=).
+/// SQLite is always available; PostgreSQL and MySQL with the `postgres` and `mysql` features.
+pub(super) trait Database {
+    /// Tables read() can pick from when given neither table= nor query=
+    fn tables(&mut self) -> Result<Vec<String>>;
+
+    /// Column names and values, column by column, of a query's result
+    fn query(&mut self, sql: &str) -> Result<(Vec<String>, Vec<Vec<Value>>)>;
+
+    /// Create, replace or add to `table`, in one transaction where the database allows
+    fn write_table(&mut self, table: &str, mode: WriteMode, columns: &[(String, SqlColumn)]) -> Result<()>;
+
+    /// A table or column name as an SQL identifier
+    fn quote(&self, name: &str) -> String {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    }
+}
+
+/// `schema.table` quoted part by part
+pub(super) fn quote_table(db: &dyn Database, table: &str) -> String {
+    table.split('.').map(|part| db.quote(part)).collect::<Vec<_>>().join(".")
+}
+
+/// Rows in the columns to write
+pub(super) fn row_count(columns: &[(String, SqlColumn)]) -> usize {
+    match columns.first() {
+        Some((_, SqlColumn::Integer(values))) => values.len(),
+        Some((_, SqlColumn::Real(values))) => values.len(),
+        Some((_, SqlColumn::Text(values))) => values.len(),
+        None => 0,
+    }
+}
+
+pub(super) fn table_exists_error(table: &str) -> DtransformError {
+    DtransformError::InvalidOperation(format!("Table '{}' already exists (mode='fail')", table))
+}
+
+/// Whether a read()/write() path is a database server rather than a file
+pub(super) fn is_connection_string(path: &str) -> bool {
+    let lower = path.to_ascii_lowercase();
+    ["postgres://", "postgresql://", "mysql://"].iter().any(|scheme| lower.starts_with(scheme))
+}
+
+/// A connection string without its password, for messages and run manifests
+pub(super) fn redact(path: &str) -> String {
+    if !is_connection_string(path) {
+        return path.to_string();
+    }
+    let (scheme, rest) = match path.split_once("://") {
+        Some(parts) => parts,
+        None => return path.to_string(),
+    };
+    let authority = rest.split('/').next().unwrap_or(rest);
+    match authority.rsplit_once('@') {
+        Some((user, host)) => {
+            let user = user.split(':').next().unwrap_or(user);
+            format!("{}://{}@{}{}", scheme, user, host, &rest[authority.len()..])
+        }
+        None => path.to_string(),
+    }
+}
+
+/// The select() and filter() steps at the start of a pipeline, run by the database
+/// as part of the read so only the rows and columns kept come over the wire
+#[derive(Debug, Clone, Default)]
+pub(super) struct Pushdown {
+    /// Column and alias of each column of a select() of plain names
+    columns: Option<Vec<(String, Option<String>)>>,
+    /// filter() conditions, on the names the columns have in the database
+    conditions: Vec<Expression>,
+}
+
+impl Pushdown {
+    /// The leading steps of `operations` the database behind `path` can run, and
+    /// how many steps that is. Stops at the first step it can't translate.
+    pub(super) fn plan(path: &str, operations: &[Operation]) -> (Pushdown, usize) {
+        // MySQL compares text ignoring case by default, unlike dt
+        let text = !path.to_ascii_lowercase().starts_with("mysql://");
+        let mut pushdown = Pushdown::default();
+        let mut taken = 0;
+
+        for operation in operations {
+            match operation {
+                Operation::Filter(filter) => {
+                    let Some(condition) = pushdown.unaliased(&filter.condition) else {
+                        break;
+                    };
+                    if sql_condition(&condition, &|name| name.to_string(), text).is_none() {
+                        break;
+                    }
+                    pushdown.conditions.push(condition);
+                }
+                Operation::Select(select) if pushdown.columns.is_none() => {
+                    let columns: Option<Vec<_>> = select
+                        .selectors
+                        .iter()
+                        .map(|(selector, alias)| match selector {
+                            ColumnSelector::Name(name) => Some((name.clone(), alias.clone())),
+                            _ => None,
+                        })
+                        .collect();
+                    match columns {
+                        Some(columns) => pushdown.columns = Some(columns),
+                        None => break,
+                    }
+                }
+                _ => break,
+            }
+            taken += 1;
+        }
+
+        (pushdown, taken)
+    }
+
+    /// `expr` with select() aliases replaced by the columns they name; None when it
+    /// uses a column the select() left out, so dt reports it
+    fn unaliased(&self, expr: &Expression) -> Option<Expression> {
+        match expr {
+            Expression::Column(ColumnRef::Name(name)) => match &self.columns {
+                None => Some(expr.clone()),
+                Some(columns) => columns
+                    .iter()
+                    .find(|(column, alias)| alias.as_ref().unwrap_or(column) == name)
+                    .map(|(column, _)| Expression::Column(ColumnRef::Name(column.clone()))),
+            },
+            Expression::BinaryOp { left, op, right } => Some(Expression::BinaryOp {
+                left: Box::new(self.unaliased(left)?),
+                op: op.clone(),
+                right: Box::new(self.unaliased(right)?),
+            }),
+            Expression::Literal(_) | Expression::List(_) => Some(expr.clone()),
+            _ => None,
+        }
+    }
+
+    /// The query reading `from` (a quoted table, or a parenthesized query) with
+    /// the columns and conditions applied
+    fn query(&self, db: &dyn Database, from: &str) -> String {
+        let columns = match &self.columns {
+            Some(columns) => columns
+                .iter()
+                .map(|(column, alias)| match alias {
+                    Some(alias) => format!("{} AS {}", db.quote(column), db.quote(alias)),
+                    None => db.quote(column),
+                })
+                .collect::<Vec<_>>()
+                .join(", "),
+            None => "*".to_string(),
+        };
+        let conditions: Vec<String> = self
+            .conditions
+            .iter()
+            .filter_map(|condition| sql_condition(condition, &|name| db.quote(name), true))
+            .collect();
+
+        let mut query = format!("SELECT {} FROM {}", columns, from);
+        if !conditions.is_empty() {
+            query.push_str(" WHERE ");
+            query.push_str(&conditions.join(" AND "));
+        }
+        query
+    }
+}
+
+/// A filter() condition as SQL: comparisons of a column with a number (or text,
+/// when `text`), `in` lists, `is null` tests, and `and` / `or` of those.
+/// None for anything else, which dt evaluates itself.
+fn sql_condition(expr: &Expression, quote: &dyn Fn(&str) -> String, text: bool) -> Option<String> {
+    let Expression::BinaryOp { left, op, right } = expr else {
+        return None;
+    };
+    if matches!(op, BinOp::And | BinOp::Or) {
+        let joiner = if *op == BinOp::And { "AND" } else { "OR" };
+        return Some(format!(
+            "({} {} {})",
+            sql_condition(left, quote, text)?,
+            joiner,
+            sql_condition(right, quote, text)?
+        ));
+    }
+
+    // The column on the left: 5 < amount is amount > 5
+    let (column, op, value) = match (left.as_ref(), right.as_ref()) {
+        (Expression::Column(ColumnRef::Name(column)), value) => (column, op.clone(), value),
+        (value, Expression::Column(ColumnRef::Name(column))) => {
+            let flipped = match op {
+                BinOp::Gt => BinOp::Lt,
+                BinOp::Lt => BinOp::Gt,
+                BinOp::Gte => BinOp::Lte,
+                BinOp::Lte => BinOp::Gte,
+                BinOp::Eq | BinOp::Neq | BinOp::Is | BinOp::IsNot => op.clone(),
+                _ => return None,
+            };
+            (column, flipped, value)
+        }
+        _ => return None,
+    };
+    let column = quote(column);
+
+    match (op, value) {
+        (BinOp::Is, Expression::Literal(Literal::Null)) => Some(format!("{} IS NULL", column)),
+        (BinOp::IsNot, Expression::Literal(Literal::Null)) => Some(format!("{} IS NOT NULL", column)),
+        (BinOp::In, Expression::List(values)) if !values.is_empty() => {
+            let values = values.iter().map(|v| sql_literal(v, text)).collect::<Option<Vec<_>>>()?;
+            Some(format!("{} IN ({})", column, values.join(", ")))
+        }
+        (op, Expression::Literal(value)) => {
+            let op = match op {
+                BinOp::Eq => "=",
+                BinOp::Neq => "<>",
+                BinOp::Gt => ">",
+                BinOp::Lt => "<",
+                BinOp::Gte => ">=",
+                BinOp::Lte => "<=",
+                _ => return None,
+            };
+            Some(format!("{} {} {}", column, op, sql_literal(value, text)?))
+        }
+        _ => None,
+    }
+}
+
+/// A number, or text when `text`, as an SQL literal. Booleans and nulls compare
+/// differently in SQL and are left to dt.
+fn sql_literal(literal: &Literal, text: bool) -> Option<String> {
+    match literal {
+        Literal::Number(n) if n.is_finite() && n.fract() == 0.0 && n.abs() < 1e15 => Some((*n as i64).to_string()),
+        Literal::Number(n) if n.is_finite() => Some(n.to_string()),
+        Literal::String(s) if text => Some(format!("'{}'", s.replace('\'', "''"))),
+        _ => None,
+    }
+}
+
+#[cfg(any(not(feature = "postgres"), not(feature = "mysql")))]
+fn not_built(name: &str, feature: &str) -> DtransformError {
+    DtransformError::InvalidOperation(format!(
+        "{} support is not built in; install dt with `--features {}`",
+        name, feature
+    ))
+}
+
+/// Connect to the database behind a connection string, or open a SQLite file
+fn open(path: &str, writable: bool) -> Result<Box<dyn Database>> {
+    let scheme = path.split("://").next().unwrap_or_default().to_ascii_lowercase();
+    match scheme.as_str() {
+        #[cfg(feature = "postgres")]
+        "postgres" | "postgresql" => Ok(Box::new(super::postgres::Postgres::connect(path)?)),
+        #[cfg(not(feature = "postgres"))]
+        "postgres" | "postgresql" => Err(not_built("PostgreSQL", "postgres")),
+        #[cfg(feature = "mysql")]
+        "mysql" => Ok(Box::new(super::mysql::Mysql::connect(path)?)),
+        #[cfg(not(feature = "mysql"))]
+        "mysql" => Err(not_built("MySQL", "mysql")),
+        _ => Ok(Box::new(super::sqlite::Sqlite::open(std::path::Path::new(path), writable)?)),
+    }
+}
+
+/// A column of query results. Values carry their own types (SQLite has no column
+/// types to speak of), so the column is integer or float when every value is,
+/// and text otherwise.
+fn values_series(name: &str, values: Vec<Value>) -> Series {
+    let name = PlSmallStr::from(name);
+    let present = || values.iter().filter(|v| !matches!(v, Value::Null));
+    let any = present().next().is_some();
+
+    if any && present().all(|v| matches!(v, Value::Integer(_))) {
+        let ints: Vec<Option<i64>> = values
+            .iter()
+            .map(|v| match v {
+                Value::Integer(i) => Some(*i),
+                _ => None,
+            })
+            .collect();
+        Series::new(name, ints)
+    } else if any && present().all(|v| matches!(v, Value::Integer(_) | Value::Real(_))) {
+        let floats: Vec<Option<f64>> = values
+            .iter()
+            .map(|v| match v {
+                Value::Integer(i) => Some(*i as f64),
+                Value::Real(f) => Some(*f),
+                _ => None,
+            })
+            .collect();
+        Series::new(name, floats)
+    } else {
+        let texts: Vec<Option<String>> = values
+            .into_iter()
+            .map(|v| match v {
+                Value::Null => None,
+                Value::Integer(i) => Some(i.to_string()),
+                Value::Real(f) => Some(f.to_string()),
+                Value::Text(s) => Some(s),
+            })
+            .collect();
+        Series::new(name, texts)
+    }
+}
+
+/// A table column as the values written to the database
+fn sql_column(column: &Column) -> Result<SqlColumn> {
+    let dtype = column.dtype();
+    if dtype.is_integer() || dtype == &DataType::Boolean {
+        let ints = column.cast(&DataType::Int64)?;
+        Ok(SqlColumn::Integer(ints.as_materialized_series().i64()?.into_iter().collect()))
+    } else if dtype.is_float() {
+        let floats = column.cast(&DataType::Float64)?;
+        Ok(SqlColumn::Real(floats.as_materialized_series().f64()?.into_iter().collect()))
+    } else {
+        // Dates and times are written as ISO 8601 text
+        let texts = column.cast(&DataType::String)?;
+        let texts = texts
+            .as_materialized_series()
+            .str()?
+            .into_iter()
+            .map(|v| v.map(str::to_string))
+            .collect();
+        Ok(SqlColumn::Text(texts))
+    }
+}
+
+impl Executor {
+    /// read('app.db', table='users'), read('postgres://...', query='select ...').
+    /// A database holding a single table can be read without either.
+    pub(super) fn read_database(&self, op: &ReadOp, path: &str) -> Result<DataFrame> {
+        let shown = redact(path);
+        let mut db = open(path, false)?;
+
+        let query = match (&op.query, &op.table) {
+            (Some(_), Some(_)) => {
+                return Err(DtransformError::InvalidOperation(
+                    "read() takes either table= or query= for a database, not both".to_string(),
+                ))
+            }
+            (Some(query), None) => match &self.pushdown {
+                Some(pushdown) => pushdown.query(db.as_ref(), &format!("({}) AS dt_source", query)),
+                None => query.clone(),
+            },
+            (None, Some(table)) => self.table_query(db.as_ref(), table),
+            (None, None) => {
+                let tables = db.tables()?;
+                match tables.as_slice() {
+                    [table] => self.table_query(db.as_ref(), table),
+                    [] => {
+                        return Err(DtransformError::InvalidOperation(format!("'{}' has no tables", shown)))
+                    }
+                    _ => {
+                        return Err(DtransformError::InvalidOperation(format!(
+                            "'{}' has several tables; choose one with table=: {}",
+                            shown,
+                            tables.join(", ")
+                        )))
+                    }
+                }
+            }
+        };
+        self.read_note(&shown, &format!("query: {}", query));
+
+        let (names, values) = db.query(&query)?;
+        let columns: Vec<Column> = names
+            .iter()
+            .zip(values)
+            .map(|(name, values)| values_series(name, values).into_column())
+            .collect();
+        let df = DataFrame::new(columns)?;
+        self.check_duplicate_columns(&df)?;
+        Ok(df)
+    }
+
+    /// The query reading a whole table, or the part the pipeline keeps
+    fn table_query(&self, db: &dyn Database, table: &str) -> String {
+        let from = quote_table(db, table);
+        match &self.pushdown {
+            Some(pushdown) => pushdown.query(db, &from),
+            None => format!("SELECT * FROM {}", from),
+        }
+    }
+
+    /// write('out.db', table='results', mode='replace')
+    pub(super) fn write_database(&self, df: &DataFrame, op: &WriteOp, path: &str) -> Result<()> {
+        let table = op.table.as_deref().ok_or_else(|| {
+            DtransformError::InvalidOperation(format!("write() to database '{}' needs table='name'", redact(path)))
+        })?;
+        if df.width() == 0 {
+            return Err(DtransformError::InvalidOperation(
+                "Cannot write a table without columns to a database".to_string(),
+            ));
+        }
+        let mode = match op.mode.as_deref() {
+            Some("append") => WriteMode::Append,
+            Some("fail") => WriteMode::Fail,
+            _ => WriteMode::Replace,
+        };
+
+        let columns = df
+            .get_columns()
+            .iter()
+            .map(|column| Ok((column.name().to_string(), sql_column(column)?)))
+            .collect::<Result<Vec<_>>>()?;
+        open(path, true)?.write_table(table, mode, &columns)
+    }
+}
@@ -0,0 +1,174 @@
+use polars::prelude::*;
+
+use crate::error::{DtransformError, Result};
+
+/// Differences between two versions of a table, matched by key columns
+#[derive(Debug, Clone)]
+pub struct TableDiff {
+    /// Columns rows were matched on (`row`, the 1-based row number, when no key was given)
+    pub keys: Vec<String>,
+    pub columns_added: Vec<String>,
+    pub columns_removed: Vec<String>,
+    pub rows_added: usize,
+    pub rows_removed: usize,
+    pub rows_changed: usize,
+    pub rows_unchanged: usize,
+    /// Changed rows per column shared by both tables, in column order
+    pub changes_by_column: Vec<(String, usize)>,
+    /// One row per added or removed row and per changed cell: the key columns,
+    /// `change` (added, removed, changed), `column`, `old` and `new` (as text)
+    pub details: DataFrame,
+}
+
+impl TableDiff {
+    pub fn is_empty(&self) -> bool {
+        self.rows_added == 0
+            && self.rows_removed == 0
+            && self.rows_changed == 0
+            && self.columns_added.is_empty()
+            && self.columns_removed.is_empty()
+    }
+}
+
+/// Compare `old` with `new`. Rows are matched on `keys`, which must be unique in
+/// both tables, or by position when `keys` is empty. Values are compared in the
+/// columns both tables have; a null equals only another null.
+pub fn diff_tables(old: &DataFrame, new: &DataFrame, keys: &[String]) -> Result<TableDiff> {
+    let (mut old, mut new, keys) = if keys.is_empty() {
+        let name = if old.schema().contains("row") || new.schema().contains("row") { "__row" } else { "row" };
+        (
+            old.with_row_index(PlSmallStr::from(name), Some(1))?,
+            new.with_row_index(PlSmallStr::from(name), Some(1))?,
+            vec![name.to_string()],
+        )
+    } else {
+        (old.clone(), new.clone(), keys.to_vec())
+    };
+
+    for (df, side) in [(&old, "old"), (&new, "new")] {
+        if let Some(missing) = keys.iter().find(|key| !df.schema().contains(key.as_str())) {
+            return Err(DtransformError::ColumnNotFound(format!(
+                "Key column '{}' is not in the {} table",
+                missing, side
+            )));
+        }
+        let duplicated = df.select(keys.iter().map(|k| k.as_str()))?.is_duplicated()?;
+        let count = duplicated.sum().unwrap_or(0);
+        if count > 0 {
+            return Err(DtransformError::InvalidOperation(format!(
+                "Key ({}) is not unique in the {} table: {} rows share a key",
+                keys.join(", "),
+                side,
+                count
+            )));
+        }
+    }
+
+    // Keys of different types are matched as text
+    for key in &keys {
+        let (old_type, new_type) = (old.column(key)?.dtype().clone(), new.column(key)?.dtype().clone());
+        if old_type != new_type {
+            let as_text = |df: &DataFrame| -> Result<Column> {
+                Ok(df.column(key)?.cast(&DataType::String)?)
+            };
+            let (old_key, new_key) = (as_text(&old)?, as_text(&new)?);
+            old.with_column(old_key)?;
+            new.with_column(new_key)?;
+        }
+    }
+
+    let old_names: Vec<String> = old.get_column_names().iter().map(|n| n.to_string()).collect();
+    let new_names: Vec<String> = new.get_column_names().iter().map(|n| n.to_string()).collect();
+    let shared: Vec<String> = old_names.iter().filter(|n| !keys.contains(n) && new_names.contains(n)).cloned().collect();
+    let columns_removed = old_names.iter().filter(|n| !keys.contains(n) && !new_names.contains(n)).cloned().collect();
+    let columns_added = new_names.iter().filter(|n| !keys.contains(n) && !old_names.contains(n)).cloned().collect();
+
+    // Full join on the keys; a marker column tells which side a row came from
+    let side = |df: DataFrame, prefix: &str| -> LazyFrame {
+        let mut exprs: Vec<Expr> = keys.iter().map(|k| col(k.as_str())).collect();
+        exprs.extend(shared.iter().map(|c| col(c.as_str()).alias(format!("{}:{}", prefix, c))));
+        exprs.push(lit(true).alias(format!("{}:present", prefix)));
+        df.lazy().select(exprs)
+    };
+    let key_exprs: Vec<Expr> = keys.iter().map(|k| col(k.as_str())).collect();
+    let joined = side(old, "__old")
+        .join(
+            side(new, "__new"),
+            key_exprs.clone(),
+            key_exprs,
+            JoinArgs::new(JoinType::Full).with_coalesce(JoinCoalesce::CoalesceColumns),
+        )
+        .collect()?;
+
+    let in_old = joined.column("__old:present")?.is_not_null();
+    let in_new = joined.column("__new:present")?.is_not_null();
+    let in_both = &in_old & &in_new;
+
+    let mut any_changed = BooleanChunked::full(PlSmallStr::from("changed"), false, joined.height());
+    let mut changes_by_column = Vec::with_capacity(shared.len());
+    let mut details = Vec::new();
+    let key_columns = joined.select(keys.iter().map(|k| k.as_str()))?;
+
+    let detail = |mask: &BooleanChunked, change: &str, column: Option<&str>, old: Option<&Series>, new: Option<&Series>| -> Result<DataFrame> {
+        let mut df = key_columns.filter(mask)?;
+        let n = df.height();
+        let text = |name: &str, values: Option<&Series>| -> Result<Column> {
+            Ok(match values {
+                Some(values) => values.filter(mask)?.cast(&DataType::String)?.with_name(PlSmallStr::from(name)).into_column(),
+                None => Series::full_null(PlSmallStr::from(name), n, &DataType::String).into_column(),
+            })
+        };
+        let column = match column {
+            Some(column) => Series::new(PlSmallStr::from("column"), vec![column; n]).into_column(),
+            None => Series::full_null(PlSmallStr::from("column"), n, &DataType::String).into_column(),
+        };
+        df.with_column(Series::new(PlSmallStr::from("change"), vec![change; n]))?;
+        df.with_column(column)?;
+        df.with_column(text("old", old)?)?;
+        df.with_column(text("new", new)?)?;
+        Ok(df)
+    };
+
+    details.push(detail(&!&in_old, "added", None, None, None)?);
+    details.push(detail(&!&in_new, "removed", None, None, None)?);
+
+    for column in &shared {
+        let mut old_values = joined.column(&format!("__old:{}", column))?.as_materialized_series().clone();
+        let mut new_values = joined.column(&format!("__new:{}", column))?.as_materialized_series().clone();
+        if old_values.dtype() != new_values.dtype() {
+            old_values = old_values.cast(&DataType::String)?;
+            new_values = new_values.cast(&DataType::String)?;
+        }
+        let changed = &old_values.not_equal_missing(&new_values)? & &in_both;
+        let count = changed.sum().unwrap_or(0) as usize;
+        changes_by_column.push((column.clone(), count));
+        if count > 0 {
+            details.push(detail(&changed, "changed", Some(column), Some(&old_values), Some(&new_values))?);
+            any_changed = &any_changed | &changed;
+        }
+    }
+
+    let mut details = details.into_iter();
+    let mut combined = details.next().unwrap_or_default();
+    for df in details {
+        combined.vstack_mut(&df)?;
+    }
+    let combined = combined.sort(keys.clone(), SortMultipleOptions::default().with_maintain_order(true))?;
+
+    let rows_added = (!&in_old).sum().unwrap_or(0) as usize;
+    let rows_removed = (!&in_new).sum().unwrap_or(0) as usize;
+    let rows_changed = any_changed.sum().unwrap_or(0) as usize;
+    let rows_unchanged = in_both.sum().unwrap_or(0) as usize - rows_changed;
+
+    Ok(TableDiff {
+        keys,
+        columns_added,
+        columns_removed,
+        rows_added,
+        rows_removed,
+        rows_changed,
+        rows_unchanged,
+        changes_by_column,
+        details: combined,
+    })
+}
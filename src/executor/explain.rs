@@ -0,0 +1,249 @@
+use polars::prelude::*;
+use std::collections::HashMap;
+use std::fmt;
+
+use super::{operation_name, Executor};
+use crate::error::{DtransformError, Result};
+use crate::parser::ast::*;
+
+/// Rows expected after a stage
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RowEstimate {
+    Exact(usize),
+    AtMost(usize),
+    Unknown,
+}
+
+impl RowEstimate {
+    fn map(self, f: impl Fn(usize) -> usize) -> RowEstimate {
+        match self {
+            RowEstimate::Exact(n) => RowEstimate::Exact(f(n)),
+            RowEstimate::AtMost(n) => RowEstimate::AtMost(f(n)),
+            RowEstimate::Unknown => RowEstimate::Unknown,
+        }
+    }
+
+    /// After an operation that may remove rows
+    fn at_most(self) -> RowEstimate {
+        match self {
+            RowEstimate::Exact(n) | RowEstimate::AtMost(n) => RowEstimate::AtMost(n),
+            RowEstimate::Unknown => RowEstimate::Unknown,
+        }
+    }
+
+    /// After an operation that keeps at most `limit` rows
+    fn capped(self, limit: usize) -> RowEstimate {
+        match self {
+            RowEstimate::Unknown => RowEstimate::AtMost(limit),
+            rows => rows.map(|n| n.min(limit)),
+        }
+    }
+}
+
+impl fmt::Display for RowEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RowEstimate::Exact(n) => write!(f, "{}", n),
+            RowEstimate::AtMost(n) => write!(f, "≤ {}", n),
+            RowEstimate::Unknown => write!(f, "?"),
+        }
+    }
+}
+
+/// One stage of a pipeline, its source or one operation, with what it is expected to produce
+#[derive(Debug, Clone)]
+pub struct PlanStage {
+    /// 1-based statement number within the program
+    pub statement: usize,
+    pub stage: String,
+    pub rows: RowEstimate,
+    /// Column names after the stage; None when they can't be known without the data
+    pub columns: Option<Vec<String>>,
+}
+
+/// What a program would do, stage by stage (dt --explain, .explain)
+#[derive(Debug, Clone, Default)]
+pub struct QueryPlan {
+    pub stages: Vec<PlanStage>,
+}
+
+impl fmt::Display for QueryPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let labels: Vec<String> = self.stages.iter().map(|s| format!("{}: {}", s.statement, s.stage)).collect();
+        let width = labels.iter().map(|l| l.chars().count()).max().unwrap_or(0).max(24);
+        writeln!(f, "{:<width$} {:>10} {:>6}  columns", "stage", "rows", "cols", width = width)?;
+
+        // Column names are listed where they change
+        let mut previous: Option<(usize, &Option<Vec<String>>)> = None;
+        for (stage, label) in self.stages.iter().zip(&labels) {
+            let cols = stage.columns.as_ref().map_or("?".to_string(), |c| c.len().to_string());
+            let names = match &stage.columns {
+                Some(columns) if previous != Some((stage.statement, &stage.columns)) => columns.join(", "),
+                _ => String::new(),
+            };
+            let line = format!("{:<width$} {:>10} {:>6}  {}", label, stage.rows.to_string(), cols, names, width = width);
+            writeln!(f, "{}", line.trim_end())?;
+            previous = Some((stage.statement, &stage.columns));
+        }
+        Ok(())
+    }
+}
+
+/// Rows left by an operation, from the rows it is given
+fn estimate_rows(op: &Operation, rows: RowEstimate) -> RowEstimate {
+    match op {
+        Operation::Filter(_)
+        | Operation::Distinct(_)
+        | Operation::DropNulls(_)
+        | Operation::CountBy(_)
+        | Operation::TopN(_)
+        | Operation::Resample(_) => rows.at_most(),
+        Operation::Take(take) => rows.capped(take.n),
+        Operation::Skip(skip) => rows.map(|n| n.saturating_sub(skip.n)),
+        Operation::Slice(slice) => rows
+            .map(|n| n.saturating_sub(slice.start))
+            .capped(slice.end.saturating_sub(slice.start)),
+        Operation::Sample(sample) => match sample.size {
+            SampleSize::Rows(n) if sample.with_replacement => RowEstimate::Exact(n),
+            SampleSize::Rows(n) => rows.capped(n),
+            SampleSize::Fraction(frac) => rows.map(|n| {
+                let size = (n as f64 * frac).round() as usize;
+                if sample.with_replacement { size } else { size.min(n) }
+            }),
+        },
+        Operation::Count | Operation::Summarize(_) => RowEstimate::Exact(1),
+        Operation::Read(_)
+        | Operation::Concat(_)
+        | Operation::Append(_)
+        | Operation::Describe(_)
+        | Operation::Upsample(_)
+        | Operation::Explode(_) => {
+            RowEstimate::Unknown
+        }
+        _ => rows,
+    }
+}
+
+/// Columns of an empty table
+fn column_names(df: &DataFrame) -> Vec<String> {
+    df.get_column_names().iter().map(|name| name.to_string()).collect()
+}
+
+impl Executor {
+    /// The stages a program would run, with the rows and columns expected after each.
+    /// Sources are read to size them, but operations only run on empty tables and
+    /// nothing is written. Variables are left as they were.
+    pub fn explain_program(&mut self, program: &Program) -> Result<QueryPlan> {
+        let saved = self.get_all_variables();
+        let result = self.explain_statements(program);
+        self.restore_variables(saved);
+        result
+    }
+
+    fn explain_statements(&mut self, program: &Program) -> Result<QueryPlan> {
+        let mut plan = QueryPlan::default();
+        // Tables assigned so far, as empty tables (None when the columns are unknown)
+        let mut assigned: HashMap<String, (Option<DataFrame>, RowEstimate)> = HashMap::new();
+
+        for (index, statement) in program.statements.iter().enumerate() {
+            let (name, pipeline) = match statement {
+                Statement::Assignment { name, pipeline } => (Some(name), pipeline),
+                Statement::Pipeline(pipeline) => (None, pipeline),
+            };
+            let (df, rows) = self.explain_pipeline(index + 1, name, pipeline, &assigned, &mut plan)?;
+
+            if let Some(name) = name {
+                // Operations such as lookup() and append() use the variables
+                match &df {
+                    Some(df) => self.set_variable(name.clone(), df.clone()),
+                    None => self.remove_variable(name),
+                }
+                assigned.insert(name.clone(), (df, rows));
+            }
+        }
+
+        Ok(plan)
+    }
+
+    fn explain_pipeline(
+        &mut self,
+        statement: usize,
+        name: Option<&String>,
+        pipeline: &Pipeline,
+        assigned: &HashMap<String, (Option<DataFrame>, RowEstimate)>,
+        plan: &mut QueryPlan,
+    ) -> Result<(Option<DataFrame>, RowEstimate)> {
+        self.current_file = match &pipeline.source {
+            Some(Source::Read(read_op)) => super::read_file_name(read_op),
+            _ => None,
+        };
+        let (source, mut df, mut rows) = match &pipeline.source {
+            Some(Source::Read(read_op)) => {
+                let df = self.execute_read((**read_op).clone())?;
+                (format!("read '{}'", read_op.path), Some(df.clear()), RowEstimate::Exact(df.height()))
+            }
+            Some(Source::Variable(var_name)) => match assigned.get(var_name) {
+                Some((df, rows)) => (var_name.clone(), df.clone(), *rows),
+                None => {
+                    let df = self
+                        .get_variable(var_name)
+                        .ok_or_else(|| DtransformError::VariableNotFound(var_name.clone()))?;
+                    (var_name.clone(), Some(df.clear()), RowEstimate::Exact(df.height()))
+                }
+            },
+            Some(Source::Concat(concat_op)) => {
+                let df = self.execute_concat(None, concat_op.clone()).ok();
+                (String::from("concat"), df.map(|df| df.clear()), RowEstimate::Unknown)
+            }
+            None => {
+                return Err(DtransformError::InvalidOperation(
+                    "Pipeline must start with a data source (read() or variable)".to_string(),
+                ));
+            }
+        };
+
+        let source = match name {
+            Some(name) => format!("{} = {}", name, source),
+            None => source,
+        };
+        plan.stages.push(PlanStage {
+            statement,
+            stage: source,
+            rows,
+            columns: df.as_ref().map(column_names),
+        });
+
+        for op in &pipeline.operations {
+            rows = estimate_rows(op, rows);
+            df = match (op, df) {
+                (Operation::Read(read_op), _) => {
+                    self.current_file = super::read_file_name(read_op);
+                    let read = self.execute_read(read_op.clone())?;
+                    rows = RowEstimate::Exact(read.height());
+                    Some(read.clear())
+                }
+                // Nothing to check or write without the data
+                (Operation::Write(_) | Operation::Expect(_), df) => df,
+                (_, Some(df)) => self.execute_operation(df, op.clone()).ok(),
+                (_, None) => None,
+            };
+            // Summaries have the same number of rows for an empty table
+            if let (Operation::Describe(_), Some(df)) = (op, &df) {
+                rows = RowEstimate::Exact(df.height());
+            }
+
+            let stage = match op {
+                Operation::Write(write_op) => format!("write '{}'", write_op.path),
+                op => operation_name(op).to_string(),
+            };
+            plan.stages.push(PlanStage {
+                statement,
+                stage,
+                rows,
+                columns: df.as_ref().map(column_names),
+            });
+        }
+
+        Ok((df, rows))
+    }
+}
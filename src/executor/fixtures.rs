@@ -0,0 +1,77 @@
+use polars::prelude::*;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+use crate::error::{DtransformError, Result};
+
+use super::Workspace;
+
+/// Samples of read() inputs for deterministic test runs. Recording saves the
+/// first rows of every table read() returns; replaying returns those samples
+/// instead of touching the original files.
+#[derive(Debug, Clone)]
+pub enum Fixtures {
+    Record { dir: PathBuf, rows: usize },
+    Replay { dir: PathBuf },
+}
+
+impl Fixtures {
+    /// Rows kept per input when recording, unless told otherwise
+    pub const DEFAULT_ROWS: usize = 100;
+
+    fn dir(&self) -> &Path {
+        match self {
+            Fixtures::Record { dir, .. } | Fixtures::Replay { dir } => dir,
+        }
+    }
+
+    /// File holding the sample of `source` (the path as written in read()).
+    /// The name is readable and stable across runs: the file name of the source
+    /// plus a short hash of the full path, so `a/x.csv` and `b/x.csv` don't collide.
+    pub fn path_for(&self, source: &str) -> PathBuf {
+        let digest = Sha256::digest(source.as_bytes());
+        let hash: String = digest.iter().take(4).map(|b| format!("{:02x}", b)).collect();
+        let name: String = Path::new(source)
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "stdin".to_string())
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_' { c } else { '_' })
+            .collect();
+        self.dir().join(format!("{}-{}.parquet", name, hash))
+    }
+
+    /// The recorded sample of `source`, when replaying
+    pub fn replay(&self, source: &str) -> Result<Option<DataFrame>> {
+        if !matches!(self, Fixtures::Replay { .. }) {
+            return Ok(None);
+        }
+        let path = self.path_for(source);
+        let file = std::fs::File::open(&path).map_err(|_| {
+            DtransformError::InvalidOperation(format!(
+                "No fixture for read('{}') in '{}'. Record one with --record {}",
+                source,
+                self.dir().display(),
+                self.dir().display()
+            ))
+        })?;
+        Ok(Some(ParquetReader::new(file).finish()?))
+    }
+
+    /// Save the first rows of `df` as the sample of `source`, when recording.
+    /// Parquet keeps the column types, so a replay sees exactly what read() returned.
+    pub fn record(&self, source: &str, df: &DataFrame, workspace: &Workspace) -> Result<()> {
+        let Fixtures::Record { dir, rows } = self else {
+            return Ok(());
+        };
+        std::fs::create_dir_all(dir)?;
+        let target = self.path_for(source);
+        let staged = workspace.stage(&target)?;
+        let mut sample = df.head(Some(*rows));
+        ParquetWriter::new(std::fs::File::create(staged.as_deref().unwrap_or(&target))?).finish(&mut sample)?;
+        match staged {
+            Some(staged) => workspace.commit(&staged, &target),
+            None => Ok(()),
+        }
+    }
+}
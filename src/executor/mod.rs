@@ -1,1316 +1,5396 @@
+use chrono::{Datelike, Months, NaiveDate, NaiveDateTime, Timelike};
 use polars::prelude::*;
 use regex::Regex;
+mod check;
+mod database;
+mod diff;
+mod explain;
+mod fixtures;
+#[cfg(feature = "mysql")]
+mod mysql;
+mod parallel;
+mod peek;
+#[cfg(feature = "postgres")]
+mod postgres;
+mod sidecar;
+mod similarity;
+mod sqlite;
+mod workspace;
+
+pub use check::{CheckIssue, StandIns};
+pub use diff::{diff_tables, TableDiff};
+pub use explain::{PlanStage, QueryPlan, RowEstimate};
+pub use fixtures::Fixtures;
+pub use peek::PeekRows;
+pub use workspace::Workspace;
+
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
 use crate::error::{DtransformError, Result};
 use crate::parser::ast::*;
 
 pub struct Executor {
     variables: HashMap<String, DataFrame>,
+    // Stage timings and files touched, collected only when enabled (dt bench, --manifest)
+    run_log: RefCell<Option<RunLog>>,
+    statement_index: usize,
+    // Explain read() decisions on stderr (dt --verbose)
+    verbose: bool,
+    // Values for {name} placeholders in write() paths (dt --param name=value)
+    params: HashMap<String, String>,
+    // Staging area for files being written
+    workspace: Workspace,
+    // Record samples of read() inputs, or replay them (dt --record / --replay)
+    fixtures: Option<Fixtures>,
+    // Files that read() takes its columns from instead, while checking a program (dt check)
+    stand_ins: Option<StandIns>,
+    // Report statements, reads and writes on stderr (dt --progress); Some(false)
+    // also silences write(progress=true) (dt --quiet)
+    progress: Option<bool>,
+    // Run independent assignments of a program concurrently (off with dt --sequential)
+    parallel: bool,
+    // The file the running pipeline read, for file_name()
+    current_file: Option<String>,
+    // Give every row read its file in FILE_NAME_COLUMN, for file_name() in a later step
+    file_names: bool,
+    // FILE_NAME_COLUMN while it is set aside for a step that keeps the rows in place
+    row_files: Option<Series>,
+    // Leading steps of the running pipeline its database source runs instead
+    pushdown: Option<database::Pushdown>,
+    // Compiled regexes by pattern and case-insensitivity, kept across statements
+    regexes: RefCell<HashMap<(String, bool), Regex>>,
 }
 
-/// Auto-detect delimiter from file content
-/// Returns (delimiter, needs_trim_whitespace)
-fn auto_detect_delimiter(content: &str, file_extension: Option<&str>) -> Result<(char, bool)> {
-    // For .tsv files, use tab delimiter but check if trimming is needed
-    if file_extension == Some("tsv") {
-        let needs_trim = content.lines().take(100).any(|line| {
-            line.trim() != line || line.contains("  ")
-        });
-        return Ok(('\t', needs_trim));
-    }
+/// Patterns kept compiled before the regex cache starts over
+const REGEX_CACHE_SIZE: usize = 256;
 
-    // For .csv files, prefer comma but check if trimming is needed
-    if file_extension == Some("csv") {
-        // Check if file has leading/trailing whitespace or multiple consecutive spaces
-        let needs_trim = content.lines().take(100).any(|line| {
-            line.trim() != line || line.contains("  ")
-        });
-        return Ok((',', needs_trim));
+/// Fill `{name}` placeholders in an output path. `{date}`, `{time}` and `{datetime}`
+/// are the current local time, optionally with a strftime format (`{date:%Y%m}`);
+/// other names come from `params`. `{{` and `}}` are literal braces.
+fn expand_path_template(template: &str, params: &HashMap<String, String>) -> Result<String> {
+    if !template.contains(['{', '}']) {
+        return Ok(template.to_string());
     }
 
-    // Sample first 100 non-empty lines for detection
-    let sample_lines: Vec<&str> = content
-        .lines()
-        .filter(|l| !l.trim().is_empty())
-        .take(100)
-        .collect();
+    let now = chrono::Local::now();
+    let mut out = String::with_capacity(template.len());
+    let mut chars = template.chars().peekable();
 
-    if sample_lines.is_empty() {
-        return Err(DtransformError::InvalidOperation(
-            "File is empty or contains no data".to_string()
-        ));
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                out.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                out.push('}');
+            }
+            '{' => {
+                let placeholder: String = chars.by_ref().take_while(|&ch| ch != '}').collect();
+                let (name, format) = match placeholder.split_once(':') {
+                    Some((name, format)) => (name.trim(), Some(format)),
+                    None => (placeholder.trim(), None),
+                };
+                let default_format = match name {
+                    "date" => Some("%Y-%m-%d"),
+                    "time" => Some("%H%M%S"),
+                    "datetime" => Some("%Y-%m-%dT%H%M%S"),
+                    _ => None,
+                };
+                match (params.get(name), default_format) {
+                    (Some(value), _) => out.push_str(value),
+                    (None, Some(default)) => {
+                        let format = format.unwrap_or(default);
+                        let items: Vec<chrono::format::Item> = chrono::format::StrftimeItems::new(format).collect();
+                        if items.contains(&chrono::format::Item::Error) {
+                            return Err(DtransformError::InvalidOperation(format!(
+                                "Invalid date format '{}' in path '{}'", format, template
+                            )));
+                        }
+                        out.push_str(&now.format_with_items(items.into_iter()).to_string());
+                    }
+                    (None, None) => {
+                        return Err(DtransformError::InvalidOperation(format!(
+                            "No value for {{{}}} in path '{}'. Pass one with --param {}=...",
+                            name, template, name
+                        )));
+                    }
+                }
+            }
+            _ => out.push(c),
+        }
     }
 
-    // Check if trimming whitespace is needed
-    let needs_trim = sample_lines.iter().any(|line| {
-        line.trim() != *line || line.contains("  ")
-    });
+    Ok(out)
+}
 
-    // Prepare content for delimiter detection
-    let detection_lines: Vec<String> = if needs_trim {
-        sample_lines.iter().map(|line| {
-            let trimmed = line.trim();
-            trimmed.split_whitespace().collect::<Vec<_>>().join(" ")
-        }).collect()
-    } else {
-        sample_lines.iter().map(|s| s.to_string()).collect()
+/// Rows per chunk when writing delimited text
+pub const DEFAULT_CHUNK_ROWS: usize = 100_000;
+
+/// Write `df` as delimited text one slice of `chunk_rows` rows at a time. Slices share
+/// the table's memory, so only the chunk being written is ever rendered. With `flush`
+/// the writer is flushed after every chunk; `progress` gets the rows written so far.
+pub fn write_delimited_chunks<W: Write>(
+    writer: &mut W,
+    df: &DataFrame,
+    separator: u8,
+    header: bool,
+    chunk_rows: usize,
+    flush: bool,
+    mut progress: impl FnMut(usize),
+) -> Result<()> {
+    let csv = |writer: &mut W, chunk: &mut DataFrame, include_header: bool| {
+        CsvWriter::new(writer)
+            .with_separator(separator)
+            .include_header(include_header)
+            .finish(chunk)
     };
 
-    // Count occurrences of common delimiters
-    let delimiters = [',', '\t', '|', ';', ' '];
-    let mut delimiter_counts: HashMap<char, Vec<usize>> = HashMap::new();
+    if df.height() == 0 {
+        // Only the header
+        csv(writer, &mut df.clear(), header)?;
+        return Ok(());
+    }
 
-    for line in &detection_lines {
-        for &delim in &delimiters {
-            let count = line.matches(delim).count();
-            delimiter_counts.entry(delim).or_insert_with(Vec::new).push(count);
+    let mut written = 0;
+    while written < df.height() {
+        let mut chunk = df.slice(written as i64, chunk_rows.max(1));
+        csv(writer, &mut chunk, header && written == 0)?;
+        written += chunk.height();
+        if flush {
+            writer.flush()?;
         }
+        progress(written);
     }
+    Ok(())
+}
 
-    // Find delimiter with:
-    // 1. Non-zero consistent counts across lines
-    // 2. Highest average count
-    let mut best_delimiter = None;
-    let mut best_score = 0.0;
-
-    for (&delim, counts) in &delimiter_counts {
-        // Filter out lines with zero occurrences
-        let non_zero_counts: Vec<usize> = counts.iter().filter(|&&c| c > 0).copied().collect();
+/// File size for progress messages: `850 B`, `12.4 MB`
+pub(crate) fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1000 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1000.0;
+    let mut unit = 0;
+    while size >= 1000.0 && unit < UNITS.len() - 1 {
+        size /= 1000.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
 
-        if non_zero_counts.is_empty() {
-            continue;
+/// Bytes per chunk when a file is read with --progress
+const PROGRESS_CHUNK_BYTES: usize = 1024 * 1024;
+
+/// Read everything from `reader` one chunk of PROGRESS_CHUNK_BYTES at a time;
+/// `progress` gets the bytes read so far after every chunk.
+fn read_chunks(mut reader: impl std::io::Read, mut progress: impl FnMut(u64)) -> Result<Vec<u8>> {
+    use std::io::Read;
+    let mut bytes = Vec::new();
+    loop {
+        let read = (&mut reader).take(PROGRESS_CHUNK_BYTES as u64).read_to_end(&mut bytes)?;
+        if read == 0 {
+            return Ok(bytes);
         }
+        progress(bytes.len() as u64);
+    }
+}
 
-        // Check consistency: most lines should have the same count
-        let min = *non_zero_counts.iter().min().unwrap();
-        let max = *non_zero_counts.iter().max().unwrap();
-        let avg = non_zero_counts.iter().sum::<usize>() as f64 / non_zero_counts.len() as f64;
+/// What a run did: every stage executed, and the files it read and wrote
+#[derive(Debug, Clone, Default)]
+pub struct RunLog {
+    pub stages: Vec<StageTiming>,
+    /// Paths read, in order, without repeats ('-' for stdin)
+    pub inputs: Vec<String>,
+    pub outputs: Vec<WrittenFile>,
+}
 
-        // Delimiter should appear consistently (variance should be low)
-        // and appear in most lines
-        let consistency = non_zero_counts.len() as f64 / detection_lines.len() as f64;
+/// A table written by write() (or by the CLI's -o)
+#[derive(Debug, Clone)]
+pub struct WrittenFile {
+    pub path: String,
+    pub rows: usize,
+}
 
-        // Prefer delimiters that appear consistently
-        if min == max || (max as f64 - min as f64) / avg < 0.3 {
-            let score = avg * consistency;
-            if score > best_score {
-                best_score = score;
-                best_delimiter = Some(delim);
-            }
-        }
-    }
+/// Time spent in one stage of a pipeline: its source or one operation
+#[derive(Debug, Clone)]
+pub struct StageTiming {
+    /// 1-based statement number within the program
+    pub statement: usize,
+    pub stage: String,
+    pub duration: Duration,
+    pub rows: usize,
+}
 
-    match best_delimiter {
-        Some(delim) => Ok((delim, needs_trim)),
-        None => {
-            // No delimiter found - likely a single-column file
-            // Check if ALL delimiter counts are zero across all lines
-            let all_zero = delimiter_counts.values().all(|counts| {
-                counts.iter().all(|&c| c == 0)
-            });
+/// Common type for matching lookup keys: the same type, a numeric supertype,
+/// or text when either side is text (or the types don't mix)
+fn lookup_key_type(left: &polars::datatypes::DataType, right: &polars::datatypes::DataType) -> polars::datatypes::DataType {
+    use polars::datatypes::DataType as PDT;
+    if left == right {
+        left.clone()
+    } else if left.is_numeric() && right.is_numeric() && (left.is_float() || right.is_float()) {
+        PDT::Float64
+    } else if left.is_integer() && right.is_integer() {
+        PDT::Int64
+    } else {
+        PDT::String
+    }
+}
 
-            if all_zero {
-                // Single column file - use comma as default (won't matter since no delimiters)
-                Ok((',', needs_trim))
-            } else {
-                // Ambiguous format - multiple delimiters present but inconsistent
-                Err(DtransformError::InvalidOperation(
-                    "Could not auto-detect delimiter. The file format is ambiguous.\n\n\
-                    Please specify the delimiter explicitly:\n\
-                    • Comma: read('file', delimiter=',')\n\
-                    • Tab: read('file', delimiter='\\t')\n\
-                    • Pipe: read('file', delimiter='|')\n\
-                    • Semicolon: read('file', delimiter=';')\n\
-                    • Space: read('file', delimiter=' ')".to_string()
-                ))
-            }
-        }
+/// A column reference as the user wrote it: `amount`, `$3`
+fn column_label(column: &ColumnRef) -> String {
+    match column {
+        ColumnRef::Name(name) => name.clone(),
+        ColumnRef::Position(pos) => format!("${}", pos),
+        ColumnRef::Index(idx) => format!("${}", idx + 1),
     }
 }
 
-impl Executor {
-    pub fn new() -> Self {
-        Self {
-            variables: HashMap::new(),
-        }
+/// Short name of an operation for timings and diagnostics
+fn operation_name(op: &Operation) -> &'static str {
+    match op {
+        Operation::Read(_) => "read",
+        Operation::Write(_) => "write",
+        Operation::Select(_) => "select",
+        Operation::Relocate(_) => "relocate",
+        Operation::Filter(_) => "filter",
+        Operation::Mutate(_) => "mutate",
+        Operation::MutateAll(_) => "mutate_all",
+        Operation::Rename(_) => "rename",
+        Operation::RenameAll(_) => "rename_all",
+        Operation::Sort(_) => "sort",
+        Operation::Take(_) => "take",
+        Operation::Skip(_) => "skip",
+        Operation::Slice(_) => "slice",
+        Operation::Sample(_) => "sample",
+        Operation::Drop(_) => "drop",
+        Operation::Distinct(_) => "distinct",
+        Operation::DropNulls(_) => "drop_nulls",
+        Operation::FillNulls(_) => "fill_nulls",
+        Operation::Concat(_) => "concat",
+        Operation::Append(_) => "append",
+        Operation::Cast(_) => "cast",
+        Operation::Describe(_) => "describe",
+        Operation::Count => "count",
+        Operation::Expect(_) => "expect",
+        Operation::CountBy(_) => "count_by",
+        Operation::TopN(_) => "top_n",
+        Operation::Summarize(_) => "summarize",
+        Operation::Resample(_) => "resample",
+        Operation::Upsample(_) => "upsample",
+        Operation::Explode(_) => "explode",
+        Operation::WithRowNumber(_) => "with_row_number",
+        Operation::SplitInto(_) => "split_into",
+        Operation::Variable(_) => "variable",
     }
+}
 
-    pub fn execute_program(&mut self, program: Program) -> Result<Option<DataFrame>> {
-        let mut last_result = None;
+/// Where delimited text is read from: a file on disk or content already in memory
+/// (stdin, a decompressed file)
+enum DelimitedInput<'a> {
+    File(&'a Path),
+    Memory { content: String, source: String },
+}
 
-        for statement in program.statements {
-            match statement {
-                Statement::Assignment { name, pipeline } => {
-                    let df = self.execute_pipeline(pipeline)?;
-                    self.variables.insert(name, df);
-                    // Assignments don't produce output in program mode
-                }
-                Statement::Pipeline(pipeline) => {
-                    let df = self.execute_pipeline(pipeline)?;
-                    last_result = Some(df);
-                }
-            }
+impl DelimitedInput<'_> {
+    fn display_name(&self) -> String {
+        match self {
+            DelimitedInput::File(path) => path.display().to_string(),
+            DelimitedInput::Memory { source, .. } => source.clone(),
         }
+    }
+}
 
-        Ok(last_result)
+/// How a file is compressed
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Compression {
+    Gzip,
+    Zstd,
+    Bzip2,
+}
+
+/// Compression of a file: `compression=` when given, otherwise from the extension
+fn compression_of(path: &Path, given: Option<&str>) -> Result<Option<Compression>> {
+    let name = match given {
+        Some(given) => given.to_string(),
+        None => path.extension().and_then(|ext| ext.to_str()).unwrap_or_default().to_lowercase(),
+    };
+    match name.as_str() {
+        "gzip" | "gz" => Ok(Some(Compression::Gzip)),
+        "zstd" | "zst" => Ok(Some(Compression::Zstd)),
+        "bzip2" | "bz2" => Ok(Some(Compression::Bzip2)),
+        _ => Ok(None),
     }
+}
 
-    pub fn execute_statement(&mut self, statement: Statement) -> Result<Option<DataFrame>> {
-        match statement {
-            Statement::Assignment { name, pipeline } => {
-                let df = self.execute_pipeline(pipeline)?;
-                self.variables.insert(name.clone(), df.clone());
-                Ok(Some(df))
-            }
-            Statement::Pipeline(pipeline) => {
-                let df = self.execute_pipeline(pipeline)?;
-                Ok(Some(df))
-            }
-        }
+/// The path whose extension names the format: `data.csv` for `data.csv.gz`
+fn format_path(path: &Path) -> &Path {
+    match path.extension().and_then(|ext| ext.to_str()).map(|ext| ext.to_lowercase()).as_deref() {
+        Some("gz" | "zst" | "bz2") => path.file_stem().map(Path::new).unwrap_or(path),
+        _ => path,
     }
+}
 
-    pub fn execute_pipeline(&mut self, pipeline: Pipeline) -> Result<DataFrame> {
-        let mut df = match pipeline.source {
-            Some(Source::Read(read_op)) => self.execute_read(read_op)?,
-            Some(Source::Variable(var_name)) => {
-                self.variables
-                    .get(&var_name)
-                    .ok_or_else(|| DtransformError::VariableNotFound(var_name.clone()))?
-                    .clone()
-            }
-            None => {
-                return Err(DtransformError::InvalidOperation(
-                    "Pipeline must start with a data source (read() or variable)".to_string(),
-                ));
-            }
-        };
+/// Internal column with the file each row was read from, while a pipeline uses file_name()
+const FILE_NAME_COLUMN: &str = "__dt_file_name";
 
-        for operation in pipeline.operations {
-            df = self.execute_operation(df, operation)?;
-        }
+/// `df` with `file` as the file of every row
+fn with_file_names(mut df: DataFrame, file: &str) -> Result<DataFrame> {
+    let height = df.height();
+    df.with_column(Series::new(PlSmallStr::from(FILE_NAME_COLUMN), vec![file; height]))?;
+    Ok(df)
+}
 
-        Ok(df)
+/// What a step does with FILE_NAME_COLUMN, which has to stay out of sight of
+/// steps that look at every column
+enum RowFiles {
+    /// The step picks, orders or repeats rows and the column goes along with them
+    Carry,
+    /// The step keeps every row in place: it runs without the column, which is
+    /// put back after it
+    SetAside,
+    /// The step replaces the rows with new ones, which come from no file
+    Remove,
+}
+
+fn row_files(op: &Operation) -> RowFiles {
+    match op {
+        Operation::Write(_)
+        | Operation::Select(_)
+        | Operation::Relocate(_)
+        | Operation::Mutate(_)
+        | Operation::Rename(_)
+        | Operation::RenameAll(_)
+        | Operation::Drop(_)
+        | Operation::Cast(_)
+        | Operation::MutateAll(_)
+        | Operation::FillNulls(_)
+        | Operation::Expect(_)
+        | Operation::WithRowNumber(_)
+        | Operation::SplitInto(_) => RowFiles::SetAside,
+        Operation::Describe(_)
+        | Operation::Count
+        | Operation::CountBy(_)
+        | Operation::Summarize(_)
+        | Operation::Resample(_) => RowFiles::Remove,
+        _ => RowFiles::Carry,
     }
+}
 
-    fn execute_operation(&mut self, df: DataFrame, op: Operation) -> Result<DataFrame> {
-        match op {
-            Operation::Read(read_op) => self.execute_read(read_op),
-            Operation::Variable(_var_name) => {
-                // Variable references should be handled as pipeline sources, not operations
-                Err(DtransformError::InvalidOperation(
-                    "Variable references can only be used as pipeline sources, not as operations".to_string()
-                ))
-            }
-            Operation::Write(write_op) => self.execute_write(df, write_op),
-            Operation::Select(select_op) => self.execute_select(df, select_op),
-            Operation::Filter(filter_op) => self.execute_filter(df, filter_op),
-            Operation::Mutate(mutate_op) => self.execute_mutate(df, mutate_op),
-            Operation::Rename(rename_op) => self.execute_rename(df, rename_op),
-            Operation::RenameAll(rename_all_op) => self.execute_rename_all(df, rename_all_op),
-            Operation::Sort(sort_op) => self.execute_sort(df, sort_op),
-            Operation::Take(take_op) => self.execute_take(df, take_op),
-            Operation::Skip(skip_op) => self.execute_skip(df, skip_op),
-            Operation::Slice(slice_op) => self.execute_slice(df, slice_op),
-            Operation::Drop(drop_op) => self.execute_drop(df, drop_op),
-            Operation::Distinct(distinct_op) => self.execute_distinct(df, distinct_op),
+/// Whether a step calls file_name(), so the rows read before it carry their file
+fn uses_file_name(op: &Operation) -> bool {
+    match op {
+        Operation::Filter(filter_op) => calls_file_name(&filter_op.condition),
+        Operation::Mutate(mutate_op) => mutate_op.assignments.iter().any(|a| calls_file_name(&a.expression)),
+        _ => false,
+    }
+}
+
+fn calls_file_name(expr: &Expression) -> bool {
+    match expr {
+        Expression::FunctionCall { name, args } => name == "file_name" || args.iter().any(calls_file_name),
+        Expression::BinaryOp { left, right, .. } => calls_file_name(left) || calls_file_name(right),
+        Expression::Negate(operand) => calls_file_name(operand),
+        Expression::MethodCall { object, args, .. } => calls_file_name(object) || args.iter().any(calls_file_name),
+        Expression::Split { string, delimiter, .. } => calls_file_name(string) || calls_file_name(delimiter),
+        Expression::Replace { text, old, new, .. } => {
+            calls_file_name(text) || calls_file_name(old) || calls_file_name(new)
+        }
+        Expression::Lookup { key, default, .. } => {
+            key.iter().any(calls_file_name) || default.as_deref().is_some_and(calls_file_name)
         }
+        Expression::FuzzyLookup { key, .. } => calls_file_name(key),
+        _ => false,
     }
+}
 
-    fn check_duplicate_columns(&self, df: &DataFrame) -> Result<()> {
-        use std::collections::HashSet;
-        let column_names: Vec<String> = df.get_column_names().iter().map(|s| s.to_string()).collect();
-        let mut seen = HashSet::new();
-        let mut duplicates = Vec::new();
+/// The single file, URL or database a read() loads; None for stdin and globs
+fn read_file_name(read_op: &ReadOp) -> Option<String> {
+    let single =
+        is_url(&read_op.path) || database::is_connection_string(&read_op.path) || !is_glob_pattern(&read_op.path);
+    (read_op.path != "-" && single).then(|| database::redact(&read_op.path))
+}
 
-        for name in &column_names {
-            if !seen.insert(name) {
-                duplicates.push(name.clone());
-            }
-        }
+const MICROS_PER_DAY: i64 = 86_400_000_000;
+
+/// Microseconds in one unit of a duration: days(n), hours(n), ...
+fn duration_unit_micros(unit: &str) -> Option<i64> {
+    match unit {
+        "weeks" => Some(7 * MICROS_PER_DAY),
+        "days" => Some(MICROS_PER_DAY),
+        "hours" => Some(3_600_000_000),
+        "minutes" => Some(60_000_000),
+        "seconds" => Some(1_000_000),
+        _ => None,
+    }
+}
 
-        if !duplicates.is_empty() {
-            return Err(DtransformError::InvalidOperation(format!(
-                "File contains duplicate column names: {}. Malformed files with repeated columns are not allowed.",
-                duplicates.join(", ")
-            )));
-        }
+/// A time window written as text: '7d', '12h', '30m', '45s', '2w'
+fn parse_window(text: &str) -> Option<i64> {
+    let text = text.trim();
+    let (count, unit) = text.split_at(text.find(|c: char| !c.is_ascii_digit())?);
+    let unit = match unit {
+        "w" => "weeks",
+        "d" => "days",
+        "h" => "hours",
+        "m" => "minutes",
+        "s" => "seconds",
+        _ => return None,
+    };
+    count.parse::<i64>().ok()?.checked_mul(duration_unit_micros(unit)?)
+}
 
-        Ok(())
+/// Rows upsample() may add before it asks for a wider step
+const MAX_UPSAMPLE_ROWS: i64 = 10_000_000;
+
+/// The every= step of resample() / upsample() in microseconds
+fn every_span(operation: &str, every: &str) -> Result<i64> {
+    match parse_window(every) {
+        Some(span) if span > 0 => Ok(span),
+        _ => Err(DtransformError::InvalidOperation(format!(
+            "{}() every= must be a time span like '15m', '1h', '1d' or '1w', got '{}'",
+            operation, every
+        ))),
     }
+}
 
-    fn execute_read(&self, op: ReadOp) -> Result<DataFrame> {
-        let path = std::path::Path::new(&op.path);
+/// Whether arithmetic with a duration moves the values in time
+fn is_date_like(dtype: &polars::datatypes::DataType) -> bool {
+    matches!(dtype, polars::datatypes::DataType::Date | polars::datatypes::DataType::Datetime(_, _))
+}
 
-        // Determine format from extension or explicit format
-        let format = op.format.as_deref().or_else(|| path.extension()?.to_str());
+/// `other` as the type of `values` when that is a date and `other` is text, so
+/// date columns compare with ISO dates: `day in ['2024-01-31']`
+fn as_comparable(values: &Series, other: &Series) -> Result<Series> {
+    if is_date_like(values.dtype()) && other.dtype() == &polars::datatypes::DataType::String {
+        Ok(other.cast(values.dtype())?)
+    } else {
+        Ok(other.clone())
+    }
+}
 
-        match format {
-            Some("csv") | Some("tsv") | None => {
-                let has_header = op.header.unwrap_or(true);
-                let skip_rows = op.skip_rows.unwrap_or(0);
-
-                // Determine delimiter and trim_whitespace
-                let (delimiter, trim_whitespace) = if op.delimiter.is_none() || op.trim_whitespace.is_none() {
-                    // Need to auto-detect delimiter and/or trim_whitespace
-                    let content = std::fs::read_to_string(path)?;
-                    let (detected_delim, detected_trim) = auto_detect_delimiter(&content, format)?;
-
-                    (
-                        op.delimiter.unwrap_or(detected_delim),
-                        op.trim_whitespace.unwrap_or(detected_trim)
-                    )
-                } else {
-                    (op.delimiter.unwrap(), op.trim_whitespace.unwrap())
-                };
+/// Seconds a URL source may take to download unless read(..., timeout=) says otherwise
+const DEFAULT_HTTP_TIMEOUT: u64 = 30;
 
-                let result = if trim_whitespace {
-                    // Read file, trim each line, and collapse multiple spaces
-                    let content = std::fs::read_to_string(path)?;
-                    let trimmed_content: String = content
-                        .lines()
-                        .map(|line| {
-                            // Trim leading/trailing whitespace
-                            let trimmed = line.trim();
-                            // Collapse multiple whitespace into single space
-                            trimmed.split_whitespace().collect::<Vec<_>>().join(" ")
-                        })
-                        .collect::<Vec<_>>()
-                        .join("\n");
-
-                    let cursor = std::io::Cursor::new(trimmed_content.as_bytes());
-                    CsvReadOptions::default()
-                        .with_has_header(has_header)
-                        .with_skip_rows(skip_rows)
-                        .with_parse_options(
-                            CsvParseOptions::default()
-                                .with_separator(delimiter as u8)
-                        )
-                        .into_reader_with_file_handle(cursor)
-                        .finish()
-                } else {
-                    // Standard file path reading
-                    CsvReadOptions::default()
-                        .with_has_header(has_header)
-                        .with_skip_rows(skip_rows)
-                        .with_parse_options(
-                            CsvParseOptions::default()
-                                .with_separator(delimiter as u8)
-                        )
-                        .try_into_reader_with_file_path(Some(path.into()))?
-                        .finish()
-                };
+/// Whether a read() path is a web address rather than a file
+fn is_url(path: &str) -> bool {
+    let lower = path.to_ascii_lowercase();
+    lower.starts_with("http://") || lower.starts_with("https://")
+}
 
-                match result {
-                    Ok(df) => {
-                        self.check_duplicate_columns(&df)?;
-                        Ok(df)
-                    },
-                    Err(e) => {
-                        let error_msg = e.to_string();
-                        if error_msg.contains("found more fields") || error_msg.contains("Schema") {
-                            Err(DtransformError::InvalidOperation(
-                                format!(
-                                    "CSV parsing error: Rows have different numbers of fields.\n\n\
-                                    The auto-detected settings may be incorrect:\n\
-                                    • Detected delimiter: {:?}\n\
-                                    • Detected trim_whitespace: {}\n\n\
-                                    Try specifying explicitly:\n\
-                                    • read('{}', delimiter=' ')  # space-separated\n\
-                                    • read('{}', delimiter='\\t')  # tab-separated\n\
-                                    • read('{}', trim_whitespace=true)\n\
-                                    • read('{}', skip_rows=N)  # skip header lines",
-                                    delimiter, trim_whitespace,
-                                    path.display(), path.display(), path.display(), path.display()
-                                )
-                            ))
-                        } else {
-                            Err(DtransformError::PolarsError(e))
-                        }
-                    }
-                }
-            }
-            Some("json") => {
-                let file = std::fs::File::open(path)?;
-                let df = JsonReader::new(file).finish()?;
-                self.check_duplicate_columns(&df)?;
-                Ok(df)
-            }
-            Some("parquet") => {
-                let file = std::fs::File::open(path)?;
-                let df = ParquetReader::new(file).finish()?;
-                self.check_duplicate_columns(&df)?;
-                Ok(df)
-            }
-            Some(_) => {
-                // Unknown extension - treat as delimited text file with auto-detection
-                let has_header = op.header.unwrap_or(true);
-                let skip_rows = op.skip_rows.unwrap_or(0);
-
-                // Determine delimiter and trim_whitespace
-                let (delimiter, trim_whitespace) = if op.delimiter.is_none() || op.trim_whitespace.is_none() {
-                    // Need to auto-detect delimiter and/or trim_whitespace
-                    let content = std::fs::read_to_string(path)?;
-                    let (detected_delim, detected_trim) = auto_detect_delimiter(&content, format)?;
-
-                    (
-                        op.delimiter.unwrap_or(detected_delim),
-                        op.trim_whitespace.unwrap_or(detected_trim)
-                    )
-                } else {
-                    (op.delimiter.unwrap(), op.trim_whitespace.unwrap())
-                };
+/// The file name in a URL, which names its format: `data.csv.gz` for
+/// `https://host/files/data.csv.gz?token=x`
+fn url_file_name(url: &str) -> &str {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    path.rsplit('/').next().unwrap_or(path)
+}
 
-                let result = if trim_whitespace {
-                    // Read file, trim each line, and collapse multiple spaces
-                    let content = std::fs::read_to_string(path)?;
-                    let trimmed_content: String = content
-                        .lines()
-                        .map(|line| {
-                            // Trim leading/trailing whitespace
-                            let trimmed = line.trim();
-                            // Collapse multiple whitespace into single space
-                            trimmed.split_whitespace().collect::<Vec<_>>().join(" ")
-                        })
-                        .collect::<Vec<_>>()
-                        .join("\n");
-
-                    let cursor = std::io::Cursor::new(trimmed_content.as_bytes());
-                    CsvReadOptions::default()
-                        .with_has_header(has_header)
-                        .with_skip_rows(skip_rows)
-                        .with_parse_options(
-                            CsvParseOptions::default()
-                                .with_separator(delimiter as u8)
-                        )
-                        .into_reader_with_file_handle(cursor)
-                        .finish()
-                } else {
-                    // Standard file path reading
-                    CsvReadOptions::default()
-                        .with_has_header(has_header)
-                        .with_skip_rows(skip_rows)
-                        .with_parse_options(
-                            CsvParseOptions::default()
-                                .with_separator(delimiter as u8)
-                        )
-                        .try_into_reader_with_file_path(Some(path.into()))?
-                        .finish()
-                };
+/// Everything a compressed stream decodes to
+fn decompress(reader: impl std::io::Read, compression: Compression) -> Result<Vec<u8>> {
+    use std::io::Read;
 
-                match result {
-                    Ok(df) => {
-                        self.check_duplicate_columns(&df)?;
-                        Ok(df)
-                    },
-                    Err(e) => {
-                        let error_msg = e.to_string();
-                        if error_msg.contains("found more fields") || error_msg.contains("Schema") {
-                            Err(DtransformError::InvalidOperation(
-                                format!(
-                                    "CSV parsing error: Rows have different numbers of fields.\n\n\
-                                    The auto-detected settings may be incorrect:\n\
-                                    • Detected delimiter: {:?}\n\
-                                    • Detected trim_whitespace: {}\n\n\
-                                    Try specifying explicitly:\n\
-                                    • read('{}', delimiter=' ')  # space-separated\n\
-                                    • read('{}', delimiter='\\t')  # tab-separated\n\
-                                    • read('{}', trim_whitespace=true)\n\
-                                    • read('{}', skip_rows=N)  # skip header lines",
-                                    delimiter, trim_whitespace,
-                                    path.display(), path.display(), path.display(), path.display()
-                                )
-                            ))
-                        } else {
-                            Err(DtransformError::PolarsError(e))
-                        }
-                    }
-                }
-            }
-        }
+    let mut bytes = Vec::new();
+    match compression {
+        // Concatenated gzip members are read as one stream, like gunzip does
+        Compression::Gzip => flate2::read::MultiGzDecoder::new(reader).read_to_end(&mut bytes)?,
+        Compression::Zstd => zstd::stream::read::Decoder::new(reader)?.read_to_end(&mut bytes)?,
+        Compression::Bzip2 => bzip2::read::MultiBzDecoder::new(reader).read_to_end(&mut bytes)?,
+    };
+    Ok(bytes)
+}
+
+/// Decode raw file bytes as text in the given encoding (UTF-8 when None)
+fn decode_text(bytes: Vec<u8>, encoding: Option<&str>, source: &str) -> Result<String> {
+    // Windows-1252 differs from Latin-1 only in 0x80-0x9F (undefined bytes map to themselves)
+    const CP1252_HIGH: [char; 32] = [
+        '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8d}', 'Ž', '\u{8f}',
+        '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9d}', 'ž', 'Ÿ',
+    ];
+
+    match encoding {
+        None | Some("utf8") | Some("utf-8") => String::from_utf8(bytes).map_err(|_| not_utf8(source)),
+        Some("utf8-lossy") => Ok(String::from_utf8_lossy(&bytes).into_owned()),
+        Some("latin1") | Some("iso-8859-1") => Ok(bytes.iter().map(|&b| b as char).collect()),
+        Some("windows-1252") | Some("cp1252") => Ok(bytes
+            .iter()
+            .map(|&b| match b {
+                0x80..=0x9F => CP1252_HIGH[(b - 0x80) as usize],
+                _ => b as char,
+            })
+            .collect()),
+        Some(other) => Err(DtransformError::InvalidOperation(format!("Unsupported encoding '{}'", other))),
     }
+}
 
-    fn execute_write(&self, df: DataFrame, op: WriteOp) -> Result<DataFrame> {
-        let path = std::path::Path::new(&op.path);
-        let format = op.format.as_deref().or_else(|| path.extension()?.to_str());
+fn not_utf8(source: &str) -> DtransformError {
+    DtransformError::InvalidOperation(format!(
+        "{} is not valid UTF-8. Set its encoding, e.g. read('{}', encoding='latin1'), \
+        or use encoding='utf8-lossy' to replace invalid bytes",
+        source, source
+    ))
+}
 
-        match format {
-            Some("csv") | Some("tsv") | None => {
-                let mut file = std::fs::File::create(path)?;
-                let delimiter = op.delimiter.unwrap_or(if format == Some("tsv") { '\t' } else { ',' });
-                let has_header = op.header.unwrap_or(true);  // Default to true if not specified
+/// Bytes from the start of delimited text that delimiter and whitespace detection look at
+const DETECTION_SAMPLE_BYTES: usize = 256 * 1024;
 
-                CsvWriter::new(&mut file)
-                    .with_separator(delimiter as u8)
-                    .include_header(has_header)
-                    .finish(&mut df.clone())?;
-            }
-            Some("json") => {
-                let mut file = std::fs::File::create(path)?;
-                JsonWriter::new(&mut file)
-                    .finish(&mut df.clone())?;
-            }
-            Some("parquet") => {
-                let mut file = std::fs::File::create(path)?;
-                ParquetWriter::new(&mut file)
-                    .finish(&mut df.clone())?;
-            }
-            Some(_) => {
-                // Unknown extension - treat as delimited text file
-                let mut file = std::fs::File::create(path)?;
-                let delimiter = op.delimiter.unwrap_or(',');
-                let has_header = op.header.unwrap_or(true);
+/// The start of delimited text for detection: up to DETECTION_SAMPLE_BYTES, ending
+/// at a line break when there is more
+fn detection_sample(content: &str) -> &str {
+    if content.len() <= DETECTION_SAMPLE_BYTES {
+        return content;
+    }
+    let mut end = DETECTION_SAMPLE_BYTES;
+    while !content.is_char_boundary(end) {
+        end -= 1;
+    }
+    match content[..end].rfind('\n') {
+        Some(line_end) => &content[..line_end],
+        None => &content[..end],
+    }
+}
 
-                CsvWriter::new(&mut file)
-                    .with_separator(delimiter as u8)
-                    .include_header(has_header)
-                    .finish(&mut df.clone())?;
-            }
+/// The detection sample of a UTF-8 file, read without the rest of the file
+fn read_detection_sample(path: &Path) -> Result<String> {
+    use std::io::Read;
+    let mut bytes = Vec::with_capacity(DETECTION_SAMPLE_BYTES);
+    std::fs::File::open(path)?.take(DETECTION_SAMPLE_BYTES as u64).read_to_end(&mut bytes)?;
+    if bytes.len() == DETECTION_SAMPLE_BYTES {
+        // Drop the partial last line, which may end inside a character
+        if let Some(line_end) = bytes.iter().rposition(|&b| b == b'\n') {
+            bytes.truncate(line_end);
         }
-
-        Ok(df)
     }
+    decode_text(bytes, None, &path.display().to_string())
+}
 
-    fn execute_select(&self, df: DataFrame, op: SelectOp) -> Result<DataFrame> {
-        let schema = df.schema();
-        let mut selected_columns = Vec::new();
-        let mut aliases = Vec::new();
-
-        for (selector, alias) in op.selectors {
-            let cols = self.resolve_selector(&selector, &schema, &df)?;
-
-            // If there's an alias, it applies to all columns from this selector
-            // (most commonly just one column, but could be multiple with regex, etc.)
-            for col in cols {
-                selected_columns.push(col);
-                aliases.push(alias.clone());
-            }
+/// Append a line trimmed, with runs of whitespace collapsed to one space
+fn push_trimmed_line(out: &mut String, line: &str) {
+    for (i, word) in line.split_whitespace().enumerate() {
+        if i > 0 {
+            out.push(' ');
         }
+        out.push_str(word);
+    }
+    out.push('\n');
+}
 
-        if selected_columns.is_empty() {
-            return Err(DtransformError::InvalidOperation(
-                "No columns selected".to_string(),
-            ));
+/// A UTF-8 file with every line trimmed, built line by line so the untrimmed text
+/// is never held in memory
+fn read_trimmed_lines(path: &Path) -> Result<String> {
+    use std::io::BufRead;
+    let source = path.display().to_string();
+    let mut reader = std::io::BufReader::new(std::fs::File::open(path)?);
+    let mut trimmed = String::new();
+    let mut line = String::new();
+    loop {
+        line.clear();
+        let read = reader.read_line(&mut line).map_err(|e| match e.kind() {
+            std::io::ErrorKind::InvalidData => not_utf8(&source),
+            _ => e.into(),
+        })?;
+        if read == 0 {
+            return Ok(trimmed);
         }
+        push_trimmed_line(&mut trimmed, &line);
+    }
+}
 
-        let mut result = df.select(&selected_columns)?;
+/// Whether the read() encoding needs the file transcoded before parsing
+fn needs_transcoding(encoding: Option<&str>) -> bool {
+    !matches!(encoding, None | Some("utf8") | Some("utf-8"))
+}
 
-        // Apply aliases where provided
-        for (i, alias_opt) in aliases.iter().enumerate() {
-            if let Some(alias) = alias_opt {
-                let old_name = result.get_column_names()[i].to_string();
-                result.rename(&old_name, PlSmallStr::from(alias.as_str()))?;
-            }
-        }
+/// Whether a read() path should be expanded as a glob (e.g. 'logs/2024-*.csv')
+fn is_glob_pattern(path: &str) -> bool {
+    path.contains(['*', '?', '['])
+}
 
-        Ok(result)
+/// Instantiate a per-column expression template for `column`. A bare name is a
+/// function applied to the column (`trim` means `trim(column)`); otherwise every
+/// `_` in the expression stands for the column (`round(_, 2)`).
+fn expand_column_template(template: &Expression, column: &str) -> Expression {
+    match template {
+        Expression::Column(ColumnRef::Name(name)) if name != "_" => Expression::FunctionCall {
+            name: name.clone(),
+            args: vec![Expression::Column(ColumnRef::Name(column.to_string()))],
+        },
+        _ => substitute_placeholder(template, column),
     }
+}
 
-    fn resolve_selector(
-        &self,
-        selector: &ColumnSelector,
-        schema: &Schema,
-        df: &DataFrame,
-    ) -> Result<Vec<String>> {
-        match selector {
-            ColumnSelector::Name(name) => {
-                if schema.contains(name) {
-                    Ok(vec![name.clone()])
-                } else {
-                    Err(DtransformError::ColumnNotFound(name.clone()))
-                }
-            }
+/// Replace every `_` column reference in an expression with `column`
+fn substitute_placeholder(expr: &Expression, column: &str) -> Expression {
+    rewrite_expression(expr, &|e| match e {
+        Expression::Column(ColumnRef::Name(name)) if name == "_" => {
+            Some(Expression::Column(ColumnRef::Name(column.to_string())))
+        }
+        _ => None,
+    })
+}
 
-            ColumnSelector::Index(idx) => {
-                let name = schema
-                    .get_at_index(*idx)
-                    .ok_or_else(|| {
-                        DtransformError::InvalidOperation(format!("Column index {} out of bounds", idx))
-                    })?
-                    .0
-                    .clone();
-                Ok(vec![name.as_str().to_string()])
-            }
+/// Rebuild an expression bottom-up, replacing every node for which `f` returns Some
+fn rewrite_expression(expr: &Expression, f: &dyn Fn(&Expression) -> Option<Expression>) -> Expression {
+    if let Some(replacement) = f(expr) {
+        return replacement;
+    }
 
-            ColumnSelector::Range(start, end) => {
-                let names: Vec<String> = schema
-                    .iter()
-                    .enumerate()
-                    .filter(|(i, _)| i >= start && i <= end)
-                    .map(|(_, (name, _))| name.as_str().to_string())
-                    .collect();
+    let sub = |e: &Expression| Box::new(rewrite_expression(e, f));
+    let sub_all = |args: &[Expression]| args.iter().map(|e| rewrite_expression(e, f)).collect();
+
+    match expr {
+        Expression::BinaryOp { left, op, right } => Expression::BinaryOp {
+            left: sub(left),
+            op: op.clone(),
+            right: sub(right),
+        },
+        Expression::Negate(operand) => Expression::Negate(sub(operand)),
+        Expression::MethodCall { object, method, args } => Expression::MethodCall {
+            object: sub(object),
+            method: method.clone(),
+            args: sub_all(args),
+        },
+        Expression::Split { string, delimiter, index } => Expression::Split {
+            string: sub(string),
+            delimiter: sub(delimiter),
+            index: *index,
+        },
+        Expression::Lookup { table, key, on, return_field, default, strict } => Expression::Lookup {
+            table: table.clone(),
+            key: sub_all(key),
+            on: on.clone(),
+            return_field: return_field.clone(),
+            default: default.as_deref().map(sub),
+            strict: *strict,
+        },
+        Expression::FuzzyLookup { table, key, on, return_field, max_dist } => Expression::FuzzyLookup {
+            table: table.clone(),
+            key: sub(key),
+            on: on.clone(),
+            return_field: return_field.clone(),
+            max_dist: *max_dist,
+        },
+        Expression::Replace { text, old, new, count, case_sensitive } => Expression::Replace {
+            text: sub(text),
+            old: sub(old),
+            new: sub(new),
+            count: *count,
+            case_sensitive: *case_sensitive,
+        },
+        Expression::FunctionCall { name, args } => Expression::FunctionCall {
+            name: name.clone(),
+            args: sub_all(args),
+        },
+        other => other.clone(),
+    }
+}
 
-                if names.is_empty() {
-                    return Err(DtransformError::InvalidOperation(
-                        format!("Range ${}..${} is out of bounds or invalid", start + 1, end + 1)
-                    ));
-                }
+/// How floats are shown in printed tables: '%.3f' for a fixed number of decimals,
+/// 'full' for every digit without scientific notation, or 'auto' (the default)
+pub fn set_float_format(spec: &str) -> Result<()> {
+    use polars_core::fmt::{set_float_fmt, set_float_precision, FloatFmt};
+    match spec.trim() {
+        "auto" => {
+            set_float_fmt(FloatFmt::Mixed);
+            set_float_precision(None);
+        }
+        "full" => {
+            set_float_fmt(FloatFmt::Full);
+            set_float_precision(None);
+        }
+        other => {
+            let precision = other
+                .strip_prefix("%.")
+                .and_then(|rest| rest.strip_suffix('f'))
+                .and_then(|digits| digits.parse::<usize>().ok())
+                .ok_or_else(|| DtransformError::InvalidOperation(format!(
+                    "Invalid float format '{}'. Expected '%.Nf' (e.g. '%.2f'), 'full' or 'auto'",
+                    other
+                )))?;
+            set_float_precision(Some(precision));
+        }
+    }
+    Ok(())
+}
 
-                Ok(names)
-            }
+/// The float format set by set_float_format()
+pub fn float_format() -> String {
+    use polars_core::fmt::{get_float_fmt, get_float_precision, FloatFmt};
+    match (get_float_precision(), get_float_fmt()) {
+        (Some(precision), _) => format!("%.{}f", precision),
+        (None, FloatFmt::Full) => "full".to_string(),
+        (None, FloatFmt::Mixed) => "auto".to_string(),
+    }
+}
 
-            ColumnSelector::Regex(pattern) => {
-                let re = Regex::new(pattern)?;
-                let names: Vec<String> = schema
-                    .iter()
-                    .filter(|(name, _)| re.is_match(name.as_str()))
-                    .map(|(name, _)| name.as_str().to_string())
-                    .collect();
-                Ok(names)
-            }
+/// A printf-style number format with one conversion: '%.2f', '%d', '%05d', '%.3e', '$%.2f'
+struct NumberFormat {
+    prefix: String,
+    suffix: String,
+    zero_pad: bool,
+    width: usize,
+    precision: Option<usize>,
+    conversion: char,
+}
 
-            ColumnSelector::Type(dtypes) => {
-                let names: Vec<String> = schema
-                    .iter()
-                    .filter(|(_, field)| {
-                        dtypes.iter().any(|dt| self.matches_dtype(dt, field))
-                    })
-                    .map(|(name, _)| name.as_str().to_string())
-                    .collect();
-                Ok(names)
-            }
+impl NumberFormat {
+    fn parse(fmt: &str) -> Result<Self> {
+        let invalid = || DtransformError::InvalidOperation(format!(
+            "Invalid number format '{}'. Expected one of %d, %05d, %f, %.2f, %e, %.3e (with optional surrounding text)",
+            fmt
+        ));
 
-            ColumnSelector::All => Ok(schema.iter().map(|(name, _)| name.as_str().to_string()).collect()),
+        let mut prefix = String::new();
+        let mut chars = fmt.chars().peekable();
+        let mut spec = None;
 
-            ColumnSelector::Except(inner) => {
-                let all_cols: Vec<String> = schema.iter().map(|(name, _)| name.as_str().to_string()).collect();
-                let excluded = self.resolve_selector(inner, schema, df)?;
-                Ok(all_cols
-                    .into_iter()
-                    .filter(|col| !excluded.contains(col))
-                    .collect())
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                prefix.push(c);
+                continue;
+            }
+            if chars.peek() == Some(&'%') {
+                chars.next();
+                prefix.push('%');
+                continue;
             }
 
-            ColumnSelector::And(left, right) => {
-                let left_cols = self.resolve_selector(left, schema, df)?;
-                let right_cols = self.resolve_selector(right, schema, df)?;
-                Ok(left_cols
-                    .into_iter()
-                    .filter(|col| right_cols.contains(col))
-                    .collect())
+            let zero_pad = chars.peek() == Some(&'0');
+            let mut width = String::new();
+            while let Some(d) = chars.peek().filter(|d| d.is_ascii_digit()) {
+                width.push(*d);
+                chars.next();
+            }
+            let width = if width.is_empty() { 0 } else { width.parse::<usize>().map_err(|_| invalid())? };
+
+            let mut precision = None;
+            if chars.peek() == Some(&'.') {
+                chars.next();
+                let mut digits = String::new();
+                while let Some(d) = chars.peek().filter(|d| d.is_ascii_digit()) {
+                    digits.push(*d);
+                    chars.next();
+                }
+                precision = Some(digits.parse::<usize>().map_err(|_| invalid())?);
             }
+            let conversion = chars.next().filter(|c| matches!(c, 'd' | 'f' | 'e')).ok_or_else(invalid)?;
+            spec = Some((zero_pad, width, precision, conversion));
+            break;
         }
-    }
 
-    fn matches_dtype(&self, dt: &crate::parser::ast::DataType, polars_dt: &polars::datatypes::DataType) -> bool {
-        use polars::datatypes::DataType as PDT;
-        use crate::parser::ast::DataType as AstDT;
-        match dt {
-            AstDT::Number => matches!(
-                polars_dt,
-                PDT::Int8
-                    | PDT::Int16
-                    | PDT::Int32
-                    | PDT::Int64
-                    | PDT::UInt8
-                    | PDT::UInt16
-                    | PDT::UInt32
-                    | PDT::UInt64
-                    | PDT::Float32
-                    | PDT::Float64
-            ),
-            AstDT::String => matches!(polars_dt, PDT::String),
-            AstDT::Boolean => matches!(polars_dt, PDT::Boolean),
-            AstDT::Date => matches!(polars_dt, PDT::Date),
-            AstDT::DateTime => matches!(polars_dt, PDT::Datetime(_, _)),
-        }
+        let (zero_pad, width, precision, conversion) = spec.ok_or_else(invalid)?;
+        let suffix = chars.collect::<String>().replace("%%", "%");
+        Ok(Self { prefix, suffix, zero_pad, width, precision, conversion })
     }
 
-    fn execute_filter(&self, df: DataFrame, op: FilterOp) -> Result<DataFrame> {
-        let mask = self.evaluate_expression(&op.condition, &df)?;
-        let mask_bool = mask.bool()?;
-        Ok(df.filter(mask_bool)?)
+    fn render(&self, value: f64) -> String {
+        let number = match self.conversion {
+            'd' => format!("{}", value.round() as i64),
+            'e' => format!("{:.*e}", self.precision.unwrap_or(6), value),
+            _ => format!("{:.*}", self.precision.unwrap_or(6), value),
+        };
+        let number = if self.zero_pad {
+            // Zeros go after the sign: -0042
+            match number.strip_prefix('-') {
+                Some(digits) => format!("-{:0>width$}", digits, width = self.width.saturating_sub(1)),
+                None => format!("{:0>width$}", number, width = self.width),
+            }
+        } else {
+            format!("{:>width$}", number, width = self.width)
+        };
+        format!("{}{}{}", self.prefix, number, self.suffix)
     }
+}
 
-    fn execute_mutate(&self, mut df: DataFrame, op: MutateOp) -> Result<DataFrame> {
-        for assignment in op.assignments {
-            let series = self.evaluate_expression(&assignment.expression, &df)?;
+/// One argument value of format() / concat()
+enum FormatValue {
+    Int(i64),
+    Float(f64),
+    Text(String),
+}
 
-            // Resolve column name from AssignmentTarget
-            let col_name = match &assignment.column {
-                AssignmentTarget::Name(name) => name.clone(),
-                AssignmentTarget::Position(pos) => {
-                    if *pos == 0 {
-                        return Err(DtransformError::InvalidOperation(
-                            "Position $0 is invalid; column positions start at $1".to_string()
-                        ));
-                    }
-                    let col_names = df.get_column_names();
-                    if *pos <= col_names.len() {
-                        // Use existing column name
-                        col_names[pos - 1].to_string()
-                    } else {
-                        // Create new column with auto-generated name (like CSV no-header behavior)
-                        format!("column_{}", pos)
-                    }
-                }
-            };
+/// A `{}` field of a format() template: `{}`, `{1}`, `{:03}`, `{:.2f}`, `{:>8}`, `{:*<6}`
+struct FormatField {
+    index: usize,
+    fill: char,
+    align: Option<char>,
+    zero_pad: bool,
+    width: usize,
+    precision: Option<usize>,
+    conversion: Option<char>,
+}
 
-            let renamed_series = series.with_name(PlSmallStr::from(col_name.as_str()));
-            let _ = df.with_column(renamed_series)?;
-        }
+enum FormatPiece {
+    Text(String),
+    Field(FormatField),
+}
 
-        Ok(df)
-    }
+/// A Python-style format string: literal text and `{}` fields; `{{` and `}}` are literal braces
+struct FormatTemplate {
+    pieces: Vec<FormatPiece>,
+}
 
-    fn execute_rename(&self, df: DataFrame, op: RenameOp) -> Result<DataFrame> {
-        let mut result = df;
-        for (col_ref, new_name) in op.mappings {
-            let old_name = self.resolve_column_name(&col_ref, &result)?;
-            result.rename(&old_name, PlSmallStr::from(new_name.as_str()))?;
-        }
-        Ok(result)
-    }
+impl FormatTemplate {
+    fn parse(template: &str) -> Result<Self> {
+        let invalid = |field: &str, reason: &str| DtransformError::InvalidOperation(format!(
+            "Invalid field '{{{}}}' in format template '{}': {}", field, template, reason
+        ));
 
-    fn execute_rename_all(&self, mut df: DataFrame, op: RenameAllOp) -> Result<DataFrame> {
-        match &op.strategy {
-            RenameStrategy::Replace { old, new } => {
-                let old_names: Vec<String> = df
-                    .get_column_names()
-                    .iter()
-                    .map(|s| s.as_str().to_string())
-                    .collect();
+        let mut pieces = Vec::new();
+        let mut text = String::new();
+        let mut next_index = 0;
+        let mut chars = template.chars().peekable();
 
-                for old_name in old_names {
-                    let new_name = old_name.replace(old, new);
-                    df.rename(&old_name, PlSmallStr::from(new_name.as_str()))?;
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
                 }
-
-                Ok(df)
-            }
-            RenameStrategy::Sequential { prefix, start, end } => {
-                let num_cols = df.width();
-                let range_size = end - start + 1;
-
-                if range_size != num_cols {
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '}' => {
                     return Err(DtransformError::InvalidOperation(format!(
-                        "Range {}..{} ({} columns) doesn't match table width ({} columns). Use select() first.",
-                        start, end, range_size, num_cols
+                        "Unmatched '}}' in format template '{}'. Use '}}}}' for a literal brace", template
                     )));
                 }
+                '{' => {
+                    let mut field = String::new();
+                    let mut closed = false;
+                    for ch in chars.by_ref() {
+                        if ch == '}' {
+                            closed = true;
+                            break;
+                        }
+                        field.push(ch);
+                    }
+                    if !closed {
+                        return Err(DtransformError::InvalidOperation(format!(
+                            "Unclosed '{{' in format template '{}'. Use '{{{{' for a literal brace", template
+                        )));
+                    }
 
-                let old_names: Vec<String> = df
-                    .get_column_names()
-                    .iter()
-                    .map(|s| s.as_str().to_string())
-                    .collect();
+                    let (position, spec) = field.split_once(':').unwrap_or((field.as_str(), ""));
+                    let index = if position.trim().is_empty() {
+                        next_index += 1;
+                        next_index - 1
+                    } else {
+                        position.trim().parse::<usize>().map_err(|_| invalid(&field, "expected an argument number"))?
+                    };
+
+                    let spec: Vec<char> = spec.chars().collect();
+                    let mut pos = 0;
+                    let (mut fill, mut align) = (' ', None);
+                    if spec.len() >= 2 && matches!(spec[1], '<' | '>' | '^') {
+                        fill = spec[0];
+                        align = Some(spec[1]);
+                        pos = 2;
+                    } else if spec.first().is_some_and(|c| matches!(c, '<' | '>' | '^')) {
+                        align = Some(spec[0]);
+                        pos = 1;
+                    }
+                    let zero_pad = spec.get(pos) == Some(&'0');
+                    let digits = |pos: &mut usize| {
+                        let start = *pos;
+                        while spec.get(*pos).is_some_and(|c| c.is_ascii_digit()) {
+                            *pos += 1;
+                        }
+                        spec[start..*pos].iter().collect::<String>()
+                    };
+                    let width = digits(&mut pos).parse::<usize>().unwrap_or(0);
+                    let mut precision = None;
+                    if spec.get(pos) == Some(&'.') {
+                        pos += 1;
+                        let prec = digits(&mut pos);
+                        precision = Some(prec.parse::<usize>().map_err(|_| invalid(&field, "expected digits after '.'"))?);
+                    }
+                    let conversion = spec.get(pos).copied();
+                    if conversion.is_some_and(|c| !matches!(c, 'd' | 'f' | 'e' | 's')) || spec.len() > pos + 1 {
+                        return Err(invalid(&field, "expected [[fill]align][0][width][.precision][d|f|e|s]"));
+                    }
 
-                for (i, old_name) in old_names.iter().enumerate() {
-                    let new_name = format!("{}{}", prefix, start + i);
-                    df.rename(old_name, PlSmallStr::from(new_name.as_str()))?;
+                    if !text.is_empty() {
+                        pieces.push(FormatPiece::Text(std::mem::take(&mut text)));
+                    }
+                    pieces.push(FormatPiece::Field(FormatField { index, fill, align, zero_pad, width, precision, conversion }));
                 }
-
-                Ok(df)
+                _ => text.push(c),
             }
         }
+        if !text.is_empty() {
+            pieces.push(FormatPiece::Text(text));
+        }
+        Ok(Self { pieces })
     }
 
-    fn execute_sort(&self, df: DataFrame, op: SortOp) -> Result<DataFrame> {
-        let col_names: Vec<String> = op
-            .columns
+    /// Largest argument number a field refers to, plus one
+    fn arg_count(&self) -> usize {
+        self.pieces
             .iter()
-            .map(|(col_ref, _)| self.resolve_column_name(col_ref, &df))
-            .collect::<Result<Vec<_>>>()?;
+            .filter_map(|piece| match piece {
+                FormatPiece::Field(field) => Some(field.index + 1),
+                FormatPiece::Text(_) => None,
+            })
+            .max()
+            .unwrap_or(0)
+    }
 
-        let descending: Vec<bool> = op
-            .columns
+    fn render(&self, values: &[&FormatValue]) -> String {
+        let mut out = String::new();
+        for piece in &self.pieces {
+            match piece {
+                FormatPiece::Text(text) => out.push_str(text),
+                FormatPiece::Field(field) => out.push_str(&field.render(values[field.index])),
+            }
+        }
+        out
+    }
+}
+
+impl FormatField {
+    fn render(&self, value: &FormatValue) -> String {
+        let number = |n: f64| match (self.conversion, self.precision) {
+            (Some('d'), _) => format!("{}", n.round() as i64),
+            (Some('e'), precision) => format!("{:.*e}", precision.unwrap_or(6), n),
+            (Some('f'), precision) => format!("{:.*}", precision.unwrap_or(6), n),
+            (_, Some(precision)) => format!("{:.*}", precision, n),
+            _ => format!("{}", n),
+        };
+        let (text, numeric) = match value {
+            FormatValue::Int(i) if self.conversion.is_none() && self.precision.is_none() => (i.to_string(), true),
+            FormatValue::Int(i) => (number(*i as f64), true),
+            FormatValue::Float(f) => (number(*f), true),
+            FormatValue::Text(s) => match self.precision {
+                // Precision truncates text, as in Python
+                Some(precision) => (s.chars().take(precision).collect(), false),
+                None => (s.clone(), false),
+            },
+        };
+
+        let missing = self.width.saturating_sub(text.chars().count());
+        if missing == 0 {
+            return text;
+        }
+        if self.zero_pad && self.align.is_none() {
+            // Zeros go after the sign: -0042
+            return match text.strip_prefix('-').filter(|_| numeric) {
+                Some(digits) => format!("-{}{}", "0".repeat(missing), digits),
+                None => format!("{}{}", "0".repeat(missing), text),
+            };
+        }
+
+        let fill = |n: usize| std::iter::repeat_n(self.fill, n).collect::<String>();
+        // Numbers align right and text left by default
+        match self.align.unwrap_or(if numeric { '>' } else { '<' }) {
+            '<' => format!("{}{}", text, fill(missing)),
+            '^' => format!("{}{}{}", fill(missing / 2), text, fill(missing - missing / 2)),
+            _ => format!("{}{}", fill(missing), text),
+        }
+    }
+}
+
+/// Unmatched keys named in the error of lookup(..., strict=true)
+const LOOKUP_MISSES_SHOWN: usize = 5;
+
+/// Words of a column name for snake_case / camelCase: split at anything other
+/// than letters and digits, and where the case changes (firstName, HTTPStatus)
+fn name_words(name: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    for part in name.split(|c: char| !c.is_alphanumeric()).filter(|part| !part.is_empty()) {
+        let chars: Vec<char> = part.chars().collect();
+        let mut word = String::new();
+        for (i, &c) in chars.iter().enumerate() {
+            let starts_word = i > 0
+                && c.is_uppercase()
+                && (!chars[i - 1].is_uppercase() || chars.get(i + 1).is_some_and(|next| next.is_lowercase()));
+            if starts_word && !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            word.push(c);
+        }
+        words.push(word);
+    }
+    words
+}
+
+fn snake_case(name: &str) -> String {
+    name_words(name).iter().map(|word| word.to_lowercase()).collect::<Vec<_>>().join("_")
+}
+
+fn camel_case(name: &str) -> String {
+    let mut result = String::new();
+    for (i, word) in name_words(name).iter().enumerate() {
+        let word = word.to_lowercase();
+        let mut chars = word.chars();
+        match chars.next() {
+            Some(first) if i > 0 => {
+                result.extend(first.to_uppercase());
+                result.push_str(chars.as_str());
+            }
+            _ => result.push_str(&word),
+        }
+    }
+    result
+}
+
+/// Give the columns of `df` the new `names`, checking first that no two end up
+/// the same. With on_conflict='suffix' later duplicates become name_2, name_3, ...
+fn rename_columns(operation: &str, mut df: DataFrame, mut names: Vec<String>, on_conflict: RenameConflict) -> Result<DataFrame> {
+    use std::collections::HashSet;
+
+    if let Some((old, _)) = df.get_column_names().iter().zip(&names).find(|(_, name)| name.is_empty()) {
+        return Err(DtransformError::InvalidOperation(format!(
+            "{}() would leave '{}' with an empty column name",
+            operation, old
+        )));
+    }
+
+    let mut seen: HashSet<String> = HashSet::new();
+    match on_conflict {
+        RenameConflict::Error => {
+            if let Some(duplicate) = names.iter().find(|name| !seen.insert(name.to_string())) {
+                let sources: Vec<String> = df
+                    .get_column_names()
+                    .iter()
+                    .zip(&names)
+                    .filter(|(_, name)| *name == duplicate)
+                    .map(|(old, _)| format!("'{}'", old))
+                    .collect();
+                return Err(DtransformError::InvalidOperation(format!(
+                    "{}() would give {} columns the name '{}' ({}). Rename them apart, or pass on_conflict='suffix'",
+                    operation, sources.len(), duplicate, sources.join(", ")
+                )));
+            }
+        }
+        RenameConflict::Suffix => {
+            let mut taken: HashSet<String> = names.iter().cloned().collect();
+            for name in names.iter_mut() {
+                if seen.insert(name.clone()) {
+                    continue;
+                }
+                let mut n = 2;
+                while taken.contains(&format!("{}_{}", name, n)) {
+                    n += 1;
+                }
+                *name = format!("{}_{}", name, n);
+                taken.insert(name.clone());
+            }
+        }
+    }
+
+    df.set_column_names(names.iter().map(|name| PlSmallStr::from(name.as_str())))?;
+    Ok(df)
+}
+
+/// Display text of a reduced value (min/max), None for null
+fn scalar_text(value: &AnyValue) -> Option<String> {
+    match value {
+        AnyValue::Null => None,
+        other => Some(other.get_str().map(|s| s.to_string()).unwrap_or_else(|| other.to_string())),
+    }
+}
+
+/// The any(...)/all(...) column quantifier that is a direct operand of `expr`, if any.
+/// The enclosing expression is the predicate that gets applied to each column.
+fn direct_quantifier(expr: &Expression) -> Option<(Quantifier, &Vec<ColumnSelector>)> {
+    let children: Vec<&Expression> = match expr {
+        Expression::BinaryOp { left, right, .. } => vec![left.as_ref(), right.as_ref()],
+        Expression::Negate(operand) => vec![operand.as_ref()],
+        Expression::MethodCall { object, args, .. } => std::iter::once(object.as_ref()).chain(args.iter()).collect(),
+        Expression::FunctionCall { args, .. } => args.iter().collect(),
+        Expression::Split { string, .. } => vec![string.as_ref()],
+        Expression::Replace { text, .. } => vec![text.as_ref()],
+        _ => return None,
+    };
+    children.into_iter().find_map(|child| match child {
+        Expression::ColumnQuantifier { quantifier, columns } => Some((*quantifier, columns)),
+        _ => None,
+    })
+}
+
+/// The first column an expression reads, left to right: `tags` in split_all(tags, ';')
+fn first_column(expr: &Expression) -> Option<&ColumnRef> {
+    match expr {
+        Expression::Column(column) => Some(column),
+        Expression::BinaryOp { left, right, .. } => first_column(left).or_else(|| first_column(right)),
+        Expression::Negate(operand) => first_column(operand),
+        Expression::MethodCall { object, args, .. } => {
+            first_column(object).or_else(|| args.iter().find_map(first_column))
+        }
+        Expression::Split { string, .. } => first_column(string),
+        Expression::Replace { text, .. } => first_column(text),
+        Expression::FunctionCall { args, .. } => args.iter().find_map(first_column),
+        _ => None,
+    }
+}
+
+/// Auto-detect delimiter from file content
+/// Returns (delimiter, needs_trim_whitespace)
+fn auto_detect_delimiter(content: &str, file_extension: Option<&str>) -> Result<(char, bool)> {
+    // For .tsv files, use tab delimiter but check if trimming is needed
+    if file_extension == Some("tsv") {
+        let needs_trim = content.lines().take(100).any(|line| {
+            line.trim() != line || line.contains("  ")
+        });
+        return Ok(('\t', needs_trim));
+    }
+
+    // For .csv files, prefer comma but check if trimming is needed
+    if file_extension == Some("csv") {
+        // Check if file has leading/trailing whitespace or multiple consecutive spaces
+        let needs_trim = content.lines().take(100).any(|line| {
+            line.trim() != line || line.contains("  ")
+        });
+        return Ok((',', needs_trim));
+    }
+
+    // Sample first 100 non-empty lines for detection
+    let sample_lines: Vec<&str> = content
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .take(100)
+        .collect();
+
+    if sample_lines.is_empty() {
+        return Err(DtransformError::InvalidOperation(
+            "File is empty or contains no data".to_string()
+        ));
+    }
+
+    // Check if trimming whitespace is needed
+    let needs_trim = sample_lines.iter().any(|line| {
+        line.trim() != *line || line.contains("  ")
+    });
+
+    // Prepare content for delimiter detection
+    let detection_lines: Vec<String> = if needs_trim {
+        sample_lines.iter().map(|line| {
+            let trimmed = line.trim();
+            trimmed.split_whitespace().collect::<Vec<_>>().join(" ")
+        }).collect()
+    } else {
+        sample_lines.iter().map(|s| s.to_string()).collect()
+    };
+
+    // Count occurrences of common delimiters
+    let delimiters = [',', '\t', '|', ';', ' '];
+    let mut delimiter_counts: HashMap<char, Vec<usize>> = HashMap::new();
+
+    for line in &detection_lines {
+        for &delim in &delimiters {
+            let count = line.matches(delim).count();
+            delimiter_counts.entry(delim).or_default().push(count);
+        }
+    }
+
+    // Find delimiter with:
+    // 1. Non-zero consistent counts across lines
+    // 2. Highest average count
+    let mut best_delimiter = None;
+    let mut best_score = 0.0;
+
+    for (&delim, counts) in &delimiter_counts {
+        // Filter out lines with zero occurrences
+        let non_zero_counts: Vec<usize> = counts.iter().filter(|&&c| c > 0).copied().collect();
+
+        if non_zero_counts.is_empty() {
+            continue;
+        }
+
+        // Check consistency: most lines should have the same count
+        let min = *non_zero_counts.iter().min().unwrap();
+        let max = *non_zero_counts.iter().max().unwrap();
+        let avg = non_zero_counts.iter().sum::<usize>() as f64 / non_zero_counts.len() as f64;
+
+        // Delimiter should appear consistently (variance should be low)
+        // and appear in most lines
+        let consistency = non_zero_counts.len() as f64 / detection_lines.len() as f64;
+
+        // Prefer delimiters that appear consistently
+        if min == max || (max as f64 - min as f64) / avg < 0.3 {
+            let score = avg * consistency;
+            if score > best_score {
+                best_score = score;
+                best_delimiter = Some(delim);
+            }
+        }
+    }
+
+    match best_delimiter {
+        Some(delim) => Ok((delim, needs_trim)),
+        None => {
+            // No delimiter found - likely a single-column file
+            // Check if ALL delimiter counts are zero across all lines
+            let all_zero = delimiter_counts.values().all(|counts| {
+                counts.iter().all(|&c| c == 0)
+            });
+
+            if all_zero {
+                // Single column file - use comma as default (won't matter since no delimiters)
+                Ok((',', needs_trim))
+            } else {
+                // Ambiguous format - multiple delimiters present but inconsistent
+                Err(DtransformError::InvalidOperation(
+                    "Could not auto-detect delimiter. The file format is ambiguous.\n\n\
+                    Please specify the delimiter explicitly:\n\
+                    • Comma: read('file', delimiter=',')\n\
+                    • Tab: read('file', delimiter='\\t')\n\
+                    • Pipe: read('file', delimiter='|')\n\
+                    • Semicolon: read('file', delimiter=';')\n\
+                    • Space: read('file', delimiter=' ')".to_string()
+                ))
+            }
+        }
+    }
+}
+
+impl Default for Executor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Executor {
+    pub fn new() -> Self {
+        Self {
+            variables: HashMap::new(),
+            run_log: RefCell::new(None),
+            statement_index: 0,
+            verbose: false,
+            params: HashMap::new(),
+            workspace: Workspace::default(),
+            fixtures: None,
+            stand_ins: None,
+            progress: None,
+            parallel: true,
+            current_file: None,
+            file_names: false,
+            row_files: None,
+            pushdown: None,
+            regexes: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Put intermediate files under `dir` instead of DT_TMPDIR or the system temp directory
+    pub fn set_temp_dir(&mut self, dir: PathBuf) {
+        self.workspace = Workspace::new(dir);
+    }
+
+    /// Record samples of every read() input, or read recorded samples instead of the inputs
+    pub fn set_fixtures(&mut self, fixtures: Fixtures) {
+        self.fixtures = Some(fixtures);
+    }
+
+    /// Value for `{name}` in write() paths
+    pub fn set_param(&mut self, name: String, value: String) {
+        self.params.insert(name, value);
+    }
+
+    /// Print how each read() was interpreted (format, delimiter, header, schema) to stderr
+    pub fn set_verbose(&mut self, verbose: bool) {
+        self.verbose = verbose;
+    }
+
+    /// Report progress on stderr: `Some(true)` for every statement, read and write,
+    /// `Some(false)` for none, `None` for writes with progress=true (or --verbose)
+    pub fn set_progress(&mut self, progress: Option<bool>) {
+        self.progress = progress;
+    }
+
+    /// Run assignments that don't depend on one another at the same time (the default),
+    /// or every statement in turn
+    pub fn set_parallel(&mut self, parallel: bool) {
+        self.parallel = parallel;
+    }
+
+    /// `[2/5] sales` on stderr with --progress, for programs of several statements
+    fn report_statement(&self, index: usize, total: usize, target: &str) {
+        if self.progress == Some(true) && total > 1 {
+            eprintln!("[{}/{}] {}", index + 1, total, target);
+        }
+    }
+
+    fn record_input(&self, path: &str) {
+        if let Some(log) = self.run_log.borrow_mut().as_mut() {
+            if !log.inputs.iter().any(|p| p == path) {
+                log.inputs.push(path.to_string());
+            }
+        }
+    }
+
+    fn record_output(&self, path: &str, rows: usize) {
+        if let Some(log) = self.run_log.borrow_mut().as_mut() {
+            log.outputs.push(WrittenFile { path: path.to_string(), rows });
+        }
+    }
+
+    /// A compiled regex, from the cache when the pattern was used before, so
+    /// expressions applied to many columns or statements compile it once
+    fn regex(&self, pattern: &str, case_insensitive: bool) -> Result<Regex> {
+        let key = (pattern.to_string(), case_insensitive);
+        if let Some(re) = self.regexes.borrow().get(&key) {
+            return Ok(re.clone());
+        }
+        let re = regex::RegexBuilder::new(pattern)
+            .case_insensitive(case_insensitive)
+            .build()
+            .map_err(|e| DtransformError::InvalidOperation(format!("Invalid regex pattern '{}': {}", pattern, e)))?;
+        let mut regexes = self.regexes.borrow_mut();
+        if regexes.len() >= REGEX_CACHE_SIZE {
+            regexes.clear();
+        }
+        regexes.insert(key, re.clone());
+        Ok(re)
+    }
+
+    fn read_note(&self, source: &str, message: &str) {
+        if self.verbose {
+            eprintln!("read '{}': {}", source, message);
+        }
+    }
+
+    /// Verbose summary of a table just read: shape, schema and suspicious columns
+    fn describe_read(&self, source: &str, df: &DataFrame) {
+        if !self.verbose {
+            return;
+        }
+        let schema: Vec<String> = df
+            .get_columns()
             .iter()
-            .map(|(_, desc)| *desc)
+            .map(|c| format!("{} {}", c.name(), c.dtype()))
             .collect();
+        self.read_note(source, &format!("{} rows × {} cols: {}", df.height(), df.width(), schema.join(", ")));
+
+        if df.height() > 0 {
+            let empty: Vec<&str> = df
+                .get_columns()
+                .iter()
+                .filter(|c| c.null_count() == df.height())
+                .map(|c| c.name().as_str())
+                .collect();
+            if !empty.is_empty() {
+                self.read_note(source, &format!("warning: entirely null: {}", empty.join(", ")));
+            }
+        }
+    }
 
-        Ok(df.sort(col_names, SortMultipleOptions::default().with_order_descending_multi(descending))?)
+    /// Start (or stop) recording stage timings and the files read and written
+    pub fn record_run(&mut self, enabled: bool) {
+        *self.run_log.get_mut() = enabled.then(RunLog::default);
     }
 
-    fn execute_take(&self, df: DataFrame, op: TakeOp) -> Result<DataFrame> {
-        Ok(df.head(Some(op.n)))
+    /// Everything recorded since the last call, in execution order
+    pub fn take_run_log(&mut self) -> RunLog {
+        self.run_log.get_mut().as_mut().map(std::mem::take).unwrap_or_default()
     }
 
-    fn execute_skip(&self, df: DataFrame, op: SkipOp) -> Result<DataFrame> {
-        let height = df.height();
-        if op.n >= height {
-            Ok(df.head(Some(0)))
-        } else {
-            Ok(df.slice(op.n as i64, height - op.n))
+    fn record_stage(&mut self, stage: &str, started: Instant, df: &DataFrame) {
+        let statement = self.statement_index;
+        if let Some(log) = self.run_log.get_mut().as_mut() {
+            log.stages.push(StageTiming {
+                statement,
+                stage: stage.to_string(),
+                duration: started.elapsed(),
+                rows: df.height(),
+            });
         }
     }
 
-    fn execute_slice(&self, df: DataFrame, op: SliceOp) -> Result<DataFrame> {
-        let start = op.start.min(df.height());
-        let len = (op.end.saturating_sub(start)).min(df.height() - start);
-        Ok(df.slice(start as i64, len))
+    pub fn execute_program(&mut self, program: Program) -> Result<Option<DataFrame>> {
+        let result = self.execute_statements(program);
+        if result.is_err() {
+            // Leave partial files behind for debugging
+            self.workspace.retain();
+        }
+        result
+    }
+
+    /// Consecutive assignments that neither write nor use one another's results
+    /// are collected into a batch and run concurrently; everything else runs in order
+    fn execute_statements(&mut self, program: Program) -> Result<Option<DataFrame>> {
+        let mut last_result = None;
+        let total = program.statements.len();
+        let mut batch = parallel::Batch::new();
+
+        for (index, statement) in program.statements.into_iter().enumerate() {
+            match statement {
+                Statement::Assignment { name, pipeline } if self.can_run_in_parallel(&pipeline) => {
+                    if parallel::depends_on(&pipeline, &batch) {
+                        self.execute_batch(std::mem::take(&mut batch), total)?;
+                    }
+                    batch.push((index, name, pipeline));
+                }
+                statement => {
+                    self.execute_batch(std::mem::take(&mut batch), total)?;
+                    self.statement_index = index + 1;
+                    match statement {
+                        Statement::Assignment { name, pipeline } => {
+                            self.report_statement(index, total, &name);
+                            let df = self.execute_pipeline(pipeline)?;
+                            self.variables.insert(name, df);
+                            // Assignments don't produce output in program mode
+                        }
+                        Statement::Pipeline(pipeline) => {
+                            self.report_statement(index, total, "result");
+                            let df = self.execute_pipeline(pipeline)?;
+                            last_result = Some(df);
+                        }
+                    }
+                }
+            }
+        }
+        self.execute_batch(batch, total)?;
+
+        Ok(last_result)
+    }
+
+    pub fn execute_statement(&mut self, statement: Statement) -> Result<Option<DataFrame>> {
+        match statement {
+            Statement::Assignment { name, pipeline } => {
+                let df = self.execute_pipeline(pipeline)?;
+                self.variables.insert(name.clone(), df.clone());
+                Ok(Some(df))
+            }
+            Statement::Pipeline(pipeline) => {
+                let df = self.execute_pipeline(pipeline)?;
+                Ok(Some(df))
+            }
+        }
+    }
+
+    pub fn execute_pipeline(&mut self, pipeline: Pipeline) -> Result<DataFrame> {
+        let started = Instant::now();
+        let source = match &pipeline.source {
+            Some(Source::Read(_)) => "read".to_string(),
+            Some(Source::Variable(name)) => name.clone(),
+            Some(Source::Concat(_)) => "concat".to_string(),
+            None => String::new(),
+        };
+        self.current_file = match &pipeline.source {
+            Some(Source::Read(read_op)) => read_file_name(read_op),
+            _ => None,
+        };
+        let mut operations = pipeline.operations;
+        self.file_names = operations.iter().any(uses_file_name);
+        let df = match pipeline.source {
+            Some(Source::Read(read_op)) => {
+                // Leading select() and filter() steps become part of a database query
+                if self.reads_database(&read_op) {
+                    let (pushdown, taken) = database::Pushdown::plan(&read_op.path, &operations);
+                    if taken > 0 {
+                        operations.drain(..taken);
+                        self.pushdown = Some(pushdown);
+                    }
+                }
+                let df = self.execute_read(*read_op);
+                self.pushdown = None;
+                df?
+            }
+            Some(Source::Variable(var_name)) => {
+                self.variables
+                    .get(&var_name)
+                    .ok_or_else(|| DtransformError::VariableNotFound(var_name.clone()))?
+                    .clone()
+            }
+            Some(Source::Concat(concat_op)) => self.execute_concat(None, concat_op)?,
+            None => {
+                return Err(DtransformError::InvalidOperation(
+                    "Pipeline must start with a data source (read() or variable)".to_string(),
+                ));
+            }
+        };
+
+        self.record_stage(&source, started, &df);
+        let result = self.run_operations(df, operations);
+        self.file_names = false;
+        result
+    }
+
+    /// Whether a read() is a query of a database that steps can be pushed into.
+    /// Not while recording or replaying fixtures or checking, which stand in for
+    /// the whole table, nor with include_filename, whose column dt adds.
+    fn reads_database(&self, op: &ReadOp) -> bool {
+        if self.fixtures.is_some() || self.stand_ins.is_some() || op.include_filename == Some(true) {
+            return false;
+        }
+        if database::is_connection_string(&op.path) {
+            return true;
+        }
+        let path = Path::new(&op.path);
+        let format = op.format.as_deref().or_else(|| format_path(path).extension()?.to_str());
+        op.path != "-"
+            && !is_url(&op.path)
+            && !is_glob_pattern(&op.path)
+            && sqlite::is_sqlite(format)
+            && matches!(compression_of(path, op.compression.as_deref()), Ok(None))
+    }
+
+    /// Apply a pipeline's operations to a table already in hand, such as the REPL's
+    /// current table, without storing it in a variable first. DataFrame clones share
+    /// their column buffers, so passing a clone copies no data.
+    pub fn execute_operations(&mut self, df: DataFrame, operations: Vec<Operation>) -> Result<DataFrame> {
+        self.current_file = None;
+        self.run_operations(df, operations)
+    }
+
+    fn run_operations(&mut self, mut df: DataFrame, operations: Vec<Operation>) -> Result<DataFrame> {
+        // Each row's file is kept until the last step that asks for it
+        let last_file_name = operations.iter().rposition(uses_file_name);
+        for (index, operation) in operations.into_iter().enumerate() {
+            let started = Instant::now();
+            let name = operation_name(&operation);
+            let handling = row_files(&operation);
+            let set_aside = match handling {
+                RowFiles::Carry => None,
+                _ => df.drop_in_place(FILE_NAME_COLUMN).ok().map(|c| c.take_materialized_series()),
+            };
+            if matches!(handling, RowFiles::SetAside) {
+                self.row_files = set_aside;
+            }
+            let result = self.execute_operation(df, operation);
+            let files = self.row_files.take();
+            df = result?;
+            if let Some(files) = files.filter(|files| files.len() == df.height()) {
+                df.with_column(files)?;
+            }
+            if Some(index) == last_file_name {
+                self.file_names = false;
+                if df.get_column_index(FILE_NAME_COLUMN).is_some() {
+                    df.drop_in_place(FILE_NAME_COLUMN)?;
+                }
+            }
+            self.record_stage(name, started, &df);
+        }
+        Ok(df)
+    }
+
+    fn execute_operation(&mut self, df: DataFrame, op: Operation) -> Result<DataFrame> {
+        match op {
+            Operation::Read(read_op) => {
+                self.current_file = read_file_name(&read_op);
+                self.execute_read(read_op)
+            }
+            Operation::Variable(_var_name) => {
+                // Variable references should be handled as pipeline sources, not operations
+                Err(DtransformError::InvalidOperation(
+                    "Variable references can only be used as pipeline sources, not as operations".to_string()
+                ))
+            }
+            Operation::Write(write_op) => self.execute_write(df, write_op),
+            Operation::Select(select_op) => self.execute_select(df, select_op),
+            Operation::Relocate(relocate_op) => self.execute_relocate(df, relocate_op),
+            Operation::Filter(filter_op) => self.execute_filter(df, filter_op),
+            Operation::Mutate(mutate_op) => self.execute_mutate(df, mutate_op),
+            Operation::Rename(rename_op) => self.execute_rename(df, rename_op),
+            Operation::RenameAll(rename_all_op) => self.execute_rename_all(df, rename_all_op),
+            Operation::Sort(sort_op) => self.execute_sort(df, sort_op),
+            Operation::Take(take_op) => self.execute_take(df, take_op),
+            Operation::Skip(skip_op) => self.execute_skip(df, skip_op),
+            Operation::Slice(slice_op) => self.execute_slice(df, slice_op),
+            Operation::Sample(sample_op) => self.execute_sample(df, sample_op),
+            Operation::Drop(drop_op) => self.execute_drop(df, drop_op),
+            Operation::Distinct(distinct_op) => self.execute_distinct(df, distinct_op),
+            Operation::Concat(concat_op) => self.execute_concat(None, concat_op),
+            Operation::Append(concat_op) => self.execute_concat(Some(df), concat_op),
+            Operation::Cast(cast_op) => self.execute_cast(df, cast_op),
+            Operation::MutateAll(mutate_all_op) => self.execute_mutate_all(df, mutate_all_op),
+            Operation::DropNulls(drop_nulls_op) => self.execute_drop_nulls(df, drop_nulls_op),
+            Operation::FillNulls(fill_nulls_op) => self.execute_fill_nulls(df, fill_nulls_op),
+            Operation::Describe(describe_op) => self.execute_describe(df, describe_op),
+            Operation::Count => {
+                let count = Series::new(PlSmallStr::from("count"), vec![df.height() as u64]);
+                Ok(DataFrame::new(vec![count.into_column()])?)
+            }
+            Operation::Expect(expect_op) => self.execute_expect(df, expect_op),
+            Operation::CountBy(count_by_op) => self.execute_count_by(df, count_by_op),
+            Operation::TopN(top_n_op) => self.execute_top_n(df, top_n_op),
+            Operation::Summarize(summarize_op) => self.execute_summarize(df, summarize_op),
+            Operation::Resample(resample_op) => self.execute_resample(df, resample_op),
+            Operation::Upsample(upsample_op) => self.execute_upsample(df, upsample_op),
+            Operation::Explode(explode_op) => self.execute_explode(df, explode_op),
+            Operation::WithRowNumber(row_number_op) => self.execute_with_row_number(df, row_number_op),
+            Operation::SplitInto(split_into_op) => self.execute_split_into(df, split_into_op),
+        }
+    }
+
+    /// Split a string once and store the parts in new columns. Missing parts are null;
+    /// extra parts are dropped unless `rest` keeps them in the last column.
+    fn execute_split_into(&self, mut df: DataFrame, op: SplitIntoOp) -> Result<DataFrame> {
+        let series = self.evaluate_expression(&op.source, &df)?;
+        let text = self.string_series("split_into", &series)?;
+        let count = op.columns.len();
+
+        let mut parts: Vec<Vec<Option<&str>>> = vec![Vec::with_capacity(text.len()); count];
+        for value in text.into_iter() {
+            let mut pieces: Box<dyn Iterator<Item = &str>> = match value {
+                Some(s) if op.rest => Box::new(s.splitn(count, op.delimiter.as_str())),
+                Some(s) => Box::new(s.split(op.delimiter.as_str())),
+                None => Box::new(std::iter::empty()),
+            };
+            for column in parts.iter_mut() {
+                column.push(pieces.next());
+            }
+        }
+
+        for (name, values) in op.columns.iter().zip(parts) {
+            df.with_column(Series::new(PlSmallStr::from(name.as_str()), values))?;
+        }
+        Ok(df)
+    }
+
+    /// One row per distinct key with its row count, most frequent first (ties by key)
+    fn execute_count_by(&self, df: DataFrame, op: CountByOp) -> Result<DataFrame> {
+        let mut keys: Vec<String> = Vec::new();
+        for column in &op.columns {
+            let key = self.resolve_column_name(column, &df)?;
+            if df.column(&key).is_err() {
+                return Err(DtransformError::ColumnNotFound(key));
+            }
+            if !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+        if keys.iter().any(|k| k == "count") {
+            return Err(DtransformError::InvalidOperation(
+                "count_by() adds a 'count' column, which clashes with a grouping column of that name; rename it first".to_string(),
+            ));
+        }
+
+        let mut order: Vec<&str> = vec!["count"];
+        order.extend(keys.iter().map(|k| k.as_str()));
+        let mut descending = vec![true];
+        descending.extend(keys.iter().map(|_| false));
+
+        let key_exprs: Vec<Expr> = keys.iter().map(|k| col(k.as_str())).collect();
+        Ok(df
+            .lazy()
+            .group_by(key_exprs)
+            .agg([len().cast(polars::datatypes::DataType::UInt64).alias("count")])
+            .sort(order, SortMultipleOptions::default().with_order_descending_multi(descending).with_nulls_last(true))
+            .collect()?)
+    }
+
+    /// The n largest (or smallest) rows by one column, over the table or within each group.
+    /// Groups appear in order of their best row; nulls rank last.
+    fn execute_top_n(&self, df: DataFrame, op: TopNOp) -> Result<DataFrame> {
+        let by = self.resolve_column_name(&op.by, &df)?;
+        let per = op
+            .per
+            .iter()
+            .map(|c| self.resolve_column_name(c, &df))
+            .collect::<Result<Vec<_>>>()?;
+        if let Some(missing) = std::iter::once(&by).chain(&per).find(|c| df.column(c).is_err()) {
+            return Err(DtransformError::ColumnNotFound(missing.clone()));
+        }
+
+        let columns: Vec<Expr> = df.get_column_names_str().iter().map(|c| col(*c)).collect();
+        let sorted = df.lazy().sort(
+            [by.as_str()],
+            SortMultipleOptions::default()
+                .with_order_descending(op.descending)
+                .with_nulls_last(true)
+                .with_maintain_order(true),
+        );
+
+        let result = if per.is_empty() {
+            sorted.limit(op.n as IdxSize)
+        } else {
+            let keys: Vec<Expr> = per.iter().map(|k| col(k.as_str())).collect();
+            sorted.group_by_stable(keys).head(Some(op.n)).select(columns)
+        };
+        Ok(result.collect()?)
+    }
+
+    /// Replace the table with one row of aggregates over all its rows
+    fn execute_summarize(&self, mut df: DataFrame, op: SummarizeOp) -> Result<DataFrame> {
+        let aggregates = op
+            .aggregations
+            .iter()
+            .map(|(name, expr)| Ok(self.aggregate_expr("summarize", expr, &mut df)?.alias(name.as_str())))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(df.lazy().select(aggregates).collect()?)
+    }
+
+    /// Aggregates per time bucket of width `every`, labelled by the bucket's start.
+    /// Buckets without rows are left out.
+    fn execute_resample(&self, mut df: DataFrame, op: ResampleOp) -> Result<DataFrame> {
+        let time = self.resolve_column_name(&op.time, &df)?;
+        every_span("resample", &op.every)?;
+        let every = polars::prelude::Duration::try_parse(op.every.trim())?;
+        let times = self.time_index("resample", &df, &time)?;
+        df.replace(&time, times)?;
+
+        let mut aggregates = Vec::with_capacity(op.aggregations.len());
+        for (name, expr) in &op.aggregations {
+            if *name == time {
+                return Err(DtransformError::InvalidOperation(format!(
+                    "resample() aggregate '{}' clashes with the time column; name it differently",
+                    name
+                )));
+            }
+            aggregates.push(self.aggregate_expr("resample", expr, &mut df)?.alias(name.as_str()));
+        }
+
+        let options = DynamicGroupOptions {
+            every,
+            period: every,
+            offset: polars::prelude::Duration::parse("0ns"),
+            label: Label::Left,
+            closed_window: ClosedWindow::Left,
+            start_by: StartBy::WindowBound,
+            ..Default::default()
+        };
+        Ok(df
+            .lazy()
+            .sort([time.as_str()], SortMultipleOptions::default())
+            .group_by_dynamic(col(time.as_str()), [], options)
+            .agg(aggregates)
+            .collect()?)
+    }
+
+    /// Insert a row for every missing step of `every` between the first and last time,
+    /// empty or, with fill='ffill', a copy of the row before it. Existing rows are kept.
+    fn execute_upsample(&self, mut df: DataFrame, op: UpsampleOp) -> Result<DataFrame> {
+        let time = self.resolve_column_name(&op.time, &df)?;
+        let every = every_span("upsample", &op.every)?;
+        let times = self.time_index("upsample", &df, &time)?;
+        let dtype = times.dtype().clone();
+        if dtype == polars::datatypes::DataType::Date && every % MICROS_PER_DAY != 0 {
+            return Err(DtransformError::InvalidOperation(format!(
+                "upsample() of Date column '{}' needs every= in whole days; cast it to datetime for '{}'",
+                time, op.every
+            )));
+        }
+
+        df.replace(&time, times)?;
+        let df = df.sort([time.as_str()], SortMultipleOptions::default().with_maintain_order(true))?;
+        let (values, _) = self.datetime_values("upsample", df.column(&time)?.as_materialized_series())?;
+        let micros: Vec<i64> = values.iter().flatten().map(|t| t.and_utc().timestamp_micros()).collect();
+        if let (Some(first), Some(last)) = (micros.first(), micros.last()) {
+            if (last - first) / every > MAX_UPSAMPLE_ROWS {
+                return Err(DtransformError::InvalidOperation(format!(
+                    "upsample() every='{}' would add more than {} rows; use a wider step",
+                    op.every, MAX_UPSAMPLE_ROWS
+                )));
+            }
+        }
+
+        // Output rows as (time, source row); a missing step copies the row
+        // before it with ffill and is empty otherwise
+        let mut rows: Vec<(i64, Option<IdxSize>)> = Vec::with_capacity(micros.len());
+        let mut next_step = micros.first().copied().unwrap_or_default();
+        for (i, &t) in micros.iter().enumerate() {
+            while next_step < t {
+                let source = op.fill_forward.then(|| (i - 1) as IdxSize);
+                rows.push((next_step, source));
+                next_step += every;
+            }
+            if next_step == t {
+                next_step += every;
+            }
+            rows.push((t, Some(i as IdxSize)));
+        }
+
+        let sources: IdxCa = rows.iter().map(|(_, source)| *source).collect();
+        let mut result = df.take(&sources)?;
+        let filled_times: Vec<Option<NaiveDateTime>> =
+            rows.iter().map(|(t, _)| chrono::DateTime::from_timestamp_micros(*t).map(|dt| dt.naive_utc())).collect();
+        let filled_times = Series::new(PlSmallStr::from(time.as_str()), filled_times).cast(&dtype)?;
+        result.replace(&time, filled_times)?;
+        Ok(result)
+    }
+
+    /// One row per element of the list columns, the other columns repeated. An
+    /// expression such as split_all(tags, ';') replaces the column it reads.
+    /// Several columns are exploded side by side and need lists of equal length.
+    fn execute_explode(&self, mut df: DataFrame, op: ExplodeOp) -> Result<DataFrame> {
+        let mut names: Vec<String> = Vec::with_capacity(op.columns.len());
+        for expr in &op.columns {
+            let name = match first_column(expr) {
+                Some(column) => self.resolve_column_name(column, &df)?,
+                None => {
+                    return Err(DtransformError::InvalidOperation(
+                        "explode() needs a column: explode(tags) or explode(split_all(tags, ';'))".to_string(),
+                    ))
+                }
+            };
+            let series = self.evaluate_expression(expr, &df)?;
+            if !matches!(series.dtype(), polars::datatypes::DataType::List(_)) {
+                return Err(DtransformError::InvalidOperation(format!(
+                    "explode() needs a list column, but '{}' is {}; split text first: explode(split_all({}, ';'))",
+                    name,
+                    series.dtype(),
+                    name
+                )));
+            }
+            if names.contains(&name) {
+                return Err(DtransformError::InvalidOperation(format!("explode() names column '{}' twice", name)));
+            }
+            df.with_column(series.with_name(PlSmallStr::from(name.as_str())))?;
+            names.push(name);
+        }
+        Ok(df.explode(names)?)
+    }
+
+    /// Number the rows from `start` as a new first column, counting over the table
+    /// or, with `per`, within each group in the order its rows appear
+    fn execute_with_row_number(&self, mut df: DataFrame, op: WithRowNumberOp) -> Result<DataFrame> {
+        if df.column(&op.name).is_ok() {
+            return Err(DtransformError::InvalidOperation(format!(
+                "with_row_number() adds column '{}', which already exists; choose another with name=",
+                op.name
+            )));
+        }
+        let name = PlSmallStr::from(op.name.as_str());
+
+        let numbers = if op.per.is_empty() {
+            let numbers: Vec<i64> = (0..df.height() as i64).map(|i| op.start + i).collect();
+            Series::new(name, numbers)
+        } else {
+            let mut keys: Vec<Expr> = Vec::with_capacity(op.per.len());
+            for column in &op.per {
+                let key = self.resolve_column_name(column, &df)?;
+                if df.column(&key).is_err() {
+                    return Err(DtransformError::ColumnNotFound(key));
+                }
+                keys.push(col(key.as_str()));
+            }
+            // A running count of ones within each group
+            let mut numbered = df.clone();
+            numbered.with_column(Series::new(PlSmallStr::from("__one"), vec![1i64; df.height()]))?;
+            let numbered = numbered
+                .lazy()
+                .select([(col("__one").cum_sum(false).over(keys) + lit(op.start - 1)).alias(name)])
+                .collect()?;
+            numbered.column(&op.name)?.as_materialized_series().clone()
+        };
+        df.insert_column(0, numbers)?;
+        Ok(df)
+    }
+
+    /// The time column of resample() / upsample() as Date or Datetime, with date text
+    /// parsed. Nulls, and text that isn't a date, are an error.
+    fn time_index(&self, operation: &str, df: &DataFrame, time: &str) -> Result<Series> {
+        use polars::datatypes::DataType as PDT;
+
+        let series = match df.column(time) {
+            Ok(column) => column.as_materialized_series().clone(),
+            Err(_) => return Err(DtransformError::ColumnNotFound(time.to_string())),
+        };
+        let times = match series.dtype() {
+            // A plain date first ('2024-01-31'), then date and time
+            PDT::String => match self.cast_series(&series, &CastType::Date(None)) {
+                Ok(dates) => dates,
+                Err(_) => self.cast_series(&series, &CastType::Datetime(None)).map_err(|_| {
+                    DtransformError::InvalidOperation(format!(
+                        "{}() can't read the text in time column '{}' as dates; cast({} -> datetime) first, \
+                         with a format if needed: cast({} -> datetime('%d/%m/%Y %H:%M'))",
+                        operation, time, time, time
+                    ))
+                })?,
+            },
+            dtype if is_date_like(dtype) => series,
+            other => {
+                return Err(DtransformError::TypeMismatch {
+                    expected: format!("Date, Datetime or date text in {}()", operation),
+                    got: format!("{:?}", other),
+                })
+            }
+        };
+        if times.null_count() > 0 {
+            return Err(DtransformError::InvalidOperation(format!(
+                "{}() time column '{}' has nulls or values that aren't dates; drop_nulls({}) first",
+                operation, time, time
+            )));
+        }
+        Ok(times.with_name(PlSmallStr::from(time)))
+    }
+
+    /// One aggregate of a grouping operation as a Polars expression: count() for
+    /// the rows of a group, a function of a single column such as mean(value), or
+    /// any()/all() of a condition, which is added to `df` as a column to aggregate
+    fn aggregate_expr(&self, operation: &str, expr: &Expression, df: &mut DataFrame) -> Result<Expr> {
+        let invalid = || {
+            DtransformError::InvalidOperation(format!(
+                "{}() aggregates are count(), any(condition), all(condition) or one of count, sum, mean, min, \
+                 max, median, std, first, last, n_unique of a column, e.g. avg = mean(value)",
+                operation
+            ))
+        };
+        let (name, args) = match expr {
+            Expression::FunctionCall { name, args } => (name.as_str(), args),
+            _ => return Err(invalid()),
+        };
+        if name == "count" && args.is_empty() {
+            return Ok(len().cast(polars::datatypes::DataType::UInt64));
+        }
+        if name == "any" || name == "all" {
+            let [condition] = args.as_slice() else {
+                return Err(invalid());
+            };
+            let mask = self.evaluate_expression(condition, df)?;
+            if mask.dtype() != &polars::datatypes::DataType::Boolean {
+                return Err(DtransformError::TypeMismatch {
+                    expected: format!("Boolean condition in {}()", name),
+                    got: format!("{:?}", mask.dtype()),
+                });
+            }
+            let column = format!("__dt_{}_{}", name, df.width());
+            df.with_column(mask.with_name(PlSmallStr::from(column.as_str())))?;
+            // Nulls are ignored, as in any()/all() of a whole column
+            return Ok(if name == "any" { col(column.as_str()).any(true) } else { col(column.as_str()).all(true) });
+        }
+        let column = match args.as_slice() {
+            [Expression::Column(column)] => self.resolve_column_name(column, df)?,
+            _ => return Err(invalid()),
+        };
+        if df.column(&column).is_err() {
+            return Err(DtransformError::ColumnNotFound(column));
+        }
+
+        let values = col(column.as_str());
+        Ok(match name {
+            "count" => values.count().cast(polars::datatypes::DataType::UInt64),
+            "sum" => values.sum(),
+            "mean" | "avg" => values.mean(),
+            "min" => values.min(),
+            "max" => values.max(),
+            "median" => values.median(),
+            "std" => values.std(1),
+            "first" => values.first(),
+            "last" => values.last(),
+            "n_unique" => values.n_unique().cast(polars::datatypes::DataType::UInt64),
+            _ => return Err(invalid()),
+        })
+    }
+
+    /// Check every expectation and report all failures at once; the table passes through unchanged
+    fn execute_expect(&self, df: DataFrame, op: ExpectOp) -> Result<DataFrame> {
+        let mut failures: Vec<String> = Vec::new();
+
+        for check in &op.checks {
+            match check {
+                Expectation::Count { target, op, value } => {
+                    let (label, actual) = match target {
+                        CountTarget::Rows => ("rows", df.height()),
+                        CountTarget::Columns => ("columns", df.width()),
+                    };
+                    let (holds, symbol) = match op {
+                        BinOp::Gt => (actual > *value, ">"),
+                        BinOp::Lt => (actual < *value, "<"),
+                        BinOp::Gte => (actual >= *value, ">="),
+                        BinOp::Lte => (actual <= *value, "<="),
+                        BinOp::Neq => (actual != *value, "!="),
+                        _ => (actual == *value, "=="),
+                    };
+                    if !holds {
+                        failures.push(format!("{} {} {} (got {})", label, symbol, value, actual));
+                    }
+                }
+                Expectation::HasColumns(columns) => {
+                    let missing: Vec<&str> = columns
+                        .iter()
+                        .filter(|name| df.column(name).is_err())
+                        .map(|name| name.as_str())
+                        .collect();
+                    if !missing.is_empty() {
+                        failures.push(format!("missing column(s): {}", missing.join(", ")));
+                    }
+                }
+                Expectation::DataType { column, data_type } => {
+                    let found = self
+                        .resolve_column_name(column, &df)
+                        .ok()
+                        .and_then(|name| df.column(&name).ok().map(|c| (name, c.dtype().clone())));
+                    match found {
+                        Some((name, dtype)) if !self.matches_dtype(data_type, &dtype) => {
+                            failures.push(format!("{} is {}, expected {:?}", name, dtype, data_type));
+                        }
+                        Some(_) => {}
+                        None => failures.push(format!("dtype({}): column not found", column_label(column))),
+                    }
+                }
+            }
+        }
+
+        if failures.is_empty() {
+            Ok(df)
+        } else {
+            Err(DtransformError::ExpectationFailed(failures.join("; ")))
+        }
+    }
+
+    fn check_duplicate_columns(&self, df: &DataFrame) -> Result<()> {
+        use std::collections::HashSet;
+        let column_names: Vec<String> = df.get_column_names().iter().map(|s| s.to_string()).collect();
+        let mut seen = HashSet::new();
+        let mut duplicates = Vec::new();
+
+        for name in &column_names {
+            if !seen.insert(name) {
+                duplicates.push(name.clone());
+            }
+        }
+
+        if !duplicates.is_empty() {
+            return Err(DtransformError::InvalidOperation(format!(
+                "File contains duplicate column names: {}. Malformed files with repeated columns are not allowed.",
+                duplicates.join(", ")
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn execute_read(&self, op: ReadOp) -> Result<DataFrame> {
+        if let Some(stand_ins) = &self.stand_ins {
+            return self.read_stand_in(stand_ins, op);
+        }
+        if let Some(fixtures) = &self.fixtures {
+            if let Some(df) = fixtures.replay(&op.path)? {
+                let fixture = fixtures.path_for(&op.path).display().to_string();
+                self.record_input(&fixture);
+                self.read_note(&op.path, &format!("replaying fixture {}", fixture));
+                return Ok(df);
+            }
+        }
+
+        let path = op.path.clone();
+        let shown = database::redact(&path);
+        let started = Instant::now();
+        if self.progress == Some(true) {
+            match std::fs::metadata(&path) {
+                Ok(meta) if meta.is_file() => eprint!("read '{}': {} ...", shown, format_bytes(meta.len())),
+                _ => eprint!("read '{}' ...", shown),
+            }
+        }
+        let df = self.read_source(op);
+        if self.progress == Some(true) {
+            match &df {
+                Ok(df) => eprintln!(
+                    "\rread '{}': {} rows × {} cols in {:.1}s",
+                    shown,
+                    df.height(),
+                    df.width(),
+                    started.elapsed().as_secs_f64()
+                ),
+                // End the line before the error is shown
+                Err(_) => eprintln!(),
+            }
+        }
+        let df = df?;
+        if let Some(fixtures) = &self.fixtures {
+            fixtures.record(&path, &df.drop(FILE_NAME_COLUMN).unwrap_or_else(|_| df.clone()), &self.workspace)?;
+        }
+        Ok(df)
+    }
+
+    fn read_source(&self, op: ReadOp) -> Result<DataFrame> {
+        let df = if op.path == "-" {
+            self.read_stdin(&op)?
+        } else if database::is_connection_string(&op.path) {
+            self.record_input(&database::redact(&op.path));
+            self.read_database(&op, &op.path)?
+        } else if is_url(&op.path) {
+            self.read_url(&op)?
+        } else if is_glob_pattern(&op.path) {
+            return self.read_glob(&op);
+        } else {
+            self.read_file(&op, Path::new(&op.path))?
+        };
+
+        let df = if op.include_filename == Some(true) {
+            self.with_filename_column(df, &op.path)?
+        } else {
+            df
+        };
+        self.describe_read(&database::redact(&op.path), &df);
+        match read_file_name(&op) {
+            Some(file) if self.file_names => with_file_names(df, &file),
+            _ => Ok(df),
+        }
+    }
+
+    /// Read every file matching a glob pattern and stack them vertically.
+    /// Columns are matched by name (in the order of the first file) and
+    /// differing types are widened to a common type.
+    fn read_glob(&self, op: &ReadOp) -> Result<DataFrame> {
+        let entries = glob::glob(&op.path).map_err(|e| {
+            DtransformError::InvalidOperation(format!("Invalid glob pattern '{}': {}", op.path, e))
+        })?;
+
+        let mut paths = Vec::new();
+        for entry in entries {
+            let path = entry.map_err(|e| DtransformError::IoError(e.into_error()))?;
+            if path.is_file() {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        if paths.is_empty() {
+            return Err(DtransformError::InvalidOperation(format!(
+                "No files match '{}'",
+                op.path
+            )));
+        }
+        self.read_note(&op.path, &format!("glob matched {} file(s)", paths.len()));
+
+        let mut frames = Vec::with_capacity(paths.len());
+        for path in &paths {
+            let name = path.display().to_string();
+            let mut df = self.read_file(op, path)?;
+            if op.include_filename == Some(true) {
+                df = self.with_filename_column(df, &name)?;
+            }
+            if self.file_names {
+                df = with_file_names(df, &name)?;
+            }
+            frames.push((format!("'{}'", name), df));
+        }
+
+        let df = self.stack_frames(frames, ConcatMode::Strict)
+            .map_err(|e| match e {
+                DtransformError::PolarsError(e) => DtransformError::InvalidOperation(format!(
+                    "Cannot combine files matching '{}': {}",
+                    op.path, e
+                )),
+                other => other,
+            })?;
+        self.describe_read(&op.path, &df);
+        Ok(df)
+    }
+
+    /// Stack stored variables and/or read() sources: concat(a, b) or df | append(b)
+    fn execute_concat(&self, base: Option<DataFrame>, op: ConcatOp) -> Result<DataFrame> {
+        let mut frames = Vec::with_capacity(op.sources.len() + 1);
+        if let Some(df) = base {
+            frames.push(("the current table".to_string(), df));
+        }
+
+        for source in op.sources {
+            let frame = match source {
+                Source::Read(read_op) => (format!("'{}'", read_op.path), self.execute_read(*read_op)?),
+                Source::Variable(var_name) => {
+                    let df = self
+                        .variables
+                        .get(&var_name)
+                        .ok_or_else(|| DtransformError::VariableNotFound(var_name.clone()))?
+                        .clone();
+                    (format!("'{}'", var_name), df)
+                }
+                Source::Concat(inner) => ("concat()".to_string(), self.execute_concat(None, inner)?),
+            };
+            frames.push(frame);
+        }
+
+        // Each row's file is stacked on its own, so it is not compared as a column
+        let mut files: Vec<Series> = Vec::with_capacity(frames.len());
+        for (_, df) in frames.iter_mut() {
+            let height = df.height();
+            files.push(match df.drop_in_place(FILE_NAME_COLUMN) {
+                Ok(column) => column.take_materialized_series(),
+                Err(_) => Series::full_null(PlSmallStr::from(FILE_NAME_COLUMN), height, &polars::datatypes::DataType::String),
+            });
+        }
+        let with_files = files.iter().any(|files| files.null_count() < files.len());
+
+        let mut stacked = self.stack_frames(frames, op.mode).map_err(|e| match e {
+            DtransformError::InvalidOperation(msg) if op.mode == ConcatMode::Strict => {
+                DtransformError::InvalidOperation(format!(
+                    "{}. Use mode='diagonal' to fill missing columns with null",
+                    msg
+                ))
+            }
+            other => other,
+        })?;
+        if with_files {
+            let mut all = files.remove(0);
+            for more in &files {
+                all.append(more)?;
+            }
+            stacked.with_column(all)?;
+        }
+        Ok(stacked)
+    }
+
+    /// Vertically stack named tables. In strict mode every table must have the
+    /// same column names (order may differ; the first table's order is used).
+    /// In diagonal mode the result has the union of all columns and missing
+    /// values are null. Differing column types are widened to a common type.
+    fn stack_frames(&self, frames: Vec<(String, DataFrame)>, mode: ConcatMode) -> Result<DataFrame> {
+        let (first_name, first_df) = match frames.first() {
+            Some(first) => first,
+            None => return Ok(DataFrame::empty()),
+        };
+        let first_columns: Vec<String> = first_df.get_column_names_str().iter().map(|c| c.to_string()).collect();
+        let first_name = first_name.clone();
+
+        let mut lazy_frames = Vec::with_capacity(frames.len());
+        for (name, df) in frames {
+            let df = if mode == ConcatMode::Strict {
+                let columns: Vec<String> = df.get_column_names_str().iter().map(|c| c.to_string()).collect();
+                let missing: Vec<&str> = first_columns
+                    .iter()
+                    .filter(|c| !columns.contains(c))
+                    .map(|c| c.as_str())
+                    .collect();
+                let extra: Vec<&str> = columns
+                    .iter()
+                    .filter(|c| !first_columns.contains(c))
+                    .map(|c| c.as_str())
+                    .collect();
+
+                if !missing.is_empty() || !extra.is_empty() {
+                    let mut details = Vec::new();
+                    if !missing.is_empty() {
+                        details.push(format!("missing columns: {}", missing.join(", ")));
+                    }
+                    if !extra.is_empty() {
+                        details.push(format!("unexpected columns: {}", extra.join(", ")));
+                    }
+                    return Err(DtransformError::InvalidOperation(format!(
+                        "Schema of {} does not match {} ({})",
+                        name,
+                        first_name,
+                        details.join("; ")
+                    )));
+                }
+
+                // Same columns, possibly in a different order
+                df.select(&first_columns)?
+            } else {
+                df
+            };
+            lazy_frames.push(df.lazy());
+        }
+
+        let args = UnionArgs { to_supertypes: true, ..Default::default() };
+        let stacked = match mode {
+            ConcatMode::Strict => concat(lazy_frames, args)?,
+            ConcatMode::Diagonal => concat_lf_diagonal(lazy_frames, args)?,
+        };
+        Ok(stacked.collect()?)
+    }
+
+    fn with_filename_column(&self, mut df: DataFrame, name: &str) -> Result<DataFrame> {
+        if df.get_column_names_str().contains(&"filename") {
+            return Err(DtransformError::InvalidOperation(
+                "Cannot add 'filename' column: the file already has a column with that name".to_string(),
+            ));
+        }
+        let height = df.height();
+        df.with_column(Series::new("filename".into(), vec![name; height]))?;
+        Ok(df)
+    }
+
+    fn read_file(&self, op: &ReadOp, path: &Path) -> Result<DataFrame> {
+        // Determine format from extension (before any .gz / .zst / .bz2) or explicit format
+        let format = op.format.as_deref().or_else(|| format_path(path).extension()?.to_str());
+        let compression = compression_of(path, op.compression.as_deref())?;
+        self.record_input(&path.display().to_string());
+        if self.verbose {
+            let source = path.display().to_string();
+            match (&op.format, format) {
+                (Some(given), _) => self.read_note(&source, &format!("format {} (given)", given)),
+                (None, Some(ext)) => self.read_note(&source, &format!("format {} (file extension)", ext)),
+                (None, None) => self.read_note(&source, "format delimited text (no extension)"),
+            }
+        }
+
+        // With --progress the file is loaded in chunks to report the bytes read so
+        // far, and parsed from memory afterwards
+        if self.progress == Some(true) && !sqlite::is_sqlite(format) {
+            let source = path.display().to_string();
+            let total = std::fs::metadata(path)?.len();
+            let bytes = read_chunks(std::fs::File::open(path)?, |read| {
+                if total > 0 {
+                    let percent = read as f64 * 100.0 / total as f64;
+                    eprint!("\rread '{}': {} / {} ({:.0}%)", source, format_bytes(read), format_bytes(total), percent);
+                } else {
+                    eprint!("\rread '{}': {}", source, format_bytes(read));
+                }
+            })?;
+            eprintln!();
+            let bytes = match compression {
+                Some(compression) => {
+                    self.read_note(&source, &format!("decompressing ({:?})", compression).to_lowercase());
+                    decompress(std::io::Cursor::new(bytes), compression)?
+                }
+                None => bytes,
+            };
+            return self.read_bytes(op, bytes, format, &source);
+        }
+
+        if let Some(compression) = compression {
+            let source = path.display().to_string();
+            self.read_note(&source, &format!("decompressing ({:?})", compression).to_lowercase());
+            let bytes = decompress(std::fs::File::open(path)?, compression)?;
+            return self.read_bytes(op, bytes, format, &source);
+        }
+
+        match format {
+            Some("json") => {
+                let file = std::fs::File::open(path)?;
+                let df = JsonReader::new(file).finish()?;
+                self.check_duplicate_columns(&df)?;
+                Ok(df)
+            }
+            Some("ndjson") | Some("jsonl") => {
+                let file = std::fs::File::open(path)?;
+                let df = JsonLineReader::new(file).finish()?;
+                self.check_duplicate_columns(&df)?;
+                Ok(df)
+            }
+            Some("parquet") => {
+                let file = std::fs::File::open(path)?;
+                let df = ParquetReader::new(file).finish()?;
+                self.check_duplicate_columns(&df)?;
+                Ok(df)
+            }
+            format if sqlite::is_sqlite(format) => self.read_database(op, &path.display().to_string()),
+            // csv, tsv, no extension, or unknown extension - delimited text with auto-detection
+            _ => self.read_delimited(op, DelimitedInput::File(path), format),
+        }
+    }
+
+    /// Column names and types of a file without loading it, through a lazy scan.
+    /// The row count comes along when it is cheap to know (Parquet metadata, or
+    /// plain JSON, which has to be read in full).
+    pub fn peek_schema(&self, path: &str) -> Result<(SchemaRef, Option<usize>)> {
+        let file_path = Path::new(path);
+        if compression_of(file_path, None)?.is_some() {
+            // A compressed file can't be scanned, only read
+            let df = self.read_file(&ReadOp::new(path), file_path)?;
+            return Ok((std::sync::Arc::new(df.schema()), Some(df.height())));
+        }
+        match file_path.extension().and_then(|ext| ext.to_str()) {
+            Some("parquet") => {
+                let schema = LazyFrame::scan_parquet(file_path, ScanArgsParquet::default())?.collect_schema()?;
+                let rows = ParquetReader::new(std::fs::File::open(file_path)?).num_rows()?;
+                Ok((schema, Some(rows)))
+            }
+            Some("ndjson") | Some("jsonl") => {
+                Ok((LazyJsonLineReader::new(file_path).finish()?.collect_schema()?, None))
+            }
+            Some("json") => {
+                let df = JsonReader::new(std::fs::File::open(file_path)?).finish()?;
+                Ok((std::sync::Arc::new(df.schema()), Some(df.height())))
+            }
+            format => {
+                // The delimiter is detected from the start of the file, as read() would
+                let sample = read_detection_sample(file_path)?;
+                let (delimiter, _) = auto_detect_delimiter(&sample, format)?;
+                let schema = LazyCsvReader::new(file_path)
+                    .with_separator(delimiter as u8)
+                    .with_has_header(true)
+                    .with_dtype_overwrite(sidecar::read(file_path)?.map(std::sync::Arc::new))
+                    .finish()?
+                    .collect_schema()?;
+                Ok((schema, None))
+            }
+        }
+    }
+
+    /// Read a table from standard input: read('-'). Defaults to delimited text
+    /// with auto-detection; use format='json', 'ndjson' or 'parquet' otherwise.
+    fn read_stdin(&self, op: &ReadOp) -> Result<DataFrame> {
+        use std::io::Read;
+
+        let mut buffer = Vec::new();
+        std::io::stdin().lock().read_to_end(&mut buffer)?;
+        self.record_input("-");
+        self.read_note("-", &match op.format.as_deref() {
+            Some(format) => format!("format {} (given)", format),
+            None => "format delimited text (stdin)".to_string(),
+        });
+
+        let buffer = match compression_of(Path::new("-"), op.compression.as_deref())? {
+            Some(compression) => decompress(buffer.as_slice(), compression)?,
+            None => buffer,
+        };
+        self.read_bytes(op, buffer, op.format.as_deref(), "-")
+    }
+
+    /// Download a table over HTTP(S): read('https://...'). Format and compression
+    /// come from the file name in the URL, as for local files. The body is
+    /// decompressed as it arrives.
+    fn read_url(&self, op: &ReadOp) -> Result<DataFrame> {
+        use std::io::Read;
+
+        let http_error = |e: reqwest::Error| {
+            DtransformError::InvalidOperation(format!("Failed to download '{}': {}", op.path, e))
+        };
+        let timeout = Duration::from_secs(op.timeout.unwrap_or(DEFAULT_HTTP_TIMEOUT));
+        let client = reqwest::blocking::Client::builder().timeout(timeout).build().map_err(http_error)?;
+        let mut request = client.get(&op.path);
+        for (name, value) in &op.headers {
+            request = request.header(name.as_str(), value.as_str());
+        }
+        let mut response = request.send().and_then(|r| r.error_for_status()).map_err(http_error)?;
+        self.record_input(&op.path);
+
+        let file_name = Path::new(url_file_name(&op.path));
+        let format = op.format.as_deref().or_else(|| format_path(file_name).extension()?.to_str());
+        if self.verbose {
+            match (&op.format, format) {
+                (Some(given), _) => self.read_note(&op.path, &format!("format {} (given)", given)),
+                (None, Some(ext)) => self.read_note(&op.path, &format!("format {} (URL file name)", ext)),
+                (None, None) => self.read_note(&op.path, "format delimited text (no extension in URL)"),
+            }
+        }
+
+        let bytes = match compression_of(file_name, op.compression.as_deref())? {
+            Some(compression) => {
+                self.read_note(&op.path, &format!("decompressing ({:?})", compression).to_lowercase());
+                decompress(response, compression)?
+            }
+            None => {
+                let mut bytes = Vec::new();
+                response.read_to_end(&mut bytes)?;
+                bytes
+            }
+        };
+        self.read_bytes(op, bytes, format, &op.path)
+    }
+
+    /// Parse a table held in memory (stdin, a decompressed file): delimited text
+    /// with auto-detection unless the format says otherwise
+    fn read_bytes(&self, op: &ReadOp, buffer: Vec<u8>, format: Option<&str>, source: &str) -> Result<DataFrame> {
+        match format {
+            Some("json") => {
+                let df = JsonReader::new(std::io::Cursor::new(buffer)).finish()?;
+                self.check_duplicate_columns(&df)?;
+                Ok(df)
+            }
+            Some("ndjson") | Some("jsonl") => {
+                let df = JsonLineReader::new(std::io::Cursor::new(buffer)).finish()?;
+                self.check_duplicate_columns(&df)?;
+                Ok(df)
+            }
+            Some("parquet") => {
+                let df = ParquetReader::new(std::io::Cursor::new(buffer)).finish()?;
+                self.check_duplicate_columns(&df)?;
+                Ok(df)
+            }
+            format => {
+                let label = if source == "-" { "Input from stdin" } else { source };
+                let content = decode_text(buffer, op.encoding.as_deref(), label)?;
+                let input = DelimitedInput::Memory { content, source: source.to_string() };
+                self.read_delimited(op, input, format)
+            }
+        }
+    }
+
+    fn read_delimited(&self, op: &ReadOp, input: DelimitedInput, format: Option<&str>) -> Result<DataFrame> {
+        let has_header = op.header.unwrap_or(true);
+        let skip_rows = op.skip_rows.unwrap_or(0);
+        let source_name = input.display_name();
+
+        // A file is only loaded whole when it has to be transcoded; detection reads
+        // the start of it, and trimming or the CSV reader stream the rest
+        let transcode = needs_transcoding(op.encoding.as_deref());
+        let file_content = match input {
+            DelimitedInput::File(path) if transcode => {
+                Some(decode_text(std::fs::read(path)?, op.encoding.as_deref(), &source_name)?)
+            }
+            _ => None,
+        };
+        let content: Option<&str> = match &input {
+            DelimitedInput::File(_) => file_content.as_deref(),
+            DelimitedInput::Memory { content, .. } => Some(content.as_str()),
+        };
+
+        // Determine delimiter and trim_whitespace
+        let (delimiter, trim_whitespace) = match (op.delimiter, op.trim_whitespace) {
+            (Some(delim), Some(trim)) => (delim, trim),
+            _ => {
+                // Need to auto-detect delimiter and/or trim_whitespace
+                let sample = match (&input, content) {
+                    (DelimitedInput::File(path), None) => std::borrow::Cow::Owned(read_detection_sample(path)?),
+                    (_, content) => std::borrow::Cow::Borrowed(detection_sample(content.unwrap_or_default())),
+                };
+                let detection_content = match &op.comment {
+                    // Comment lines would skew the delimiter counts
+                    Some(prefix) => sample
+                        .lines()
+                        .filter(|line| !line.starts_with(prefix.as_str()))
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                    None => sample.to_string(),
+                };
+                match (op.delimiter, auto_detect_delimiter(&detection_content, format)) {
+                    // An explicit delimiter never fails on an ambiguous sample
+                    (Some(delim), Err(_)) => (delim, op.trim_whitespace.unwrap_or(false)),
+                    (delim, detected) => {
+                        let (detected_delim, detected_trim) = detected?;
+                        (
+                            delim.unwrap_or(detected_delim),
+                            op.trim_whitespace.unwrap_or(detected_trim)
+                        )
+                    }
+                }
+            }
+        };
+
+        if self.verbose {
+            let origin = |given: bool| if given { "given" } else { "detected" };
+            self.read_note(&source_name, &format!(
+                "delimiter {:?} ({}), trim_whitespace {} ({}), header row {}",
+                delimiter,
+                origin(op.delimiter.is_some()),
+                trim_whitespace,
+                origin(op.trim_whitespace.is_some()),
+                if has_header { "yes" } else { "no" },
+            ));
+            if let Some(encoding) = &op.encoding {
+                self.read_note(&source_name, &format!("decoded from {}", encoding));
+            }
+            if skip_rows > 0 {
+                self.read_note(&source_name, &format!("skipped {} line(s) before the data", skip_rows));
+            }
+            if let Some(prefix) = &op.comment {
+                let comments = match (&input, content) {
+                    (DelimitedInput::File(path), None) => {
+                        use std::io::BufRead;
+                        let mut comments = 0;
+                        for line in std::io::BufReader::new(std::fs::File::open(path)?).split(b'\n') {
+                            if line?.starts_with(prefix.as_bytes()) {
+                                comments += 1;
+                            }
+                        }
+                        comments
+                    }
+                    (_, content) => content
+                        .unwrap_or_default()
+                        .lines()
+                        .filter(|line| line.starts_with(prefix.as_str()))
+                        .count(),
+                };
+                self.read_note(&source_name, &format!("{} comment line(s) starting with {:?}", comments, prefix));
+            }
+            if !op.null_values.is_empty() {
+                self.read_note(&source_name, &format!("null values: {:?}", op.null_values));
+            }
+        }
+
+        // Column types saved by write(..., schema=true); without a header row the
+        // names wouldn't match
+        let sidecar_source = match &input {
+            DelimitedInput::File(path) => Some(path.to_path_buf()),
+            DelimitedInput::Memory { source, .. } if source != "-" && !is_url(source) => Some(PathBuf::from(source)),
+            DelimitedInput::Memory { .. } => None,
+        };
+        let column_types = match sidecar_source {
+            Some(path) if has_header && op.schema != Some(false) => sidecar::read(&path)?,
+            _ => None,
+        };
+        if let Some(types) = &column_types {
+            self.read_note(&source_name, &format!(
+                "{} column type(s) from {}", types.len(), sidecar::sidecar_path(Path::new(&source_name)).display()
+            ));
+        }
+
+        let read_options = CsvReadOptions::default()
+            .with_has_header(has_header)
+            .with_schema_overwrite(column_types.map(std::sync::Arc::new))
+            .with_skip_rows(skip_rows)
+            .with_parse_options(
+                CsvParseOptions::default()
+                    .with_separator(delimiter as u8)
+                    .with_quote_char(match op.quote_char {
+                        Some(quote) => quote.map(|c| c as u8),
+                        None => Some(b'"'),
+                    })
+                    .with_comment_prefix(op.comment.as_deref())
+                    .with_null_values(if op.null_values.is_empty() {
+                        None
+                    } else {
+                        Some(NullValues::AllColumns(
+                            op.null_values.iter().map(|v| PlSmallStr::from(v.as_str())).collect(),
+                        ))
+                    })
+            );
+
+        let result = match (content, &input) {
+            (Some(content), _) if trim_whitespace => {
+                // Trim each line and collapse multiple spaces
+                let mut trimmed_content = String::with_capacity(content.len());
+                for line in content.lines() {
+                    push_trimmed_line(&mut trimmed_content, line);
+                }
+                let cursor = std::io::Cursor::new(trimmed_content.as_bytes());
+                read_options.into_reader_with_file_handle(cursor).finish()
+            }
+            (None, DelimitedInput::File(path)) if trim_whitespace => {
+                let trimmed_content = read_trimmed_lines(path)?;
+                let cursor = std::io::Cursor::new(trimmed_content.as_bytes());
+                read_options.into_reader_with_file_handle(cursor).finish()
+            }
+            (Some(content), DelimitedInput::Memory { .. }) => {
+                read_options.into_reader_with_file_handle(std::io::Cursor::new(content.as_bytes())).finish()
+            }
+            // Transcoded to UTF-8 in memory
+            (Some(content), DelimitedInput::File(_)) if transcode => {
+                read_options.into_reader_with_file_handle(std::io::Cursor::new(content.as_bytes())).finish()
+            }
+            // Standard file path reading
+            (_, DelimitedInput::File(path)) => read_options
+                .try_into_reader_with_file_path(Some(path.into()))?
+                .finish(),
+            (None, DelimitedInput::Memory { .. }) => unreachable!("in-memory input always has content"),
+        };
+
+        match result {
+            Ok(df) => {
+                self.check_duplicate_columns(&df)?;
+                Ok(df)
+            },
+            Err(e) => {
+                let error_msg = e.to_string();
+                if error_msg.contains("found more fields") || error_msg.contains("Schema") {
+                    Err(DtransformError::InvalidOperation(
+                        format!(
+                            "CSV parsing error: Rows have different numbers of fields.\n\n\
+                            The auto-detected settings may be incorrect:\n\
+                            • Detected delimiter: {:?}\n\
+                            • Detected trim_whitespace: {}\n\n\
+                            Try specifying explicitly:\n\
+                            • read('{}', delimiter=' ')  # space-separated\n\
+                            • read('{}', delimiter='\\t')  # tab-separated\n\
+                            • read('{}', trim_whitespace=true)\n\
+                            • read('{}', skip_rows=N)  # skip header lines",
+                            delimiter, trim_whitespace,
+                            source_name, source_name, source_name, source_name
+                        )
+                    ))
+                } else {
+                    Err(DtransformError::PolarsError(e))
+                }
+            }
+        }
+    }
+
+    fn execute_write(&self, df: DataFrame, op: WriteOp) -> Result<DataFrame> {
+        let target = expand_path_template(&op.path, &self.params)?;
+        let format = op.format.as_deref().or_else(|| format_path(Path::new(&target)).extension()?.to_str());
+        let delimited = target != "-"
+            && !database::is_connection_string(&target)
+            && !sqlite::is_sqlite(format)
+            && !matches!(format, Some("json") | Some("ndjson") | Some("jsonl") | Some("parquet"));
+        if op.schema == Some(true) && (!delimited || op.header == Some(false)) {
+            return Err(DtransformError::InvalidOperation(
+                "schema=true only applies to delimited files (CSV, TSV, ...) written with a header row".to_string(),
+            ));
+        }
+
+        if target == "-" {
+            // Standard output: delimited text unless a format is given
+            let compression = compression_of(Path::new("-"), op.compression.as_deref())?;
+            let stdout = std::io::stdout();
+            let handle = std::io::BufWriter::new(stdout.lock());
+            let mut handle = self.write_compressed(handle, compression, &df, &op, op.format.as_deref(), &target)?;
+            handle.flush()?;
+            self.record_output("-", df.height());
+            return Ok(df);
+        }
+
+        // Database servers: write('postgres://...', table='schema.table')
+        if database::is_connection_string(&target) {
+            self.write_database(&df, &op, &target)?;
+            self.record_output(&database::redact(&target), df.height());
+            return Ok(df);
+        }
+
+        let path = Path::new(&target);
+        let compression = compression_of(path, op.compression.as_deref())?;
+
+        // SQLite writes are atomic through a transaction instead
+        if sqlite::is_sqlite(format) {
+            self.write_database(&df, &op, &target)?;
+            self.record_output(&target, df.height());
+            return Ok(df);
+        }
+
+        self.write_staged(path, |file| {
+            self.write_compressed(file, compression, &df, &op, format, &target)?;
+            Ok(())
+        })?;
+        self.record_output(&target, df.height());
+
+        if delimited {
+            self.write_sidecar(&df, &op, path)?;
+        }
+
+        Ok(df)
+    }
+
+    /// Create `path` the way write() does: `write` fills a file in the workspace,
+    /// which then replaces `path` whole, so a failed write never leaves a
+    /// truncated file (the partial one is kept in the workspace). Devices and
+    /// FIFOs (/dev/stdout) are written directly.
+    pub fn write_staged(
+        &self,
+        path: &Path,
+        write: impl FnOnce(&mut std::io::BufWriter<std::fs::File>) -> Result<()>,
+    ) -> Result<()> {
+        let staged = self.workspace.stage(path)?;
+        let mut file = std::io::BufWriter::new(std::fs::File::create(staged.as_deref().unwrap_or(path))?);
+        if let Err(e) = write(&mut file) {
+            // Keep the partial file for debugging
+            self.workspace.retain();
+            return Err(e);
+        }
+        let file = file.into_inner().map_err(|e| e.into_error())?;
+        if let Some(staged) = staged {
+            file.sync_all()?;
+            drop(file);
+            self.workspace.commit(&staged, path)?;
+        }
+        Ok(())
+    }
+
+    /// Save the column types next to a delimited file written with schema=true.
+    /// Otherwise a sidecar left by an earlier write no longer describes the
+    /// file, and is removed.
+    fn write_sidecar(&self, df: &DataFrame, op: &WriteOp, path: &Path) -> Result<()> {
+        let sidecar_path = sidecar::sidecar_path(path);
+        if op.schema != Some(true) {
+            if sidecar_path.is_file() {
+                std::fs::remove_file(&sidecar_path)?;
+            }
+            return Ok(());
+        }
+
+        let as_text = op
+            .formats
+            .iter()
+            .map(|(col_ref, _)| self.resolve_column_name(col_ref, df))
+            .collect::<Result<Vec<_>>>()?;
+        let rendered = sidecar::render(df, &as_text)?;
+        match self.workspace.stage(&sidecar_path)? {
+            Some(staged) => {
+                std::fs::write(&staged, rendered)?;
+                self.workspace.commit(&staged, &sidecar_path)?;
+            }
+            None => std::fs::write(&sidecar_path, rendered)?,
+        }
+        Ok(())
+    }
+
+    /// write_table() through an encoder when the output is compressed; the writer
+    /// is returned once the compressed stream is complete
+    fn write_compressed<W: std::io::Write>(
+        &self,
+        mut writer: W,
+        compression: Option<Compression>,
+        df: &DataFrame,
+        op: &WriteOp,
+        format: Option<&str>,
+        target: &str,
+    ) -> Result<W> {
+        match compression {
+            None => {
+                self.write_table(&mut writer, df, op, format, target)?;
+                Ok(writer)
+            }
+            Some(Compression::Gzip) => {
+                let mut encoder = flate2::write::GzEncoder::new(writer, flate2::Compression::default());
+                self.write_table(&mut encoder, df, op, format, target)?;
+                Ok(encoder.finish()?)
+            }
+            Some(Compression::Zstd) => {
+                let mut encoder = zstd::stream::write::Encoder::new(writer, 0)?;
+                self.write_table(&mut encoder, df, op, format, target)?;
+                Ok(encoder.finish()?)
+            }
+            Some(Compression::Bzip2) => {
+                let mut encoder = bzip2::write::BzEncoder::new(writer, bzip2::Compression::default());
+                self.write_table(&mut encoder, df, op, format, target)?;
+                Ok(encoder.finish()?)
+            }
+        }
+    }
+
+    fn write_table<W: std::io::Write>(&self, writer: &mut W, df: &DataFrame, op: &WriteOp, format: Option<&str>, target: &str) -> Result<()> {
+        let formatted;
+        let df = if op.formats.is_empty() {
+            df
+        } else {
+            formatted = self.apply_write_formats(df, &op.formats)?;
+            &formatted
+        };
+
+        if format != Some("json") && (op.orient.is_some() || op.pretty.is_some()) {
+            return Err(DtransformError::InvalidOperation(
+                "orient= and pretty= only apply to JSON output (format='json' or a .json path)".to_string(),
+            ));
+        }
+
+        match format {
+            Some("json") => self.write_json(writer, df, op)?,
+            Some("ndjson") | Some("jsonl") => {
+                JsonWriter::new(writer)
+                    .with_json_format(JsonFormat::JsonLines)
+                    .finish(&mut df.clone())?;
+            }
+            Some("parquet") => {
+                ParquetWriter::new(writer)
+                    .finish(&mut df.clone())?;
+            }
+            _ => {
+                // csv, tsv, or unknown extension - treat as delimited text
+                let delimiter = op.delimiter.unwrap_or(if format == Some("tsv") { '\t' } else { ',' });
+                let has_header = op.header.unwrap_or(true);  // Default to true if not specified
+                let chunk_rows = op.chunk_size.unwrap_or(DEFAULT_CHUNK_ROWS);
+                let show_progress = match self.progress {
+                    Some(false) => false,
+                    Some(true) => op.progress.unwrap_or(true),
+                    None => op.progress.unwrap_or(self.verbose),
+                };
+                let total = df.height();
+
+                write_delimited_chunks(
+                    writer,
+                    df,
+                    delimiter as u8,
+                    has_header,
+                    chunk_rows,
+                    op.flush.unwrap_or(false),
+                    |written| {
+                        if show_progress {
+                            let percent = written as f64 * 100.0 / total as f64;
+                            eprint!("\rwrite '{}': {} / {} rows ({:.0}%)", target, written, total, percent);
+                        }
+                    },
+                )?;
+                if show_progress && total > 0 {
+                    eprintln!();
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Render the given columns as text for output: printf-style formats for
+    /// numbers ('%.2f', '%d', '%.3e', with surrounding text like '$%.2f' or '%.1f%%'),
+    /// strftime formats for dates ('%Y-%m-%d', '%d/%m/%Y %H:%M')
+    fn apply_write_formats(&self, df: &DataFrame, formats: &[(ColumnRef, String)]) -> Result<DataFrame> {
+        let mut result = df.clone();
+
+        for (col_ref, fmt) in formats {
+            let name = self.resolve_column_name(col_ref, &result)?;
+            let series = result
+                .column(&name)
+                .map_err(|_| DtransformError::ColumnNotFound(name.clone()))?
+                .as_materialized_series()
+                .clone();
+
+            let text: Vec<Option<String>> = if series.dtype().is_numeric() {
+                let number_format = NumberFormat::parse(fmt)?;
+                let values = series.cast(&polars::datatypes::DataType::Float64)?;
+                values.f64()?.into_iter().map(|v| v.map(|v| number_format.render(v))).collect()
+            } else {
+                let items: Vec<chrono::format::Item> = chrono::format::StrftimeItems::new(fmt).collect();
+                if items.contains(&chrono::format::Item::Error) {
+                    return Err(DtransformError::InvalidOperation(format!(
+                        "Invalid date format '{}' for column '{}' in write()", fmt, name
+                    )));
+                }
+                let (values, _) = self.datetime_values("formats", &series)?;
+                values
+                    .into_iter()
+                    .map(|v| {
+                        v.map(|v| {
+                            // Specifiers the value can't fill (%z on a naive time) fail here
+                            let mut text = String::new();
+                            std::fmt::Write::write_fmt(&mut text, format_args!("{}", v.format_with_items(items.iter())))
+                                .map(|_| text)
+                                .map_err(|_| {
+                                    DtransformError::InvalidOperation(format!(
+                                        "Date format '{}' for column '{}' in write() needs a time zone",
+                                        fmt, name
+                                    ))
+                                })
+                        })
+                        .transpose()
+                    })
+                    .collect::<Result<_>>()?
+            };
+
+            result.replace(&name, Series::new(PlSmallStr::from(name.as_str()), text))?;
+        }
+
+        Ok(result)
+    }
+
+    /// JSON as an array of row objects (orient='records', the default) or as one
+    /// object of column arrays (orient='columns'), optionally indented
+    fn write_json<W: std::io::Write>(&self, writer: &mut W, df: &DataFrame, op: &WriteOp) -> Result<()> {
+        let columns = op.orient.as_deref() == Some("columns");
+        let pretty = op.pretty.unwrap_or(false);
+
+        if !columns && !pretty {
+            JsonWriter::new(writer)
+                .with_json_format(JsonFormat::Json)
+                .finish(&mut df.clone())?;
+            return Ok(());
+        }
+
+        // Let polars render the values, then reshape/indent the document
+        let mut table = if columns {
+            df.clone().lazy().select([all().implode()]).collect()?
+        } else {
+            df.clone()
+        };
+        let mut buffer = Vec::new();
+        JsonWriter::new(&mut buffer)
+            .with_json_format(JsonFormat::Json)
+            .finish(&mut table)?;
+
+        let mut value: serde_json::Value = serde_json::from_slice(&buffer)
+            .map_err(|e| DtransformError::InvalidOperation(format!("Failed to build JSON output: {}", e)))?;
+        if columns {
+            // One row of lists: [{"a": [...], "b": [...]}] -> {"a": [...], "b": [...]}
+            value = match value {
+                serde_json::Value::Array(mut rows) if !rows.is_empty() => rows.swap_remove(0),
+                _ => serde_json::Value::Object(serde_json::Map::new()),
+            };
+        }
+
+        let result = if pretty {
+            serde_json::to_writer_pretty(&mut *writer, &value)
+        } else {
+            serde_json::to_writer(&mut *writer, &value)
+        };
+        result.map_err(|e| DtransformError::InvalidOperation(format!("Failed to write JSON: {}", e)))?;
+        writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn execute_select(&self, df: DataFrame, op: SelectOp) -> Result<DataFrame> {
+        let schema = df.schema();
+
+        // -col / -re(...) items remove columns from the rest of the selection,
+        // or from all columns when nothing else is selected: select(-tmp, -re('^debug_'))
+        let (exclusions, mut selections): (Vec<_>, Vec<_>) = op
+            .selectors
+            .into_iter()
+            .partition(|(selector, _)| matches!(selector, ColumnSelector::Except(_)));
+
+        let mut excluded: Vec<String> = Vec::new();
+        for (selector, alias) in exclusions {
+            if let Some(alias) = alias {
+                return Err(DtransformError::InvalidOperation(format!(
+                    "Cannot alias an excluded column (as {})", alias
+                )));
+            }
+            if let ColumnSelector::Except(inner) = selector {
+                excluded.extend(self.resolve_selector(&inner, &schema)?);
+            }
+        }
+        if selections.is_empty() {
+            selections.push((ColumnSelector::All, None));
+        }
+
+        // (source column, output name) in output order
+        let mut outputs: Vec<(String, String)> = Vec::new();
+
+        for (selector, alias) in selections {
+            let mut cols = self.resolve_selector(&selector, &schema)?;
+            cols.retain(|col| !excluded.contains(col));
+
+            if let Some(ref alias) = alias {
+                if cols.len() > 1 {
+                    return Err(DtransformError::InvalidOperation(format!(
+                        "Alias '{}' matches {} columns ({}). An alias can only rename a single column",
+                        alias, cols.len(), cols.join(", ")
+                    )));
+                }
+            }
+
+            for col in cols {
+                let output = alias.clone().unwrap_or_else(|| col.clone());
+                // The same column picked again under the same name, e.g. select(id, types(Number))
+                if outputs.iter().any(|(source, name)| source == &col && name == &output) {
+                    continue;
+                }
+                outputs.push((col, output));
+            }
+        }
+
+        if outputs.is_empty() {
+            return Err(DtransformError::InvalidOperation(
+                "No columns selected".to_string(),
+            ));
+        }
+
+        // Two different columns ending up under one name
+        let mut collisions: Vec<String> = Vec::new();
+        for (i, (_, name)) in outputs.iter().enumerate() {
+            if outputs[..i].iter().any(|(_, earlier)| earlier == name) {
+                continue;
+            }
+            let sources: Vec<&str> = outputs
+                .iter()
+                .filter(|(_, other)| other == name)
+                .map(|(source, _)| source.as_str())
+                .collect();
+            if sources.len() > 1 {
+                collisions.push(format!("'{}' (from {})", name, sources.join(", ")));
+            }
+        }
+        if !collisions.is_empty() {
+            return Err(DtransformError::InvalidOperation(format!(
+                "select() would produce duplicate column names: {}. Use 'as' to give them distinct names",
+                collisions.join("; ")
+            )));
+        }
+
+        let columns = outputs
+            .iter()
+            .map(|(source, name)| Ok(df.column(source)?.clone().with_name(PlSmallStr::from(name.as_str()))))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(DataFrame::new(columns)?)
+    }
+
+    fn resolve_selector(
+        &self,
+        selector: &ColumnSelector,
+        schema: &Schema,
+    ) -> Result<Vec<String>> {
+        // The internal file column is never selected
+        if schema.contains(FILE_NAME_COLUMN) {
+            let mut visible = schema.clone();
+            visible.shift_remove(FILE_NAME_COLUMN);
+            return self.resolve_selector(selector, &visible);
+        }
+
+        match selector {
+            ColumnSelector::Name(name) => {
+                if schema.contains(name) {
+                    Ok(vec![name.clone()])
+                } else {
+                    Err(DtransformError::ColumnNotFound(name.clone()))
+                }
+            }
+
+            ColumnSelector::Index(idx) => {
+                let name = schema
+                    .get_at_index(*idx)
+                    .ok_or_else(|| {
+                        DtransformError::InvalidOperation(format!("Column index {} out of bounds", idx))
+                    })?
+                    .0
+                    .clone();
+                Ok(vec![name.as_str().to_string()])
+            }
+
+            ColumnSelector::Range(start, end) => {
+                let names: Vec<String> = schema
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| i >= start && i <= end)
+                    .map(|(_, (name, _))| name.as_str().to_string())
+                    .collect();
+
+                if names.is_empty() {
+                    return Err(DtransformError::InvalidOperation(
+                        format!("Range ${}..${} is out of bounds or invalid", start + 1, end + 1)
+                    ));
+                }
+
+                Ok(names)
+            }
+
+            ColumnSelector::Regex(pattern) => {
+                let re = self.regex(pattern, false)?;
+                let names: Vec<String> = schema
+                    .iter()
+                    .filter(|(name, _)| re.is_match(name.as_str()))
+                    .map(|(name, _)| name.as_str().to_string())
+                    .collect();
+                Ok(names)
+            }
+
+            ColumnSelector::Type(dtypes) => {
+                let names: Vec<String> = schema
+                    .iter()
+                    .filter(|(_, field)| {
+                        dtypes.iter().any(|dt| self.matches_dtype(dt, field))
+                    })
+                    .map(|(name, _)| name.as_str().to_string())
+                    .collect();
+                Ok(names)
+            }
+
+            ColumnSelector::All => Ok(schema.iter().map(|(name, _)| name.as_str().to_string()).collect()),
+
+            ColumnSelector::Except(inner) => {
+                let all_cols: Vec<String> = schema.iter().map(|(name, _)| name.as_str().to_string()).collect();
+                let excluded = self.resolve_selector(inner, schema)?;
+                Ok(all_cols
+                    .into_iter()
+                    .filter(|col| !excluded.contains(col))
+                    .collect())
+            }
+
+            ColumnSelector::And(left, right) => {
+                let left_cols = self.resolve_selector(left, schema)?;
+                let right_cols = self.resolve_selector(right, schema)?;
+                Ok(left_cols
+                    .into_iter()
+                    .filter(|col| right_cols.contains(col))
+                    .collect())
+            }
+        }
+    }
+
+    fn matches_dtype(&self, dt: &crate::parser::ast::DataType, polars_dt: &polars::datatypes::DataType) -> bool {
+        use polars::datatypes::DataType as PDT;
+        use crate::parser::ast::DataType as AstDT;
+        match dt {
+            AstDT::Number => matches!(
+                polars_dt,
+                PDT::Int8
+                    | PDT::Int16
+                    | PDT::Int32
+                    | PDT::Int64
+                    | PDT::UInt8
+                    | PDT::UInt16
+                    | PDT::UInt32
+                    | PDT::UInt64
+                    | PDT::Float32
+                    | PDT::Float64
+            ),
+            AstDT::String => matches!(polars_dt, PDT::String),
+            AstDT::Boolean => matches!(polars_dt, PDT::Boolean),
+            AstDT::Date => matches!(polars_dt, PDT::Date),
+            AstDT::DateTime => matches!(polars_dt, PDT::Datetime(_, _)),
+        }
+    }
+
+    fn execute_filter(&self, df: DataFrame, op: FilterOp) -> Result<DataFrame> {
+        let mask = self.evaluate_expression(&op.condition, &df)?;
+        let mask_bool = mask.bool()?;
+        Ok(df.filter(mask_bool)?)
+    }
+
+    fn execute_mutate(&self, mut df: DataFrame, op: MutateOp) -> Result<DataFrame> {
+        for assignment in op.assignments {
+            let series = self.evaluate_expression(&assignment.expression, &df)?;
+
+            // Resolve column name from AssignmentTarget
+            let col_name = match &assignment.column {
+                AssignmentTarget::Name(name) => name.clone(),
+                AssignmentTarget::Position(pos) => {
+                    if *pos == 0 {
+                        return Err(DtransformError::InvalidOperation(
+                            "Position $0 is invalid; column positions start at $1".to_string()
+                        ));
+                    }
+                    let col_names = df.get_column_names();
+                    if *pos <= col_names.len() {
+                        // Use existing column name
+                        col_names[pos - 1].to_string()
+                    } else {
+                        // Create new column with auto-generated name (like CSV no-header behavior)
+                        format!("column_{}", pos)
+                    }
+                }
+            };
+
+            let renamed_series = series.with_name(PlSmallStr::from(col_name.as_str()));
+            let _ = df.with_column(renamed_series)?;
+        }
+
+        Ok(df)
+    }
+
+    fn execute_mutate_all(&self, mut df: DataFrame, op: MutateAllOp) -> Result<DataFrame> {
+        let schema = df.schema();
+        let names = self.resolve_selector(&op.columns, &schema)?;
+
+        // Every column is computed from the input table, then replaced in place
+        let mut results = Vec::with_capacity(names.len());
+        for name in &names {
+            let expression = expand_column_template(&op.template, name);
+            let series = self.evaluate_expression(&expression, &df)?;
+            results.push(series.with_name(PlSmallStr::from(name.as_str())));
+        }
+        for series in results {
+            let name = series.name().to_string();
+            df.replace(&name, series)?;
+        }
+        Ok(df)
+    }
+
+    fn execute_rename(&self, df: DataFrame, op: RenameOp) -> Result<DataFrame> {
+        // Mappings apply in order to the list of names; the table is renamed once at the end
+        let mut names: Vec<String> = df.get_column_names().iter().map(|s| s.to_string()).collect();
+        for mapping in &op.mappings {
+            match mapping {
+                RenameMapping::Column(ColumnRef::Name(name), new_name) => {
+                    // Names refer to the table before this step, so rename(a -> b, b -> a) swaps
+                    let index = df
+                        .get_column_index(name)
+                        .or_else(|| names.iter().position(|n| n == name))
+                        .ok_or_else(|| DtransformError::ColumnNotFound(name.clone()))?;
+                    names[index] = new_name.clone();
+                }
+                RenameMapping::Column(col_ref, new_name) => {
+                    // $n is a position, which renaming doesn't move
+                    let old_name = self.resolve_column_name(col_ref, &df)?;
+                    names[df.try_get_column_index(&old_name)?] = new_name.clone();
+                }
+                RenameMapping::Regex { pattern, replacement } => {
+                    self.rename_by_regex(&mut names, pattern, replacement)?;
+                }
+            }
+        }
+        rename_columns("rename", df, names, op.on_conflict)
+    }
+
+    /// Rename every column matching `pattern`, expanding $1 / ${name} in `replacement`.
+    /// Columns that don't match keep their name.
+    fn rename_by_regex(&self, names: &mut [String], pattern: &str, replacement: &str) -> Result<()> {
+        let re = self.regex(pattern, false)?;
+        for name in names.iter_mut().filter(|name| re.is_match(name)) {
+            let new_name = re.replace_all(name, replacement).into_owned();
+            if new_name.is_empty() {
+                return Err(DtransformError::InvalidOperation(format!(
+                    "Renaming '{}' with re('{}') -> '{}' gives an empty column name",
+                    name, pattern, replacement
+                )));
+            }
+            *name = new_name;
+        }
+        Ok(())
+    }
+
+    fn execute_rename_all(&self, df: DataFrame, op: RenameAllOp) -> Result<DataFrame> {
+        let mut names: Vec<String> = df.get_column_names().iter().map(|s| s.to_string()).collect();
+        match &op.strategy {
+            RenameStrategy::Replace { old, new } => {
+                for name in names.iter_mut() {
+                    *name = name.replace(old, new);
+                }
+            }
+            RenameStrategy::Sequential { prefix, start, end } => {
+                let num_cols = df.width();
+                let range_size = end - start + 1;
+
+                if range_size != num_cols {
+                    return Err(DtransformError::InvalidOperation(format!(
+                        "Range {}..{} ({} columns) doesn't match table width ({} columns). Use select() first.",
+                        start, end, range_size, num_cols
+                    )));
+                }
+
+                for (i, name) in names.iter_mut().enumerate() {
+                    *name = format!("{}{}", prefix, start + i);
+                }
+            }
+            RenameStrategy::Regex { pattern, replacement } => self.rename_by_regex(&mut names, pattern, replacement)?,
+            RenameStrategy::Lowercase => names.iter_mut().for_each(|name| *name = name.to_lowercase()),
+            RenameStrategy::Uppercase => names.iter_mut().for_each(|name| *name = name.to_uppercase()),
+            RenameStrategy::SnakeCase => names.iter_mut().for_each(|name| *name = snake_case(name)),
+            RenameStrategy::CamelCase => names.iter_mut().for_each(|name| *name = camel_case(name)),
+            RenameStrategy::Trim => names.iter_mut().for_each(|name| *name = name.trim().to_string()),
+            RenameStrategy::Prefix(prefix) => names.iter_mut().for_each(|name| name.insert_str(0, prefix)),
+            RenameStrategy::Suffix(suffix) => names.iter_mut().for_each(|name| name.push_str(suffix)),
+            RenameStrategy::StripPrefix(prefix) => names.iter_mut().for_each(|name| {
+                if let Some(rest) = name.strip_prefix(prefix.as_str()) {
+                    *name = rest.to_string();
+                }
+            }),
+            RenameStrategy::StripSuffix(suffix) => names.iter_mut().for_each(|name| {
+                if let Some(rest) = name.strip_suffix(suffix.as_str()) {
+                    *name = rest.to_string();
+                }
+            }),
+        }
+        rename_columns("rename_all", df, names, op.on_conflict)
+    }
+
+    fn execute_sort(&self, df: DataFrame, op: SortOp) -> Result<DataFrame> {
+        let col_names: Vec<String> = op
+            .columns
+            .iter()
+            .map(|sort_col| self.resolve_column_name(&sort_col.column, &df))
+            .collect::<Result<Vec<_>>>()?;
+
+        let options = SortMultipleOptions::default()
+            .with_order_descending_multi(op.columns.iter().map(|c| c.descending))
+            .with_nulls_last_multi(op.columns.iter().map(|c| c.nulls_last))
+            .with_maintain_order(op.stable)
+            .with_multithreaded(op.multithreaded);
+
+        match op.limit {
+            // Partial sort: only the first n rows are fully ordered
+            Some(limit) => {
+                let by_column = col_names
+                    .iter()
+                    .map(|name| df.column(name).cloned())
+                    .collect::<PolarsResult<Vec<_>>>()?;
+                Ok(df.sort_impl(by_column, options, Some((0, limit)))?)
+            }
+            None => Ok(df.sort(col_names, options)?),
+        }
+    }
+
+    fn execute_cast(&self, mut df: DataFrame, op: CastOp) -> Result<DataFrame> {
+        for (selector, cast_type) in &op.casts {
+            let schema = df.schema();
+            for name in self.resolve_selector(selector, &schema)? {
+                let series = df.column(&name)?.as_materialized_series().clone();
+                let converted = self.cast_series(&series, cast_type)?.with_name(PlSmallStr::from(name.as_str()));
+                df.replace(&name, converted)?;
+            }
+        }
+        Ok(df)
+    }
+
+    /// Convert a column to another type. Values that cannot be converted become null.
+    fn cast_series(&self, series: &Series, cast_type: &CastType) -> Result<Series> {
+        use polars::datatypes::DataType as PDT;
+
+        let result = match cast_type {
+            // Whole numbers are parsed exactly; other text goes through Float64 so
+            // that '3.0' becomes 3 rather than null
+            CastType::Int if series.dtype() == &PDT::String => {
+                let exact = series.cast(&PDT::Int64)?;
+                if exact.null_count() == series.null_count() {
+                    exact
+                } else {
+                    let via_float = series.cast(&PDT::Float64)?.cast(&PDT::Int64)?;
+                    let ints: Int64Chunked = exact
+                        .i64()?
+                        .into_iter()
+                        .zip(via_float.i64()?)
+                        .map(|(exact, via_float)| exact.or(via_float))
+                        .collect();
+                    ints.into_series().with_name(series.name().clone())
+                }
+            }
+            CastType::Int => series.cast(&PDT::Int64)?,
+            CastType::Float => series.cast(&PDT::Float64)?,
+            CastType::String => series.cast(&PDT::String)?,
+            CastType::Bool => match series.dtype() {
+                PDT::String => {
+                    let values: BooleanChunked = series
+                        .str()?
+                        .into_iter()
+                        .map(|opt| {
+                            opt.and_then(|s| match s.trim().to_lowercase().as_str() {
+                                "true" | "t" | "yes" | "y" | "1" => Some(true),
+                                "false" | "f" | "no" | "n" | "0" => Some(false),
+                                _ => None,
+                            })
+                        })
+                        .collect();
+                    values.into_series()
+                }
+                _ => series.cast(&PDT::Boolean)?,
+            },
+            CastType::Date(format) => match series.dtype() {
+                PDT::String => series.str()?.as_date(format.as_deref(), true)?.into_series(),
+                _ => series.cast(&PDT::Date)?,
+            },
+            CastType::Datetime(format) => match series.dtype() {
+                PDT::String => {
+                    let ambiguous = StringChunked::new(PlSmallStr::from("ambiguous"), ["raise"]);
+                    series
+                        .str()?
+                        .as_datetime(format.as_deref(), TimeUnit::Microseconds, true, false, None, &ambiguous)?
+                        .into_series()
+                }
+                _ => series.cast(&PDT::Datetime(TimeUnit::Microseconds, None))?,
+            },
+        };
+        Ok(result)
+    }
+
+    /// Read a Date, Datetime or date-like text column as naive datetimes.
+    /// The flag is true when the input holds dates without a time of day.
+    fn datetime_values(&self, name: &str, series: &Series) -> Result<(Vec<Option<NaiveDateTime>>, bool)> {
+        use polars::datatypes::DataType as PDT;
+
+        let (series, is_date) = match series.dtype() {
+            PDT::Date => (series.clone(), true),
+            PDT::Datetime(_, _) => (series.clone(), false),
+            PDT::String => {
+                // Try a plain date first ('2024-01-31'), then date and time
+                match self.cast_series(series, &CastType::Date(None)) {
+                    Ok(dates) => (dates, true),
+                    Err(_) => (self.cast_series(series, &CastType::Datetime(None))?, false),
+                }
+            }
+            other => {
+                return Err(DtransformError::TypeMismatch {
+                    expected: format!("Date, Datetime or date text in {}()", name),
+                    got: format!("{:?}", other),
+                })
+            }
+        };
+
+        let values = match series.dtype() {
+            PDT::Date => series
+                .date()?
+                .as_date_iter()
+                .map(|opt| opt.and_then(|d| d.and_hms_opt(0, 0, 0)))
+                .collect(),
+            _ => series.datetime()?.as_datetime_iter().collect(),
+        };
+        Ok((values, is_date))
+    }
+
+    /// Dates or datetimes moved by durations (`sign` -1 subtracts). Dates stay
+    /// dates when every duration is a whole number of days.
+    fn shift_by_duration(&self, dates: &Series, durations: &Series, sign: i64) -> Result<Series> {
+        let (values, is_date) = self.datetime_values("date arithmetic", dates)?;
+        let micros = durations
+            .cast(&polars::datatypes::DataType::Duration(TimeUnit::Microseconds))?
+            .cast(&polars::datatypes::DataType::Int64)?;
+        let micros = micros.i64()?;
+        let whole_days = micros.into_iter().flatten().all(|m| m % MICROS_PER_DAY == 0);
+
+        let shifted: Vec<Option<NaiveDateTime>> = values
+            .iter()
+            .zip(micros)
+            .map(|(dt, m)| Some((*dt)? + chrono::Duration::microseconds(sign * m?)))
+            .collect();
+        if is_date && whole_days {
+            let dates: Vec<Option<NaiveDate>> = shifted.iter().map(|dt| dt.map(|dt| dt.date())).collect();
+            Ok(Series::new(PlSmallStr::from("shifted"), dates))
+        } else {
+            Ok(Series::new(PlSmallStr::from("shifted"), shifted))
+        }
+    }
+
+    /// rolling_mean(x, '7d', ts), or rolling_mean(x, by=ts, window='7d'): aggregate
+    /// `x` over the rows whose `ts` lies in the window ending at the current row's
+    /// `ts`. `ts` must be sorted ascending.
+    fn rolling_by_time(&self, name: &str, args: &[Expression], df: &DataFrame) -> Result<Series> {
+        let window = self.window_arg(name, &args[1])?;
+        if window <= 0 {
+            return Err(DtransformError::InvalidOperation(format!("{}() window must be positive", name)));
+        }
+        let values = self.evaluate_expression(&args[0], df)?.cast(&polars::datatypes::DataType::Float64)?;
+        let values: Vec<Option<f64>> = values.f64()?.into_iter().collect();
+        let (times, _) = self.datetime_values(name, &self.evaluate_expression(&args[2], df)?)?;
+        let times: Vec<i64> = times
+            .iter()
+            .map(|t| t.map(|t| t.and_utc().timestamp_micros()))
+            .collect::<Option<_>>()
+            .ok_or_else(|| DtransformError::InvalidOperation(format!("{}() time column has nulls", name)))?;
+        if times.windows(2).any(|pair| pair[0] > pair[1]) {
+            return Err(DtransformError::InvalidOperation(format!(
+                "{}() needs the time column in ascending order; sort() by it first",
+                name
+            )));
+        }
+
+        // One sweep: the window's rows are start..=i, with a running sum and count,
+        // and for min/max the rows that can still be the extreme, best first
+        let keeps = |kept: f64, new: f64| if name == "rolling_min" { kept < new } else { kept > new };
+        let mut start = 0;
+        let (mut sum, mut count) = (0.0, 0usize);
+        let mut extremes: std::collections::VecDeque<(usize, f64)> = std::collections::VecDeque::new();
+        let mut result = Vec::with_capacity(values.len());
+        for (i, &time) in times.iter().enumerate() {
+            if let Some(value) = values[i] {
+                sum += value;
+                count += 1;
+                while extremes.back().is_some_and(|&(_, kept)| !keeps(kept, value)) {
+                    extremes.pop_back();
+                }
+                extremes.push_back((i, value));
+            }
+            while times[start] <= time.saturating_sub(window) {
+                if let Some(value) = values[start] {
+                    sum -= value;
+                    count -= 1;
+                }
+                start += 1;
+            }
+            while extremes.front().is_some_and(|&(row, _)| row < start) {
+                extremes.pop_front();
+            }
+            if count == 0 {
+                // Drop rounding left over from rows that left the window
+                sum = 0.0;
+            }
+
+            result.push(match name {
+                "rolling_mean" => (count > 0).then(|| sum / count as f64),
+                "rolling_sum" => Some(sum),
+                _ => extremes.front().map(|&(_, value)| value),
+            });
+        }
+        Ok(Series::new(PlSmallStr::from(name), result))
+    }
+
+    /// Length of a time window in microseconds: '7d', '12h', '30m', '45s', '2w',
+    /// or a duration such as days(7)
+    fn window_arg(&self, name: &str, arg: &Expression) -> Result<i64> {
+        let invalid = || {
+            DtransformError::InvalidOperation(format!(
+                "{}() time window must be text like '7d', '12h', '30m' or a duration like days(7)",
+                name
+            ))
+        };
+        match arg {
+            Expression::Literal(crate::parser::ast::Literal::String(text)) => parse_window(text).ok_or_else(invalid),
+            Expression::FunctionCall { name: unit, args } if args.len() == 1 => {
+                let unit = duration_unit_micros(unit).ok_or_else(invalid)?;
+                self.int_arg(name, &args[0])?.checked_mul(unit).ok_or_else(invalid)
+            }
+            _ => Err(invalid()),
+        }
+    }
+
+    fn execute_take(&self, df: DataFrame, op: TakeOp) -> Result<DataFrame> {
+        Ok(df.head(Some(op.n)))
+    }
+
+    /// Random rows in random order. Without replacement a sample larger than the
+    /// table returns every row (shuffled).
+    fn execute_sample(&self, df: DataFrame, op: SampleOp) -> Result<DataFrame> {
+        let n = match op.size {
+            SampleSize::Rows(n) => n,
+            SampleSize::Fraction(frac) => (df.height() as f64 * frac).round() as usize,
+        };
+        let n = if op.with_replacement { n } else { n.min(df.height()) };
+        Ok(df.sample_n_literal(n, op.with_replacement, true, op.seed)?)
+    }
+
+    fn execute_skip(&self, df: DataFrame, op: SkipOp) -> Result<DataFrame> {
+        let height = df.height();
+        if op.n >= height {
+            Ok(df.head(Some(0)))
+        } else {
+            Ok(df.slice(op.n as i64, height - op.n))
+        }
+    }
+
+    fn execute_slice(&self, df: DataFrame, op: SliceOp) -> Result<DataFrame> {
+        let start = op.start.min(df.height());
+        let len = (op.end.saturating_sub(start)).min(df.height() - start);
+        Ok(df.slice(start as i64, len))
+    }
+
+    /// Move the selected columns, in table order, to the front or end of the table or
+    /// next to another column; the other columns keep their order
+    fn execute_relocate(&self, df: DataFrame, op: RelocateOp) -> Result<DataFrame> {
+        let schema = df.schema();
+        let mut moved: Vec<String> = Vec::new();
+        for selector in &op.columns {
+            moved.extend(self.resolve_selector(selector, &schema)?);
+        }
+        let mut order: Vec<String> = schema.iter_names().map(|name| name.to_string()).collect();
+        moved = order.iter().filter(|name| moved.contains(name)).cloned().collect();
+        order.retain(|name| !moved.contains(name));
+
+        let at = match &op.anchor {
+            RelocateAnchor::First => 0,
+            RelocateAnchor::Last => order.len(),
+            RelocateAnchor::Before(anchor) | RelocateAnchor::After(anchor) => {
+                let name = self.resolve_column_name(anchor, &df)?;
+                if df.column(&name).is_err() {
+                    return Err(DtransformError::ColumnNotFound(name));
+                }
+                let index = order.iter().position(|column| *column == name).ok_or_else(|| {
+                    DtransformError::InvalidOperation(format!(
+                        "relocate() can't place columns next to '{}', which is one of the columns it moves",
+                        name
+                    ))
+                })?;
+                match op.anchor {
+                    RelocateAnchor::After(_) => index + 1,
+                    _ => index,
+                }
+            }
+        };
+        order.splice(at..at, moved);
+        Ok(df.select(order)?)
+    }
+
+    fn execute_drop(&self, df: DataFrame, op: DropOp) -> Result<DataFrame> {
+        let schema = df.schema();
+        let mut columns_to_drop: Vec<String> = Vec::new();
+
+        // Resolve all selectors to column names
+        for selector in op.columns {
+            let names = match self.resolve_selector(&selector, &schema) {
+                Ok(names) => names,
+                Err(DtransformError::ColumnNotFound(_)) if op.if_exists => continue,
+                Err(e) => return Err(e),
+            };
+            for name in names {
+                // Overlapping selectors: drop(a, re('^a'))
+                if !columns_to_drop.contains(&name) {
+                    columns_to_drop.push(name);
+                }
+            }
+        }
+
+        // Drop all columns at once
+        let mut result = df;
+        for col_name in columns_to_drop {
+            result = result.drop(&col_name)?;
+        }
+        Ok(result)
+    }
+
+    fn execute_drop_nulls(&self, df: DataFrame, op: DropNullsOp) -> Result<DataFrame> {
+        if op.columns.is_empty() {
+            return Ok(df.drop_nulls::<String>(None)?);
+        }
+
+        let schema = df.schema();
+        let mut subset: Vec<String> = Vec::new();
+        for selector in &op.columns {
+            subset.extend(self.resolve_selector(selector, &schema)?);
+        }
+        Ok(df.drop_nulls(Some(&subset))?)
+    }
+
+    fn execute_fill_nulls(&self, mut df: DataFrame, op: FillNullsOp) -> Result<DataFrame> {
+        for (selector, value_expr) in &op.fills {
+            let schema = df.schema();
+            let names = self.resolve_selector(selector, &schema)?;
+            let value = self.evaluate_expression(value_expr, &df)?;
+
+            for name in names {
+                let column = df.column(&name)?.as_materialized_series().clone();
+                if column.null_count() == 0 {
+                    continue;
+                }
+                let filled = self.fill_series_nulls(&column, &value)?;
+                df.replace(&name, filled.with_name(PlSmallStr::from(name.as_str())))?;
+            }
+        }
+        Ok(df)
+    }
+
+    /// Replace nulls in `series` with the matching row of `value`. The column keeps
+    /// its type when the value fits it (0 into an integer column stays integer);
+    /// numbers that don't fit widen it to a common type, and anything else is an
+    /// error rather than a column of a different kind.
+    fn fill_series_nulls(&self, series: &Series, value: &Series) -> Result<Series> {
+        let mask = series.is_not_null();
+        // Filling an integer column with fractional values must not truncate them
+        let loses_fraction = series.dtype().is_integer()
+            && value.dtype().is_float()
+            && value
+                .cast(&polars::datatypes::DataType::Float64)?
+                .f64()?
+                .into_iter()
+                .any(|v| v.is_some_and(|v| v.fract() != 0.0));
+        match value.strict_cast(series.dtype()) {
+            Ok(value) if series.dtype() != &polars::datatypes::DataType::Null && !loses_fraction => {
+                Ok(series.zip_with(&mask, &value)?)
+            }
+            _ if series.dtype() == &polars::datatypes::DataType::Null
+                || (series.dtype().is_numeric() && value.dtype().is_numeric()) =>
+            {
+                self.conditional("fill_nulls", vec![(mask.into_series(), series.clone())], value.clone())
+            }
+            _ => Err(DtransformError::InvalidOperation(format!(
+                "fill_nulls() can't fill column '{}' ({}) with {} values that don't convert to {}; cast({} -> ...) first to change its type",
+                series.name(), series.dtype(), value.dtype(), series.dtype(), series.name()
+            ))),
+        }
+    }
+
+    fn execute_distinct(&self, df: DataFrame, op: DistinctOp) -> Result<DataFrame> {
+        use polars::prelude::UniqueKeepStrategy;
+
+        match op.columns {
+            // No columns specified - deduplicate on all columns
+            None => {
+                let columns: Vec<String> = df
+                    .get_column_names_str()
+                    .into_iter()
+                    .filter(|name| *name != FILE_NAME_COLUMN)
+                    .map(|name| name.to_string())
+                    .collect();
+                df.unique::<Vec<String>, String>(Some(&columns), UniqueKeepStrategy::First, None)
+                    .map_err(DtransformError::from)
+            }
+
+            // Specific columns - deduplicate based on those columns
+            Some(ref selectors) => {
+                // Resolve selectors to column names
+                let schema = df.schema();
+                let mut column_names: Vec<String> = Vec::new();
+
+                for selector in selectors {
+                    let names = self.resolve_selector(selector, &schema)?;
+                    column_names.extend(names);
+                }
+
+                // Use Polars unique with subset
+                df.unique::<Vec<String>, String>(
+                    Some(&column_names),
+                    UniqueKeepStrategy::First,
+                    None
+                ).map_err(DtransformError::from)
+            }
+        }
+    }
+
+    /// One row of statistics per column. mean/std are only filled in for numeric
+    /// columns; min/max are rendered as text so every column fits the same table.
+    fn execute_describe(&self, df: DataFrame, op: DescribeOp) -> Result<DataFrame> {
+        let names: Vec<String> = match op.columns {
+            None => df.get_column_names().iter().map(|s| s.to_string()).collect(),
+            Some(ref selectors) => {
+                let schema = df.schema();
+                let mut names: Vec<String> = Vec::new();
+                for selector in selectors {
+                    for name in self.resolve_selector(selector, &schema)? {
+                        if !names.contains(&name) {
+                            names.push(name);
+                        }
+                    }
+                }
+                names
+            }
+        };
+
+        let mut dtypes = Vec::with_capacity(names.len());
+        let mut counts = Vec::with_capacity(names.len());
+        let mut nulls = Vec::with_capacity(names.len());
+        let mut means = Vec::with_capacity(names.len());
+        let mut stds = Vec::with_capacity(names.len());
+        let mut mins = Vec::with_capacity(names.len());
+        let mut maxs = Vec::with_capacity(names.len());
+        let mut uniques = Vec::with_capacity(names.len());
+
+        for name in &names {
+            let series = df.column(name)?.as_materialized_series();
+            let numeric = series.dtype().is_numeric();
+
+            dtypes.push(format!("{}", series.dtype()));
+            counts.push((series.len() - series.null_count()) as u64);
+            nulls.push(series.null_count() as u64);
+            means.push(if numeric { series.mean() } else { None });
+            stds.push(if numeric { series.std(1) } else { None });
+            mins.push(series.min_reduce().ok().and_then(|s| scalar_text(s.value())));
+            maxs.push(series.max_reduce().ok().and_then(|s| scalar_text(s.value())));
+            let has_nulls = series.null_count() > 0;
+            uniques.push(series.n_unique().ok().map(|n| (n - has_nulls as usize) as u64));
+        }
+
+        let columns = vec![
+            Series::new(PlSmallStr::from("column"), names).into_column(),
+            Series::new(PlSmallStr::from("dtype"), dtypes).into_column(),
+            Series::new(PlSmallStr::from("count"), counts).into_column(),
+            Series::new(PlSmallStr::from("nulls"), nulls).into_column(),
+            Series::new(PlSmallStr::from("mean"), means).into_column(),
+            Series::new(PlSmallStr::from("std"), stds).into_column(),
+            Series::new(PlSmallStr::from("min"), mins).into_column(),
+            Series::new(PlSmallStr::from("max"), maxs).into_column(),
+            Series::new(PlSmallStr::from("unique"), uniques).into_column(),
+        ];
+        Ok(DataFrame::new(columns)?)
+    }
+
+    /// Type, null count, min and max of every column: the part of describe()
+    /// shown under REPL previews
+    pub fn column_stats(&self, df: &DataFrame) -> Result<DataFrame> {
+        let stats = self.execute_describe(df.clone(), DescribeOp { columns: None })?;
+        Ok(stats.select(["column", "dtype", "nulls", "min", "max"])?)
+    }
+
+    fn resolve_column_name(&self, col_ref: &ColumnRef, df: &DataFrame) -> Result<String> {
+        match col_ref {
+            ColumnRef::Name(name) => Ok(name.clone()),
+            ColumnRef::Index(idx) => {
+                let col_names = df.get_column_names();
+                if *idx < col_names.len() {
+                    Ok(col_names[*idx].to_string())
+                } else {
+                    Err(DtransformError::InvalidOperation(format!(
+                        "Column index {} out of bounds (table has {} columns)",
+                        idx, col_names.len()
+                    )))
+                }
+            }
+            ColumnRef::Position(pos) => {
+                // $1 = first column (index 0), $2 = second column (index 1), etc.
+                if *pos == 0 {
+                    return Err(DtransformError::InvalidOperation(
+                        "Positional columns start at $1, not $0".to_string()
+                    ));
+                }
+                let zero_based_idx = pos - 1;
+                let col_names = df.get_column_names();
+                if zero_based_idx < col_names.len() {
+                    Ok(col_names[zero_based_idx].to_string())
+                } else {
+                    Err(DtransformError::InvalidOperation(format!(
+                        "Column ${} out of bounds (table has {} columns)",
+                        pos, col_names.len()
+                    )))
+                }
+            }
+        }
+    }
+
+    fn evaluate_expression(&self, expr: &Expression, df: &DataFrame) -> Result<Series> {
+        // any(cols) < 0: apply the enclosing predicate to each column and combine
+        if let Some((quantifier, columns)) = direct_quantifier(expr) {
+            return self.evaluate_quantified(expr, quantifier, columns, df);
+        }
+
+        match expr {
+            Expression::Literal(lit) => self.literal_to_series(lit, df.height()),
+
+            Expression::List(literals) => {
+                use crate::parser::ast::Literal as AstLiteral;
+                // Convert list of literals to a Series for 'in' operator
+                // This is a temporary series, not a full DataFrame series
+                if literals.is_empty() {
+                    return Ok(Series::new_empty(PlSmallStr::from("list"), &polars::datatypes::DataType::Null));
+                }
+                // Convert literals to Series based on their type
+                match &literals[0] {
+                    AstLiteral::Number(_) => {
+                        let values: Vec<f64> = literals.iter().map(|lit| {
+                            match lit {
+                                AstLiteral::Number(n) => *n,
+                                _ => 0.0, // Type mismatch, but handle gracefully
+                            }
+                        }).collect();
+                        Ok(Series::new(PlSmallStr::from("list"), values))
+                    }
+                    AstLiteral::String(_) => {
+                        let values: Vec<String> = literals.iter().map(|lit| {
+                            match lit {
+                                AstLiteral::String(s) => s.clone(),
+                                _ => String::new(),
+                            }
+                        }).collect();
+                        Ok(Series::new(PlSmallStr::from("list"), values))
+                    }
+                    AstLiteral::Boolean(_) => {
+                        let values: Vec<bool> = literals.iter().map(|lit| {
+                            match lit {
+                                AstLiteral::Boolean(b) => *b,
+                                _ => false,
+                            }
+                        }).collect();
+                        Ok(Series::new(PlSmallStr::from("list"), values))
+                    }
+                    AstLiteral::Null => {
+                        Ok(Series::new_null(PlSmallStr::from("list"), literals.len()))
+                    }
+                }
+            }
+
+            Expression::Column(col_ref) => {
+                // Check if this is actually a variable reference
+                if let ColumnRef::Name(name) = col_ref {
+                    if let Some(var_df) = self.variables.get(name) {
+                        // It's a stored variable - extract first column
+                        let col = var_df.get_columns().first()
+                            .ok_or_else(|| DtransformError::InvalidOperation(
+                                format!("Variable '{}' has no columns", name)
+                            ))?;
+                        return Ok(col.as_materialized_series().clone());
+                    }
+                }
+
+                // Not a variable - treat as column reference
+                let col_name = self.resolve_column_name(col_ref, df)?;
+                df.column(&col_name)
+                    .map(|col| col.as_materialized_series().clone())
+                    .map_err(DtransformError::PolarsError)
+            }
+
+            Expression::Variable(var_name) => {
+                // Resolve variable and extract first column
+                let var_df = self.variables.get(var_name)
+                    .ok_or_else(|| DtransformError::VariableNotFound(var_name.clone()))?;
+                let col = var_df.get_columns().first()
+                    .ok_or_else(|| DtransformError::InvalidOperation(
+                        format!("Variable '{}' has no columns", var_name)
+                    ))?;
+                Ok(col.as_materialized_series().clone())
+            }
+
+            Expression::VariableColumn { variable, column } => {
+                let var_df = self.variables.get(variable)
+                    .ok_or_else(|| DtransformError::VariableNotFound(variable.clone()))?;
+                let col_name = self.resolve_column_name(column, var_df)?;
+                var_df.column(&col_name)
+                    .map(|col| col.as_materialized_series().clone())
+                    .map_err(|_| DtransformError::ColumnNotFound(format!(
+                        "{} (in variable '{}')",
+                        col_name, variable
+                    )))
+            }
+
+            Expression::BinaryOp { left, op: op @ (BinOp::Matches | BinOp::NotMatches), right } => {
+                // msg ~ re('timeout') is matches(msg, re('timeout')); the pattern isn't a column
+                let args = [left.as_ref().clone(), right.as_ref().clone()];
+                let mask = self.apply_function("matches", &args, df)?;
+                match op {
+                    BinOp::NotMatches => Ok((!mask.bool()?).into_series()),
+                    _ => Ok(mask),
+                }
+            }
+
+            Expression::BinaryOp { left, op, right } => {
+                let left_series = self.evaluate_expression(left, df)?;
+                let right_series = self.evaluate_expression(right, df)?;
+                self.apply_binary_op(&left_series, op, &right_series, df)
+            }
+
+            Expression::Negate(operand) => {
+                let series = self.evaluate_expression(operand, df)?;
+                if !series.dtype().is_numeric() {
+                    return Err(DtransformError::TypeMismatch {
+                        expected: "numeric value for unary minus".to_string(),
+                        got: format!("{:?}", series.dtype()),
+                    });
+                }
+                let result = if series.dtype().is_integer() {
+                    let values = series.cast(&polars::datatypes::DataType::Int64)?;
+                    values.i64()?.apply_values(|v| -v).into_series()
+                } else {
+                    let values = series.cast(&polars::datatypes::DataType::Float64)?;
+                    values.f64()?.apply_values(|v| -v).into_series()
+                };
+                Ok(result.with_name(series.name().clone()))
+            }
+
+            Expression::MethodCall { object, method, args } => {
+                // x.f(a, b) is the same as f(x, a, b)
+                let mut full_args = Vec::with_capacity(args.len() + 1);
+                full_args.push(object.as_ref().clone());
+                full_args.extend(args.iter().cloned());
+                self.apply_function(method, &full_args, df)
+            }
+
+            Expression::Split { string, delimiter, index } => {
+                // Evaluate string and delimiter expressions
+                let string_series = self.evaluate_expression(string, df)?;
+                let delimiter_series = self.evaluate_expression(delimiter, df)?;
+
+                // Get delimiter as string (should be a single value)
+                let delim = match delimiter_series.dtype() {
+                    polars::datatypes::DataType::String => {
+                        delimiter_series.str()
+                            .map_err(|_| DtransformError::InvalidOperation("Delimiter must be a string".to_string()))?
+                            .get(0)
+                            .ok_or_else(|| DtransformError::InvalidOperation("Delimiter is null".to_string()))?
+                            .to_string()
+                    }
+                    _ => return Err(DtransformError::InvalidOperation("Delimiter must be a string".to_string())),
+                };
+
+                // Apply split to string series
+                let string_ca = string_series.str()
+                    .map_err(|_| DtransformError::InvalidOperation("Split can only be applied to string columns".to_string()))?;
+
+                // Without an index, keep every part as a list column
+                let index = match index {
+                    Some(index) => index,
+                    None => {
+                        let mut builder = ListStringChunkedBuilder::new(
+                            PlSmallStr::from("split"),
+                            string_ca.len(),
+                            string_ca.len() * 2,
+                        );
+                        for opt_str in string_ca.into_iter() {
+                            match opt_str {
+                                Some(s) => builder.append_values_iter(s.split(&delim)),
+                                None => builder.append_null(),
+                            }
+                        }
+                        return Ok(builder.finish().into_series());
+                    }
+                };
+
+                // Split each string and extract the specified index
+                let result: Vec<Option<String>> = string_ca.into_iter().map(|opt_str| {
+                    opt_str.and_then(|s| {
+                        let parts: Vec<&str> = s.split(&delim).collect();
+                        // Negative indices count from the end: [-1] is the last part
+                        let position = if *index < 0 {
+                            parts.len().checked_sub(index.unsigned_abs() as usize)?
+                        } else {
+                            *index as usize
+                        };
+                        // Return None if index is out of bounds, otherwise return the element
+                        parts.get(position).map(|&part| part.to_string())
+                    })
+                }).collect();
+
+                Ok(Series::new(PlSmallStr::from("split"), result))
+            }
+
+            Expression::FuzzyLookup { table, key, on, return_field, max_dist } => {
+                self.fuzzy_lookup(table, key, on, return_field, *max_dist, df)
+            }
+
+            Expression::Lookup { table, key, on, return_field, default, strict } => {
+                // Get the lookup table from variables
+                let lookup_df = self.variables.get(table)
+                    .ok_or_else(|| DtransformError::VariableNotFound(table.clone()))?;
+
+                let on_col_names = on.iter()
+                    .map(|field| self.lookup_column(table, lookup_df, field, "on"))
+                    .collect::<Result<Vec<_>>>()?;
+                let return_col_name = self.lookup_column(table, lookup_df, return_field, "return")?;
+
+                if key.len() != on_col_names.len() {
+                    return Err(DtransformError::InvalidOperation(format!(
+                        "lookup() has {} key expression(s) but {} on= field(s)",
+                        key.len(), on_col_names.len()
+                    )));
+                }
+
+                // Left join of the row keys against the lookup table, so any key and
+                // value types work; for repeated keys the last row wins
+                let mut left_columns = Vec::with_capacity(key.len());
+                let mut right_columns = Vec::with_capacity(key.len() + 1);
+                let mut key_names = Vec::with_capacity(key.len());
+                for (i, (key_expr, on_col_name)) in key.iter().zip(&on_col_names).enumerate() {
+                    let key_name = format!("__key{}", i);
+                    let key_series = self.evaluate_expression(key_expr, df)?;
+                    let lookup_keys = lookup_df.column(on_col_name)?.as_materialized_series();
+
+                    let key_type = lookup_key_type(key_series.dtype(), lookup_keys.dtype());
+                    left_columns.push(key_series.cast(&key_type)?.with_name(PlSmallStr::from(key_name.as_str())).into_column());
+                    right_columns.push(lookup_keys.cast(&key_type)?.with_name(PlSmallStr::from(key_name.as_str())).into_column());
+                    key_names.push(key_name);
+                }
+                right_columns.push(
+                    lookup_df.column(&return_col_name)?
+                        .as_materialized_series()
+                        .clone()
+                        .with_name(PlSmallStr::from("__value"))
+                        .into_column(),
+                );
+                // Tells a match with a null value apart from no match
+                right_columns.push(
+                    Series::new(PlSmallStr::from("__matched"), vec![true; lookup_df.height()]).into_column(),
+                );
+
+                let left = DataFrame::new(left_columns)?
+                    .with_row_index(PlSmallStr::from("__row"), None)?;
+                let right = DataFrame::new(right_columns)?
+                    .unique_stable(Some(&key_names), UniqueKeepStrategy::Last, None)?;
+
+                let join_keys: Vec<Expr> = key_names.iter().map(|name| col(name.as_str())).collect();
+                let joined = left
+                    .lazy()
+                    .join(right.lazy(), join_keys.clone(), join_keys, JoinArgs::new(JoinType::Left))
+                    .sort(["__row"], SortMultipleOptions::default())
+                    .collect()?;
+
+                let value = joined
+                    .column("__value")?
+                    .as_materialized_series()
+                    .clone()
+                    .with_name(PlSmallStr::from(return_col_name.as_str()));
+                let matched = joined.column("__matched")?.as_materialized_series().is_not_null();
+
+                if *strict {
+                    let unmatched: Vec<usize> = matched
+                        .into_iter()
+                        .enumerate()
+                        .filter(|(_, m)| *m != Some(true))
+                        .map(|(row, _)| row)
+                        .collect();
+                    if !unmatched.is_empty() {
+                        let mut shown: Vec<String> = Vec::new();
+                        for &row in &unmatched {
+                            let parts = key_names
+                                .iter()
+                                .map(|name| {
+                                    let value = joined.column(name)?.get(row)?;
+                                    Ok(scalar_text(&value).unwrap_or_else(|| "null".to_string()))
+                                })
+                                .collect::<Result<Vec<_>>>()?;
+                            let text = format!("({})", parts.join(", "));
+                            if !shown.contains(&text) {
+                                shown.push(text);
+                            }
+                            if shown.len() == LOOKUP_MISSES_SHOWN {
+                                break;
+                            }
+                        }
+                        return Err(DtransformError::InvalidOperation(format!(
+                            "lookup() in '{}' found no match for {} row(s) (strict=true); unmatched keys include {}",
+                            table,
+                            unmatched.len(),
+                            shown.join(", ")
+                        )));
+                    }
+                }
+
+                match default {
+                    Some(default) => {
+                        let default = self.evaluate_expression(default, df)?;
+                        let filled = self.fill_series_nulls(&value, &default)?;
+                        Ok(self
+                            .conditional("lookup", vec![(matched.into_series(), value)], filled)?
+                            .with_name(PlSmallStr::from(return_col_name.as_str())))
+                    }
+                    None => Ok(value),
+                }
+            }
+
+            Expression::Replace { text, old, new, count, case_sensitive } => {
+                // Evaluate text and new expressions
+                let text_series = self.evaluate_expression(text, df)?;
+                let new_series = self.evaluate_expression(new, df)?;
+
+                // Ensure text is a string column
+                let text_ca = text_series.str()
+                    .map_err(|_| DtransformError::InvalidOperation(
+                        "replace() can only be applied to string columns".to_string()
+                    ))?;
+
+                // Extract new string
+                use polars::datatypes::DataType;
+                let new_str = match new_series.dtype() {
+                    DataType::String => {
+                        new_series.str()
+                            .map_err(|_| DtransformError::InvalidOperation("Replacement text must be a string".to_string()))?
+                            .get(0)
+                            .ok_or_else(|| DtransformError::InvalidOperation("Replacement text is null".to_string()))?
+                            .to_string()
+                    }
+                    _ => return Err(DtransformError::InvalidOperation("Replacement text must be a string".to_string())),
+                };
+
+                // 0 means "all occurrences" for both str::replacen and Regex::replacen
+                let limit = count.unwrap_or(0);
+
+                // Check if old pattern is a regex or a literal string
+                match old.as_ref() {
+                    Expression::Regex(pattern) => {
+                        // Use regex replacement; $1 / ${name} in the replacement refer to capture groups
+                        let re = self.regex(pattern, !case_sensitive)?;
+
+                        let result: Vec<Option<String>> = text_ca.into_iter().map(|opt_str| {
+                            opt_str.map(|s| re.replacen(s, limit, new_str.as_str()).to_string())
+                        }).collect();
+
+                        Ok(Series::new(PlSmallStr::from("replace"), result))
+                    }
+                    _ => {
+                        // Evaluate as expression and use literal string replacement
+                        let old_series = self.evaluate_expression(old, df)?;
+                        let old_str = match old_series.dtype() {
+                            DataType::String => {
+                                old_series.str()
+                                    .map_err(|_| DtransformError::InvalidOperation("Pattern must be a string".to_string()))?
+                                    .get(0)
+                                    .ok_or_else(|| DtransformError::InvalidOperation("Pattern is null".to_string()))?
+                                    .to_string()
+                            }
+                            _ => return Err(DtransformError::InvalidOperation("Pattern must be a string".to_string())),
+                        };
+
+                        let result: Vec<Option<String>> = if *case_sensitive {
+                            text_ca.into_iter().map(|opt_str| {
+                                opt_str.map(|s| match count {
+                                    Some(n) => s.replacen(&old_str, &new_str, *n),
+                                    None => s.replace(&old_str, &new_str),
+                                })
+                            }).collect()
+                        } else {
+                            // Case-insensitive literal match: escape the pattern and
+                            // keep '$' in the replacement literal
+                            let re = self.regex(&regex::escape(&old_str), true)?;
+                            text_ca.into_iter().map(|opt_str| {
+                                opt_str.map(|s| re.replacen(s, limit, regex::NoExpand(&new_str)).to_string())
+                            }).collect()
+                        };
+
+                        Ok(Series::new(PlSmallStr::from("replace"), result))
+                    }
+                }
+            }
+
+            Expression::FunctionCall { name, args } => self.apply_function(name, args, df),
+
+            // Bare any(cols)/all(cols): the columns themselves must be boolean
+            Expression::ColumnQuantifier { quantifier, columns } => {
+                self.evaluate_quantified(expr, *quantifier, columns, df)
+            }
+
+            Expression::Regex(pattern) => {
+                // Regex literal shouldn't be evaluated directly as a series
+                Err(DtransformError::InvalidOperation(
+                    format!("Regex pattern '{}' cannot be used directly. Use it with replace() function.", pattern)
+                ))
+            }
+        }
+    }
+
+    /// Evaluate `predicate` once per column matched by the quantifier (with the
+    /// quantifier replaced by that column) and combine the results with or (any)
+    /// or and (all). Over no columns, any() is false and all() is true.
+    fn evaluate_quantified(
+        &self,
+        predicate: &Expression,
+        quantifier: Quantifier,
+        columns: &[ColumnSelector],
+        df: &DataFrame,
+    ) -> Result<Series> {
+        let schema = df.schema();
+        let mut names: Vec<String> = Vec::new();
+        for selector in columns {
+            for name in self.resolve_selector(selector, &schema)? {
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+
+        let (combine, label) = match quantifier {
+            Quantifier::Any => (BinOp::Or, "any"),
+            Quantifier::All => (BinOp::And, "all"),
+        };
+
+        let mut result: Option<Series> = None;
+        for name in &names {
+            let per_column = rewrite_expression(predicate, &|e| match e {
+                Expression::ColumnQuantifier { .. } => Some(Expression::Column(ColumnRef::Name(name.clone()))),
+                _ => None,
+            });
+            let mask = self.evaluate_expression(&per_column, df)?;
+            if mask.dtype() != &polars::datatypes::DataType::Boolean {
+                return Err(DtransformError::TypeMismatch {
+                    expected: format!("Boolean condition for {}() over columns (e.g. {}(types(Number)) > 0)", label, label),
+                    got: format!("{:?} for column '{}'", mask.dtype(), name),
+                });
+            }
+            result = Some(match result {
+                Some(acc) => self.apply_binary_op(&acc, &combine, &mask, df)?,
+                None => mask,
+            });
+        }
+
+        let result = result.unwrap_or_else(|| {
+            Series::new(PlSmallStr::from(label), vec![quantifier == Quantifier::All; df.height()])
+        });
+        Ok(result.with_name(PlSmallStr::from(label)))
+    }
+
+    fn literal_to_series(&self, lit: &crate::parser::ast::Literal, len: usize) -> Result<Series> {
+        use crate::parser::ast::Literal as Lit;
+        match lit {
+            Lit::Number(n) => Ok(Series::new(PlSmallStr::from("literal"), vec![*n; len])),
+            Lit::String(s) => Ok(Series::new(PlSmallStr::from("literal"), vec![s.as_str(); len])),
+            Lit::Boolean(b) => Ok(Series::new(PlSmallStr::from("literal"), vec![*b; len])),
+            Lit::Null => Ok(Series::new_null(PlSmallStr::from("literal"), len)),
+        }
+    }
+
+    fn apply_binary_op(&self, left: &Series, op: &BinOp, right: &Series, _df: &DataFrame) -> Result<Series> {
+        use polars::datatypes::DataType;
+
+        // Null propagation rules:
+        // - `is` / `is not` are null-aware: null is null → true, value is null → false
+        // - `==` / `!=` against a bare `null` literal behave like `is` / `is not`
+        // - All other comparisons involving null yield null (rows are dropped by filter)
+        let left_is_null_literal = matches!(left.dtype(), DataType::Null);
+        let right_is_null_literal = matches!(right.dtype(), DataType::Null);
+
+        if left_is_null_literal || right_is_null_literal {
+            let other = if left_is_null_literal { right } else { left };
+            match op {
+                BinOp::Is | BinOp::Eq => return Ok(other.is_null().into_series()),
+                BinOp::IsNot | BinOp::Neq => return Ok(other.is_not_null().into_series()),
+                BinOp::Gt | BinOp::Lt | BinOp::Gte | BinOp::Lte => {
+                    return Ok(BooleanChunked::full_null(PlSmallStr::from("compare"), other.len()).into_series());
+                }
+                _ => {}
+            }
+        }
+
+        let result = match op {
+            BinOp::Add => {
+                // Handle string concatenation
+                match (left.dtype(), right.dtype()) {
+                    (DataType::String, DataType::String) => {
+                        let left_str = left.str().map_err(|_| DtransformError::TypeMismatch {
+                            expected: "String".to_string(),
+                            got: format!("{:?}", left.dtype()),
+                        })?;
+                        let right_str = right.str().map_err(|_| DtransformError::TypeMismatch {
+                            expected: "String".to_string(),
+                            got: format!("{:?}", right.dtype()),
+                        })?;
+
+                        // Concatenate strings element-wise
+                        let result: Vec<Option<String>> = left_str.into_iter()
+                            .zip(right_str)
+                            .map(|(l, r)| {
+                                match (l, r) {
+                                    (Some(ls), Some(rs)) => Some(format!("{}{}", ls, rs)),
+                                    _ => None,
+                                }
+                            })
+                            .collect();
+
+                        Series::new(PlSmallStr::from("concat"), result)
+                    }
+                    (dtype, DataType::Duration(_)) if is_date_like(dtype) => self.shift_by_duration(left, right, 1)?,
+                    (DataType::Duration(_), dtype) if is_date_like(dtype) => self.shift_by_duration(right, left, 1)?,
+                    // Numeric addition (default behavior)
+                    _ => (left + right)?,
+                }
+            }
+            BinOp::Sub => match (left.dtype(), right.dtype()) {
+                (dtype, DataType::Duration(_)) if is_date_like(dtype) => self.shift_by_duration(left, right, -1)?,
+                _ => (left - right)?,
+            },
+            BinOp::Mul => (left * right)?,
+            BinOp::Div => (left / right)?,
+            BinOp::Mod | BinOp::Pow => {
+                if !left.dtype().is_numeric() || !right.dtype().is_numeric() {
+                    return Err(DtransformError::TypeMismatch {
+                        expected: "numeric operands".to_string(),
+                        got: format!("{:?} and {:?}", left.dtype(), right.dtype()),
+                    });
+                }
+                if matches!(op, BinOp::Mod) && left.dtype().is_integer() && right.dtype().is_integer() {
+                    // Integer remainder stays integral; modulo by zero is null
+                    let a = left.cast(&DataType::Int64)?;
+                    let b = right.cast(&DataType::Int64)?;
+                    a.i64()?.into_iter().zip(b.i64()?)
+                        .map(|(a, b)| match (a, b) {
+                            (Some(a), Some(b)) if b != 0 => Some(((a % b) + b) % b),
+                            _ => None,
+                        })
+                        .collect::<Int64Chunked>()
+                        .into_series()
+                } else {
+                    let a = left.cast(&DataType::Float64)?;
+                    let b = right.cast(&DataType::Float64)?;
+                    a.f64()?.into_iter().zip(b.f64()?)
+                        .map(|(a, b)| match (a, b) {
+                            (Some(a), Some(b)) if matches!(op, BinOp::Mod) => Some(a - b * (a / b).floor()),
+                            (Some(a), Some(b)) => Some(a.powf(b)),
+                            _ => None,
+                        })
+                        .collect::<Float64Chunked>()
+                        .into_series()
+                }
+            }
+            BinOp::Gt => left.gt(right)?.into_series(),
+            BinOp::Lt => left.lt(right)?.into_series(),
+            BinOp::Gte => left.gt_eq(right)?.into_series(),
+            BinOp::Lte => left.lt_eq(right)?.into_series(),
+            BinOp::Eq => left.equal(right)?.into_series(),
+            BinOp::Neq => left.not_equal(right)?.into_series(),
+            BinOp::Is => left.equal_missing(right)?.into_series(),
+            BinOp::IsNot => left.not_equal_missing(right)?.into_series(),
+            BinOp::And => {
+                let left_bool = left.bool()?;
+                let right_bool = right.bool()?;
+                (left_bool & right_bool).into_series()
+            }
+            BinOp::Or => {
+                let left_bool = left.bool()?;
+                let right_bool = right.bool()?;
+                (left_bool | right_bool).into_series()
+            }
+            BinOp::Matches | BinOp::NotMatches => {
+                return Err(DtransformError::InvalidOperation(
+                    "~ and !~ take a pattern on the right: msg ~ re('timeout|refused')".to_string(),
+                ));
+            }
+            BinOp::NotIn => {
+                let mask = self.apply_binary_op(left, &BinOp::In, right, _df)?;
+                (!mask.bool()?).into_series()
+            }
+            BinOp::In => {
+                // Check if left values are in right collection
+                // right is a series of values to check against
+                // left is the column to test
+
+                use std::collections::HashSet;
+                use polars::datatypes::DataType;
+
+                match left.dtype() {
+                    DataType::String => {
+                        let left_str = left.str()?;
+                        let right_str = right.str()?;
+
+                        // Collect right values into a HashSet for O(1) lookup
+                        let right_set: HashSet<Option<&str>> = right_str.into_iter().collect();
+
+                        // Check each left value
+                        let mask: BooleanChunked = left_str
+                            .into_iter()
+                            .map(|val| right_set.contains(&val))
+                            .collect();
+
+                        mask.into_series()
+                    }
+                    dtype if dtype.is_numeric() => {
+                        // Convert both to f64 for comparison
+                        let left_f64 = left.cast(&DataType::Float64)?;
+                        let right_f64 = right.cast(&DataType::Float64)?;
+
+                        let left_num = left_f64.f64()?;
+                        let right_num = right_f64.f64()?;
+
+                        // Collect right values into a Vec for comparison
+                        let right_values: Vec<Option<f64>> = right_num.into_iter().collect();
+
+                        // Check each left value
+                        let mask: BooleanChunked = left_num
+                            .into_iter()
+                            .map(|left_val| {
+                                right_values.iter().any(|right_val| {
+                                    match (left_val, right_val) {
+                                        (Some(l), Some(r)) => (l - r).abs() < f64::EPSILON,
+                                        (None, None) => true,
+                                        _ => false,
+                                    }
+                                })
+                            })
+                            .collect();
+
+                        mask.into_series()
+                    }
+                    DataType::Boolean | DataType::Date | DataType::Datetime(_, _) | DataType::Duration(_) | DataType::Time => {
+                        // Compare the underlying integers, with the right side read as the
+                        // left side's type first (date text becomes a date)
+                        let right = as_comparable(left, right)?.cast(left.dtype())?;
+                        let left_keys = left.to_physical_repr().cast(&DataType::Int64)?;
+                        let right_keys = right.to_physical_repr().cast(&DataType::Int64)?;
+
+                        let right_set: HashSet<i64> = right_keys.i64()?.into_iter().flatten().collect();
+                        let mask: BooleanChunked = left_keys
+                            .i64()?
+                            .into_iter()
+                            .map(|val| val.is_some_and(|v| right_set.contains(&v)))
+                            .collect();
+
+                        mask.into_series()
+                    }
+                    _ => {
+                        return Err(DtransformError::TypeMismatch {
+                            expected: "String, Number, Boolean or date/time".to_string(),
+                            got: format!("{:?}", left.dtype()),
+                        });
+                    }
+                }
+            }
+        };
+        Ok(result)
     }
 
-    fn execute_drop(&self, df: DataFrame, op: DropOp) -> Result<DataFrame> {
-        let schema = df.schema();
-        let mut columns_to_drop: Vec<String> = Vec::new();
+    fn apply_function(&self, name: &str, args: &[Expression], df: &DataFrame) -> Result<Series> {
+        match name {
+            // Cumulative functions
+            "cumsum" | "cum_sum" | "cumprod" | "cum_prod" | "cummin" | "cum_min" | "cummax" | "cum_max" => {
+                self.expect_arg_count(name, args, 1)?;
+                let series = self.evaluate_expression(&args[0], df)?;
+                let result = match name {
+                    "cumsum" | "cum_sum" => cum_sum(&series, false)?,
+                    "cumprod" | "cum_prod" => cum_prod(&series, false)?,
+                    "cummin" | "cum_min" => cum_min(&series, false)?,
+                    _ => cum_max(&series, false)?,
+                };
+                Ok(result.with_name(PlSmallStr::from(name)))
+            }
 
-        // Resolve all selectors to column names
-        for selector in op.columns {
-            let names = self.resolve_selector(&selector, &schema, &df)?;
-            columns_to_drop.extend(names);
-        }
+            // Ranking functions (1-based; ties share the lowest rank)
+            "rank" | "dense_rank" => {
+                if args.is_empty() || args.len() > 2 {
+                    return Err(DtransformError::InvalidOperation(format!(
+                        "{}() expects 1 or 2 arguments: {}(column) or {}(column, 'desc')",
+                        name, name, name
+                    )));
+                }
+                let series = self.evaluate_expression(&args[0], df)?;
+                let descending = match args.get(1) {
+                    Some(arg) => self.string_arg(name, arg)? == "desc",
+                    None => false,
+                };
+                let method = if name == "rank" { RankMethod::Min } else { RankMethod::Dense };
+                Ok(series
+                    .rank(RankOptions { method, descending }, None)
+                    .with_name(PlSmallStr::from(name)))
+            }
 
-        // Drop all columns at once
-        let mut result = df;
-        for col_name in columns_to_drop {
-            result = result.drop(&col_name)?;
-        }
-        Ok(result)
-    }
+            // Fixed-size rolling windows over the preceding n rows (including the current row),
+            // or over a span of time with a third argument: rolling_mean(x, '7d', ts),
+            // also written rolling_mean(x, by=ts, window='7d')
+            "rolling_mean" | "rolling_sum" | "rolling_min" | "rolling_max" => {
+                if args.len() == 3 {
+                    return self.rolling_by_time(name, args, df);
+                }
+                self.expect_arg_count(name, args, 2)?;
+                let series = self.evaluate_expression(&args[0], df)?;
+                let window_size = self.usize_arg(name, &args[1])?;
+                if window_size == 0 {
+                    return Err(DtransformError::InvalidOperation(format!(
+                        "{}() window size must be at least 1",
+                        name
+                    )));
+                }
+                let options = RollingOptionsFixedWindow {
+                    window_size,
+                    min_periods: window_size,
+                    ..Default::default()
+                };
+                let result = match name {
+                    "rolling_mean" => series.rolling_mean(options)?,
+                    "rolling_sum" => series.rolling_sum(options)?,
+                    "rolling_min" => series.rolling_min(options)?,
+                    _ => series.rolling_max(options)?,
+                };
+                Ok(result.with_name(PlSmallStr::from(name)))
+            }
 
-    fn execute_distinct(&self, df: DataFrame, op: DistinctOp) -> Result<DataFrame> {
-        use polars::prelude::UniqueKeepStrategy;
+            // Offset functions: value from n rows before/after (default 1)
+            "lag" | "lead" => {
+                if args.is_empty() || args.len() > 2 {
+                    return Err(DtransformError::InvalidOperation(format!(
+                        "{}() expects 1 or 2 arguments: {}(column) or {}(column, n)",
+                        name, name, name
+                    )));
+                }
+                let series = self.evaluate_expression(&args[0], df)?;
+                let n = match args.get(1) {
+                    Some(arg) => self.usize_arg(name, arg)? as i64,
+                    None => 1,
+                };
+                let periods = if name == "lag" { n } else { -n };
+                Ok(series.shift(periods).with_name(PlSmallStr::from(name)))
+            }
 
-        match op.columns {
-            // No columns specified - deduplicate on all columns
-            None => {
-                df.unique::<Vec<String>, String>(None, UniqueKeepStrategy::First, None)
-                    .map_err(DtransformError::from)
+            // Aggregates: reduce the column to a scalar and broadcast it to every row
+            "sum" | "mean" | "avg" | "min" | "max" | "median" | "std" => {
+                self.expect_arg_count(name, args, 1)?;
+                let series = self.evaluate_expression(&args[0], df)?;
+                let scalar = match name {
+                    "sum" => series.sum_reduce()?,
+                    "mean" | "avg" => series.mean_reduce(),
+                    "min" => series.min_reduce()?,
+                    "max" => series.max_reduce()?,
+                    "median" => series.median_reduce()?,
+                    _ => series.std_reduce(1)?,
+                };
+                Ok(scalar.into_series(PlSmallStr::from(name)).new_from_index(0, df.height()))
             }
 
-            // Specific columns - deduplicate based on those columns
-            Some(ref selectors) => {
-                // Resolve selectors to column names
-                let schema = df.schema();
-                let mut column_names: Vec<String> = Vec::new();
+            // count() counts rows; count(x) counts non-null values of x
+            "count" => {
+                let count = match args {
+                    [] => df.height(),
+                    [arg] => {
+                        let series = self.evaluate_expression(arg, df)?;
+                        series.len() - series.null_count()
+                    }
+                    _ => {
+                        return Err(DtransformError::InvalidOperation(
+                            "count() expects 0 or 1 arguments: count() or count(column)".to_string()
+                        ));
+                    }
+                };
+                Ok(Series::new(PlSmallStr::from("count"), vec![count as u64; df.height()]))
+            }
 
-                for selector in selectors {
-                    let names = self.resolve_selector(selector, &schema, &df)?;
-                    column_names.extend(names);
-                }
+            // Table introspection, broadcast to every row
+            "has_column" => {
+                self.expect_arg_count(name, args, 1)?;
+                let column = self.string_arg(name, &args[0])?;
+                let present = df.get_column_index(&column).is_some();
+                Ok(Series::new(PlSmallStr::from(name), vec![present; df.height()]))
+            }
+            "ncols" | "nrows" => {
+                self.expect_arg_count(name, args, 0)?;
+                let value = if name == "ncols" { df.width() } else { df.height() };
+                Ok(Series::new(PlSmallStr::from(name), vec![value as u64; df.height()]))
+            }
 
-                // Use Polars unique with subset
-                df.unique::<Vec<String>, String>(
-                    Some(&column_names),
-                    UniqueKeepStrategy::First,
-                    None
-                ).map_err(DtransformError::from)
+            // Boolean aggregates: any(cond) / all(cond), nulls are ignored
+            "any" | "all" => {
+                self.expect_arg_count(name, args, 1)?;
+                let series = self.evaluate_expression(&args[0], df)?;
+                let mask = series.bool().map_err(|_| DtransformError::TypeMismatch {
+                    expected: "Boolean condition".to_string(),
+                    got: format!("{:?}", series.dtype()),
+                })?;
+                let value = if name == "any" { mask.any() } else { mask.all() };
+                Ok(Series::new(PlSmallStr::from(name), vec![value; df.height()]))
             }
-        }
-    }
 
-    fn resolve_column_name(&self, col_ref: &ColumnRef, df: &DataFrame) -> Result<String> {
-        match col_ref {
-            ColumnRef::Name(name) => Ok(name.clone()),
-            ColumnRef::Index(idx) => {
-                let col_names = df.get_column_names();
-                if *idx < col_names.len() {
-                    Ok(col_names[*idx].to_string())
-                } else {
-                    Err(DtransformError::InvalidOperation(format!(
-                        "Column index {} out of bounds (table has {} columns)",
-                        idx, col_names.len()
-                    )))
-                }
+            // String functions
+            "upper" | "lower" | "trim" | "length" => {
+                self.expect_arg_count(name, args, 1)?;
+                let series = self.evaluate_expression(&args[0], df)?;
+                let text = self.string_series(name, &series)?;
+                let result = match name {
+                    "upper" => text.apply_values(|s| s.to_uppercase().into()).into_series(),
+                    "lower" => text.apply_values(|s| s.to_lowercase().into()).into_series(),
+                    "trim" => text.apply_values(|s| s.trim().to_string().into()).into_series(),
+                    _ => {
+                        let lengths: Vec<Option<u32>> = text
+                            .into_iter()
+                            .map(|opt| opt.map(|s| s.chars().count() as u32))
+                            .collect();
+                        Series::new(PlSmallStr::from(name), lengths)
+                    }
+                };
+                Ok(result.with_name(PlSmallStr::from(name)))
             }
-            ColumnRef::Position(pos) => {
-                // $1 = first column (index 0), $2 = second column (index 1), etc.
-                if *pos == 0 {
+
+            // substr(text, start[, len]): 0-based start, negative counts from the end
+            "substr" => {
+                if args.len() < 2 || args.len() > 3 {
                     return Err(DtransformError::InvalidOperation(
-                        "Positional columns start at $1, not $0".to_string()
+                        "substr() expects 2 or 3 arguments: substr(text, start[, length])".to_string(),
                     ));
                 }
-                let zero_based_idx = pos - 1;
-                let col_names = df.get_column_names();
-                if zero_based_idx < col_names.len() {
-                    Ok(col_names[zero_based_idx].to_string())
-                } else {
-                    Err(DtransformError::InvalidOperation(format!(
-                        "Column ${} out of bounds (table has {} columns)",
-                        pos, col_names.len()
-                    )))
-                }
+                let series = self.evaluate_expression(&args[0], df)?;
+                let text = self.string_series(name, &series)?;
+                let start = self.int_arg(name, &args[1])?;
+                let length = match args.get(2) {
+                    Some(arg) => Some(self.usize_arg(name, arg)?),
+                    None => None,
+                };
+                let result = text.apply_values(|s| {
+                    let char_count = s.chars().count() as i64;
+                    let begin = if start < 0 { (char_count + start).max(0) } else { start.min(char_count) } as usize;
+                    let chars = s.chars().skip(begin);
+                    match length {
+                        Some(n) => chars.take(n).collect::<String>().into(),
+                        None => chars.collect::<String>().into(),
+                    }
+                });
+                Ok(result.into_series().with_name(PlSmallStr::from(name)))
             }
-        }
-    }
-
-    fn evaluate_expression(&self, expr: &Expression, df: &DataFrame) -> Result<Series> {
-        match expr {
-            Expression::Literal(lit) => self.literal_to_series(lit, df.height()),
 
-            Expression::List(literals) => {
-                use crate::parser::ast::Literal as AstLiteral;
-                // Convert list of literals to a Series for 'in' operator
-                // This is a temporary series, not a full DataFrame series
-                if literals.is_empty() {
-                    return Ok(Series::new_empty(PlSmallStr::from("list"), &polars::datatypes::DataType::Null));
+            // pad_left(text, width[, char]) / pad_right(text, width[, char])
+            "pad_left" | "pad_right" => {
+                if args.len() < 2 || args.len() > 3 {
+                    return Err(DtransformError::InvalidOperation(format!(
+                        "{}() expects 2 or 3 arguments: {}(text, width[, char])",
+                        name, name
+                    )));
                 }
-                // Convert literals to Series based on their type
-                match &literals[0] {
-                    AstLiteral::Number(_) => {
-                        let values: Vec<f64> = literals.iter().map(|lit| {
-                            match lit {
-                                AstLiteral::Number(n) => *n,
-                                _ => 0.0, // Type mismatch, but handle gracefully
-                            }
-                        }).collect();
-                        Ok(Series::new(PlSmallStr::from("list"), values))
-                    }
-                    AstLiteral::String(_) => {
-                        let values: Vec<String> = literals.iter().map(|lit| {
-                            match lit {
-                                AstLiteral::String(s) => s.clone(),
-                                _ => String::new(),
-                            }
-                        }).collect();
-                        Ok(Series::new(PlSmallStr::from("list"), values))
-                    }
-                    AstLiteral::Boolean(_) => {
-                        let values: Vec<bool> = literals.iter().map(|lit| {
-                            match lit {
-                                AstLiteral::Boolean(b) => *b,
-                                _ => false,
+                // Numbers are padded as text, e.g. pad_left(id, 6, '0')
+                let series = self.evaluate_expression(&args[0], df)?;
+                let series = if series.dtype().is_numeric() {
+                    series.cast(&polars::datatypes::DataType::String)?
+                } else {
+                    series
+                };
+                let text = self.string_series(name, &series)?;
+                let width = self.usize_arg(name, &args[1])?;
+                let fill = match args.get(2) {
+                    Some(arg) => {
+                        let fill = self.string_arg(name, arg)?;
+                        let mut chars = fill.chars();
+                        match (chars.next(), chars.next()) {
+                            (Some(c), None) => c,
+                            _ => {
+                                return Err(DtransformError::InvalidOperation(format!(
+                                    "{}() padding must be a single character, got '{}'",
+                                    name, fill
+                                )))
                             }
-                        }).collect();
-                        Ok(Series::new(PlSmallStr::from("list"), values))
+                        }
                     }
-                    AstLiteral::Null => {
-                        Ok(Series::new_null(PlSmallStr::from("list"), literals.len()))
+                    None => ' ',
+                };
+                let result = text.apply_values(|s| {
+                    let missing = width.saturating_sub(s.chars().count());
+                    let padding: String = std::iter::repeat_n(fill, missing).collect();
+                    if name == "pad_left" {
+                        format!("{}{}", padding, s).into()
+                    } else {
+                        format!("{}{}", s, padding).into()
                     }
-                }
+                });
+                Ok(result.into_series().with_name(PlSmallStr::from(name)))
             }
 
-            Expression::Column(col_ref) => {
-                // Check if this is actually a variable reference
-                if let ColumnRef::Name(name) = col_ref {
-                    if let Some(var_df) = self.variables.get(name) {
-                        // It's a stored variable - extract first column
-                        let col = var_df.get_columns().first()
-                            .ok_or_else(|| DtransformError::InvalidOperation(
-                                format!("Variable '{}' has no columns", name)
-                            ))?;
-                        return Ok(col.as_materialized_series().clone());
+            // contains(text, 'sub' | re('pattern')), starts_with(text, 'prefix'), ends_with(text, 'suffix')
+            "contains" | "starts_with" | "ends_with" => {
+                self.expect_arg_count(name, args, 2)?;
+                let series = self.evaluate_expression(&args[0], df)?;
+                let text = self.string_series(name, &series)?;
+                let result: BooleanChunked = match (&args[1], name) {
+                    (Expression::Regex(pattern), "contains") => {
+                        let re = self.regex(pattern, false)?;
+                        text.into_iter().map(|opt| opt.map(|s| re.is_match(s))).collect()
                     }
-                }
-
-                // Not a variable - treat as column reference
-                let col_name = self.resolve_column_name(col_ref, df)?;
-                df.column(&col_name)
-                    .map(|col| col.as_materialized_series().clone())
-                    .map_err(|e| DtransformError::PolarsError(e))
-            }
-
-            Expression::Variable(var_name) => {
-                // Resolve variable and extract first column
-                let var_df = self.variables.get(var_name)
-                    .ok_or_else(|| DtransformError::VariableNotFound(var_name.clone()))?;
-                let col = var_df.get_columns().first()
-                    .ok_or_else(|| DtransformError::InvalidOperation(
-                        format!("Variable '{}' has no columns", var_name)
-                    ))?;
-                Ok(col.as_materialized_series().clone())
+                    (arg, _) => {
+                        let needle = self.string_arg(name, arg)?;
+                        text.into_iter()
+                            .map(|opt| opt.map(|s| match name {
+                                "contains" => s.contains(needle.as_str()),
+                                "starts_with" => s.starts_with(needle.as_str()),
+                                _ => s.ends_with(needle.as_str()),
+                            }))
+                            .collect()
+                    }
+                };
+                Ok(result.into_series().with_name(PlSmallStr::from(name)))
             }
 
-            Expression::BinaryOp { left, op, right } => {
-                let left_series = self.evaluate_expression(left, df)?;
-                let right_series = self.evaluate_expression(right, df)?;
-                self.apply_binary_op(&left_series, op, &right_series, df)
+            // Edit distance and Jaro-Winkler similarity of two strings
+            "levenshtein" | "jaro_winkler" => self.string_similarity(name, args, df),
+
+            // Regex test: matches(msg, re('^ERR')), or with the pattern as text: matches(msg, '^ERR')
+            "matches" => {
+                self.expect_arg_count(name, args, 2)?;
+                let series = self.evaluate_expression(&args[0], df)?;
+                let text = self.string_series(name, &series)?;
+                let pattern = match &args[1] {
+                    Expression::Regex(pattern) => pattern.clone(),
+                    arg => self.string_arg(name, arg)?,
+                };
+                let re = self.regex(&pattern, false)?;
+                let result: BooleanChunked = text.into_iter().map(|opt| opt.map(|s| re.is_match(s))).collect();
+                Ok(result.into_series().with_name(PlSmallStr::from(name)))
             }
 
-            Expression::MethodCall { object, method, args } => {
-                let obj_series = self.evaluate_expression(object, df)?;
-                self.apply_method(&obj_series, method, args, df)
+            // Every part as a list, the same as split() without an index: split_all(tags, ';')
+            "split_all" => {
+                self.expect_arg_count(name, args, 2)?;
+                let split = Expression::Split {
+                    string: Box::new(args[0].clone()),
+                    delimiter: Box::new(args[1].clone()),
+                    index: None,
+                };
+                Ok(self.evaluate_expression(&split, df)?.with_name(PlSmallStr::from(name)))
             }
 
-            Expression::Split { string, delimiter, index } => {
-                // Evaluate string and delimiter expressions
-                let string_series = self.evaluate_expression(string, df)?;
-                let delimiter_series = self.evaluate_expression(delimiter, df)?;
-
-                // Get delimiter as string (should be a single value)
-                let delim = match delimiter_series.dtype() {
-                    polars::datatypes::DataType::String => {
-                        delimiter_series.str()
-                            .map_err(|_| DtransformError::InvalidOperation("Delimiter must be a string".to_string()))?
-                            .get(0)
-                            .ok_or_else(|| DtransformError::InvalidOperation("Delimiter is null".to_string()))?
-                            .to_string()
+            // Capture group of a regex match: extract(sku, re('^([A-Z]+)-'), 1); null when there's no match
+            "extract" => {
+                if args.len() < 2 || args.len() > 3 {
+                    return Err(DtransformError::InvalidOperation(format!(
+                        "extract() expects 2 or 3 arguments (text, re('pattern'), [group]), got {}", args.len()
+                    )));
+                }
+                let series = self.evaluate_expression(&args[0], df)?;
+                let text = self.string_series(name, &series)?;
+                let pattern = match &args[1] {
+                    Expression::Regex(pattern) => pattern.clone(),
+                    arg => self.string_arg(name, arg)?,
+                };
+                let re = self.regex(&pattern, false)?;
+
+                // Group 1 by default, or the whole match when the pattern has no groups
+                let group = match args.get(2) {
+                    Some(Expression::Literal(crate::parser::ast::Literal::String(group_name))) => {
+                        re.capture_names().position(|n| n == Some(group_name.as_str())).ok_or_else(|| {
+                            DtransformError::InvalidOperation(format!(
+                                "extract(): pattern '{}' has no group named '{}'", pattern, group_name
+                            ))
+                        })?
                     }
-                    _ => return Err(DtransformError::InvalidOperation("Delimiter must be a string".to_string())),
+                    Some(arg) => self.usize_arg(name, arg)?,
+                    None => usize::from(re.captures_len() > 1),
                 };
+                if group >= re.captures_len() {
+                    return Err(DtransformError::InvalidOperation(format!(
+                        "extract(): pattern '{}' has {} group(s), asked for group {}",
+                        pattern, re.captures_len() - 1, group
+                    )));
+                }
 
-                // Apply split to string series
-                let string_ca = string_series.str()
-                    .map_err(|_| DtransformError::InvalidOperation("Split can only be applied to string columns".to_string()))?;
-
-                // Split each string and extract the specified index
-                let result: Vec<Option<String>> = string_ca.into_iter().map(|opt_str| {
-                    opt_str.and_then(|s| {
-                        let parts: Vec<&str> = s.split(&delim).collect();
-                        // Return None if index is out of bounds, otherwise return the element
-                        parts.get(*index).map(|&part| part.to_string())
-                    })
-                }).collect();
+                let result: StringChunked = text
+                    .into_iter()
+                    .map(|opt| opt.and_then(|s| re.captures(s)?.get(group).map(|m| m.as_str())))
+                    .collect();
+                Ok(result.into_series().with_name(PlSmallStr::from(name)))
+            }
 
-                Ok(Series::new(PlSmallStr::from("split"), result))
+            // Type conversion: to_int(x), to_float(x), to_string(x), to_bool(x), to_date(x[, fmt]), to_datetime(x[, fmt])
+            "to_int" | "to_float" | "to_string" | "to_bool" => {
+                self.expect_arg_count(name, args, 1)?;
+                let series = self.evaluate_expression(&args[0], df)?;
+                let cast_type = match name {
+                    "to_int" => CastType::Int,
+                    "to_float" => CastType::Float,
+                    "to_bool" => CastType::Bool,
+                    _ => CastType::String,
+                };
+                Ok(self.cast_series(&series, &cast_type)?.with_name(PlSmallStr::from(name)))
             }
 
-            Expression::Lookup { table, key, on, return_field } => {
-                use crate::parser::ast::LookupField;
+            "to_date" | "to_datetime" => {
+                if args.is_empty() || args.len() > 2 {
+                    return Err(DtransformError::InvalidOperation(format!(
+                        "{}() expects 1 or 2 arguments: {}(text[, format])",
+                        name, name
+                    )));
+                }
+                let series = self.evaluate_expression(&args[0], df)?;
+                let format = match args.get(1) {
+                    Some(arg) => Some(self.string_arg(name, arg)?),
+                    None => None,
+                };
+                let cast_type = if name == "to_date" { CastType::Date(format) } else { CastType::Datetime(format) };
+                Ok(self.cast_series(&series, &cast_type)?.with_name(PlSmallStr::from(name)))
+            }
 
-                // Get the lookup table from variables
-                let lookup_df = self.variables.get(table)
-                    .ok_or_else(|| DtransformError::VariableNotFound(table.clone()))?;
+            // Date parts: year(d), month(d), day(d), weekday(d) (1 = Monday), hour(d)
+            "year" | "month" | "day" | "weekday" | "hour" | "minute" | "second" => {
+                self.expect_arg_count(name, args, 1)?;
+                let series = self.evaluate_expression(&args[0], df)?;
+                let (values, _) = self.datetime_values(name, &series)?;
+                let parts: Vec<Option<i32>> = values
+                    .iter()
+                    .map(|opt| {
+                        opt.map(|dt| match name {
+                            "year" => dt.year(),
+                            "month" => dt.month() as i32,
+                            "day" => dt.day() as i32,
+                            "weekday" => dt.weekday().number_from_monday() as i32,
+                            "hour" => dt.hour() as i32,
+                            "minute" => dt.minute() as i32,
+                            _ => dt.second() as i32,
+                        })
+                    })
+                    .collect();
+                Ok(Series::new(PlSmallStr::from(name), parts))
+            }
 
-                // Resolve the 'on' field name
-                let on_col_name = match on {
-                    LookupField::Name(name) => name.clone(),
-                    LookupField::Position(pos) => {
-                        let schema = lookup_df.schema();
-                        let col_names: Vec<_> = schema.iter_names().collect();
-                        if *pos == 0 || *pos > col_names.len() {
-                            return Err(DtransformError::InvalidOperation(format!(
-                                "Lookup table '{}' has {} columns, but on=${} was specified",
-                                table, col_names.len(), pos
-                            )));
-                        }
-                        col_names[pos - 1].to_string()
+            // date_diff(start, end, unit): whole units from start to end
+            "date_diff" => {
+                self.expect_arg_count(name, args, 3)?;
+                let start = self.evaluate_expression(&args[0], df)?;
+                let end = self.evaluate_expression(&args[1], df)?;
+                let unit = self.string_arg(name, &args[2])?;
+                let unit_micros = match unit.as_str() {
+                    "weeks" | "week" => 7 * 86_400_000_000i64,
+                    "days" | "day" => 86_400_000_000,
+                    "hours" | "hour" => 3_600_000_000,
+                    "minutes" | "minute" => 60_000_000,
+                    "seconds" | "second" => 1_000_000,
+                    _ => {
+                        return Err(DtransformError::InvalidOperation(format!(
+                            "date_diff() unit must be one of weeks, days, hours, minutes, seconds; got '{}'",
+                            unit
+                        )))
                     }
                 };
+                let (start_values, _) = self.datetime_values(name, &start)?;
+                let (end_values, _) = self.datetime_values(name, &end)?;
+                let diffs: Vec<Option<i64>> = start_values
+                    .iter()
+                    .zip(end_values.iter())
+                    .map(|(a, b)| match (a, b) {
+                        (Some(a), Some(b)) => (*b - *a).num_microseconds().map(|us| us / unit_micros),
+                        _ => None,
+                    })
+                    .collect();
+                Ok(Series::new(PlSmallStr::from(name), diffs))
+            }
 
-                // Resolve the 'return' field name
-                let return_col_name = match return_field {
-                    LookupField::Name(name) => name.clone(),
-                    LookupField::Position(pos) => {
-                        let schema = lookup_df.schema();
-                        let col_names: Vec<_> = schema.iter_names().collect();
-                        if *pos == 0 || *pos > col_names.len() {
-                            return Err(DtransformError::InvalidOperation(format!(
-                                "Lookup table '{}' has {} columns, but return=${} was specified",
-                                table, col_names.len(), pos
-                            )));
-                        }
-                        col_names[pos - 1].to_string()
+            // date_add(d, n, unit): shift by n units (n may be a column or negative)
+            "date_add" => {
+                self.expect_arg_count(name, args, 3)?;
+                let series = self.evaluate_expression(&args[0], df)?;
+                let amounts = self.evaluate_expression(&args[1], df)?;
+                let unit = self.string_arg(name, &args[2])?;
+                let (values, is_date) = self.datetime_values(name, &series)?;
+                let amounts = amounts.cast(&polars::datatypes::DataType::Int64)?;
+                let amounts = amounts.i64()?;
+
+                let shift = |dt: NaiveDateTime, n: i64| -> Option<NaiveDateTime> {
+                    let months = |m: i64| {
+                        let months = Months::new(u32::try_from(m.unsigned_abs()).ok()?);
+                        if m >= 0 { dt.checked_add_months(months) } else { dt.checked_sub_months(months) }
+                    };
+                    match unit.as_str() {
+                        "years" | "year" => months(n.checked_mul(12)?),
+                        "months" | "month" => months(n),
+                        "weeks" | "week" => dt.checked_add_signed(chrono::Duration::try_weeks(n)?),
+                        "days" | "day" => dt.checked_add_signed(chrono::Duration::try_days(n)?),
+                        "hours" | "hour" => dt.checked_add_signed(chrono::Duration::try_hours(n)?),
+                        "minutes" | "minute" => dt.checked_add_signed(chrono::Duration::try_minutes(n)?),
+                        _ => dt.checked_add_signed(chrono::Duration::try_seconds(n)?),
                     }
                 };
-
-                // Verify the lookup table has both columns
-                if !lookup_df.schema().contains(&on_col_name) {
-                    return Err(DtransformError::ColumnNotFound(format!(
-                        "Lookup table '{}' does not have column '{}' (specified in on=)",
-                        table, on_col_name
+                if !matches!(
+                    unit.as_str(),
+                    "years" | "year" | "months" | "month" | "weeks" | "week" | "days" | "day"
+                        | "hours" | "hour" | "minutes" | "minute" | "seconds" | "second"
+                ) {
+                    return Err(DtransformError::InvalidOperation(format!(
+                        "date_add() unit must be one of years, months, weeks, days, hours, minutes, seconds; got '{}'",
+                        unit
                     )));
                 }
-                if !lookup_df.schema().contains(&return_col_name) {
-                    return Err(DtransformError::ColumnNotFound(format!(
-                        "Lookup table '{}' does not have column '{}' (specified in return=)",
-                        table, return_col_name
-                    )));
+
+                let shifted: Vec<Option<NaiveDateTime>> = values
+                    .iter()
+                    .zip(amounts)
+                    .map(|(dt, n)| shift((*dt)?, n?))
+                    .collect();
+
+                // Dates stay dates unless a time unit was added
+                if is_date && matches!(unit.as_str(), "years" | "year" | "months" | "month" | "weeks" | "week" | "days" | "day") {
+                    let dates: Vec<Option<NaiveDate>> = shifted.iter().map(|dt| dt.map(|dt| dt.date())).collect();
+                    Ok(Series::new(PlSmallStr::from(name), dates))
+                } else {
+                    Ok(Series::new(PlSmallStr::from(name), shifted))
                 }
+            }
 
-                // Get the 'on' column from the lookup table (this is the key column)
-                let lookup_key_col = lookup_df.column(&on_col_name)
-                    .map_err(|e| DtransformError::PolarsError(e))?
-                    .as_materialized_series();
+            // now(): current local date and time, the same for every row
+            "now" => {
+                self.expect_arg_count(name, args, 0)?;
+                let now = chrono::Local::now().naive_local();
+                Ok(Series::new(PlSmallStr::from(name), vec![now; df.height()]))
+            }
 
-                // Get the return field column from the lookup table
-                let lookup_value_col = lookup_df.column(&return_col_name)
-                    .map_err(|e| DtransformError::PolarsError(e))?
-                    .as_materialized_series();
+            // Durations for date arithmetic: today() - days(7), ts + hours(n)
+            "weeks" | "days" | "hours" | "minutes" | "seconds" => {
+                self.expect_arg_count(name, args, 1)?;
+                let unit = duration_unit_micros(name).unwrap_or_default() as f64;
+                let amounts = self.evaluate_expression(&args[0], df)?.cast(&polars::datatypes::DataType::Float64)?;
+                let micros: Int64Chunked = amounts
+                    .f64()?
+                    .into_iter()
+                    .map(|n| n.map(|n| (n * unit).round() as i64))
+                    .collect();
+                Ok(micros
+                    .into_series()
+                    .cast(&polars::datatypes::DataType::Duration(TimeUnit::Microseconds))?
+                    .with_name(PlSmallStr::from(name)))
+            }
 
-                // Evaluate the key expression for each row
-                let key_series = self.evaluate_expression(key, df)?;
+            // today(): current local date
+            "today" => {
+                self.expect_arg_count(name, args, 0)?;
+                let today = chrono::Local::now().date_naive();
+                Ok(Series::new(PlSmallStr::from(name), vec![today; df.height()]))
+            }
 
-                // Build a lookup map based on the data type
-                use std::collections::HashMap;
-                use polars::datatypes::DataType;
+            // file_name(): the file each row was read from, kept with the rows by the
+            // pipeline's reads, or the path of the pipeline's read()
+            "file_name" => {
+                self.expect_arg_count(name, args, 0)?;
+                if let Ok(files) = df.column(FILE_NAME_COLUMN) {
+                    return Ok(files.as_materialized_series().clone().with_name(PlSmallStr::from(name)));
+                }
+                if let Some(files) = self.row_files.as_ref().filter(|files| files.len() == df.height()) {
+                    return Ok(files.clone().with_name(PlSmallStr::from(name)));
+                }
+                match &self.current_file {
+                    Some(file) => Ok(Series::new(PlSmallStr::from(name), vec![file.as_str(); df.height()])),
+                    None => Err(DtransformError::InvalidOperation(
+                        "file_name() needs rows read from files: not from a variable or stdin, nor after a step \
+                         that replaces them, such as summarize()"
+                            .to_string(),
+                    )),
+                }
+            }
 
-                match (lookup_key_col.dtype(), lookup_value_col.dtype()) {
-                    (DataType::String, DataType::String) => {
-                        let lookup_keys = lookup_key_col.str()
-                            .map_err(|_| DtransformError::TypeMismatch {
-                                expected: "String".to_string(),
-                                got: format!("{:?}", lookup_key_col.dtype()),
-                            })?;
-                        let lookup_values = lookup_value_col.str()
-                            .map_err(|_| DtransformError::TypeMismatch {
-                                expected: "String".to_string(),
-                                got: format!("{:?}", lookup_value_col.dtype()),
-                            })?;
-
-                        // Build lookup map
-                        let mut map: HashMap<String, String> = HashMap::new();
-                        for i in 0..lookup_df.height() {
-                            if let (Some(k), Some(v)) = (lookup_keys.get(i), lookup_values.get(i)) {
-                                map.insert(k.to_string(), v.to_string());
-                            }
-                        }
+            // List functions: list_len(l), list_get(l, i), list_contains(l, v), list_join(l, sep)
+            "list_len" => {
+                self.expect_arg_count(name, args, 1)?;
+                let series = self.evaluate_expression(&args[0], df)?;
+                let list = self.list_series(name, &series)?;
+                let lengths: Vec<Option<u32>> = list
+                    .into_iter()
+                    .map(|opt| opt.map(|items| items.len() as u32))
+                    .collect();
+                Ok(Series::new(PlSmallStr::from(name), lengths))
+            }
 
-                        // Apply lookup
-                        let input_keys = key_series.str()
-                            .map_err(|_| DtransformError::TypeMismatch {
-                                expected: "String".to_string(),
-                                got: format!("{:?}", key_series.dtype()),
-                            })?;
+            // 0-based like split()[i]; negative counts from the end; out of range gives null
+            "list_get" => {
+                self.expect_arg_count(name, args, 2)?;
+                let series = self.evaluate_expression(&args[0], df)?;
+                let list = self.list_series(name, &series)?;
+                let index = self.int_arg(name, &args[1])?;
+                let mut values = Vec::with_capacity(list.len());
+                for opt in list.into_iter() {
+                    let value = opt.and_then(|items| {
+                        let position = if index < 0 {
+                            items.len().checked_sub(index.unsigned_abs() as usize)?
+                        } else {
+                            index as usize
+                        };
+                        items.get(position).ok().map(|v| v.into_static())
+                    });
+                    values.push(value.unwrap_or(AnyValue::Null));
+                }
+                let inner_dtype = list.inner_dtype().clone();
+                Ok(Series::from_any_values_and_dtype(PlSmallStr::from(name), &values, &inner_dtype, false)?)
+            }
 
-                        let result: Vec<Option<String>> = input_keys.into_iter()
-                            .map(|opt_key| {
-                                opt_key.and_then(|k| map.get(k).cloned())
+            "list_contains" => {
+                self.expect_arg_count(name, args, 2)?;
+                let series = self.evaluate_expression(&args[0], df)?;
+                let list = self.list_series(name, &series)?;
+                let result: BooleanChunked = match &args[1] {
+                    Expression::Literal(crate::parser::ast::Literal::Number(n)) => {
+                        let target = *n;
+                        list.into_iter()
+                            .map(|opt| -> PolarsResult<Option<bool>> {
+                                let Some(items) = opt else { return Ok(None) };
+                                let items = items.cast(&polars::datatypes::DataType::Float64)?;
+                                let found = items.f64()?.into_iter().any(|v| v == Some(target));
+                                Ok(Some(found))
                             })
-                            .collect();
-
-                        Ok(Series::new(PlSmallStr::from(return_col_name.as_str()), result))
+                            .collect::<PolarsResult<_>>()?
                     }
-                    (DataType::String, value_dtype) if matches!(
-                        value_dtype,
-                        DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::Int64 |
-                        DataType::UInt8 | DataType::UInt16 | DataType::UInt32 | DataType::UInt64 |
-                        DataType::Float32 | DataType::Float64
-                    ) => {
-                        let lookup_keys = lookup_key_col.str()
-                            .map_err(|_| DtransformError::TypeMismatch {
-                                expected: "String".to_string(),
-                                got: format!("{:?}", lookup_key_col.dtype()),
-                            })?;
-
-                        // Convert value column to f64
-                        let lookup_values_f64 = lookup_value_col.cast(&DataType::Float64)
-                            .map_err(|e| DtransformError::PolarsError(e))?;
-                        let lookup_values = lookup_values_f64.f64()
-                            .map_err(|_| DtransformError::InvalidOperation("Failed to cast to Float64".to_string()))?;
-
-                        // Build lookup map
-                        let mut map: HashMap<String, f64> = HashMap::new();
-                        for i in 0..lookup_df.height() {
-                            if let (Some(k), Some(v)) = (lookup_keys.get(i), lookup_values.get(i)) {
-                                map.insert(k.to_string(), v);
-                            }
-                        }
-
-                        // Apply lookup
-                        let input_keys = key_series.str()
-                            .map_err(|_| DtransformError::TypeMismatch {
-                                expected: "String".to_string(),
-                                got: format!("{:?}", key_series.dtype()),
-                            })?;
-
-                        let result: Vec<Option<f64>> = input_keys.into_iter()
-                            .map(|opt_key| {
-                                opt_key.and_then(|k| map.get(k).copied())
+                    arg => {
+                        let target = self.string_arg(name, arg)?;
+                        list.into_iter()
+                            .map(|opt| -> PolarsResult<Option<bool>> {
+                                let Some(items) = opt else { return Ok(None) };
+                                let items = items.cast(&polars::datatypes::DataType::String)?;
+                                let found = items.str()?.into_iter().any(|v| v == Some(target.as_str()));
+                                Ok(Some(found))
                             })
-                            .collect();
-
-                        Ok(Series::new(PlSmallStr::from(return_col_name.as_str()), result))
-                    }
-                    _ => {
-                        // Generic fallback for other type combinations
-                        // This is less efficient but more general
-                        Err(DtransformError::InvalidOperation(
-                            format!(
-                                "Unsupported lookup type combination: key={:?}, value={:?}",
-                                lookup_key_col.dtype(),
-                                lookup_value_col.dtype()
-                            )
-                        ))
+                            .collect::<PolarsResult<_>>()?
                     }
-                }
+                };
+                Ok(result.into_series().with_name(PlSmallStr::from(name)))
             }
 
-            Expression::Replace { text, old, new } => {
-                // Evaluate text and new expressions
-                let text_series = self.evaluate_expression(text, df)?;
-                let new_series = self.evaluate_expression(new, df)?;
+            // Null items are skipped when joining
+            "list_join" => {
+                self.expect_arg_count(name, args, 2)?;
+                let series = self.evaluate_expression(&args[0], df)?;
+                let list = self.list_series(name, &series)?;
+                let separator = self.string_arg(name, &args[1])?;
+                let joined: Vec<Option<String>> = list
+                    .into_iter()
+                    .map(|opt| -> PolarsResult<Option<String>> {
+                        let Some(items) = opt else { return Ok(None) };
+                        let items = items.cast(&polars::datatypes::DataType::String)?;
+                        let parts: Vec<&str> = items.str()?.into_iter().flatten().collect();
+                        Ok(Some(parts.join(&separator)))
+                    })
+                    .collect::<PolarsResult<_>>()?;
+                Ok(Series::new(PlSmallStr::from(name), joined))
+            }
 
-                // Ensure text is a string column
-                let text_ca = text_series.str()
-                    .map_err(|_| DtransformError::InvalidOperation(
-                        "replace() can only be applied to string columns".to_string()
-                    ))?;
+            // Math: round(x[, decimals]), abs(x)
+            "round" => {
+                if args.is_empty() || args.len() > 2 {
+                    return Err(DtransformError::InvalidOperation(
+                        "round() expects 1 or 2 arguments: round(x[, decimals])".to_string(),
+                    ));
+                }
+                let series = self.evaluate_expression(&args[0], df)?;
+                let decimals = match args.get(1) {
+                    Some(arg) => self.int_arg(name, arg)?,
+                    None => 0,
+                };
+                if series.dtype().is_integer() {
+                    return Ok(series.with_name(PlSmallStr::from(name)));
+                }
+                let factor = 10f64.powi(decimals as i32);
+                let values = series.cast(&polars::datatypes::DataType::Float64)?;
+                let rounded: Float64Chunked = values
+                    .f64()?
+                    .apply_values(|v| (v * factor).round() / factor);
+                Ok(rounded.into_series().with_name(PlSmallStr::from(name)))
+            }
 
-                // Extract new string
-                use polars::datatypes::DataType;
-                let new_str = match new_series.dtype() {
-                    DataType::String => {
-                        new_series.str()
-                            .map_err(|_| DtransformError::InvalidOperation("Replacement text must be a string".to_string()))?
-                            .get(0)
-                            .ok_or_else(|| DtransformError::InvalidOperation("Replacement text is null".to_string()))?
-                            .to_string()
-                    }
-                    _ => return Err(DtransformError::InvalidOperation("Replacement text must be a string".to_string())),
+            "abs" => {
+                self.expect_arg_count(name, args, 1)?;
+                let series = self.evaluate_expression(&args[0], df)?;
+                let result = if series.dtype().is_integer() {
+                    let values = series.cast(&polars::datatypes::DataType::Int64)?;
+                    values.i64()?.apply_values(|v| v.abs()).into_series()
+                } else {
+                    let values = series.cast(&polars::datatypes::DataType::Float64)?;
+                    values.f64()?.apply_values(|v| v.abs()).into_series()
                 };
+                Ok(result.with_name(PlSmallStr::from(name)))
+            }
 
-                // Check if old pattern is a regex or a literal string
-                match old.as_ref() {
-                    Expression::Regex(pattern) => {
-                        // Use regex replacement
-                        let re = Regex::new(pattern)
-                            .map_err(|e| DtransformError::InvalidOperation(
-                                format!("Invalid regex pattern '{}': {}", pattern, e)
-                            ))?;
+            // Null checks: is_null(x), is_not_null(x), coalesce(a, b, ...)
+            "is_null" | "is_not_null" => {
+                self.expect_arg_count(name, args, 1)?;
+                let series = self.evaluate_expression(&args[0], df)?;
+                let mask = if name == "is_null" { series.is_null() } else { series.is_not_null() };
+                Ok(mask.into_series().with_name(PlSmallStr::from(name)))
+            }
 
-                        let result: Vec<Option<String>> = text_ca.into_iter().map(|opt_str| {
-                            opt_str.map(|s| re.replace_all(s, &new_str).to_string())
-                        }).collect();
+            // Range test with both bounds included: between(x, 10, 20),
+            // between(day, '2024-01-01', '2024-03-31')
+            "between" => {
+                self.expect_arg_count(name, args, 3)?;
+                let values = self.evaluate_expression(&args[0], df)?;
+                let low = as_comparable(&values, &self.evaluate_expression(&args[1], df)?)?;
+                let high = as_comparable(&values, &self.evaluate_expression(&args[2], df)?)?;
+                let above = self.apply_binary_op(&values, &BinOp::Gte, &low, df)?;
+                let below = self.apply_binary_op(&values, &BinOp::Lte, &high, df)?;
+                let mask = self.apply_binary_op(&above, &BinOp::And, &below, df)?;
+                Ok(mask.with_name(PlSmallStr::from(name)))
+            }
 
-                        Ok(Series::new(PlSmallStr::from("replace"), result))
+            // concat(a, '-', b) and format('{}-{:03}', region, id): numbers become text;
+            // a null argument makes the row null
+            "concat" | "format" => {
+                let (template, values) = if name == "concat" {
+                    if args.is_empty() {
+                        return Err(DtransformError::InvalidOperation(
+                            "concat() expects at least one argument".to_string(),
+                        ));
                     }
-                    _ => {
-                        // Evaluate as expression and use literal string replacement
-                        let old_series = self.evaluate_expression(old, df)?;
-                        let old_str = match old_series.dtype() {
-                            DataType::String => {
-                                old_series.str()
-                                    .map_err(|_| DtransformError::InvalidOperation("Pattern must be a string".to_string()))?
-                                    .get(0)
-                                    .ok_or_else(|| DtransformError::InvalidOperation("Pattern is null".to_string()))?
-                                    .to_string()
-                            }
-                            _ => return Err(DtransformError::InvalidOperation("Pattern must be a string".to_string())),
-                        };
+                    (FormatTemplate::parse(&"{}".repeat(args.len()))?, args)
+                } else {
+                    let Some((template, values)) = args.split_first() else {
+                        return Err(DtransformError::InvalidOperation(
+                            "format() expects a template: format('{}-{:03}', region, id)".to_string(),
+                        ));
+                    };
+                    let template = FormatTemplate::parse(&self.string_arg(name, template)?)?;
+                    if template.arg_count() != values.len() {
+                        return Err(DtransformError::InvalidOperation(format!(
+                            "format() template uses {} argument(s) but {} were given",
+                            template.arg_count(),
+                            values.len()
+                        )));
+                    }
+                    (template, values)
+                };
 
-                        let result: Vec<Option<String>> = text_ca.into_iter().map(|opt_str| {
-                            opt_str.map(|s| s.replace(&old_str, &new_str))
-                        }).collect();
+                let columns = values
+                    .iter()
+                    .map(|arg| {
+                        let series = self.evaluate_expression(arg, df)?;
+                        if series.len() != df.height() {
+                            return Err(DtransformError::InvalidOperation(format!(
+                                "{}() arguments must have one value per row ({} rows, got {})",
+                                name, df.height(), series.len()
+                            )));
+                        }
+                        self.format_values(&series)
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                let result: StringChunked = (0..df.height())
+                    .map(|row| {
+                        let row_values: Option<Vec<&FormatValue>> = columns.iter().map(|c| c[row].as_ref()).collect();
+                        row_values.map(|v| template.render(&v))
+                    })
+                    .collect();
+                Ok(result.into_series().with_name(PlSmallStr::from(name)))
+            }
 
-                        Ok(Series::new(PlSmallStr::from("replace"), result))
+            // First non-null value among the arguments, row by row
+            "coalesce" => {
+                if args.is_empty() {
+                    return Err(DtransformError::InvalidOperation(
+                        "coalesce() expects at least one argument".to_string(),
+                    ));
+                }
+                let mut result = self.evaluate_expression(&args[0], df)?;
+                for arg in &args[1..] {
+                    if result.null_count() == 0 {
+                        break;
                     }
+                    let next = self.evaluate_expression(arg, df)?;
+                    result = self.fill_series_nulls(&result, &next)?;
                 }
+                Ok(result.with_name(PlSmallStr::from(name)))
             }
 
-            Expression::Regex(pattern) => {
-                // Regex literal shouldn't be evaluated directly as a series
-                Err(DtransformError::InvalidOperation(
-                    format!("Regex pattern '{}' cannot be used directly. Use it with replace() function.", pattern)
-                ))
+            // Conditionals: if(cond, then, else) and case_when(cond1, value1, ..., [default])
+            "if" => {
+                self.expect_arg_count(name, args, 3)?;
+                let cond = self.evaluate_expression(&args[0], df)?;
+
+                // Same answer on every row, e.g. has_column('email'): only evaluate the
+                // chosen branch, so the other may refer to columns that don't exist
+                if let Ok(mask) = cond.bool() {
+                    if !mask.is_empty() && mask.null_count() == 0 {
+                        if mask.all() {
+                            return Ok(self.evaluate_expression(&args[1], df)?.with_name(PlSmallStr::from(name)));
+                        }
+                        if !mask.any() {
+                            return Ok(self.evaluate_expression(&args[2], df)?.with_name(PlSmallStr::from(name)));
+                        }
+                    }
+                }
+
+                let then = self.evaluate_expression(&args[1], df)?;
+                let otherwise = self.evaluate_expression(&args[2], df)?;
+                Ok(self.conditional(name, vec![(cond, then)], otherwise)?.with_name(PlSmallStr::from(name)))
             }
-        }
-    }
 
-    fn literal_to_series(&self, lit: &crate::parser::ast::Literal, len: usize) -> Result<Series> {
-        use crate::parser::ast::Literal as Lit;
-        match lit {
-            Lit::Number(n) => Ok(Series::new(PlSmallStr::from("literal"), vec![*n; len])),
-            Lit::String(s) => Ok(Series::new(PlSmallStr::from("literal"), vec![s.as_str(); len])),
-            Lit::Boolean(b) => Ok(Series::new(PlSmallStr::from("literal"), vec![*b; len])),
-            Lit::Null => Ok(Series::new_null(PlSmallStr::from("literal"), len)),
+            "case_when" => {
+                if args.len() < 2 {
+                    return Err(DtransformError::InvalidOperation(
+                        "case_when() expects condition/value pairs: case_when(cond1, value1, cond2, value2, ..., [default])"
+                            .to_string(),
+                    ));
+                }
+                let mut branches = Vec::with_capacity(args.len() / 2);
+                for pair in args.chunks_exact(2) {
+                    let cond = self.evaluate_expression(&pair[0], df)?;
+                    let value = self.evaluate_expression(&pair[1], df)?;
+                    branches.push((cond, value));
+                }
+                // An odd trailing argument is the default; without one, unmatched rows are null
+                let otherwise = match args.len() % 2 {
+                    1 => self.evaluate_expression(&args[args.len() - 1], df)?,
+                    _ => Series::new_null(PlSmallStr::from("default"), df.height()),
+                };
+                Ok(self.conditional(name, branches, otherwise)?.with_name(PlSmallStr::from(name)))
+            }
+
+            _ => Err(DtransformError::InvalidOperation(format!(
+                "Unknown function '{}()'",
+                name
+            ))),
         }
     }
 
-    fn apply_binary_op(&self, left: &Series, op: &BinOp, right: &Series, _df: &DataFrame) -> Result<Series> {
-        use polars::datatypes::DataType;
-
-        let result = match op {
-            BinOp::Add => {
-                // Handle string concatenation
-                match (left.dtype(), right.dtype()) {
-                    (DataType::String, DataType::String) => {
-                        let left_str = left.str().map_err(|_| DtransformError::TypeMismatch {
-                            expected: "String".to_string(),
-                            got: format!("{:?}", left.dtype()),
-                        })?;
-                        let right_str = right.str().map_err(|_| DtransformError::TypeMismatch {
-                            expected: "String".to_string(),
-                            got: format!("{:?}", right.dtype()),
-                        })?;
+    /// Pick, for each row, the value of the first branch whose condition is true,
+    /// falling back to `otherwise`. A null condition counts as false. Branch
+    /// values of different types are widened to a common type.
+    fn conditional(&self, name: &str, branches: Vec<(Series, Series)>, otherwise: Series) -> Result<Series> {
+        let mut columns = Vec::with_capacity(branches.len() * 2 + 1);
+        let mut expr = col("otherwise");
+        columns.push(otherwise.with_name(PlSmallStr::from("otherwise")).into_column());
+
+        for (i, (cond, value)) in branches.into_iter().enumerate().rev() {
+            if cond.dtype() != &polars::datatypes::DataType::Boolean {
+                return Err(DtransformError::TypeMismatch {
+                    expected: format!("Boolean condition in {}()", name),
+                    got: format!("{:?}", cond.dtype()),
+                });
+            }
+            let cond_name = format!("cond_{}", i);
+            let value_name = format!("value_{}", i);
+            expr = when(col(&cond_name)).then(col(&value_name)).otherwise(expr);
+            columns.push(cond.with_name(PlSmallStr::from(cond_name)).into_column());
+            columns.push(value.with_name(PlSmallStr::from(value_name)).into_column());
+        }
 
-                        // Concatenate strings element-wise
-                        let result: Vec<Option<String>> = left_str.into_iter()
-                            .zip(right_str.into_iter())
-                            .map(|(l, r)| {
-                                match (l, r) {
-                                    (Some(ls), Some(rs)) => Some(format!("{}{}", ls, rs)),
-                                    _ => None,
-                                }
-                            })
-                            .collect();
+        let result = DataFrame::new(columns)?
+            .lazy()
+            .select([expr.alias(name)])
+            .collect()?;
+        Ok(result.column(name)?.as_materialized_series().clone())
+    }
 
-                        Series::new(PlSmallStr::from("concat"), result)
-                    }
-                    // Numeric addition (default behavior)
-                    _ => (left + right)?,
+    /// A column of a lookup table given by on= / return=, by name or position
+    fn lookup_column(&self, table: &str, lookup_df: &DataFrame, field: &LookupField, param: &str) -> Result<String> {
+        let name = match field {
+            LookupField::Name(name) => name.clone(),
+            LookupField::Position(pos) => {
+                let schema = lookup_df.schema();
+                let col_names: Vec<_> = schema.iter_names().collect();
+                if *pos == 0 || *pos > col_names.len() {
+                    return Err(DtransformError::InvalidOperation(format!(
+                        "Lookup table '{}' has {} columns, but {}=${} was specified",
+                        table, col_names.len(), param, pos
+                    )));
                 }
+                col_names[pos - 1].to_string()
             }
-            BinOp::Sub => (left - right)?,
-            BinOp::Mul => (left * right)?,
-            BinOp::Div => (left / right)?,
-            BinOp::Gt => left.gt(right)?.into_series(),
-            BinOp::Lt => left.lt(right)?.into_series(),
-            BinOp::Gte => left.gt_eq(right)?.into_series(),
-            BinOp::Lte => left.lt_eq(right)?.into_series(),
-            BinOp::Eq => left.equal(right)?.into_series(),
-            BinOp::Neq => left.not_equal(right)?.into_series(),
-            BinOp::And => {
-                let left_bool = left.bool()?;
-                let right_bool = right.bool()?;
-                (left_bool & right_bool).into_series()
-            }
-            BinOp::Or => {
-                let left_bool = left.bool()?;
-                let right_bool = right.bool()?;
-                (left_bool | right_bool).into_series()
-            }
-            BinOp::In => {
-                // Check if left values are in right collection
-                // right is a series of values to check against
-                // left is the column to test
-
-                use std::collections::HashSet;
-                use polars::datatypes::DataType;
-
-                match left.dtype() {
-                    DataType::String => {
-                        let left_str = left.str()?;
-                        let right_str = right.str()?;
-
-                        // Collect right values into a HashSet for O(1) lookup
-                        let right_set: HashSet<Option<&str>> = right_str.into_iter().collect();
+        };
+        if !lookup_df.schema().contains(&name) {
+            return Err(DtransformError::ColumnNotFound(format!(
+                "Lookup table '{}' does not have column '{}' (specified in {}=)",
+                table, name, param
+            )));
+        }
+        Ok(name)
+    }
 
-                        // Check each left value
-                        let mask: BooleanChunked = left_str
-                            .into_iter()
-                            .map(|val| right_set.contains(&val))
-                            .collect();
+    fn expect_arg_count(&self, name: &str, args: &[Expression], expected: usize) -> Result<()> {
+        if args.len() != expected {
+            return Err(DtransformError::InvalidOperation(format!(
+                "{}() expects {} argument(s), got {}",
+                name, expected, args.len()
+            )));
+        }
+        Ok(())
+    }
 
-                        mask.into_series()
-                    }
-                    DataType::Int64 | DataType::Int32 | DataType::Float64 | DataType::Float32 => {
-                        // Convert both to f64 for comparison
-                        let left_f64 = left.cast(&DataType::Float64)?;
-                        let right_f64 = right.cast(&DataType::Float64)?;
+    fn usize_arg(&self, name: &str, arg: &Expression) -> Result<usize> {
+        match arg {
+            Expression::Literal(crate::parser::ast::Literal::Number(n)) if *n >= 0.0 && n.fract() == 0.0 => Ok(*n as usize),
+            _ => Err(DtransformError::InvalidOperation(format!(
+                "{}() expects a non-negative whole number argument",
+                name
+            ))),
+        }
+    }
 
-                        let left_num = left_f64.f64()?;
-                        let right_num = right_f64.f64()?;
+    fn int_arg(&self, name: &str, arg: &Expression) -> Result<i64> {
+        match arg {
+            Expression::Literal(crate::parser::ast::Literal::Number(n)) if n.fract() == 0.0 => Ok(*n as i64),
+            _ => Err(DtransformError::InvalidOperation(format!(
+                "{}() expects a whole number argument",
+                name
+            ))),
+        }
+    }
 
-                        // Collect right values into a Vec for comparison
-                        let right_values: Vec<Option<f64>> = right_num.into_iter().collect();
+    /// Values of a series for format() / concat(): integers, floats, or anything else as text
+    fn format_values(&self, series: &Series) -> Result<Vec<Option<FormatValue>>> {
+        use polars::datatypes::DataType as PDT;
+        Ok(if series.dtype().is_integer() {
+            series.cast(&PDT::Int64)?.i64()?.into_iter().map(|v| v.map(FormatValue::Int)).collect()
+        } else if series.dtype().is_float() {
+            series.cast(&PDT::Float64)?.f64()?.into_iter().map(|v| v.map(FormatValue::Float)).collect()
+        } else {
+            series
+                .cast(&PDT::String)?
+                .str()?
+                .into_iter()
+                .map(|v| v.map(|s| FormatValue::Text(s.to_string())))
+                .collect()
+        })
+    }
 
-                        // Check each left value
-                        let mask: BooleanChunked = left_num
-                            .into_iter()
-                            .map(|left_val| {
-                                right_values.iter().any(|right_val| {
-                                    match (left_val, right_val) {
-                                        (Some(l), Some(r)) => (l - r).abs() < f64::EPSILON,
-                                        (None, None) => true,
-                                        _ => false,
-                                    }
-                                })
-                            })
-                            .collect();
+    fn string_series<'s>(&self, name: &str, series: &'s Series) -> Result<&'s StringChunked> {
+        series.str().map_err(|_| DtransformError::TypeMismatch {
+            expected: format!("String column in {}()", name),
+            got: format!("{:?}", series.dtype()),
+        })
+    }
 
-                        mask.into_series()
-                    }
-                    _ => {
-                        return Err(DtransformError::TypeMismatch {
-                            expected: "String or Number".to_string(),
-                            got: format!("{:?}", left.dtype()),
-                        });
-                    }
-                }
-            }
-        };
-        Ok(result)
+    fn list_series<'s>(&self, name: &str, series: &'s Series) -> Result<&'s ListChunked> {
+        series.list().map_err(|_| DtransformError::TypeMismatch {
+            expected: format!("List column in {}()", name),
+            got: format!("{:?}", series.dtype()),
+        })
     }
 
-    fn apply_method(&self, _obj: &Series, method: &str, _args: &[Expression], _df: &DataFrame) -> Result<Series> {
-        // String methods have been removed. Use function-based operations instead:
-        // - For replace: use replace(column, 'old', 'new')
-        Err(DtransformError::InvalidOperation(format!(
-            "Method '{}' is not supported. Use function-based operations instead.\n\
-            Example: mutate(clean = replace(text, 'old', 'new'))",
-            method
-        )))
+    fn string_arg(&self, name: &str, arg: &Expression) -> Result<String> {
+        match arg {
+            Expression::Literal(crate::parser::ast::Literal::String(s)) => Ok(s.clone()),
+            _ => Err(DtransformError::InvalidOperation(format!(
+                "{}() expects a string literal argument",
+                name
+            ))),
+        }
     }
 
     pub fn get_variable(&self, name: &str) -> Option<&DataFrame> {
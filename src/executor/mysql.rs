@@ -0,0 +1,131 @@
+use mysql::prelude::Queryable;
+use mysql::{Conn, Opts, Params, TxOpts};
+
+use super::database::{quote_table, row_count, table_exists_error, Database, SqlColumn, Value, WriteMode};
+use crate::error::{DtransformError, Result};
+
+/// A MySQL or MariaDB server: read('mysql://user@host/db', query=...)
+pub(super) struct Mysql {
+    conn: Conn,
+}
+
+impl Mysql {
+    pub(super) fn connect(url: &str) -> Result<Self> {
+        let opts = Opts::from_url(url).map_err(|e| DtransformError::InvalidOperation(format!("MySQL: {}", e)))?;
+        let conn = Conn::new(opts).map_err(mysql_error)?;
+        Ok(Mysql { conn })
+    }
+}
+
+fn mysql_error(e: mysql::Error) -> DtransformError {
+    DtransformError::InvalidOperation(format!("MySQL: {}", e))
+}
+
+fn row_value(value: &mysql::Value) -> Value {
+    match value {
+        mysql::Value::NULL => Value::Null,
+        mysql::Value::Int(n) => Value::Integer(*n),
+        mysql::Value::UInt(n) => Value::Integer(*n as i64),
+        mysql::Value::Float(f) => Value::Real((*f).into()),
+        mysql::Value::Double(f) => Value::Real(*f),
+        mysql::Value::Bytes(bytes) => Value::Text(String::from_utf8_lossy(bytes).into_owned()),
+        mysql::Value::Date(year, month, day, 0, 0, 0, 0) => {
+            Value::Text(format!("{:04}-{:02}-{:02}", year, month, day))
+        }
+        mysql::Value::Date(year, month, day, hour, minute, second, micros) => Value::Text(format!(
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:06}",
+            year, month, day, hour, minute, second, micros
+        )),
+        mysql::Value::Time(negative, days, hours, minutes, seconds, _) => Value::Text(format!(
+            "{}{:02}:{:02}:{:02}",
+            if *negative { "-" } else { "" },
+            *days * 24 + u32::from(*hours),
+            minutes,
+            seconds
+        )),
+    }
+}
+
+impl Database for Mysql {
+    fn quote(&self, name: &str) -> String {
+        format!("`{}`", name.replace('`', "``"))
+    }
+
+    fn tables(&mut self) -> Result<Vec<String>> {
+        self.conn.query::<String, _>("SHOW TABLES").map_err(mysql_error)
+    }
+
+    fn query(&mut self, sql: &str) -> Result<(Vec<String>, Vec<Vec<Value>>)> {
+        // The binary protocol returns typed values rather than text
+        let mut result = self.conn.exec_iter(sql, ()).map_err(mysql_error)?;
+        let names: Vec<String> = result.columns().as_ref().iter().map(|column| column.name_str().to_string()).collect();
+        let mut columns: Vec<Vec<Value>> = vec![Vec::new(); names.len()];
+        for row in result.by_ref() {
+            let row = row.map_err(mysql_error)?;
+            for (i, column) in columns.iter_mut().enumerate() {
+                column.push(row.as_ref(i).map_or(Value::Null, row_value));
+            }
+        }
+        Ok((names, columns))
+    }
+
+    /// Rows are inserted in one transaction, but MySQL commits DROP and CREATE
+    /// TABLE immediately, so a failed replace can leave an empty table
+    fn write_table(&mut self, table: &str, mode: WriteMode, columns: &[(String, SqlColumn)]) -> Result<()> {
+        let table_name = quote_table(&*self, table);
+        let definitions: Vec<String> = columns
+            .iter()
+            .map(|(name, column)| {
+                let sql_type = match column {
+                    SqlColumn::Integer(_) => "BIGINT",
+                    SqlColumn::Real(_) => "DOUBLE",
+                    SqlColumn::Text(_) => "TEXT",
+                };
+                format!("{} {}", self.quote(name), sql_type)
+            })
+            .collect();
+        let names: Vec<String> = columns.iter().map(|(name, _)| self.quote(name)).collect();
+        let placeholders = vec!["?"; names.len()];
+        let insert = format!("INSERT INTO {} ({}) VALUES ({})", table_name, names.join(", "), placeholders.join(", "));
+
+        let (schema, name) = match table.split_once('.') {
+            Some((schema, name)) => (Some(schema), name),
+            None => (None, table),
+        };
+        let exists = self
+            .conn
+            .exec_first::<i64, _, _>(
+                "SELECT COUNT(*) FROM information_schema.tables \
+                 WHERE table_schema = COALESCE(?, DATABASE()) AND table_name = ?",
+                (schema, name),
+            )
+            .map_err(mysql_error)?
+            .unwrap_or(0)
+            > 0;
+        if exists && mode == WriteMode::Fail {
+            return Err(table_exists_error(table));
+        }
+
+        let mut tx = self.conn.start_transaction(TxOpts::default()).map_err(mysql_error)?;
+        if exists && mode == WriteMode::Replace {
+            tx.query_drop(format!("DROP TABLE {}", table_name)).map_err(mysql_error)?;
+        }
+        if !exists || mode == WriteMode::Replace {
+            tx.query_drop(format!("CREATE TABLE {} ({})", table_name, definitions.join(", ")))
+                .map_err(mysql_error)?;
+        }
+        let rows = (0..row_count(columns)).map(|row| {
+            let values = columns
+                .iter()
+                .map(|(_, column)| match column {
+                    SqlColumn::Integer(values) => mysql::Value::from(values[row]),
+                    SqlColumn::Real(values) => mysql::Value::from(values[row]),
+                    SqlColumn::Text(values) => mysql::Value::from(values[row].clone()),
+                })
+                .collect();
+            Params::Positional(values)
+        });
+        tx.exec_batch(insert, rows).map_err(mysql_error)?;
+        tx.commit().map_err(mysql_error)
+    }
+}
@@ -0,0 +1,216 @@
+use polars::prelude::*;
+use std::cell::RefCell;
+use std::collections::HashSet;
+
+use super::{Executor, Fixtures, RunLog, Workspace};
+use crate::error::Result;
+use crate::parser::ast::*;
+
+/// Consecutive assignments that don't use one another's results:
+/// (statement index, variable, pipeline)
+pub(super) type Batch = Vec<(usize, String, Pipeline)>;
+
+/// Every name a pipeline might use as a variable: sources, concat() and append(),
+/// lookup tables, `var.col`, and bare names in expressions, which are columns
+/// unless a variable has that name
+fn referenced_names(pipeline: &Pipeline) -> HashSet<String> {
+    let mut names = HashSet::new();
+    if let Some(source) = &pipeline.source {
+        source_names(source, &mut names);
+    }
+    for op in &pipeline.operations {
+        match op {
+            Operation::Variable(name) => {
+                names.insert(name.clone());
+            }
+            Operation::Concat(concat_op) | Operation::Append(concat_op) => {
+                concat_op.sources.iter().for_each(|source| source_names(source, &mut names))
+            }
+            Operation::Filter(filter_op) => expression_names(&filter_op.condition, &mut names),
+            Operation::Mutate(mutate_op) => {
+                mutate_op.assignments.iter().for_each(|a| expression_names(&a.expression, &mut names))
+            }
+            Operation::MutateAll(mutate_all_op) => expression_names(&mutate_all_op.template, &mut names),
+            Operation::FillNulls(fill_op) => fill_op.fills.iter().for_each(|(_, e)| expression_names(e, &mut names)),
+            Operation::Summarize(SummarizeOp { aggregations })
+            | Operation::Resample(ResampleOp { aggregations, .. }) => {
+                aggregations.iter().for_each(|(_, e)| expression_names(e, &mut names))
+            }
+            Operation::Explode(explode_op) => explode_op.columns.iter().for_each(|e| expression_names(e, &mut names)),
+            Operation::SplitInto(split_op) => expression_names(&split_op.source, &mut names),
+            _ => {}
+        }
+    }
+    names
+}
+
+fn source_names(source: &Source, names: &mut HashSet<String>) {
+    match source {
+        Source::Variable(name) => {
+            names.insert(name.clone());
+        }
+        Source::Concat(concat_op) => concat_op.sources.iter().for_each(|source| source_names(source, names)),
+        Source::Read(_) => {}
+    }
+}
+
+fn expression_names(expr: &Expression, names: &mut HashSet<String>) {
+    match expr {
+        Expression::Column(ColumnRef::Name(name)) | Expression::Variable(name) => {
+            names.insert(name.clone());
+        }
+        Expression::VariableColumn { variable, .. } => {
+            names.insert(variable.clone());
+        }
+        Expression::BinaryOp { left, right, .. } => {
+            expression_names(left, names);
+            expression_names(right, names);
+        }
+        Expression::Negate(operand) => expression_names(operand, names),
+        Expression::MethodCall { object, args, .. } => {
+            expression_names(object, names);
+            args.iter().for_each(|arg| expression_names(arg, names));
+        }
+        Expression::Split { string, delimiter, .. } => {
+            expression_names(string, names);
+            expression_names(delimiter, names);
+        }
+        Expression::Lookup { table, key, default, .. } => {
+            names.insert(table.clone());
+            key.iter().for_each(|k| expression_names(k, names));
+            if let Some(default) = default {
+                expression_names(default, names);
+            }
+        }
+        Expression::FuzzyLookup { table, key, .. } => {
+            names.insert(table.clone());
+            expression_names(key, names);
+        }
+        Expression::Replace { text, old, new, .. } => {
+            expression_names(text, names);
+            expression_names(old, names);
+            expression_names(new, names);
+        }
+        Expression::FunctionCall { args, .. } => args.iter().for_each(|arg| expression_names(arg, names)),
+        Expression::Literal(_)
+        | Expression::Column(_)
+        | Expression::List(_)
+        | Expression::Regex(_)
+        | Expression::ColumnQuantifier { .. } => {}
+    }
+}
+
+/// Whether `pipeline` may use a variable assigned in the batch
+pub(super) fn depends_on(pipeline: &Pipeline, batch: &Batch) -> bool {
+    let names = referenced_names(pipeline);
+    batch.iter().any(|(_, name, _)| names.contains(name))
+}
+
+fn reads_stdin(source: &Source) -> bool {
+    match source {
+        Source::Read(read_op) => read_op.path == "-",
+        Source::Concat(concat_op) => concat_op.sources.iter().any(reads_stdin),
+        Source::Variable(_) => false,
+    }
+}
+
+/// Pipelines without side effects: nothing written and stdin not read
+fn side_effect_free(pipeline: &Pipeline) -> bool {
+    !pipeline.source.as_ref().is_some_and(reads_stdin)
+        && pipeline.operations.iter().all(|op| match op {
+            Operation::Write(_) => false,
+            Operation::Read(read_op) => read_op.path != "-",
+            Operation::Concat(concat_op) | Operation::Append(concat_op) => !concat_op.sources.iter().any(reads_stdin),
+            _ => true,
+        })
+}
+
+impl Executor {
+    /// Whether an assignment can run alongside others on its own thread
+    pub(super) fn can_run_in_parallel(&self, pipeline: &Pipeline) -> bool {
+        self.parallel && !matches!(self.fixtures, Some(Fixtures::Record { .. })) && side_effect_free(pipeline)
+    }
+
+    /// Executor for one statement on another thread: the same variables (tables
+    /// share their memory), settings and fixtures, and a run log of its own
+    fn worker(&self, statement: usize) -> Executor {
+        Executor {
+            variables: self.variables.clone(),
+            run_log: RefCell::new(self.run_log.borrow().as_ref().map(|_| RunLog::default())),
+            statement_index: statement,
+            verbose: self.verbose,
+            params: self.params.clone(),
+            // Never used: statements that write don't run in parallel
+            workspace: Workspace::default(),
+            fixtures: self.fixtures.clone(),
+            stand_ins: self.stand_ins.clone(),
+            progress: self.progress,
+            parallel: false,
+            current_file: None,
+            file_names: false,
+            row_files: None,
+            pushdown: None,
+            regexes: RefCell::new(self.regexes.borrow().clone()),
+        }
+    }
+
+    /// Run the batch's assignments, each on its own thread when there are several,
+    /// and store the results in statement order
+    pub(super) fn execute_batch(&mut self, batch: Batch, total: usize) -> Result<()> {
+        if batch.len() < 2 {
+            for (index, name, pipeline) in batch {
+                self.statement_index = index + 1;
+                self.report_statement(index, total, &name);
+                let df = self.execute_pipeline(pipeline)?;
+                self.variables.insert(name, df);
+            }
+            return Ok(());
+        }
+
+        if self.verbose {
+            let names: Vec<&str> = batch.iter().map(|(_, name, _)| name.as_str()).collect();
+            eprintln!("Running {} statements in parallel: {}", batch.len(), names.join(", "));
+        }
+        let workers: Vec<(String, Executor, Pipeline)> = batch
+            .into_iter()
+            .map(|(index, name, pipeline)| {
+                self.report_statement(index, total, &name);
+                let worker = self.worker(index + 1);
+                (name, worker, pipeline)
+            })
+            .collect();
+
+        let results: Vec<(String, Result<(DataFrame, RunLog)>)> = std::thread::scope(|scope| {
+            let handles: Vec<_> = workers
+                .into_iter()
+                .map(|(name, mut worker, pipeline)| {
+                    let handle = scope.spawn(move || -> Result<(DataFrame, RunLog)> {
+                        let df = worker.execute_pipeline(pipeline)?;
+                        Ok((df, worker.take_run_log()))
+                    });
+                    (name, handle)
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|(name, handle)| (name, handle.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic))))
+                .collect()
+        });
+
+        // The first failing statement is the one reported
+        for (name, result) in results {
+            let (df, log) = result?;
+            if let Some(run_log) = self.run_log.get_mut() {
+                run_log.stages.extend(log.stages);
+                for input in log.inputs {
+                    if !run_log.inputs.contains(&input) {
+                        run_log.inputs.push(input);
+                    }
+                }
+                run_log.outputs.extend(log.outputs);
+            }
+            self.variables.insert(name, df);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,143 @@
+use polars::prelude::*;
+use std::io::BufRead;
+use std::path::Path;
+
+use super::{compression_of, format_path, Executor};
+use crate::error::Result;
+use crate::parser::ast::ReadOp;
+
+/// Which rows `dt peek` shows
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PeekRows {
+    /// The first n rows
+    Head(usize),
+    /// n rows picked uniformly from the whole file; the seed makes the pick repeatable
+    Sample { size: usize, seed: Option<u64> },
+}
+
+/// SplitMix64: small and fast, which is all reservoir sampling needs
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn new(seed: Option<u64>) -> Self {
+        let seed = seed.unwrap_or_else(|| {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_nanos() as u64)
+                .unwrap_or_default()
+        });
+        SplitMix64(seed)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// A number in 0..bound
+    fn below(&mut self, bound: u64) -> u64 {
+        ((self.next() as u128 * bound as u128) >> 64) as u64
+    }
+}
+
+/// The records of delimited text, line by line: a line break inside a quoted
+/// field continues the record
+struct Records<R: BufRead> {
+    reader: R,
+}
+
+impl<R: BufRead> Iterator for Records<R> {
+    type Item = std::io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut record = Vec::new();
+        loop {
+            match self.reader.read_until(b'\n', &mut record) {
+                Ok(0) => return (!record.is_empty()).then_some(Ok(record)),
+                Ok(_) => {
+                    let quotes = record.iter().filter(|&&b| b == b'"').count();
+                    if quotes % 2 == 0 {
+                        return Some(Ok(record));
+                    }
+                }
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+impl Executor {
+    /// A few rows of a file and its total row count when that was seen. Delimited
+    /// text is streamed: a head stops after n rows, and a sample keeps a reservoir
+    /// of n rows while reading every line once, so the file is never loaded whole.
+    /// Types are inferred from the rows kept. Other formats are read, then sampled.
+    pub fn peek_file(&self, path: &str, rows: PeekRows) -> Result<(DataFrame, Option<usize>)> {
+        self.peek_read(&ReadOp::new(path), rows)
+    }
+
+    /// peek_file() with the options of a read()
+    pub(super) fn peek_read(&self, op: &ReadOp, rows: PeekRows) -> Result<(DataFrame, Option<usize>)> {
+        let path = op.path.as_str();
+        let file_path = Path::new(path);
+        let format = op.format.as_deref().or_else(|| format_path(file_path).extension()?.to_str());
+        let streamed = compression_of(file_path, op.compression.as_deref())?.is_none()
+            && !matches!(format, Some("json") | Some("ndjson") | Some("jsonl") | Some("parquet"))
+            && !super::sqlite::is_sqlite(format);
+
+        if !streamed {
+            let df = self.read_file(op, file_path)?;
+            let total = df.height();
+            let df = match rows {
+                PeekRows::Head(n) => df.head(Some(n)),
+                PeekRows::Sample { size, seed } => df.sample_n_literal(size.min(total), false, false, seed)?,
+            };
+            return Ok((df, Some(total)));
+        }
+
+        let mut records = Records { reader: std::io::BufReader::new(std::fs::File::open(file_path)?) };
+        let mut text = match records.next() {
+            Some(header) => header?,
+            None => Vec::new(),
+        };
+
+        let (kept, total) = match rows {
+            PeekRows::Head(n) => {
+                let kept = records.by_ref().take(n).collect::<std::io::Result<Vec<_>>>()?;
+                (kept, None)
+            }
+            PeekRows::Sample { size, seed } => {
+                // Algorithm R: row i replaces a kept row with probability size / (i + 1)
+                let mut rng = SplitMix64::new(seed);
+                let mut reservoir: Vec<(usize, Vec<u8>)> = Vec::with_capacity(size);
+                let mut seen = 0;
+                for record in records {
+                    let record = record?;
+                    if reservoir.len() < size {
+                        reservoir.push((seen, record));
+                    } else {
+                        let slot = rng.below(seen as u64 + 1) as usize;
+                        if slot < size {
+                            reservoir[slot] = (seen, record);
+                        }
+                    }
+                    seen += 1;
+                }
+                // Show the rows in file order
+                reservoir.sort_by_key(|(row, _)| *row);
+                (reservoir.into_iter().map(|(_, record)| record).collect(), Some(seen))
+            }
+        };
+
+        for record in kept {
+            if !text.ends_with(b"\n") {
+                text.push(b'\n');
+            }
+            text.extend_from_slice(&record);
+        }
+        let df = self.read_bytes(op, text, format, path)?;
+        Ok((df, total))
+    }
+}
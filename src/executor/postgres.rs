@@ -0,0 +1,134 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use postgres::types::ToSql;
+use postgres::{Client, NoTls, Row};
+
+use super::database::{quote_table, row_count, table_exists_error, Database, SqlColumn, Value, WriteMode};
+use crate::error::{DtransformError, Result};
+
+/// A PostgreSQL server: read('postgres://user@host/db', query=...)
+pub(super) struct Postgres {
+    client: Client,
+}
+
+impl Postgres {
+    pub(super) fn connect(url: &str) -> Result<Self> {
+        let client = Client::connect(url, NoTls).map_err(postgres_error)?;
+        Ok(Postgres { client })
+    }
+}
+
+fn postgres_error(e: postgres::Error) -> DtransformError {
+    DtransformError::InvalidOperation(format!("PostgreSQL: {}", e))
+}
+
+/// One value of a result row. Types without a counterpart here are an error
+/// that suggests casting them in the query.
+fn row_value(row: &Row, i: usize) -> Result<Value> {
+    let column = &row.columns()[i];
+    let value = match column.type_().name() {
+        "bool" => row.try_get::<_, Option<bool>>(i).map(|v| v.map(|b| Value::Integer(b as i64))),
+        "int2" => row.try_get::<_, Option<i16>>(i).map(|v| v.map(|n| Value::Integer(n.into()))),
+        "int4" => row.try_get::<_, Option<i32>>(i).map(|v| v.map(|n| Value::Integer(n.into()))),
+        "int8" => row.try_get::<_, Option<i64>>(i).map(|v| v.map(Value::Integer)),
+        "float4" => row.try_get::<_, Option<f32>>(i).map(|v| v.map(|f| Value::Real(f.into()))),
+        "float8" => row.try_get::<_, Option<f64>>(i).map(|v| v.map(Value::Real)),
+        "date" => row.try_get::<_, Option<NaiveDate>>(i).map(|v| v.map(|d| Value::Text(d.to_string()))),
+        "timestamp" => row
+            .try_get::<_, Option<NaiveDateTime>>(i)
+            .map(|v| v.map(|dt| Value::Text(dt.format("%Y-%m-%d %H:%M:%S%.f").to_string()))),
+        "text" | "varchar" | "bpchar" | "name" => row.try_get::<_, Option<String>>(i).map(|v| v.map(Value::Text)),
+        other => {
+            return Err(DtransformError::InvalidOperation(format!(
+                "PostgreSQL column '{}' has type {}, which dt can't read; cast it in the query, e.g. {}::text",
+                column.name(),
+                other,
+                column.name()
+            )))
+        }
+    };
+    Ok(value.map_err(postgres_error)?.unwrap_or(Value::Null))
+}
+
+impl Database for Postgres {
+    fn tables(&mut self) -> Result<Vec<String>> {
+        let rows = self
+            .client
+            .query(
+                "SELECT table_name::text FROM information_schema.tables \
+                 WHERE table_schema = current_schema() ORDER BY table_name",
+                &[],
+            )
+            .map_err(postgres_error)?;
+        Ok(rows.iter().map(|row| row.get::<_, String>(0)).collect())
+    }
+
+    fn query(&mut self, sql: &str) -> Result<(Vec<String>, Vec<Vec<Value>>)> {
+        let statement = self.client.prepare(sql).map_err(postgres_error)?;
+        let names: Vec<String> = statement.columns().iter().map(|column| column.name().to_string()).collect();
+        let mut columns: Vec<Vec<Value>> = vec![Vec::new(); names.len()];
+        for row in self.client.query(&statement, &[]).map_err(postgres_error)? {
+            for (i, column) in columns.iter_mut().enumerate() {
+                column.push(row_value(&row, i)?);
+            }
+        }
+        Ok((names, columns))
+    }
+
+    /// The whole write is one transaction, so a failure leaves the database as it was
+    fn write_table(&mut self, table: &str, mode: WriteMode, columns: &[(String, SqlColumn)]) -> Result<()> {
+        let table_name = quote_table(&*self, table);
+        let definitions: Vec<String> = columns
+            .iter()
+            .map(|(name, column)| {
+                let sql_type = match column {
+                    SqlColumn::Integer(_) => "BIGINT",
+                    SqlColumn::Real(_) => "DOUBLE PRECISION",
+                    SqlColumn::Text(_) => "TEXT",
+                };
+                format!("{} {}", self.quote(name), sql_type)
+            })
+            .collect();
+        let names: Vec<String> = columns.iter().map(|(name, _)| self.quote(name)).collect();
+        // Numbers are cast so they also fit integer and real columns of other sizes
+        let placeholders: Vec<String> = columns
+            .iter()
+            .enumerate()
+            .map(|(i, (_, column))| match column {
+                SqlColumn::Integer(_) => format!("${}::BIGINT", i + 1),
+                SqlColumn::Real(_) => format!("${}::DOUBLE PRECISION", i + 1),
+                SqlColumn::Text(_) => format!("${}", i + 1),
+            })
+            .collect();
+        let insert = format!("INSERT INTO {} ({}) VALUES ({})", table_name, names.join(", "), placeholders.join(", "));
+
+        let mut tx = self.client.transaction().map_err(postgres_error)?;
+        let exists: bool = tx
+            .query_one("SELECT to_regclass($1::text) IS NOT NULL", &[&table_name])
+            .map_err(postgres_error)?
+            .get(0);
+        if exists && mode == WriteMode::Fail {
+            return Err(table_exists_error(table));
+        }
+        if exists && mode == WriteMode::Replace {
+            tx.batch_execute(&format!("DROP TABLE {}", table_name)).map_err(postgres_error)?;
+        }
+        if !exists || mode == WriteMode::Replace {
+            tx.batch_execute(&format!("CREATE TABLE {} ({})", table_name, definitions.join(", ")))
+                .map_err(postgres_error)?;
+        }
+
+        let statement = tx.prepare(&insert).map_err(postgres_error)?;
+        for row in 0..row_count(columns) {
+            let params: Vec<&(dyn ToSql + Sync)> = columns
+                .iter()
+                .map(|(_, column)| match column {
+                    SqlColumn::Integer(values) => &values[row] as &(dyn ToSql + Sync),
+                    SqlColumn::Real(values) => &values[row] as &(dyn ToSql + Sync),
+                    SqlColumn::Text(values) => &values[row] as &(dyn ToSql + Sync),
+                })
+                .collect();
+            tx.execute(&statement, &params).map_err(postgres_error)?;
+        }
+        tx.commit().map_err(postgres_error)
+    }
+}
@@ -0,0 +1,112 @@
+use polars::prelude::*;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+
+use crate::error::{DtransformError, Result};
+
+/// Column types of a delimited file, kept next to it so a CSV round trip keeps
+/// dates as dates and text such as zip codes as text: data.csv -> data.csv.schema.json
+pub(super) fn sidecar_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".schema.json");
+    PathBuf::from(name)
+}
+
+/// Name of a type in a sidecar; None for types that aren't read back from text
+/// (lists, structs, time zones), which are left to inference
+fn type_name(dtype: &DataType) -> Option<String> {
+    let name = match dtype {
+        DataType::Boolean => "bool",
+        DataType::Int8 => "int8",
+        DataType::Int16 => "int16",
+        DataType::Int32 => "int32",
+        DataType::Int64 => "int64",
+        DataType::UInt8 => "uint8",
+        DataType::UInt16 => "uint16",
+        DataType::UInt32 => "uint32",
+        DataType::UInt64 => "uint64",
+        DataType::Float32 => "float32",
+        DataType::Float64 => "float64",
+        DataType::String => "string",
+        DataType::Date => "date",
+        DataType::Datetime(unit, None) => return Some(format!("datetime[{}]", unit)),
+        _ => return None,
+    };
+    Some(name.to_string())
+}
+
+fn parse_type(name: &str) -> Option<DataType> {
+    let dtype = match name {
+        "bool" => DataType::Boolean,
+        "int8" => DataType::Int8,
+        "int16" => DataType::Int16,
+        "int32" => DataType::Int32,
+        "int64" => DataType::Int64,
+        "uint8" => DataType::UInt8,
+        "uint16" => DataType::UInt16,
+        "uint32" => DataType::UInt32,
+        "uint64" => DataType::UInt64,
+        "float32" => DataType::Float32,
+        "float64" => DataType::Float64,
+        "string" => DataType::String,
+        "date" => DataType::Date,
+        "datetime[ms]" => DataType::Datetime(TimeUnit::Milliseconds, None),
+        "datetime[μs]" | "datetime[us]" => DataType::Datetime(TimeUnit::Microseconds, None),
+        "datetime[ns]" => DataType::Datetime(TimeUnit::Nanoseconds, None),
+        _ => return None,
+    };
+    Some(dtype)
+}
+
+/// The sidecar of `df` as written: {"columns": [{"name": "id", "type": "int64"}, ...]}.
+/// Columns in `as_text` were written through formats= and are left out.
+pub(super) fn render(df: &DataFrame, as_text: &[String]) -> Result<String> {
+    let columns: Vec<Value> = df
+        .get_columns()
+        .iter()
+        .filter(|column| !as_text.iter().any(|name| name == column.name().as_str()))
+        .filter_map(|column| {
+            let dtype = type_name(column.dtype())?;
+            Some(json!({ "name": column.name().as_str(), "type": dtype }))
+        })
+        .collect();
+    let document = json!({ "columns": columns });
+    serde_json::to_string_pretty(&document)
+        .map_err(|e| DtransformError::InvalidOperation(format!("Cannot write schema sidecar: {}", e)))
+}
+
+/// Column types from the sidecar of `path`, or None when it has none
+pub(super) fn read(path: &Path) -> Result<Option<Schema>> {
+    let sidecar = sidecar_path(path);
+    let text = match std::fs::read_to_string(&sidecar) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let invalid = |detail: String| {
+        DtransformError::InvalidOperation(format!(
+            "Invalid schema sidecar '{}': {}. Fix or delete it, or read with schema=false",
+            sidecar.display(),
+            detail
+        ))
+    };
+
+    let document: Value = serde_json::from_str(&text).map_err(|e| invalid(e.to_string()))?;
+    let columns = document
+        .get("columns")
+        .and_then(Value::as_array)
+        .ok_or_else(|| invalid("expected {\"columns\": [...]}".to_string()))?;
+
+    let mut fields = Vec::with_capacity(columns.len());
+    for column in columns {
+        let name = column.get("name").and_then(Value::as_str);
+        let type_text = column.get("type").and_then(Value::as_str);
+        let (name, type_text) = name
+            .zip(type_text)
+            .ok_or_else(|| invalid(format!("expected {{\"name\": ..., \"type\": ...}}, got {}", column)))?;
+        let dtype = parse_type(type_text).ok_or_else(|| invalid(format!("unknown type '{}' for '{}'", type_text, name)))?;
+        fields.push(Field::new(PlSmallStr::from(name), dtype));
+    }
+    let schema: Schema = fields.into_iter().collect();
+    Ok(Some(schema))
+}
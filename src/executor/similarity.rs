@@ -0,0 +1,154 @@
+use polars::prelude::*;
+use std::collections::HashMap;
+
+use super::Executor;
+use crate::error::{DtransformError, Result};
+use crate::parser::ast::{Expression, LookupField};
+
+/// Edits (insertions, deletions, substitutions of one character) that turn `a` into `b`
+pub(super) fn levenshtein(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+/// Jaro-Winkler similarity, from 0 (nothing in common) to 1 (equal). A shared
+/// prefix of up to four characters raises the score, which suits names.
+pub(super) fn jaro_winkler(a: &str, b: &str) -> f64 {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+
+    // Characters match when equal and no further apart than the window
+    let window = (a.len().max(b.len()) / 2).saturating_sub(1);
+    let mut a_matched = vec![false; a.len()];
+    let mut b_matched = vec![false; b.len()];
+    let mut matches = 0usize;
+    for (i, ca) in a.iter().enumerate() {
+        let start = i.saturating_sub(window);
+        let end = (i + window + 1).min(b.len());
+        for j in start..end {
+            if !b_matched[j] && b[j] == *ca {
+                a_matched[i] = true;
+                b_matched[j] = true;
+                matches += 1;
+                break;
+            }
+        }
+    }
+    if matches == 0 {
+        return 0.0;
+    }
+
+    // Matched characters that appear in a different order, counted in pairs
+    let a_order = a.iter().zip(&a_matched).filter(|(_, m)| **m).map(|(c, _)| c);
+    let b_order = b.iter().zip(&b_matched).filter(|(_, m)| **m).map(|(c, _)| c);
+    let transpositions = a_order.zip(b_order).filter(|(x, y)| x != y).count() / 2;
+
+    let m = matches as f64;
+    let jaro = (m / a.len() as f64 + m / b.len() as f64 + (m - transpositions as f64) / m) / 3.0;
+    let prefix = a.iter().zip(&b).take(4).take_while(|(x, y)| x == y).count();
+    jaro + prefix as f64 * 0.1 * (1.0 - jaro)
+}
+
+impl Executor {
+    /// levenshtein(a, b) / jaro_winkler(a, b), row by row; null if either side is null
+    pub(super) fn string_similarity(&self, name: &str, args: &[Expression], df: &DataFrame) -> Result<Series> {
+        self.expect_arg_count(name, args, 2)?;
+        let left = self.evaluate_expression(&args[0], df)?;
+        let right = self.evaluate_expression(&args[1], df)?;
+        let left = self.string_series(name, &left)?;
+        let right = self.string_series(name, &right)?;
+        let pairs = left.into_iter().zip(right);
+        let result = if name == "levenshtein" {
+            let distances: Vec<Option<u32>> = pairs
+                .map(|pair| match pair {
+                    (Some(a), Some(b)) => Some(levenshtein(a, b) as u32),
+                    _ => None,
+                })
+                .collect();
+            Series::new(PlSmallStr::from(name), distances)
+        } else {
+            let scores: Vec<Option<f64>> = pairs
+                .map(|pair| match pair {
+                    (Some(a), Some(b)) => Some(jaro_winkler(a, b)),
+                    _ => None,
+                })
+                .collect();
+            Series::new(PlSmallStr::from(name), scores)
+        };
+        Ok(result)
+    }
+
+    /// fuzzy_lookup(table, key=name, on='name', return='id', max_dist=2): the value
+    /// from the row whose `on` text is closest to the key by Levenshtein distance,
+    /// if no more than `max_dist` edits away. Ties go to the first such row; keys
+    /// without a close enough row, and null keys, give null.
+    pub(super) fn fuzzy_lookup(
+        &self,
+        table: &str,
+        key: &Expression,
+        on: &LookupField,
+        return_field: &LookupField,
+        max_dist: usize,
+        df: &DataFrame,
+    ) -> Result<Series> {
+        let lookup_df = self
+            .variables
+            .get(table)
+            .ok_or_else(|| DtransformError::VariableNotFound(table.to_string()))?;
+        let on_name = self.lookup_column(table, lookup_df, on, "on")?;
+        let return_name = self.lookup_column(table, lookup_df, return_field, "return")?;
+
+        let candidates = lookup_df.column(&on_name)?.as_materialized_series().clone();
+        let candidates = self.string_series("fuzzy_lookup", &candidates)?;
+        let candidates: Vec<(usize, &str)> = candidates
+            .into_iter()
+            .enumerate()
+            .filter_map(|(row, value)| value.map(|text| (row, text)))
+            .collect();
+
+        let keys = self.evaluate_expression(key, df)?;
+        let keys = self.string_series("fuzzy_lookup", &keys)?;
+
+        // Each distinct key is matched once
+        let mut closest: HashMap<&str, Option<IdxSize>> = HashMap::new();
+        let mut rows: Vec<Option<IdxSize>> = Vec::with_capacity(keys.len());
+        for key in keys {
+            let row = match key {
+                Some(key) => *closest.entry(key).or_insert_with(|| {
+                    let key_len = key.chars().count();
+                    candidates
+                        .iter()
+                        // Lengths further apart than max_dist can't be close enough
+                        .filter(|(_, text)| text.chars().count().abs_diff(key_len) <= max_dist)
+                        .map(|(row, text)| (levenshtein(key, text), *row))
+                        .filter(|(distance, _)| *distance <= max_dist)
+                        .min()
+                        .map(|(_, row)| row as IdxSize)
+                }),
+                None => None,
+            };
+            rows.push(row);
+        }
+
+        let rows: IdxCa = rows.into_iter().collect();
+        let values = lookup_df.column(&return_name)?.as_materialized_series().take(&rows)?;
+        Ok(values.with_name(PlSmallStr::from(return_name.as_str())))
+    }
+}
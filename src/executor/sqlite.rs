@@ -0,0 +1,123 @@
+use rusqlite::types::Value as SqliteValue;
+use rusqlite::{Connection, OpenFlags};
+use std::path::Path;
+
+use super::database::{quote_table, row_count, table_exists_error, Database, SqlColumn, Value, WriteMode};
+use crate::error::{DtransformError, Result};
+
+/// Formats read and written through SQLite: `format='sqlite'` or a .db / .sqlite / .sqlite3 path
+pub(super) fn is_sqlite(format: Option<&str>) -> bool {
+    matches!(format, Some("sqlite" | "sqlite3" | "db"))
+}
+
+/// A SQLite database file
+pub(super) struct Sqlite {
+    conn: Connection,
+    path: String,
+}
+
+impl Sqlite {
+    pub(super) fn open(path: &Path, writable: bool) -> Result<Self> {
+        let path_name = path.display().to_string();
+        let conn = if writable {
+            Connection::open(path)
+        } else {
+            Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        };
+        match conn {
+            Ok(conn) => Ok(Sqlite { conn, path: path_name }),
+            Err(e) => Err(sqlite_error(&path_name, e)),
+        }
+    }
+}
+
+fn sqlite_error(path: &str, e: rusqlite::Error) -> DtransformError {
+    DtransformError::InvalidOperation(format!("SQLite '{}': {}", path, e))
+}
+
+fn sqlite_value(column: &SqlColumn, row: usize) -> SqliteValue {
+    match column {
+        SqlColumn::Integer(values) => values[row].map_or(SqliteValue::Null, SqliteValue::Integer),
+        SqlColumn::Real(values) => values[row].map_or(SqliteValue::Null, SqliteValue::Real),
+        SqlColumn::Text(values) => values[row].clone().map_or(SqliteValue::Null, SqliteValue::Text),
+    }
+}
+
+impl Database for Sqlite {
+    fn tables(&mut self) -> Result<Vec<String>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name")
+            .map_err(|e| sqlite_error(&self.path, e))?;
+        stmt.query_map([], |row| row.get::<_, String>(0))
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<String>>>())
+            .map_err(|e| sqlite_error(&self.path, e))
+    }
+
+    fn query(&mut self, sql: &str) -> Result<(Vec<String>, Vec<Vec<Value>>)> {
+        let mut stmt = self.conn.prepare(sql).map_err(|e| sqlite_error(&self.path, e))?;
+        let names: Vec<String> = stmt.column_names().iter().map(|name| name.to_string()).collect();
+        let mut columns: Vec<Vec<Value>> = vec![Vec::new(); names.len()];
+        let mut rows = stmt.query([]).map_err(|e| sqlite_error(&self.path, e))?;
+        while let Some(row) = rows.next().map_err(|e| sqlite_error(&self.path, e))? {
+            for (i, column) in columns.iter_mut().enumerate() {
+                let value = match row.get::<_, SqliteValue>(i).map_err(|e| sqlite_error(&self.path, e))? {
+                    SqliteValue::Null => Value::Null,
+                    SqliteValue::Integer(n) => Value::Integer(n),
+                    SqliteValue::Real(f) => Value::Real(f),
+                    SqliteValue::Text(s) => Value::Text(s),
+                    SqliteValue::Blob(b) => Value::Text(String::from_utf8_lossy(&b).into_owned()),
+                };
+                column.push(value);
+            }
+        }
+        Ok((names, columns))
+    }
+
+    /// The whole write is one transaction, so a failure leaves the database as it was
+    fn write_table(&mut self, table: &str, mode: WriteMode, columns: &[(String, SqlColumn)]) -> Result<()> {
+        let table_name = quote_table(&*self, table);
+        let definitions: Vec<String> = columns
+            .iter()
+            .map(|(name, column)| {
+                let sql_type = match column {
+                    SqlColumn::Integer(_) => "INTEGER",
+                    SqlColumn::Real(_) => "REAL",
+                    SqlColumn::Text(_) => "TEXT",
+                };
+                format!("{} {}", self.quote(name), sql_type)
+            })
+            .collect();
+        let names: Vec<String> = columns.iter().map(|(name, _)| self.quote(name)).collect();
+        let placeholders: Vec<String> = (1..=names.len()).map(|i| format!("?{}", i)).collect();
+        let insert = format!("INSERT INTO {} ({}) VALUES ({})", table_name, names.join(", "), placeholders.join(", "));
+
+        let path = &self.path;
+        let tx = self.conn.transaction().map_err(|e| sqlite_error(path, e))?;
+        let exists: bool = tx
+            .query_row(
+                "SELECT count(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = ?1",
+                [table],
+                |row| row.get(0),
+            )
+            .map_err(|e| sqlite_error(path, e))?;
+        if exists && mode == WriteMode::Fail {
+            return Err(table_exists_error(table));
+        }
+        if exists && mode == WriteMode::Replace {
+            tx.execute(&format!("DROP TABLE {}", table_name), []).map_err(|e| sqlite_error(path, e))?;
+        }
+        if !exists || mode == WriteMode::Replace {
+            tx.execute(&format!("CREATE TABLE {} ({})", table_name, definitions.join(", ")), [])
+                .map_err(|e| sqlite_error(path, e))?;
+        }
+        {
+            let mut stmt = tx.prepare(&insert).map_err(|e| sqlite_error(path, e))?;
+            for row in 0..row_count(columns) {
+                let values = columns.iter().map(|(_, column)| sqlite_value(column, row));
+                stmt.execute(rusqlite::params_from_iter(values)).map_err(|e| sqlite_error(path, e))?;
+            }
+        }
+        tx.commit().map_err(|e| sqlite_error(path, e))
+    }
+}
@@ -0,0 +1,151 @@
+use std::cell::{Cell, RefCell};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::error::{DtransformError, Result};
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Private directory for intermediate files of one run, such as written files
+/// before they are moved into place. The directory is created on first use
+/// under `DT_TMPDIR` or the system temp directory, removed when the workspace is
+/// dropped, and kept for debugging, with the partial files of failed writes,
+/// when the run failed (`retain()`).
+pub struct Workspace {
+    root: PathBuf,
+    dir: RefCell<Option<PathBuf>>,
+    retain: Cell<bool>,
+}
+
+impl Default for Workspace {
+    fn default() -> Self {
+        let root = std::env::var_os("DT_TMPDIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(std::env::temp_dir);
+        Self::new(root)
+    }
+}
+
+impl Workspace {
+    pub fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            dir: RefCell::new(None),
+            retain: Cell::new(false),
+        }
+    }
+
+    /// The run's directory, created on first call. The name is unique per
+    /// process and call, so concurrent runs never share a directory.
+    pub fn dir(&self) -> Result<PathBuf> {
+        if let Some(dir) = self.dir.borrow().as_ref() {
+            return Ok(dir.clone());
+        }
+
+        std::fs::create_dir_all(&self.root)?;
+        let stamp = chrono::Local::now().format("%Y%m%dT%H%M%S");
+        let dir = loop {
+            let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+            let candidate = self.root.join(format!("dt-{}-{}-{}", stamp, std::process::id(), id));
+            match std::fs::create_dir(&candidate) {
+                Ok(()) => break candidate,
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+                Err(e) => {
+                    return Err(DtransformError::InvalidOperation(format!(
+                        "Cannot create temporary directory in '{}': {}",
+                        self.root.display(),
+                        e
+                    )))
+                }
+            }
+        };
+
+        *self.dir.borrow_mut() = Some(dir.clone());
+        Ok(dir)
+    }
+
+    /// A fresh path in the run's directory to write `target` to before it is
+    /// committed. None when `target` has to be written in place: an existing
+    /// device, FIFO or other non-regular file (`/dev/stdout`), or a symlink to
+    /// nowhere.
+    pub fn stage(&self, target: &Path) -> Result<Option<PathBuf>> {
+        let Some(target) = resolve_target(target) else {
+            return Ok(None);
+        };
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        Ok(Some(self.dir()?.join(format!("{}-{}.partial", id, file_name(&target)))))
+    }
+
+    /// Move a staged file into place. Readers of `target` see either the old
+    /// file or the complete new one; a symlinked target keeps its link, and the
+    /// file it points to is replaced with the permissions it had. When the run's
+    /// directory is on another file system, the file is copied next to the
+    /// target first and renamed from there.
+    pub fn commit(&self, staged: &Path, target: &Path) -> Result<()> {
+        let target = resolve_target(target).unwrap_or_else(|| target.to_path_buf());
+        if let Ok(meta) = std::fs::metadata(&target) {
+            std::fs::set_permissions(staged, meta.permissions())?;
+        }
+        match std::fs::rename(staged, &target) {
+            Err(e) if e.kind() == ErrorKind::CrossesDevices => {
+                let parent = match target.parent() {
+                    Some(parent) if !parent.as_os_str().is_empty() => parent,
+                    _ => Path::new("."),
+                };
+                let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+                let beside = parent.join(format!(".{}.dt-{}-{}.partial", file_name(&target), std::process::id(), id));
+                let moved = std::fs::copy(staged, &beside)
+                    .and_then(|_| std::fs::File::open(&beside)?.sync_all())
+                    .and_then(|_| std::fs::rename(&beside, &target));
+                if moved.is_err() {
+                    let _ = std::fs::remove_file(&beside);
+                }
+                moved?;
+                std::fs::remove_file(staged)?;
+                Ok(())
+            }
+            result => Ok(result?),
+        }
+    }
+
+    /// Keep the directory after the workspace is dropped (the run failed)
+    pub fn retain(&self) {
+        self.retain.set(true);
+    }
+}
+
+fn file_name(target: &Path) -> String {
+    target
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "output".to_string())
+}
+
+/// The file a write to `target` replaces: `target` itself, or the file a symlink
+/// points to. None when it must be written in place instead of replaced.
+fn resolve_target(target: &Path) -> Option<PathBuf> {
+    let is_symlink = std::fs::symlink_metadata(target).is_ok_and(|m| m.file_type().is_symlink());
+    match std::fs::metadata(target) {
+        Ok(meta) if !meta.is_file() => None,
+        Ok(_) if is_symlink => std::fs::canonicalize(target).ok(),
+        Ok(_) => Some(target.to_path_buf()),
+        // A dangling symlink creates the file it points to
+        Err(_) if is_symlink => None,
+        Err(_) => Some(target.to_path_buf()),
+    }
+}
+
+impl Drop for Workspace {
+    fn drop(&mut self) {
+        let Some(dir) = self.dir.get_mut().take() else {
+            return;
+        };
+        let empty = std::fs::read_dir(&dir).map(|mut d| d.next().is_none()).unwrap_or(true);
+        if self.retain.get() && !empty {
+            eprintln!("Intermediate files kept in {}", dir.display());
+        } else {
+            let _ = std::fs::remove_dir_all(&dir);
+        }
+    }
+}
@@ -1,10 +1,12 @@
+pub mod builder;
 pub mod error;
 pub mod executor;
 pub mod parser;
 pub mod repl;
 
+pub use builder::{call, col, lit, nth};
 pub use error::{DtransformError, Result};
 pub use executor::Executor;
-pub use parser::{parse, parse_program};
-pub use parser::ast::Program;
+pub use parser::{format_program, parse, parse_program, substitute_variables};
+pub use parser::ast::{Expression, Pipeline, Program};
 pub use repl::Repl;
@@ -1,12 +1,25 @@
-use clap::Parser;
-use data_transform::{error::Result, Executor, Repl};
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use data_transform::parser::ast::{Operation, Program, Statement};
+use data_transform::parser::catalog::{FUNCTIONS, KEYWORDS, OPERATIONS};
+use data_transform::executor::{
+    diff_tables, write_delimited_chunks, Fixtures, PeekRows, RunLog, StandIns, WrittenFile, DEFAULT_CHUNK_ROWS,
+};
+use data_transform::{error::{DtransformError, Result}, Executor, Repl};
 use polars::prelude::*;
+use sha2::{Digest, Sha256};
+use std::io::{IsTerminal, Write};
+use std::process::{Command as Process, Stdio};
+use std::time::{Duration, Instant};
 
 #[derive(Parser)]
 #[command(name = "dt")]
 #[command(about = "Data Transform - Simple, fast data transformation", long_about = None)]
 #[command(version)]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Pipeline to execute
     #[arg(value_name = "PIPELINE")]
     pipeline: Option<String>,
@@ -19,64 +32,851 @@ struct Cli {
     #[arg(short, long)]
     interactive: bool,
 
-    /// Output file (default: stdout)
+    /// Output file (default: stdout as a table, '-' for CSV on stdout)
     #[arg(short, long, value_name = "FILE")]
     output: Option<String>,
 
     /// Verbose output
     #[arg(short, long)]
     verbose: bool,
+
+    /// Show progress on stderr: each statement, and rows read and written
+    #[arg(long)]
+    progress: bool,
+
+    /// No progress output, even for write(progress=true)
+    #[arg(short, long, conflicts_with_all = ["verbose", "progress"])]
+    quiet: bool,
+
+    /// Value for ${param:name} in the program and {name} in write() paths (repeatable)
+    #[arg(short = 'p', long = "param", value_name = "NAME=VALUE", value_parser = parse_param)]
+    params: Vec<(String, String)>,
+
+    /// Run statements one at a time, instead of independent assignments concurrently
+    #[arg(long)]
+    sequential: bool,
+
+    /// Directory for intermediate files (default: $DT_TMPDIR or the system temp directory)
+    #[arg(long, value_name = "DIR")]
+    temp_dir: Option<std::path::PathBuf>,
+
+    /// Write a JSON record of the run: inputs with checksums, program, stage row counts, outputs, timing
+    #[arg(long, value_name = "FILE")]
+    manifest: Option<String>,
+
+    /// Save the first rows of every read() input into DIR as test fixtures
+    #[arg(long, value_name = "DIR", conflicts_with = "replay")]
+    record: Option<std::path::PathBuf>,
+
+    /// Rows kept per input with --record
+    #[arg(long, value_name = "N", default_value_t = Fixtures::DEFAULT_ROWS, requires = "record")]
+    record_rows: usize,
+
+    /// Read the fixtures saved in DIR by --record instead of the real inputs
+    #[arg(long, value_name = "DIR")]
+    replay: Option<std::path::PathBuf>,
+
+    /// Columns shown in printed tables before the middle ones are elided (-1 for all)
+    #[arg(long, value_name = "N", allow_negative_numbers = true)]
+    max_cols: Option<i64>,
+
+    /// Rows shown in printed tables before the middle ones are elided (-1 for all)
+    #[arg(long, value_name = "N", allow_negative_numbers = true)]
+    max_rows: Option<i64>,
+
+    /// Truncate cell text in printed tables to N characters, with an ellipsis
+    #[arg(long, value_name = "CHARS")]
+    max_width: Option<usize>,
+
+    /// Show floats in printed tables as '%.3f' (fixed decimals), 'full' or 'auto'
+    #[arg(long, value_name = "FMT")]
+    float_fmt: Option<String>,
+
+    /// Don't wrap printed tables to the terminal width
+    #[arg(long)]
+    full_width: bool,
+
+    /// Print every row and column, at full width
+    #[arg(long, conflicts_with_all = ["max_cols", "max_rows"])]
+    no_elide: bool,
+
+    /// Print the stages the program would run, with the rows and columns expected after each
+    #[arg(long, conflicts_with = "explain_json")]
+    explain: bool,
+
+    /// Print the parsed program as JSON instead of running it
+    #[arg(long)]
+    explain_json: bool,
+
+    /// Run a program saved as JSON by --explain-json
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["pipeline", "file"])]
+    plan: Option<String>,
+
+    /// Print the DSL's operations, functions and keywords as JSON, for editor completion
+    #[arg(long)]
+    list_functions: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Pretty-print a file of any supported format
+    Cat(CatArgs),
+
+    /// Show the schema and a few rows of a file, or a random sample of a large one
+    Peek(PeekArgs),
+
+    /// Run a pipeline repeatedly and report timings per stage
+    Bench(BenchArgs),
+
+    /// Summarize the differences between two files
+    Diff(DiffArgs),
+
+    /// Lay out scripts the standard way: one operation per line, single quotes, even spacing
+    Fmt(FmtArgs),
+
+    /// Check scripts for errors without running them
+    Check(CheckArgs),
+
+    /// Print a shell completion script
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct BenchArgs {
+    /// Pipeline to benchmark
+    #[arg(value_name = "PIPELINE", required_unless_present = "file")]
+    pipeline: Option<String>,
+
+    /// Read pipeline from file
+    #[arg(short, long, value_name = "FILE", conflicts_with = "pipeline")]
+    file: Option<String>,
+
+    /// Number of timed runs
+    #[arg(short = 'n', long, value_name = "N", default_value_t = 5, value_parser = clap::value_parser!(u32).range(1..))]
+    iterations: u32,
+
+    /// Untimed runs before measuring (warms file caches)
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    warmup: u32,
+}
+
+#[derive(Args)]
+struct DiffArgs {
+    /// The earlier version
+    #[arg(value_name = "OLD")]
+    old: String,
+
+    /// The later version
+    #[arg(value_name = "NEW")]
+    new: String,
+
+    /// Columns that identify a row (comma-separated); rows are matched by position without one
+    #[arg(short, long, value_name = "COLS", value_delimiter = ',')]
+    key: Vec<String>,
+
+    /// Write every difference (one row per added/removed row and changed cell) to this file
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<String>,
+
+    /// Exit with status 1 when the files differ
+    #[arg(long)]
+    exit_code: bool,
+}
+
+#[derive(Args)]
+struct FmtArgs {
+    /// Scripts to format
+    #[arg(value_name = "FILE", required = true)]
+    files: Vec<String>,
+
+    /// Rewrite the files in place instead of printing them
+    #[arg(short, long)]
+    write: bool,
+
+    /// Only list the files that aren't formatted, and exit with status 1 if there are any
+    #[arg(long, conflicts_with = "write")]
+    check: bool,
+}
+
+#[derive(Args)]
+struct CheckArgs {
+    /// Scripts to check
+    #[arg(value_name = "FILE", required = true)]
+    files: Vec<String>,
+
+    /// Also run the scripts on empty tables with the columns of FILE, read in place of
+    /// every read(); PATH=FILE stands in for read('PATH') only (repeatable)
+    #[arg(short, long, value_name = "[PATH=]FILE")]
+    against: Vec<String>,
+
+    /// Value for ${param:name} in the scripts (repeatable)
+    #[arg(short = 'p', long = "param", value_name = "NAME=VALUE", value_parser = parse_param)]
+    params: Vec<(String, String)>,
+}
+
+#[derive(Args)]
+struct CatArgs {
+    /// File to view ('-' for stdin)
+    #[arg(value_name = "FILE")]
+    file: String,
+
+    /// Only show these columns (comma-separated)
+    #[arg(short, long, value_name = "COLS", value_delimiter = ',')]
+    columns: Vec<String>,
+
+    /// Number of rows to show
+    #[arg(short = 'n', long, value_name = "N", default_value_t = 100)]
+    rows: usize,
+
+    /// Show every row
+    #[arg(short, long, conflicts_with = "rows")]
+    all: bool,
+
+    /// Truncate cell text to this many characters
+    #[arg(short = 'w', long, value_name = "CHARS", default_value_t = 32)]
+    max_width: usize,
+
+    /// Print directly instead of through $PAGER
+    #[arg(long)]
+    no_pager: bool,
+}
+
+#[derive(Args)]
+struct PeekArgs {
+    /// File to look at
+    #[arg(value_name = "FILE")]
+    file: String,
+
+    /// Number of rows to show from the start of the file
+    #[arg(short = 'n', long, value_name = "N", default_value_t = 10, conflicts_with = "sample")]
+    rows: usize,
+
+    /// Show N rows sampled from the whole file instead (streamed, so it suits very large files)
+    #[arg(short, long, value_name = "N")]
+    sample: Option<usize>,
+
+    /// Seed for --sample, to get the same rows again
+    #[arg(long, value_name = "SEED", requires = "sample")]
+    seed: Option<u64>,
 }
 
 fn main() -> Result<()> {
+    match run() {
+        // The reader of stdout stopped early (`| head`): nothing is wrong
+        Err(e) if e.is_broken_pipe() => Ok(()),
+        result => result,
+    }
+}
+
+fn run() -> Result<()> {
     let cli = Cli::parse();
+    apply_display_options(&cli)?;
 
-    if cli.interactive || (cli.pipeline.is_none() && cli.file.is_none()) {
+    if let Some(Command::Cat(args)) = cli.command {
+        cat_file(&args)?;
+    } else if let Some(Command::Peek(args)) = cli.command {
+        peek_file(&args)?;
+    } else if let Some(Command::Bench(args)) = cli.command {
+        bench_pipeline(&args)?;
+    } else if let Some(Command::Diff(args)) = cli.command {
+        if !diff_files(&args)? && args.exit_code {
+            std::process::exit(1);
+        }
+    } else if let Some(Command::Fmt(args)) = cli.command {
+        if !format_files(&args)? {
+            std::process::exit(1);
+        }
+    } else if let Some(Command::Check(args)) = cli.command {
+        if !check_scripts(&args)? {
+            std::process::exit(1);
+        }
+    } else if let Some(Command::Completions { shell }) = cli.command {
+        // Generated into memory first, as clap_complete panics on write errors
+        let mut script = Vec::new();
+        clap_complete::generate(shell, &mut Cli::command(), "dt", &mut script);
+        std::io::stdout().lock().write_all(&script)?;
+    } else if cli.list_functions {
+        let catalog = serde_json::json!({
+            "operations": OPERATIONS,
+            "functions": FUNCTIONS,
+            "keywords": KEYWORDS,
+        });
+        let mut stdout = std::io::stdout().lock();
+        writeln!(stdout, "{}", serde_json::to_string_pretty(&catalog).unwrap_or_default())?;
+    } else if cli.explain || cli.explain_json {
+        let source = match (&cli.plan, &cli.pipeline, &cli.file) {
+            (None, Some(pipeline), _) => pipeline.clone(),
+            (Some(path), _, _) | (None, None, Some(path)) => std::fs::read_to_string(path)?,
+            (None, None, None) => {
+                return Err(DtransformError::InvalidOperation(
+                    "--explain needs a pipeline, --file or --plan".to_string(),
+                ))
+            }
+        };
+        if cli.explain {
+            explain_plan(&source, &cli)?;
+        } else {
+            explain_json(&source, &cli)?;
+        }
+    } else if let Some(plan_path) = &cli.plan {
+        let plan = std::fs::read_to_string(plan_path)?;
+        execute_pipeline(&plan, Some(plan_path), &cli)?;
+    } else if cli.interactive || (cli.pipeline.is_none() && cli.file.is_none()) {
         // Start REPL
         let mut repl = Repl::new()?;
         repl.run()?;
-    } else if let Some(pipeline_str) = cli.pipeline {
+    } else if let Some(pipeline_str) = &cli.pipeline {
         // Execute inline pipeline
-        execute_pipeline(&pipeline_str, cli.output, cli.verbose)?;
-    } else if let Some(file_path) = cli.file {
+        execute_pipeline(pipeline_str, None, &cli)?;
+    } else if let Some(file_path) = &cli.file {
         // Execute pipeline from file
         let pipeline_str = std::fs::read_to_string(file_path)?;
-        execute_pipeline(&pipeline_str, cli.output, cli.verbose)?;
+        execute_pipeline(&pipeline_str, Some(file_path), &cli)?;
     }
 
     Ok(())
 }
 
-fn execute_pipeline(pipeline_str: &str, output: Option<String>, verbose: bool) -> Result<()> {
-    let program = data_transform::parse_program(pipeline_str)?;
+/// Table printing options, read by polars whenever a table is formatted
+fn apply_display_options(cli: &Cli) -> Result<()> {
+    let (max_cols, max_rows) = if cli.no_elide { (Some(-1), Some(-1)) } else { (cli.max_cols, cli.max_rows) };
+    if let Some(n) = max_cols {
+        std::env::set_var("POLARS_FMT_MAX_COLS", n.to_string());
+    }
+    if let Some(n) = max_rows {
+        std::env::set_var("POLARS_FMT_MAX_ROWS", n.to_string());
+    }
+    if let Some(chars) = cli.max_width {
+        std::env::set_var("POLARS_FMT_STR_LEN", chars.to_string());
+    }
+    if cli.full_width || cli.no_elide {
+        std::env::set_var("POLARS_TABLE_WIDTH", u16::MAX.to_string());
+    }
+    if let Some(fmt) = &cli.float_fmt {
+        data_transform::executor::set_float_format(fmt)?;
+    }
+    Ok(())
+}
 
-    if verbose {
-        println!("Executing {} statement(s)", program.statements.len());
+/// `--param name=value`
+fn parse_param(arg: &str) -> std::result::Result<(String, String), String> {
+    match arg.split_once('=') {
+        Some((name, value)) if !name.trim().is_empty() => Ok((name.trim().to_string(), value.to_string())),
+        _ => Err(format!("expected NAME=VALUE, got '{}'", arg)),
     }
+}
 
+/// The program to run: a JSON plan with --plan, otherwise dt source with placeholders filled in
+fn load_program(source: &str, cli: &Cli) -> Result<Program> {
+    if let Some(plan_path) = &cli.plan {
+        return serde_json::from_str(source)
+            .map_err(|e| DtransformError::ParseError(format!("Invalid plan {}: {}", plan_path, e)));
+    }
+    let params = cli.params.iter().cloned().collect();
+    data_transform::parse_program(&data_transform::substitute_variables(source, &params)?)
+}
+
+/// `--explain-json`: print the program as JSON, loadable again with --plan
+fn explain_json(source: &str, cli: &Cli) -> Result<()> {
+    let program = load_program(source, cli)?;
+    let text = serde_json::to_string_pretty(&program)
+        .map_err(|e| DtransformError::InvalidOperation(format!("Cannot serialize program: {}", e)))?;
+    writeln!(std::io::stdout().lock(), "{}", text)?;
+    Ok(())
+}
+
+/// `--explain`: print the stages of the program with the rows and columns expected after each
+fn explain_plan(source: &str, cli: &Cli) -> Result<()> {
+    let program = load_program(source, cli)?;
     let mut executor = Executor::new();
+    executor.set_verbose(cli.verbose);
+    if let Some(dir) = &cli.replay {
+        executor.set_fixtures(Fixtures::Replay { dir: dir.clone() });
+    }
+    print!("{}", executor.explain_program(&program)?);
+    Ok(())
+}
+
+/// Run a program from the command line; `script` is the file it came from (-f or --plan).
+/// With --manifest the run is recorded and the manifest is written even if it fails.
+fn execute_pipeline(pipeline_str: &str, script: Option<&str>, cli: &Cli) -> Result<()> {
+    let started_at = chrono::Local::now();
+    let started = Instant::now();
+
+    let mut executor = Executor::new();
+    executor.set_verbose(cli.verbose);
+    if cli.progress || cli.quiet {
+        executor.set_progress(Some(cli.progress));
+    }
+    executor.set_parallel(!cli.sequential);
+    executor.record_run(cli.manifest.is_some());
+    for (name, value) in &cli.params {
+        executor.set_param(name.clone(), value.clone());
+    }
+    if let Some(dir) = &cli.temp_dir {
+        executor.set_temp_dir(dir.clone());
+    }
+    if let Some(dir) = &cli.record {
+        executor.set_fixtures(Fixtures::Record { dir: dir.clone(), rows: cli.record_rows });
+    } else if let Some(dir) = &cli.replay {
+        executor.set_fixtures(Fixtures::Replay { dir: dir.clone() });
+    }
+
+    let result = run_program(&mut executor, pipeline_str, cli);
+
+    if let Some(manifest_path) = &cli.manifest {
+        let mut log = executor.take_run_log();
+        if let Ok(Some(written)) = &result {
+            log.outputs.push(written.clone());
+        }
+        let run = ManifestRun {
+            script,
+            program: pipeline_str,
+            started_at,
+            duration: started.elapsed(),
+            error: result.as_ref().err().map(|e| e.to_string()),
+        };
+        write_manifest(manifest_path, &run, &log)?;
+    }
+
+    result.map(|_| ())
+}
+
+/// Execute the program and print or save its result; returns the file written by -o, if any
+fn run_program(executor: &mut Executor, pipeline_str: &str, cli: &Cli) -> Result<Option<WrittenFile>> {
+    let verbose = cli.verbose;
+    let program = load_program(pipeline_str, cli)?;
+    let wrote_to_stdout = writes_to_stdout(&program);
+
+    // Diagnostics go to stderr so stdout stays clean for piping
+    if verbose {
+        eprintln!("Executing {} statement(s)", program.statements.len());
+    }
+
     let result = executor.execute_program(program)?;
+    let mut written = None;
 
     if let Some(df) = result {
-        if let Some(output_path) = output {
-            // Write to file
-            let mut file = std::fs::File::create(output_path)?;
-            CsvWriter::new(&mut file).finish(&mut df.clone())?;
+        if let Some(output_path) = &cli.output {
+            let total = df.height();
+            let progress = |written: usize| {
+                if cli.progress {
+                    let percent = written as f64 * 100.0 / total as f64;
+                    eprint!("\rwrite '{}': {} / {} rows ({:.0}%)", output_path, written, total, percent);
+                }
+            };
+            if output_path == "-" {
+                // CSV to stdout
+                let stdout = std::io::stdout();
+                let mut handle = std::io::BufWriter::new(stdout.lock());
+                write_delimited_chunks(&mut handle, &df, b',', true, DEFAULT_CHUNK_ROWS, false, progress)?;
+                handle.flush()?;
+            } else {
+                // Write to file, staged and moved into place like write()
+                executor.write_staged(std::path::Path::new(output_path), |file| {
+                    write_delimited_chunks(file, &df, b',', true, DEFAULT_CHUNK_ROWS, false, progress)
+                })?;
+            }
+            if cli.progress && total > 0 {
+                eprintln!();
+            }
+            written = Some(WrittenFile { path: output_path.clone(), rows: df.height() });
 
             if verbose {
-                println!(
+                eprintln!(
                     "Output written: {} rows × {} cols",
                     df.height(),
                     df.width()
                 );
             }
-        } else {
-            // Write to stdout
-            println!("{}", df);
+        } else if !wrote_to_stdout {
+            // Write to stdout; a closed pipe is an error for main() to let pass
+            writeln!(std::io::stdout().lock(), "{}", df)?;
         }
     } else if verbose {
-        println!("No output (script only performed assignments)");
+        eprintln!("No output (script only performed assignments)");
+    }
+
+    Ok(written)
+}
+
+/// How a command-line run went, for the manifest
+struct ManifestRun<'a> {
+    script: Option<&'a str>,
+    program: &'a str,
+    started_at: chrono::DateTime<chrono::Local>,
+    duration: Duration,
+    error: Option<String>,
+}
+
+fn write_manifest(path: &str, run: &ManifestRun, log: &RunLog) -> Result<()> {
+    let inputs: Vec<serde_json::Value> = log.inputs.iter().map(|p| file_entry(p, None)).collect::<Result<_>>()?;
+    let outputs: Vec<serde_json::Value> = log
+        .outputs
+        .iter()
+        .map(|w| file_entry(&w.path, Some(w.rows)))
+        .collect::<Result<_>>()?;
+    let stages: Vec<serde_json::Value> = log
+        .stages
+        .iter()
+        .map(|s| {
+            serde_json::json!({
+                "statement": s.statement,
+                "stage": s.stage,
+                "rows": s.rows,
+                "duration_ms": millis(s.duration),
+            })
+        })
+        .collect();
+
+    let manifest = serde_json::json!({
+        "dt_version": env!("CARGO_PKG_VERSION"),
+        "status": if run.error.is_some() { "error" } else { "ok" },
+        "error": run.error,
+        "started_at": run.started_at.to_rfc3339(),
+        "duration_ms": millis(run.duration),
+        "script": run.script,
+        "program": run.program,
+        "inputs": inputs,
+        "stages": stages,
+        "outputs": outputs,
+    });
+
+    let text = serde_json::to_string_pretty(&manifest)
+        .map_err(|e| DtransformError::InvalidOperation(format!("Cannot write manifest: {}", e)))?;
+    std::fs::write(path, text + "\n")?;
+    Ok(())
+}
+
+/// Milliseconds, to the microsecond
+fn millis(duration: Duration) -> f64 {
+    (duration.as_secs_f64() * 1_000_000.0).round() / 1000.0
+}
+
+/// Path, size and SHA-256 of a file read or written ('-' has no size or checksum)
+fn file_entry(path: &str, rows: Option<usize>) -> Result<serde_json::Value> {
+    let mut entry = serde_json::json!({ "path": path });
+    // Only local files are hashed; stdin, URLs and databases are recorded by name
+    if std::path::Path::new(path).is_file() {
+        let mut file = std::fs::File::open(path)?;
+        let bytes = file.metadata()?.len();
+        let mut hasher = Sha256::new();
+        std::io::copy(&mut file, &mut hasher)?;
+        let digest: String = hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect();
+        entry["bytes"] = bytes.into();
+        entry["sha256"] = digest.into();
+    }
+    if let Some(rows) = rows {
+        entry["rows"] = rows.into();
+    }
+    Ok(entry)
+}
+
+/// `dt bench`: run the program `iterations` times on fresh executors and print
+/// min/mean/max per stage and for the whole run
+fn bench_pipeline(args: &BenchArgs) -> Result<()> {
+    let (label, source) = match (&args.file, &args.pipeline) {
+        (Some(path), _) => (path.clone(), std::fs::read_to_string(path)?),
+        (None, Some(pipeline)) => ("pipeline".to_string(), pipeline.clone()),
+        (None, None) => unreachable!("clap requires a pipeline or --file"),
+    };
+    let program = data_transform::parse_program(&source)?;
+
+    for _ in 0..args.warmup {
+        Executor::new().execute_program(program.clone())?;
+    }
+
+    let mut runs: Vec<RunLog> = Vec::new();
+    let mut totals: Vec<Duration> = Vec::new();
+    for _ in 0..args.iterations {
+        let mut executor = Executor::new();
+        executor.record_run(true);
+        let started = Instant::now();
+        executor.execute_program(program.clone())?;
+        totals.push(started.elapsed());
+        runs.push(executor.take_run_log());
+    }
+
+    eprintln!("Benchmark: {} ({} iterations)\n", label, args.iterations);
+    println!("{:<24} {:>10} {:>12} {:>12} {:>12}", "stage", "rows", "min", "mean", "max");
+
+    // Every run executes the same stages in the same order
+    for (i, stage) in runs[0].stages.iter().enumerate() {
+        let durations: Vec<Duration> = runs.iter().filter_map(|run| run.stages.get(i)).map(|t| t.duration).collect();
+        let name = format!("{}: {}", stage.statement, stage.stage);
+        print_timing_row(&name, &stage.rows.to_string(), &durations);
+    }
+    print_timing_row("total", "", &totals);
+
+    Ok(())
+}
+
+fn print_timing_row(name: &str, rows: &str, durations: &[Duration]) {
+    let min = durations.iter().min().copied().unwrap_or_default();
+    let max = durations.iter().max().copied().unwrap_or_default();
+    let mean = durations.iter().sum::<Duration>() / durations.len().max(1) as u32;
+    println!(
+        "{:<24} {:>10} {:>12} {:>12} {:>12}",
+        name,
+        rows,
+        format_duration(min),
+        format_duration(mean),
+        format_duration(max)
+    );
+}
+
+fn format_duration(duration: Duration) -> String {
+    let ms = duration.as_secs_f64() * 1000.0;
+    if ms >= 1000.0 {
+        format!("{:.2} s", ms / 1000.0)
+    } else {
+        format!("{:.3} ms", ms)
+    }
+}
+
+/// read() of one file, for the subcommands that work on files
+fn read_path(path: &str) -> Result<DataFrame> {
+    let path = path.replace('\\', "\\\\").replace('\'', "\\'");
+    let program = data_transform::parse_program(&format!("read('{}')", path))?;
+    Ok(Executor::new().execute_program(program)?.unwrap_or_default())
+}
+
+/// `dt diff`: print a summary of how NEW differs from OLD; true if they are the same
+fn diff_files(args: &DiffArgs) -> Result<bool> {
+    let old = read_path(&args.old)?;
+    let new = read_path(&args.new)?;
+    let keys: Vec<String> = args.key.iter().map(|k| k.trim().to_string()).filter(|k| !k.is_empty()).collect();
+    let diff = diff_tables(&old, &new, &keys)?;
+
+    let matched_on = if keys.is_empty() { "row number".to_string() } else { keys.join(", ") };
+    println!(
+        "Comparing {} ({} rows) with {} ({} rows) on {}\n",
+        args.old,
+        old.height(),
+        args.new,
+        new.height(),
+        matched_on
+    );
+    if !diff.columns_added.is_empty() {
+        println!("{:<17}{}", "Columns added:", diff.columns_added.join(", "));
+    }
+    if !diff.columns_removed.is_empty() {
+        println!("{:<17}{}", "Columns removed:", diff.columns_removed.join(", "));
+    }
+    if !diff.columns_added.is_empty() || !diff.columns_removed.is_empty() {
+        println!();
+    }
+    println!("{:<17}{}", "Rows added:", diff.rows_added);
+    println!("{:<17}{}", "Rows removed:", diff.rows_removed);
+    println!("{:<17}{}", "Rows changed:", diff.rows_changed);
+    println!("{:<17}{}", "Rows unchanged:", diff.rows_unchanged);
+
+    let changed: Vec<&(String, usize)> = diff.changes_by_column.iter().filter(|(_, n)| *n > 0).collect();
+    if !changed.is_empty() {
+        println!("\nChanges by column:");
+        let width = changed.iter().map(|(c, _)| c.chars().count()).max().unwrap_or(0);
+        for (column, count) in changed {
+            println!("  {:<width$}  {}", column, count, width = width);
+        }
     }
 
+    if let Some(output) = &args.output {
+        let mut executor = Executor::new();
+        executor.set_variable("diff".to_string(), diff.details.clone());
+        let path = output.replace('\\', "\\\\").replace('\'', "\\'");
+        executor.execute_program(data_transform::parse_program(&format!("diff | write('{}')", path))?)?;
+        eprintln!("\n{} difference(s) written to {}", diff.details.height(), output);
+    }
+
+    Ok(diff.is_empty())
+}
+
+/// `dt fmt`: print, rewrite or check each script; false if --check found one to format
+fn format_files(args: &FmtArgs) -> Result<bool> {
+    let mut formatted = true;
+    for path in &args.files {
+        let source = std::fs::read_to_string(path)?;
+        let pretty = data_transform::format_program(&source)
+            .map_err(|e| DtransformError::ParseError(format!("{}: {}", path, e)))?;
+        if args.check {
+            if pretty != source {
+                println!("{}", path);
+                formatted = false;
+            }
+        } else if args.write {
+            if pretty != source {
+                std::fs::write(path, &pretty)?;
+            }
+        } else {
+            print!("{}", pretty);
+        }
+    }
+    Ok(formatted)
+}
+
+/// `dt check`: report each script's problems, or that it is fine; false if any has one
+fn check_scripts(args: &CheckArgs) -> Result<bool> {
+    let mut stand_ins = StandIns::default();
+    for against in &args.against {
+        match against.split_once('=') {
+            Some((path, file)) => {
+                stand_ins.by_path.insert(path.to_string(), file.to_string());
+            }
+            None => stand_ins.fallback = Some(against.clone()),
+        }
+    }
+    let stand_ins = (!args.against.is_empty()).then_some(stand_ins);
+    let params = args.params.iter().cloned().collect();
+
+    let mut passed = true;
+    for path in &args.files {
+        let problems = check_script(path, &params, stand_ins.as_ref())?;
+        if problems.is_empty() {
+            println!("{}: ok", path);
+        } else {
+            passed = false;
+            for problem in problems {
+                println!("{}", problem);
+            }
+        }
+    }
+    Ok(passed)
+}
+
+/// The problems of one script, each starting with `path:line:`. Syntax errors come
+/// with the line and a marker under the column; with stand-ins the parsed program
+/// is also run on empty tables.
+fn check_script(
+    path: &str,
+    params: &std::collections::HashMap<String, String>,
+    stand_ins: Option<&StandIns>,
+) -> Result<Vec<String>> {
+    let source = std::fs::read_to_string(path)?;
+    let statements = match data_transform::substitute_variables(&source, params)
+        .and_then(|source| data_transform::parser::split_statements(&source))
+    {
+        Ok(statements) => statements,
+        Err(DtransformError::PestError(message)) => return Ok(vec![format!("{}: syntax error\n{}", path, message)]),
+        Err(e) => return Ok(vec![format!("{}: {}", path, e)]),
+    };
+
+    let mut problems = Vec::new();
+    let mut program = Program { statements: Vec::new() };
+    let mut lines = Vec::new();
+    for (line, text) in statements {
+        match data_transform::parse(&text) {
+            Ok(statement) => {
+                program.statements.push(statement);
+                lines.push(line);
+            }
+            Err(e) => problems.push(format!("{}:{}: {}", path, line, e)),
+        }
+    }
+
+    if let (true, Some(stand_ins)) = (problems.is_empty(), stand_ins) {
+        let mut executor = Executor::new();
+        executor.set_stand_ins(stand_ins.clone());
+        for issue in executor.check_program(&program) {
+            let line = lines[issue.statement - 1];
+            problems.push(format!("{}:{}: {}: {}", path, line, issue.stage, issue.message));
+        }
+    }
+    Ok(problems)
+}
+
+/// `dt cat`: read one file and show it as a table, paged when stdout is a terminal
+fn cat_file(args: &CatArgs) -> Result<()> {
+    let df = read_path(&args.file)?;
+    let df = if args.columns.is_empty() {
+        df
+    } else {
+        let columns: Vec<&str> = args.columns.iter().map(|c| c.trim()).collect();
+        if let Some(missing) = columns.iter().find(|c| df.column(c).is_err()) {
+            return Err(DtransformError::ColumnNotFound(missing.to_string()));
+        }
+        df.select(columns)?
+    };
+
+    let rows = df.height();
+    let shown = if args.all { rows } else { rows.min(args.rows) };
+
+    // Show every selected row and column; only cell text is truncated
+    std::env::set_var("POLARS_FMT_MAX_ROWS", shown.to_string());
+    std::env::set_var("POLARS_FMT_MAX_COLS", "-1");
+    std::env::set_var("POLARS_FMT_STR_LEN", args.max_width.to_string());
+    std::env::set_var("POLARS_FMT_TABLE_HIDE_DATAFRAME_SHAPE_INFORMATION", "1");
+
+    let mut text = format!("[Table: {} rows × {} cols]\n{}\n", rows, df.width(), df.head(Some(shown)));
+    if rows > shown {
+        text.push_str(&format!("... {} more rows\n", rows - shown));
+    }
+
+    let stdout = std::io::stdout();
+    if args.no_pager || !stdout.is_terminal() || !page(&text) {
+        stdout.lock().write_all(text.as_bytes())?;
+    }
     Ok(())
 }
+
+/// `dt peek`: the schema inferred from a head or a reservoir sample of a file, and those rows
+fn peek_file(args: &PeekArgs) -> Result<()> {
+    let rows = match args.sample {
+        Some(size) => PeekRows::Sample { size, seed: args.seed },
+        None => PeekRows::Head(args.rows),
+    };
+    let (df, total) = Executor::new().peek_file(&args.file, rows)?;
+
+    let picked = match (rows, total) {
+        (PeekRows::Sample { .. }, Some(total)) => format!("{} of {} rows, sampled", df.height(), total),
+        (_, Some(total)) => format!("first {} of {} rows", df.height(), total),
+        (_, None) => format!("first {} rows", df.height()),
+    };
+    let mut out = std::io::stdout().lock();
+    writeln!(out, "{} ({})", args.file, picked)?;
+    let width = df.get_column_names().iter().map(|name| name.chars().count()).max().unwrap_or(0);
+    for column in df.get_columns() {
+        writeln!(out, "  {:<width$}  {}", column.name().as_str(), column.dtype(), width = width)?;
+    }
+
+    std::env::set_var("POLARS_FMT_MAX_ROWS", df.height().to_string());
+    std::env::set_var("POLARS_FMT_TABLE_HIDE_DATAFRAME_SHAPE_INFORMATION", "1");
+    writeln!(out, "\n{}", df)?;
+    Ok(())
+}
+
+/// Send text through $PAGER (default `less -SFX`); false if no pager could be started
+fn page(text: &str) -> bool {
+    let pager = std::env::var("PAGER").unwrap_or_else(|_| "less -SFX".to_string());
+    let child = Process::new("sh")
+        .arg("-c")
+        .arg(&pager)
+        .stdin(Stdio::piped())
+        .spawn();
+
+    match child {
+        Ok(mut child) => {
+            if let Some(mut stdin) = child.stdin.take() {
+                // The pager may quit before reading everything
+                let _ = stdin.write_all(text.as_bytes());
+            }
+            child.wait().is_ok()
+        }
+        Err(_) => false,
+    }
+}
+
+/// Whether the program already writes a table to stdout via write('-')
+fn writes_to_stdout(program: &Program) -> bool {
+    program.statements.iter().any(|statement| {
+        let pipeline = match statement {
+            Statement::Assignment { pipeline, .. } | Statement::Pipeline(pipeline) => pipeline,
+        };
+        pipeline
+            .operations
+            .iter()
+            .any(|op| matches!(op, Operation::Write(write_op) if write_op.path == "-"))
+    })
+}
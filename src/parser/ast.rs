@@ -19,8 +19,9 @@ pub struct Pipeline {
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Source {
-    Read(ReadOp),
+    Read(Box<ReadOp>),
     Variable(String),
+    Concat(ConcatOp),
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -29,6 +30,7 @@ pub enum Operation {
     Variable(String),  // Variable reference (e.g., "data" in "data | filter(...)")
     Write(WriteOp),
     Select(SelectOp),
+    Relocate(RelocateOp),
     Filter(FilterOp),
     Mutate(MutateOp),
     Rename(RenameOp),
@@ -37,8 +39,26 @@ pub enum Operation {
     Take(TakeOp),
     Skip(SkipOp),
     Slice(SliceOp),
+    Sample(SampleOp),
     Drop(DropOp),
     Distinct(DistinctOp),
+    Concat(ConcatOp),   // concat(a, b, ...) - stack tables as a new source
+    Append(ConcatOp),   // ... | append(b, ...) - stack tables below the current one
+    Cast(CastOp),
+    MutateAll(MutateAllOp),
+    DropNulls(DropNullsOp),
+    FillNulls(FillNullsOp),
+    Describe(DescribeOp),
+    Count,              // Replace the table with its row count
+    Expect(ExpectOp),   // Fail unless the table meets every expectation
+    CountBy(CountByOp),
+    TopN(TopNOp),
+    Summarize(SummarizeOp),
+    Resample(ResampleOp),
+    Upsample(UpsampleOp),
+    Explode(ExplodeOp),
+    WithRowNumber(WithRowNumberOp),
+    SplitInto(SplitIntoOp),
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -49,6 +69,17 @@ pub struct ReadOp {
     pub header: Option<bool>,  // NEW: Whether the file has a header row
     pub skip_rows: Option<usize>,  // NEW: Number of rows to skip before reading
     pub trim_whitespace: Option<bool>,  // NEW: Trim leading/trailing whitespace from each line
+    pub include_filename: Option<bool>,  // Add a 'filename' column with the source path
+    pub quote_char: Option<Option<char>>,  // Some(None) = no quoting: quote_char=''
+    pub comment: Option<String>,  // Skip lines starting with this prefix
+    pub null_values: Vec<String>,  // Extra strings read as null, e.g. 'NA'
+    pub encoding: Option<String>,  // Text encoding of the file (utf8, utf8-lossy, latin1, windows-1252)
+    pub compression: Option<String>,  // gzip, zstd, bzip2 or none; by default from the extension (.gz, .zst, .bz2)
+    pub headers: Vec<(String, String)>,  // HTTP request headers for URL sources
+    pub timeout: Option<u64>,  // Seconds allowed for downloading a URL source
+    pub table: Option<String>,  // SQLite table to read
+    pub query: Option<String>,  // SQLite query to read instead of a table
+    pub schema: Option<bool>,  // Apply the column types in <file>.schema.json when present (default true)
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -57,6 +88,16 @@ pub struct WriteOp {
     pub format: Option<String>,
     pub header: Option<bool>,
     pub delimiter: Option<char>,  // NEW: Delimiter character for output
+    pub orient: Option<String>,   // JSON layout: "records" (array of objects) or "columns"
+    pub pretty: Option<bool>,     // Indented JSON
+    pub formats: Vec<(ColumnRef, String)>,  // printf-style number / strftime date format per column
+    pub chunk_size: Option<usize>,  // Rows per chunk of delimited output
+    pub flush: Option<bool>,        // Flush after every chunk
+    pub progress: Option<bool>,     // Report rows written on stderr
+    pub compression: Option<String>,  // gzip, zstd, bzip2 or none; by default from the extension (.gz, .zst, .bz2)
+    pub table: Option<String>,  // SQLite table to write
+    pub mode: Option<String>,   // SQLite: replace (default), append or fail when the table exists
+    pub schema: Option<bool>,   // Also write the column types to <file>.schema.json (delimited output)
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -109,18 +150,42 @@ pub enum AssignmentTarget {
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct RenameOp {
-    pub mappings: Vec<(ColumnRef, String)>,
+    pub mappings: Vec<RenameMapping>,
+    pub on_conflict: RenameConflict,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RenameMapping {
+    Column(ColumnRef, String),
+    Regex { pattern: String, replacement: String },  // Every matching column, $1/${name} refer to groups
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct RenameAllOp {
     pub strategy: RenameStrategy,
+    pub on_conflict: RenameConflict,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum RenameConflict {
+    Error,   // Two columns would end up with the same name: fail before renaming
+    Suffix,  // Later duplicates get _2, _3, ...
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum RenameStrategy {
     Replace { old: String, new: String },
     Sequential { prefix: String, start: usize, end: usize },
+    Regex { pattern: String, replacement: String },
+    Lowercase,
+    Uppercase,
+    SnakeCase,            // First Name, firstName -> first_name
+    CamelCase,            // first_name, First Name -> firstName
+    Trim,                 // Leading and trailing whitespace
+    Prefix(String),       // Added before every name
+    Suffix(String),       // Added after every name
+    StripPrefix(String),  // Removed from names that start with it
+    StripSuffix(String),  // Removed from names that end with it
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -132,7 +197,17 @@ pub enum ColumnRef {
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SortOp {
-    pub columns: Vec<(ColumnRef, bool)>, // (column, descending)
+    pub columns: Vec<SortColumn>,
+    pub limit: Option<usize>,  // Keep only the first n rows (partial top-k sort)
+    pub stable: bool,          // Keep the input order of equal rows (default: true)
+    pub multithreaded: bool,   // Sort using multiple threads (default: true)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SortColumn {
+    pub column: ColumnRef,
+    pub descending: bool,
+    pub nulls_last: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -151,9 +226,53 @@ pub struct SliceOp {
     pub end: usize,
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SampleOp {
+    pub size: SampleSize,
+    pub seed: Option<u64>,          // Fixed seed for a reproducible sample
+    pub with_replacement: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SampleSize {
+    Rows(usize),       // sample(100)
+    Fraction(f64),     // sample(frac=0.1)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RelocateOp {
+    pub columns: Vec<ColumnSelector>,
+    pub anchor: RelocateAnchor,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RelocateAnchor {
+    First,               // Front of the table: before=first, or no anchor
+    Last,                // End of the table: after=last
+    Before(ColumnRef),
+    After(ColumnRef),
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct DropOp {
     pub columns: Vec<ColumnSelector>,
+    pub if_exists: bool,  // Skip named columns that aren't in the table
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MutateAllOp {
+    pub columns: ColumnSelector,
+    pub template: Expression,  // Function name (trim) or expression using `_` for the column
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DropNullsOp {
+    pub columns: Vec<ColumnSelector>,  // Empty = a null in any column drops the row
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FillNullsOp {
+    pub fills: Vec<(ColumnSelector, Expression)>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -161,17 +280,98 @@ pub struct DistinctOp {
     pub columns: Option<Vec<ColumnSelector>>,  // None = all columns
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DescribeOp {
+    pub columns: Option<Vec<ColumnSelector>>,  // None = all columns
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CountByOp {
+    pub columns: Vec<ColumnRef>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TopNOp {
+    pub n: usize,
+    pub by: ColumnRef,
+    pub descending: bool,       // true = largest first (default), `by=col asc` for smallest
+    pub per: Vec<ColumnRef>,    // Empty = over the whole table
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SummarizeOp {
+    pub aggregations: Vec<(String, Expression)>,  // Output column and aggregate: total = sum(amount)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResampleOp {
+    pub time: ColumnRef,
+    pub every: String,                            // Bucket width: '15m', '1h', '1d', '1w'
+    pub aggregations: Vec<(String, Expression)>,  // Output column and aggregate: avg = mean(value)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UpsampleOp {
+    pub time: ColumnRef,
+    pub every: String,       // Step between rows: '1h', '1d'
+    pub fill_forward: bool,  // fill='ffill': new rows copy the row before them instead of being null
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExplodeOp {
+    pub columns: Vec<Expression>,  // List columns, or expressions giving a list in place of their column
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WithRowNumberOp {
+    pub name: String,          // Column name (default: row_id)
+    pub start: i64,            // Number of the first row (default: 1)
+    pub per: Vec<ColumnRef>,   // Count within each group; empty = over the whole table
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SplitIntoOp {
+    pub source: Expression,
+    pub delimiter: String,
+    pub columns: Vec<String>,
+    pub rest: bool,  // Last column keeps the unsplit remainder instead of one part
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExpectOp {
+    pub checks: Vec<Expectation>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Expectation {
+    /// rows > 0, columns = 12 (op is one of the comparison BinOps)
+    Count { target: CountTarget, op: BinOp, value: usize },
+    HasColumns(Vec<String>),
+    DataType { column: ColumnRef, data_type: DataType },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum CountTarget {
+    Rows,
+    Columns,
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Expression {
     Literal(Literal),
     Column(ColumnRef),
     List(Vec<Literal>),  // List literal for 'in' operator: ['a', 'b', 'c']
     Variable(String),  // Variable reference (e.g., "want" in "filter($3 in want)")
+    VariableColumn {
+        variable: String,           // Stored variable name
+        column: ColumnRef,          // Column of the variable (e.g., "id" in "ref.id")
+    },
     BinaryOp {
         left: Box<Expression>,
         op: BinOp,
         right: Box<Expression>,
     },
+    Negate(Box<Expression>),  // Unary minus: -balance
     MethodCall {
         object: Box<Expression>,
         method: String,
@@ -180,20 +380,45 @@ pub enum Expression {
     Split {
         string: Box<Expression>,
         delimiter: Box<Expression>,
-        index: usize,
+        index: Option<i64>,         // 0-based; negative counts from the end (-1 = last); None = list of all parts
     },
     Lookup {
         table: String,              // Variable name of the lookup table
-        key: Box<Expression>,       // Expression to evaluate as lookup key
-        on: LookupField,            // Field in lookup table to match against
+        key: Vec<Expression>,       // Key expressions; more than one for a composite key
+        on: Vec<LookupField>,       // Fields in lookup table to match against, one per key
         return_field: LookupField,  // Field to return from lookup table
+        default: Option<Box<Expression>>,  // Value for rows whose key has no match (default=)
+        strict: bool,               // strict=true: a key without a match is an error
+    },
+    FuzzyLookup {
+        table: String,              // Variable name of the lookup table
+        key: Box<Expression>,       // Text to match
+        on: LookupField,            // Text field of the lookup table to match against
+        return_field: LookupField,  // Field to return from the closest row
+        max_dist: usize,            // Most edits between the key and its match (default: 2)
     },
     Replace {
         text: Box<Expression>,      // Expression to perform replacement on
         old: Box<Expression>,       // Pattern to replace
         new: Box<Expression>,       // Replacement text
+        count: Option<usize>,       // n=: replace only the first n occurrences
+        case_sensitive: bool,       // case=false: match ignoring case
     },
     Regex(String),  // Regex pattern literal: re('pattern')
+    FunctionCall {
+        name: String,               // Built-in function name (cumsum, rank, ...)
+        args: Vec<Expression>,
+    },
+    ColumnQuantifier {
+        quantifier: Quantifier,     // any(...) or all(...)
+        columns: Vec<ColumnSelector>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Quantifier {
+    Any,
+    All,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -216,6 +441,8 @@ pub enum BinOp {
     Sub,
     Mul,
     Div,
+    Mod,  // Remainder, with the sign of the divisor (-7 % 3 == 2)
+    Pow,
     Gt,
     Lt,
     Gte,
@@ -225,4 +452,36 @@ pub enum BinOp {
     And,
     Or,
     In,  // Membership test (value in collection)
+    NotIn,  // Negated membership test (value not in collection)
+    Is,     // Null-aware equality (null is null → true)
+    IsNot,  // Null-aware inequality
+    Matches,     // Regex match: msg ~ re('timeout|refused')
+    NotMatches,  // Negated regex match: msg !~ re('^DEBUG')
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConcatOp {
+    pub sources: Vec<Source>,
+    pub mode: ConcatMode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ConcatMode {
+    Strict,    // All tables must have the same columns
+    Diagonal,  // Union of all columns; missing values are filled with null
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CastOp {
+    pub casts: Vec<(ColumnSelector, CastType)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum CastType {
+    Int,
+    Float,
+    String,
+    Bool,
+    Date(Option<String>),      // Optional strftime format, e.g. '%Y-%m-%d'
+    Datetime(Option<String>),
 }
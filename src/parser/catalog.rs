@@ -0,0 +1,160 @@
+use serde::Serialize;
+
+/// A name of the language, as offered to completion (REPL, `dt --list-functions`)
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct CatalogEntry {
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    pub signature: &'static str,
+    pub description: &'static str,
+}
+
+const fn entry(
+    name: &'static str,
+    aliases: &'static [&'static str],
+    signature: &'static str,
+    description: &'static str,
+) -> CatalogEntry {
+    CatalogEntry { name, aliases, signature, description }
+}
+
+/// Pipeline operations, in the order of the grammar
+pub const OPERATIONS: &[CatalogEntry] = &[
+    entry("read", &[], "read(path, ...)", "Read a file, glob or '-' for stdin"),
+    entry("write", &["save", "to", "export"], "write(path, ...)", "Write the table to a file or '-' for stdout"),
+    entry("select", &[], "select(columns...)", "Keep, reorder or rename columns"),
+    entry("relocate", &[], "relocate(columns..., before=col | after=col)", "Move columns, keeping the others in order"),
+    entry("filter", &[], "filter(condition)", "Keep rows where the condition is true"),
+    entry("mutate", &[], "mutate(name = expr, ...)", "Add or replace columns"),
+    entry("mutate_all", &["across"], "mutate_all(selector, expr)", "Apply an expression to every matched column"),
+    entry("rename", &[], "rename(old -> new, ..., on_conflict='error')", "Rename columns"),
+    entry("rename_all", &[], "rename_all(strategy, on_conflict='error')", "Rename every column by a strategy"),
+    entry("sort", &["order", "order_by"], "sort(columns... [asc|desc], ...)", "Sort rows"),
+    entry("take", &["head", "limit"], "take(n)", "First n rows"),
+    entry("skip", &["offset"], "skip(n)", "Drop the first n rows"),
+    entry("slice", &[], "slice(start, end)", "Rows from start up to end"),
+    entry("sample", &[], "sample(n | frac=f, seed=s)", "Random rows"),
+    entry("drop_nulls", &[], "drop_nulls([columns...])", "Drop rows with nulls"),
+    entry("fill_nulls", &[], "fill_nulls(columns = value, ...)", "Replace nulls"),
+    entry("drop", &["remove"], "drop(columns..., if_exists=false)", "Remove columns"),
+    entry("distinct", &[], "distinct([columns...])", "Drop duplicate rows"),
+    entry("concat", &["union"], "concat(tables..., mode='strict')", "Stack tables"),
+    entry("append", &[], "append(tables..., mode='strict')", "Stack tables under the current one"),
+    entry("cast", &[], "cast(column -> type, ...)", "Convert column types"),
+    entry("describe", &["summary"], "describe([columns...])", "Summary statistics per column"),
+    entry("count", &[], "count()", "Number of rows"),
+    entry("count_by", &[], "count_by(columns...)", "Rows per group, most frequent first"),
+    entry("top_n", &[], "top_n(n, by=col [asc], per=cols)", "Largest rows overall or per group"),
+    entry("summarize", &[], "summarize(name = fn(col), ...)", "One row of aggregates over the whole table"),
+    entry("resample", &[], "resample(ts, every='1h', agg(name = fn(col), ...))", "Aggregates per time bucket"),
+    entry("upsample", &[], "upsample(ts, every='1d', fill=null|'ffill')", "Add rows for missing time steps"),
+    entry("explode", &["unnest"], "explode(list_columns...)", "One row per list element"),
+    entry("with_row_number", &["with_row_index"], "with_row_number(name='row_id', start=1, per=cols)", "Add a row number column, optionally per group"),
+    entry("split_into", &[], "split_into(text, sep, columns..., rest=false)", "Split a string into several columns"),
+    entry("expect", &[], "expect(rows > 0, columns = n, has = [...], dtype(col) = Type)", "Fail unless the table meets expectations"),
+];
+
+/// Functions usable in expressions
+pub const FUNCTIONS: &[CatalogEntry] = &[
+    // Window
+    entry("cumsum", &["cum_sum"], "cumsum(x)", "Running sum"),
+    entry("cumprod", &["cum_prod"], "cumprod(x)", "Running product"),
+    entry("cummin", &["cum_min"], "cummin(x)", "Running minimum"),
+    entry("cummax", &["cum_max"], "cummax(x)", "Running maximum"),
+    entry("rank", &[], "rank(x[, 'desc'])", "Rank with gaps after ties"),
+    entry("dense_rank", &[], "dense_rank(x[, 'desc'])", "Rank without gaps"),
+    entry("rolling_mean", &[], "rolling_mean(x, n)", "Mean of the last n rows"),
+    entry("rolling_sum", &[], "rolling_sum(x, n)", "Sum of the last n rows"),
+    entry("rolling_min", &[], "rolling_min(x, n)", "Minimum of the last n rows"),
+    entry("rolling_max", &[], "rolling_max(x, n)", "Maximum of the last n rows"),
+    entry("lag", &[], "lag(x[, n])", "Value n rows before"),
+    entry("lead", &[], "lead(x[, n])", "Value n rows after"),
+    // Aggregate
+    entry("sum", &[], "sum(x)", "Sum of the column"),
+    entry("mean", &["avg"], "mean(x)", "Mean of the column"),
+    entry("median", &[], "median(x)", "Median of the column"),
+    entry("min", &[], "min(x)", "Minimum of the column"),
+    entry("max", &[], "max(x)", "Maximum of the column"),
+    entry("std", &[], "std(x)", "Sample standard deviation"),
+    entry("count", &[], "count([x])", "Number of rows, or of non-null values of x"),
+    entry("any", &[], "any(cond | columns)", "Condition holds for some row, or for some column"),
+    entry("all", &[], "all(cond | columns)", "Condition holds for every row, or for every column"),
+    // Table
+    entry("has_column", &[], "has_column('name')", "Whether the table has the column"),
+    entry("ncols", &[], "ncols()", "Number of columns"),
+    entry("nrows", &[], "nrows()", "Number of rows"),
+    // String
+    entry("upper", &[], "upper(s)", "Uppercase"),
+    entry("lower", &[], "lower(s)", "Lowercase"),
+    entry("trim", &[], "trim(s)", "Remove surrounding whitespace"),
+    entry("length", &[], "length(s)", "Number of characters"),
+    entry("substr", &[], "substr(s, start[, len])", "Substring"),
+    entry("pad_left", &[], "pad_left(s, width[, char])", "Pad on the left"),
+    entry("pad_right", &[], "pad_right(s, width[, char])", "Pad on the right"),
+    entry("concat", &[], "concat(a, b, ...)", "Values joined into text"),
+    entry("format", &[], "format('{}-{:03}', a, b, ...)", "Values formatted into a template"),
+    entry("contains", &[], "contains(s, text)", "Whether s contains text"),
+    entry("levenshtein", &[], "levenshtein(a, b)", "Edit distance between two strings"),
+    entry("jaro_winkler", &[], "jaro_winkler(a, b)", "Similarity of two strings, 0 to 1"),
+    entry("matches", &[], "matches(s, re('pattern'))", "Whether the regex matches s; also s ~ re('pattern')"),
+    entry("starts_with", &[], "starts_with(s, text)", "Whether s starts with text"),
+    entry("ends_with", &[], "ends_with(s, text)", "Whether s ends with text"),
+    entry("replace", &[], "replace(s, pattern, replacement, n=, case=)", "Replace text or regex matches"),
+    entry("extract", &[], "extract(s, re('pattern')[, group])", "Capture group of a regex match, null if none"),
+    entry("split", &[], "split(s, sep)[i]", "Split into a list, or pick one part"),
+    entry("split_all", &[], "split_all(s, sep)", "Split into a list of every part"),
+    entry("re", &[], "re('pattern')", "Regular expression"),
+    // Conversion
+    entry("to_int", &[], "to_int(x)", "Convert to integer"),
+    entry("to_float", &[], "to_float(x)", "Convert to float"),
+    entry("to_string", &[], "to_string(x)", "Convert to text"),
+    entry("to_bool", &[], "to_bool(x)", "Convert to boolean"),
+    entry("to_date", &[], "to_date(x[, format])", "Parse a date"),
+    entry("to_datetime", &[], "to_datetime(x[, format])", "Parse a date and time"),
+    // Date
+    entry("year", &[], "year(d)", "Year"),
+    entry("month", &[], "month(d)", "Month"),
+    entry("day", &[], "day(d)", "Day of month"),
+    entry("weekday", &[], "weekday(d)", "Day of week, 1 = Monday"),
+    entry("hour", &[], "hour(d)", "Hour"),
+    entry("minute", &[], "minute(d)", "Minute"),
+    entry("second", &[], "second(d)", "Second"),
+    entry("date_diff", &[], "date_diff(start, end, unit)", "Whole units between two dates"),
+    entry("date_add", &[], "date_add(d, n, unit)", "Shift a date by n units"),
+    entry("now", &[], "now()", "Current local date and time"),
+    entry("today", &[], "today()", "Current local date"),
+    entry("days", &[], "days(n)", "Duration of n days"),
+    entry("hours", &[], "hours(n)", "Duration of n hours"),
+    entry("minutes", &[], "minutes(n)", "Duration of n minutes"),
+    entry("seconds", &[], "seconds(n)", "Duration of n seconds"),
+    entry("weeks", &[], "weeks(n)", "Duration of n weeks"),
+    entry("file_name", &[], "file_name()", "File the rows were read from"),
+    // List
+    entry("list_len", &[], "list_len(l)", "Number of items"),
+    entry("list_get", &[], "list_get(l, i)", "Item at index i"),
+    entry("list_contains", &[], "list_contains(l, value)", "Whether the list holds value"),
+    entry("list_join", &[], "list_join(l, sep)", "Join items into text"),
+    // Math and nulls
+    entry("round", &[], "round(x[, decimals])", "Round"),
+    entry("abs", &[], "abs(x)", "Absolute value"),
+    entry("is_null", &[], "is_null(x)", "Whether the value is null"),
+    entry("is_not_null", &[], "is_not_null(x)", "Whether the value is not null"),
+    entry("coalesce", &[], "coalesce(a, b, ...)", "First non-null value"),
+    entry("between", &[], "between(x, low, high)", "Whether low <= x <= high"),
+    // Conditional and lookup
+    entry("if", &[], "if(cond, then, else)", "Value by condition"),
+    entry("case_when", &[], "case_when(cond, value, ..., [default])", "Value of the first true condition"),
+    entry("fuzzy_lookup", &[], "fuzzy_lookup(table, key, on=col, return=col, max_dist=2)", "Value from the row with the closest text key"),
+    entry("lookup", &[], "lookup(table, key, on=col, return=col, default=v, strict=false)", "Value from another table by key; key=(a, b) with on=(x, y) for a composite key"),
+];
+
+/// Words of the grammar that aren't operations or functions
+pub const KEYWORDS: &[&str] = &[
+    "and", "or", "not", "in", "is", "as", "asc", "desc", "nulls", "true", "false", "null",
+    "on", "return",
+];
+
+/// Every name an entry answers to, aliases included
+pub fn names(entries: &[CatalogEntry]) -> impl Iterator<Item = &str> {
+    entries.iter().flat_map(|e| std::iter::once(e.name).chain(e.aliases.iter().copied()))
+}
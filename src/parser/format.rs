@@ -0,0 +1,260 @@
+use pest::Parser;
+
+use super::{parse_program, DtransformParser, Rule};
+use crate::error::{DtransformError, Result};
+
+/// Calls whose `name = value` arguments define columns, written with spaces
+/// around `=`; elsewhere `=` names a parameter and is written tight: read('a.csv', header=true)
+const ASSIGNING_CALLS: &[&str] = &["mutate", "summarize", "agg", "fill_nulls"];
+
+/// Words that read as operators: `a in [...]`, `x and (y or z)`
+const KEYWORDS: &[&str] = &["and", "or", "not", "in", "is"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Identifier,
+    Number,
+    Str,
+    Positional,
+    Comment,
+    Punct,
+}
+
+#[derive(Debug)]
+struct Token<'a> {
+    kind: Kind,
+    text: &'a str,
+    start: usize,
+    /// Line breaks between the previous token and this one
+    newlines: usize,
+    /// Nothing at all between the previous token and this one
+    glued: bool,
+}
+
+impl Token<'_> {
+    fn is(&self, text: &str) -> bool {
+        self.kind == Kind::Punct && self.text == text
+    }
+
+    fn is_keyword(&self) -> bool {
+        self.kind == Kind::Identifier && KEYWORDS.contains(&self.text)
+    }
+
+    /// Ends an operand, so a `-` after it subtracts rather than negates
+    fn ends_value(&self) -> bool {
+        match self.kind {
+            Kind::Identifier => !self.is_keyword(),
+            Kind::Number | Kind::Str | Kind::Positional => true,
+            Kind::Comment => false,
+            Kind::Punct => matches!(self.text, ")" | "]" | "}"),
+        }
+    }
+
+    /// The token as written out: double-quoted strings become single-quoted
+    /// unless that would need new escapes
+    fn render(&self) -> String {
+        match self.kind {
+            Kind::Str if self.text.starts_with('"') => {
+                let inner = &self.text[1..self.text.len() - 1];
+                if inner.contains('\'') || inner.contains('"') {
+                    self.text.to_string()
+                } else {
+                    format!("'{}'", inner)
+                }
+            }
+            Kind::Comment => self.text.trim_end().to_string(),
+            _ => self.text.to_string(),
+        }
+    }
+}
+
+/// Split program text into tokens; the text has already parsed, so anything
+/// unexpected is kept as a one-character token
+fn tokenize(source: &str) -> Vec<Token<'_>> {
+    const PUNCTS: &[&str] = &["->", "=>", "==", "!=", "!~", ">=", "<=", "**", ".."];
+
+    let bytes = source.as_bytes();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    let mut newlines = 0;
+    let mut glued = true;
+    while pos < source.len() {
+        let rest = &source[pos..];
+        let c = rest.chars().next().unwrap_or_default();
+        if c.is_whitespace() {
+            newlines += usize::from(c == '\n');
+            glued = false;
+            pos += c.len_utf8();
+            continue;
+        }
+
+        let (kind, len) = if c == '#' || rest.starts_with("//") {
+            (Kind::Comment, rest.find('\n').unwrap_or(rest.len()))
+        } else if c == '\'' || c == '"' {
+            let mut end = 1;
+            while end < rest.len() && bytes[pos + end] != c as u8 {
+                end += if bytes[pos + end] == b'\\' { 2 } else { 1 };
+            }
+            (Kind::Str, (end + 1).min(rest.len()))
+        } else if c == '$' && rest[1..].starts_with(|d: char| d.is_ascii_digit()) {
+            (Kind::Positional, 1 + rest[1..].find(|d: char| !d.is_ascii_digit()).unwrap_or(rest.len() - 1))
+        } else if c.is_ascii_digit() {
+            let mut end = rest.find(|d: char| !d.is_ascii_digit()).unwrap_or(rest.len());
+            if rest[end..].starts_with('.') && rest[end + 1..].starts_with(|d: char| d.is_ascii_digit()) {
+                end += 1 + rest[end + 1..].find(|d: char| !d.is_ascii_digit()).unwrap_or(rest.len() - end - 1);
+            }
+            // Suffixes: 10k, 2.5m
+            end += rest[end..].find(|d: char| !d.is_ascii_alphabetic()).unwrap_or(rest.len() - end);
+            (Kind::Number, end)
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let end = rest.find(|d: char| !(d.is_ascii_alphanumeric() || d == '_')).unwrap_or(rest.len());
+            (Kind::Identifier, end)
+        } else {
+            let len = PUNCTS.iter().find(|p| rest.starts_with(*p)).map_or(c.len_utf8(), |p| p.len());
+            (Kind::Punct, len)
+        };
+
+        tokens.push(Token { kind, text: &rest[..len], start: pos, newlines, glued });
+        pos += len;
+        newlines = 0;
+        glued = true;
+    }
+    tokens
+}
+
+/// One statement, one operation per line: continuation lines start with `| `,
+/// and a comment inside the statement ends its line
+fn format_statement(tokens: &[Token]) -> String {
+    let mut out = String::new();
+    // The open brackets, each with the call it belongs to
+    let mut open: Vec<Option<&str>> = Vec::new();
+    let mut prev: Option<&Token> = None;
+    let mut line_start = true;
+    let mut tight = false;
+
+    for token in tokens {
+        let indent = "    ".repeat(open.len() + 1);
+        if token.kind == Kind::Comment {
+            if !line_start {
+                out.push_str(if token.newlines > 0 { "\n" } else { "  " });
+                if token.newlines > 0 {
+                    out.push_str(&indent);
+                }
+            }
+            out.push_str(&token.render());
+            out.push('\n');
+            out.push_str(&indent);
+            line_start = true;
+            continue;
+        }
+
+        if token.is("|") && open.is_empty() {
+            if !line_start {
+                out.push('\n');
+                out.push_str(&indent);
+            }
+            out.push_str("| ");
+            line_start = true;
+            prev = Some(token);
+            continue;
+        }
+
+        let assigning = match open.last() {
+            None => true,
+            Some(Some(call)) => ASSIGNING_CALLS.contains(call),
+            Some(None) => false,
+        };
+        let negation = token.is("-") && !prev.is_some_and(Token::ends_value);
+        let space = match (prev, token.text) {
+            _ if line_start || tight => false,
+            (_, "," | ")" | "]" | "}" | ":" | "." | "..") if token.kind == Kind::Punct => false,
+            (Some(p), "(") if token.kind == Kind::Punct => p.kind != Kind::Identifier || p.is_keyword(),
+            (Some(p), "[") if token.kind == Kind::Punct => !(p.is(")") || p.is("]")),
+            (_, "=") if token.kind == Kind::Punct => assigning,
+            // Date literals: d'2024-01-31'
+            (Some(p), _) if token.kind == Kind::Str && token.glued && p.kind == Kind::Identifier => false,
+            _ => true,
+        };
+
+        if space {
+            out.push(' ');
+        }
+        out.push_str(&token.render());
+        line_start = false;
+        tight = token.kind == Kind::Punct
+            && (matches!(token.text, "(" | "[" | "{" | "." | "..") || negation || (token.text == "=" && !assigning));
+
+        if token.kind == Kind::Punct {
+            match token.text {
+                "(" => open.push(prev.filter(|p| p.kind == Kind::Identifier).map(|p| p.text)),
+                "[" | "{" => open.push(None),
+                ")" | "]" | "}" => {
+                    open.pop();
+                }
+                _ => {}
+            }
+        }
+        prev = Some(token);
+    }
+    out.trim_end().to_string()
+}
+
+/// Start a new line for `text`, after a blank one if the source had one before `first`
+fn emit(out: &mut String, first: &Token, text: &str) {
+    if !out.is_empty() {
+        out.push_str(if first.newlines > 1 { "\n\n" } else { "\n" });
+    }
+    out.push_str(text);
+}
+
+/// Lay out a program the same way every time: one statement after another,
+/// one operation per line, single quotes, and single spaces around operators
+/// and `name = value` column definitions. Comments are kept, as is a single
+/// blank line where the source had one or more. The result parses to the same
+/// program, and formatting it again changes nothing.
+pub fn format_program(source: &str) -> Result<String> {
+    let pairs = DtransformParser::parse(Rule::program, source)
+        .map_err(|e| DtransformError::PestError(e.to_string()))?;
+    let starts: Vec<usize> = pairs
+        .into_iter()
+        .next()
+        .unwrap()
+        .into_inner()
+        .filter(|pair| pair.as_rule() == Rule::statement_inner)
+        .map(|pair| pair.as_span().start())
+        .collect();
+
+    let tokens = tokenize(source);
+    let mut out = String::new();
+
+    let mut rest = &tokens[..];
+    // Comments above the first statement
+    while let Some((comment, tail)) = rest.split_first().filter(|(t, _)| !starts.contains(&t.start)) {
+        emit(&mut out, comment, &comment.render());
+        rest = tail;
+    }
+
+    while !rest.is_empty() {
+        let len = rest[1..].iter().position(|t| starts.contains(&t.start)).map_or(rest.len(), |i| i + 1);
+        let (statement, tail) = rest.split_at(len);
+        let code = statement.iter().rposition(|t| t.kind != Kind::Comment).map_or(0, |i| i + 1);
+        emit(&mut out, &statement[0], &format_statement(&statement[..code]));
+        for comment in &statement[code..] {
+            if comment.newlines == 0 {
+                out.push_str("  ");
+                out.push_str(&comment.render());
+            } else {
+                emit(&mut out, comment, &comment.render());
+            }
+        }
+        rest = tail;
+    }
+    out.push('\n');
+
+    if parse_program(&out)? != parse_program(source)? {
+        return Err(DtransformError::InvalidOperation(
+            "Formatting would change what the program does; the script was left as it is".to_string(),
+        ));
+    }
+    Ok(out)
+}
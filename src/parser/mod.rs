@@ -1,4 +1,6 @@
 pub mod ast;
+pub mod catalog;
+mod format;
 
 use pest::Parser;
 use pest_derive::Parser;
@@ -6,10 +8,132 @@ use pest_derive::Parser;
 use ast::*;
 use crate::error::{DtransformError, Result};
 
+pub use format::format_program;
+
 #[derive(Parser)]
 #[grammar = "parser/grammar.pest"]
 pub struct DtransformParser;
 
+/// Fill `${...}` placeholders in program text before it is parsed:
+/// `${param:name}` is a `--param name=value`, `${env:name}` and `${NAME}` are
+/// environment variables. The bare form only applies to upper-case names that
+/// are set, so regex replacement references like `${1}`, `${year}` or `${YEAR}`
+/// are left alone. `$${` is a literal `${`; comments are not filled in.
+///
+/// Values are inserted as data, never as code: inside a string literal they are
+/// escaped for its quote, elsewhere a value that is not a single number or
+/// name becomes a string literal.
+pub fn substitute_variables(source: &str, params: &std::collections::HashMap<String, String>) -> Result<String> {
+    let mut out = String::with_capacity(source.len());
+    let mut quote: Option<char> = None;
+    let mut i = 0;
+
+    while let Some(c) = source[i..].chars().next() {
+        let rest = &source[i..];
+        match quote {
+            Some(_) if c == '\\' => {
+                // An escape: copy it and the character it escapes
+                let escaped = rest.chars().nth(1).map_or(0, char::len_utf8);
+                out.push_str(&rest[..1 + escaped]);
+                i += 1 + escaped;
+                continue;
+            }
+            Some(q) if c == q => quote = None,
+            None if c == '\'' || c == '"' => quote = Some(c),
+            None if c == '#' || rest.starts_with("//") => {
+                let line_end = rest.find('\n').unwrap_or(rest.len());
+                out.push_str(&rest[..line_end]);
+                i += line_end;
+                continue;
+            }
+            _ => {}
+        }
+
+        if rest.starts_with("$${") {
+            out.push_str("${");
+            i += 3;
+            continue;
+        }
+        if rest.starts_with("${") {
+            if let Some(end) = rest.find('}') {
+                let placeholder = &rest[..=end];
+                if let Some(value) = placeholder_value(&placeholder[2..end], placeholder, params)? {
+                    out.push_str(&quote_value(&value, quote));
+                    i += end + 1;
+                    continue;
+                }
+            }
+        }
+
+        out.push(c);
+        i += c.len_utf8();
+    }
+
+    Ok(out)
+}
+
+/// The value of one placeholder, or None for text that is not a placeholder.
+/// Parameter and `env:` names match regardless of case.
+fn placeholder_value(
+    name: &str,
+    placeholder: &str,
+    params: &std::collections::HashMap<String, String>,
+) -> Result<Option<String>> {
+    if let Some(param) = name.strip_prefix("param:") {
+        let param = param.trim();
+        return params
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(param))
+            .map(|(_, value)| Some(value.clone()))
+            .ok_or_else(|| DtransformError::ParseError(format!(
+                "No value for {}. Pass one with --param {}=...",
+                placeholder, param
+            )));
+    }
+    if let Some(env_name) = name.strip_prefix("env:") {
+        let env_name = env_name.trim();
+        return std::env::var(env_name)
+            .ok()
+            .or_else(|| std::env::vars().find(|(key, _)| key.eq_ignore_ascii_case(env_name)).map(|(_, value)| value))
+            .map(Some)
+            .ok_or_else(|| DtransformError::ParseError(format!(
+                "Environment variable {} in {} is not set",
+                env_name, placeholder
+            )));
+    }
+    Ok(if is_env_name(name) { std::env::var(name).ok() } else { None })
+}
+
+/// `DATA_DIR`, `HOME`: names the bare `${NAME}` form treats as environment variables
+fn is_env_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_uppercase() || c == '_')
+        && name.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+        && name.chars().any(|c| c.is_ascii_uppercase())
+}
+
+/// A substituted value as program text: escaped inside a string literal,
+/// as is when it is one number or name, otherwise quoted
+fn quote_value(value: &str, quote: Option<char>) -> String {
+    let escape = |q: char| {
+        value.chars().fold(String::with_capacity(value.len()), |mut text, c| {
+            if c == '\\' || c == q {
+                text.push('\\');
+            }
+            text.push(c);
+            text
+        })
+    };
+    match quote {
+        Some(q) => escape(q),
+        None if is_token(Rule::number, value) || is_token(Rule::identifier, value) => value.to_string(),
+        None => format!("'{}'", escape('\'')),
+    }
+}
+
+fn is_token(rule: Rule, text: &str) -> bool {
+    DtransformParser::parse(rule, text).is_ok_and(|pairs| pairs.as_str().len() == text.len())
+}
+
 // Parse a multi-statement program (for files/CLI)
 pub fn parse_program(input: &str) -> Result<Program> {
     let pairs = DtransformParser::parse(Rule::program, input)
@@ -19,6 +143,20 @@ pub fn parse_program(input: &str) -> Result<Program> {
     parse_program_inner(program_pair)
 }
 
+/// The text of each statement in a program, with the line it starts on, for
+/// running a script one statement at a time. Statements may span several lines.
+pub fn split_statements(input: &str) -> Result<Vec<(usize, String)>> {
+    let pairs = DtransformParser::parse(Rule::program, input)
+        .map_err(|e| DtransformError::PestError(e.to_string()))?;
+
+    let program_pair = pairs.into_iter().next().unwrap();
+    Ok(program_pair
+        .into_inner()
+        .filter(|pair| pair.as_rule() == Rule::statement_inner)
+        .map(|pair| (pair.line_col().0, pair.as_str().to_string()))
+        .collect())
+}
+
 // Parse a single statement (for REPL)
 pub fn parse(input: &str) -> Result<Statement> {
     let pairs = DtransformParser::parse(Rule::statement, input)
@@ -68,11 +206,8 @@ fn parse_pipeline(pair: pest::iterators::Pair<Rule>) -> Result<Pipeline> {
     let mut source = None;
 
     for inner_pair in pair.into_inner() {
-        match inner_pair.as_rule() {
-            Rule::operation => {
-                operations.push(parse_operation(inner_pair)?);
-            }
-            _ => {}
+        if inner_pair.as_rule() == Rule::operation {
+            operations.push(parse_operation(inner_pair)?);
         }
     }
 
@@ -80,13 +215,17 @@ fn parse_pipeline(pair: pest::iterators::Pair<Rule>) -> Result<Pipeline> {
     if !operations.is_empty() {
         match &operations[0] {
             Operation::Read(read_op) => {
-                source = Some(Source::Read(read_op.clone()));
+                source = Some(Source::Read(Box::new(read_op.clone())));
                 operations.remove(0);
             }
             Operation::Variable(var_name) => {
                 source = Some(Source::Variable(var_name.clone()));
                 operations.remove(0);
             }
+            Operation::Concat(concat_op) => {
+                source = Some(Source::Concat(concat_op.clone()));
+                operations.remove(0);
+            }
             _ => {}
         }
     }
@@ -110,8 +249,38 @@ fn parse_operation(pair: pest::iterators::Pair<Rule>) -> Result<Operation> {
         Rule::take_op => Ok(Operation::Take(parse_take_op(inner)?)),
         Rule::skip_op => Ok(Operation::Skip(parse_skip_op(inner)?)),
         Rule::slice_op => Ok(Operation::Slice(parse_slice_op(inner)?)),
+        Rule::sample_op => Ok(Operation::Sample(parse_sample_op(inner)?)),
         Rule::drop_op => Ok(Operation::Drop(parse_drop_op(inner)?)),
+        Rule::relocate_op => Ok(Operation::Relocate(parse_relocate_op(inner)?)),
         Rule::distinct_op => Ok(Operation::Distinct(parse_distinct_op(inner)?)),
+        Rule::concat_op => Ok(Operation::Concat(parse_concat_op(inner)?)),
+        Rule::append_op => Ok(Operation::Append(parse_concat_op(inner)?)),
+        Rule::cast_op => Ok(Operation::Cast(parse_cast_op(inner)?)),
+        Rule::mutate_all_op => {
+            let mut inner_pairs = inner.into_inner();
+            let columns = parse_selector(inner_pairs.next().unwrap())?;
+            let template = parse_expression(inner_pairs.next().unwrap())?;
+            Ok(Operation::MutateAll(MutateAllOp { columns, template }))
+        }
+        Rule::drop_nulls_op => Ok(Operation::DropNulls(parse_drop_nulls_op(inner)?)),
+        Rule::fill_nulls_op => Ok(Operation::FillNulls(parse_fill_nulls_op(inner)?)),
+        Rule::describe_op => Ok(Operation::Describe(DescribeOp { columns: parse_optional_selectors(inner)? })),
+        Rule::count_op => Ok(Operation::Count),
+        Rule::expect_op => Ok(Operation::Expect(parse_expect_op(inner)?)),
+        Rule::count_by_op => Ok(Operation::CountBy(CountByOp {
+            columns: parse_column_ref_list(inner.into_inner().next().unwrap())?,
+        })),
+        Rule::top_n_op => Ok(Operation::TopN(parse_top_n_op(inner)?)),
+        Rule::summarize_op => Ok(Operation::Summarize(SummarizeOp {
+            aggregations: parse_agg_items("summarize", inner.into_inner())?,
+        })),
+        Rule::resample_op => Ok(Operation::Resample(parse_resample_op(inner)?)),
+        Rule::upsample_op => Ok(Operation::Upsample(parse_upsample_op(inner)?)),
+        Rule::row_number_op => Ok(Operation::WithRowNumber(parse_row_number_op(inner)?)),
+        Rule::explode_op => Ok(Operation::Explode(ExplodeOp {
+            columns: inner.into_inner().map(parse_expression).collect::<Result<Vec<_>>>()?,
+        })),
+        Rule::split_into_op => Ok(Operation::SplitInto(parse_split_into_op(inner)?)),
         Rule::variable_ref => {
             // This is a variable reference used as a source
             Ok(Operation::Variable(inner.as_str().trim().to_string()))
@@ -129,6 +298,17 @@ fn parse_read_op(pair: pest::iterators::Pair<Rule>) -> Result<ReadOp> {
     let mut header = None;
     let mut skip_rows = None;
     let mut trim_whitespace = None;
+    let mut include_filename = None;
+    let mut quote_char = None;
+    let mut comment = None;
+    let mut null_values = Vec::new();
+    let mut encoding = None;
+    let mut compression = None;
+    let mut headers = Vec::new();
+    let mut timeout = None;
+    let mut table = None;
+    let mut query = None;
+    let mut schema = None;
 
     if let Some(params_pair) = inner_pairs.next() {
         for param in params_pair.into_inner() {
@@ -156,12 +336,80 @@ fn parse_read_op(pair: pest::iterators::Pair<Rule>) -> Result<ReadOp> {
                     let trim_str = parse_param_value(value)?;
                     trim_whitespace = Some(trim_str == "true");
                 }
+                "include_filename" => {
+                    let include_str = parse_param_value(value)?;
+                    include_filename = Some(include_str == "true");
+                }
+                "quote_char" | "quote" => {
+                    let quote_str = parse_param_value(value)?;
+                    let mut chars = quote_str.chars();
+                    quote_char = match (chars.next(), chars.next()) {
+                        (None, _) => Some(None),
+                        (Some(c), None) => Some(Some(c)),
+                        _ => {
+                            return Err(DtransformError::ParseError(format!(
+                                "quote_char must be a single character (or '' for no quoting), got '{}'", quote_str
+                            )))
+                        }
+                    };
+                }
+                "comment" => comment = Some(parse_param_value(value)?).filter(|c| !c.is_empty()),
+                "null_values" | "na_values" => null_values = parse_param_list(value)?,
+                "encoding" => {
+                    let encoding_str = parse_param_value(value)?.to_lowercase();
+                    if !SUPPORTED_ENCODINGS.contains(&encoding_str.as_str()) {
+                        return Err(DtransformError::ParseError(format!(
+                            "Unsupported encoding '{}'. Supported: {}", encoding_str, SUPPORTED_ENCODINGS.join(", ")
+                        )));
+                    }
+                    encoding = Some(encoding_str);
+                }
+                "compression" => compression = Some(parse_compression(&parse_param_value(value)?)?),
+                "headers" => {
+                    for (key, header_value) in parse_param_map(name, value)? {
+                        match key {
+                            ColumnRef::Name(header_name) => headers.push((header_name, header_value)),
+                            _ => {
+                                return Err(DtransformError::ParseError(
+                                    "headers expects {'Name': 'value', ...}; header names can't be positions".to_string()
+                                ))
+                            }
+                        }
+                    }
+                }
+                "timeout" => {
+                    let timeout_str = parse_param_value(value)?;
+                    timeout = Some(timeout_str.parse::<u64>().map_err(|_| {
+                        DtransformError::ParseError(format!("Invalid timeout value (seconds): {}", timeout_str))
+                    })?);
+                }
+                "table" => table = Some(parse_param_value(value)?),
+                "query" => query = Some(parse_param_value(value)?),
+                "schema" => schema = Some(parse_bool_param(name, &parse_param_value(value)?)?),
                 _ => {}
             }
         }
     }
 
-    Ok(ReadOp { path, format, delimiter, header, skip_rows, trim_whitespace })
+    Ok(ReadOp {
+        path,
+        format,
+        delimiter,
+        header,
+        skip_rows,
+        trim_whitespace,
+        include_filename,
+        quote_char,
+        comment,
+        null_values,
+        encoding,
+        compression,
+        headers,
+        timeout,
+        table,
+        query,
+        schema,
+    })
 }
 
 fn parse_write_op(pair: pest::iterators::Pair<Rule>) -> Result<WriteOp> {
@@ -171,6 +419,16 @@ fn parse_write_op(pair: pest::iterators::Pair<Rule>) -> Result<WriteOp> {
     let mut format = None;
     let mut header = None;
     let mut delimiter = None;
+    let mut orient = None;
+    let mut pretty = None;
+    let mut formats = Vec::new();
+    let mut chunk_size = None;
+    let mut flush = None;
+    let mut progress = None;
+    let mut compression = None;
+    let mut table = None;
+    let mut mode = None;
+    let mut schema = None;
 
     if let Some(params_pair) = inner_pairs.next() {
         for param in params_pair.into_inner() {
@@ -180,17 +438,54 @@ fn parse_write_op(pair: pest::iterators::Pair<Rule>) -> Result<WriteOp> {
 
             match name {
                 "format" => format = Some(parse_param_value(value)?),
+                "chunk_size" => {
+                    let rows = parse_number_as_usize(&parse_param_value(value)?)?;
+                    if rows == 0 {
+                        return Err(DtransformError::ParseError(
+                            "write() chunk_size must be at least 1".to_string()
+                        ));
+                    }
+                    chunk_size = Some(rows);
+                }
+                "flush" => flush = Some(parse_bool_param(name, &parse_param_value(value)?)?),
+                "progress" => progress = Some(parse_bool_param(name, &parse_param_value(value)?)?),
                 "header" => header = Some(parse_param_value(value)? == "true"),
                 "delimiter" => {
                     let delim_str = parse_param_value(value)?;
                     delimiter = delim_str.chars().next();
                 }
+                "orient" => {
+                    let value = parse_param_value(value)?;
+                    if value != "records" && value != "columns" {
+                        return Err(DtransformError::ParseError(format!(
+                            "orient must be 'records' or 'columns', got '{}'", value
+                        )));
+                    }
+                    orient = Some(value);
+                }
+                "pretty" => pretty = Some(parse_bool_param(name, &parse_param_value(value)?)?),
+                "formats" => formats = parse_param_map(name, value)?,
+                "compression" => compression = Some(parse_compression(&parse_param_value(value)?)?),
+                "table" => table = Some(parse_param_value(value)?),
+                "mode" => {
+                    let value = parse_param_value(value)?;
+                    if !matches!(value.as_str(), "replace" | "append" | "fail") {
+                        return Err(DtransformError::ParseError(format!(
+                            "mode must be 'replace', 'append' or 'fail', got '{}'", value
+                        )));
+                    }
+                    mode = Some(value);
+                }
+                "schema" => schema = Some(parse_bool_param(name, &parse_param_value(value)?)?),
                 _ => {}
             }
         }
     }
 
-    Ok(WriteOp { path, format, header, delimiter })
+    Ok(WriteOp {
+        path, format, header, delimiter, orient, pretty, formats, chunk_size, flush, progress, compression, table, mode,
+        schema,
+    })
 }
 
 fn parse_select_op(pair: pest::iterators::Pair<Rule>) -> Result<SelectOp> {
@@ -332,6 +627,264 @@ fn parse_data_type(pair: pest::iterators::Pair<Rule>) -> Result<DataType> {
     }
 }
 
+fn parse_column_ref_list(pair: pest::iterators::Pair<Rule>) -> Result<Vec<ColumnRef>> {
+    pair.into_inner().map(parse_column_ref).collect()
+}
+
+fn parse_top_n_op(pair: pest::iterators::Pair<Rule>) -> Result<TopNOp> {
+    let mut inner_pairs = pair.into_inner();
+    let n = parse_number_as_usize(inner_pairs.next().unwrap().as_str())?;
+    let mut by = None;
+    let mut descending = true;
+    let mut per = Vec::new();
+
+    for inner_pair in inner_pairs {
+        match inner_pair.as_rule() {
+            Rule::top_n_by => {
+                let mut parts = inner_pair.into_inner();
+                by = Some(parse_column_ref(parts.next().unwrap())?);
+                if let Some(order) = parts.next() {
+                    descending = order.as_str() == "desc";
+                }
+            }
+            Rule::top_n_per => {
+                let value = inner_pair.into_inner().next().unwrap();
+                per = match value.as_rule() {
+                    Rule::column_ref_list => parse_column_ref_list(value)?,
+                    _ => vec![parse_column_ref(value)?],
+                };
+            }
+            _ => {}
+        }
+    }
+
+    let by = by.ok_or_else(|| DtransformError::ParseError(
+        "top_n() needs a column to rank by: top_n(5, by=amount)".to_string()
+    ))?;
+
+    Ok(TopNOp { n, by, descending, per })
+}
+
+/// `name = aggregate` items of summarize() and resample(agg(...)), each name used once
+fn parse_agg_items(operation: &str, items: pest::iterators::Pairs<Rule>) -> Result<Vec<(String, Expression)>> {
+    let mut aggregations: Vec<(String, Expression)> = Vec::new();
+    for item in items {
+        let mut parts = item.into_inner();
+        let name = parts.next().unwrap().as_str().to_string();
+        if aggregations.iter().any(|(existing, _)| *existing == name) {
+            return Err(DtransformError::ParseError(format!(
+                "{}() names column '{}' twice", operation, name
+            )));
+        }
+        aggregations.push((name, parse_expression(parts.next().unwrap())?));
+    }
+    Ok(aggregations)
+}
+
+fn parse_resample_op(pair: pest::iterators::Pair<Rule>) -> Result<ResampleOp> {
+    let mut inner_pairs = pair.into_inner();
+    let time = parse_column_ref(inner_pairs.next().unwrap())?;
+    let mut every = None;
+    let mut aggregations: Vec<(String, Expression)> = Vec::new();
+
+    for inner_pair in inner_pairs {
+        match inner_pair.as_rule() {
+            Rule::resample_agg => aggregations.extend(parse_agg_items("resample", inner_pair.into_inner())?),
+            Rule::param => {
+                let mut param_inner = inner_pair.into_inner();
+                let name = param_inner.next().unwrap().as_str();
+                let value = parse_param_value(param_inner.next().unwrap())?;
+                match name {
+                    "every" => every = Some(value),
+                    _ => {
+                        return Err(DtransformError::ParseError(format!(
+                            "Unknown resample() parameter '{}'. Supported: every", name
+                        )))
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let every = every.ok_or_else(|| DtransformError::ParseError(
+        "resample() needs a bucket width: resample(ts, every='1h', agg(...))".to_string()
+    ))?;
+    if aggregations.is_empty() {
+        return Err(DtransformError::ParseError(
+            "resample() needs aggregates: resample(ts, every='1h', agg(count = count()))".to_string()
+        ));
+    }
+
+    Ok(ResampleOp { time, every, aggregations })
+}
+
+fn parse_upsample_op(pair: pest::iterators::Pair<Rule>) -> Result<UpsampleOp> {
+    let mut inner_pairs = pair.into_inner();
+    let time = parse_column_ref(inner_pairs.next().unwrap())?;
+    let mut every = None;
+    let mut fill_forward = false;
+
+    for param in inner_pairs.next().unwrap().into_inner() {
+        let mut param_inner = param.into_inner();
+        let name = param_inner.next().unwrap().as_str();
+        let value = parse_param_value(param_inner.next().unwrap())?;
+        match name {
+            "every" => every = Some(value),
+            "fill" => {
+                fill_forward = match value.as_str() {
+                    "ffill" | "forward" => true,
+                    "null" => false,
+                    _ => {
+                        return Err(DtransformError::ParseError(format!(
+                            "upsample() fill must be 'ffill' or null, got '{}'", value
+                        )))
+                    }
+                }
+            }
+            _ => {
+                return Err(DtransformError::ParseError(format!(
+                    "Unknown upsample() parameter '{}'. Supported: every, fill", name
+                )))
+            }
+        }
+    }
+
+    let every = every.ok_or_else(|| DtransformError::ParseError(
+        "upsample() needs a step: upsample(ts, every='1d')".to_string()
+    ))?;
+
+    Ok(UpsampleOp { time, every, fill_forward })
+}
+
+fn parse_row_number_op(pair: pest::iterators::Pair<Rule>) -> Result<WithRowNumberOp> {
+    let mut name = "row_id".to_string();
+    let mut start = 1;
+    let mut per = Vec::new();
+
+    for arg in pair.into_inner() {
+        let arg = arg.into_inner().next().unwrap();
+        match arg.as_rule() {
+            Rule::row_number_per => {
+                let value = arg.into_inner().next().unwrap();
+                per = match value.as_rule() {
+                    Rule::column_ref_list => parse_column_ref_list(value)?,
+                    _ => vec![parse_column_ref(value)?],
+                };
+            }
+            _ => {
+                let mut param_inner = arg.into_inner();
+                let param_name = param_inner.next().unwrap().as_str();
+                let value = parse_param_value(param_inner.next().unwrap())?;
+                match param_name {
+                    "name" => name = value,
+                    "start" => {
+                        start = value.parse::<i64>().map_err(|_| DtransformError::ParseError(format!(
+                            "with_row_number() start must be a whole number, got '{}'", value
+                        )))?;
+                    }
+                    _ => {
+                        return Err(DtransformError::ParseError(format!(
+                            "Unknown with_row_number() parameter '{}'. Supported: name, start, per", param_name
+                        )))
+                    }
+                }
+            }
+        }
+    }
+
+    if name.is_empty() {
+        return Err(DtransformError::ParseError("with_row_number() name can't be empty".to_string()));
+    }
+
+    Ok(WithRowNumberOp { name, start, per })
+}
+
+fn parse_split_into_op(pair: pest::iterators::Pair<Rule>) -> Result<SplitIntoOp> {
+    let mut inner_pairs = pair.into_inner();
+    let source = parse_expression(inner_pairs.next().unwrap())?;
+    let delimiter = parse_string(inner_pairs.next().unwrap())?;
+    let mut columns = Vec::new();
+    let mut rest = false;
+
+    for inner_pair in inner_pairs {
+        match inner_pair.as_rule() {
+            Rule::identifier => {
+                let name = inner_pair.as_str().to_string();
+                if columns.contains(&name) {
+                    return Err(DtransformError::ParseError(format!(
+                        "split_into() names column '{}' twice", name
+                    )));
+                }
+                columns.push(name);
+            }
+            Rule::params => {
+                for param in inner_pair.into_inner() {
+                    let mut param_inner = param.into_inner();
+                    let name = param_inner.next().unwrap().as_str();
+                    let value = parse_param_value(param_inner.next().unwrap())?;
+                    match name {
+                        "rest" => rest = parse_bool_param(name, &value)?,
+                        _ => {
+                            return Err(DtransformError::ParseError(format!(
+                                "Unknown split_into() parameter '{}'. Supported: rest", name
+                            )))
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if delimiter.is_empty() {
+        return Err(DtransformError::ParseError("split_into() needs a non-empty separator".to_string()));
+    }
+
+    Ok(SplitIntoOp { source, delimiter, columns, rest })
+}
+
+fn parse_expect_op(pair: pest::iterators::Pair<Rule>) -> Result<ExpectOp> {
+    let mut checks = Vec::new();
+
+    for expectation in pair.into_inner() {
+        let check = expectation.into_inner().next().unwrap();
+        match check.as_rule() {
+            Rule::expect_count => {
+                let mut inner = check.into_inner();
+                let target = match inner.next().unwrap().as_str() {
+                    "rows" => CountTarget::Rows,
+                    _ => CountTarget::Columns,
+                };
+                let op = match inner.next().unwrap().as_str() {
+                    ">=" => BinOp::Gte,
+                    "<=" => BinOp::Lte,
+                    ">" => BinOp::Gt,
+                    "<" => BinOp::Lt,
+                    "!=" => BinOp::Neq,
+                    _ => BinOp::Eq,
+                };
+                let value = parse_number_as_usize(inner.next().unwrap().as_str())?;
+                checks.push(Expectation::Count { target, op, value });
+            }
+            Rule::expect_has => {
+                let list = check.into_inner().next().unwrap();
+                let columns = list.into_inner().map(parse_string).collect::<Result<Vec<_>>>()?;
+                checks.push(Expectation::HasColumns(columns));
+            }
+            Rule::expect_dtype => {
+                let mut inner = check.into_inner();
+                let column = parse_column_ref(inner.next().unwrap())?;
+                let data_type = parse_data_type(inner.next().unwrap())?;
+                checks.push(Expectation::DataType { column, data_type });
+            }
+            _ => return Err(DtransformError::ParseError(format!("Unknown expectation: {}", check.as_str()))),
+        }
+    }
+
+    Ok(ExpectOp { checks })
+}
+
 fn parse_filter_op(pair: pest::iterators::Pair<Rule>) -> Result<FilterOp> {
     let condition = parse_expression(pair.into_inner().next().unwrap())?;
     Ok(FilterOp { condition })
@@ -385,32 +938,73 @@ fn parse_assignment(pair: pest::iterators::Pair<Rule>) -> Result<Assignment> {
 
 fn parse_rename_op(pair: pest::iterators::Pair<Rule>) -> Result<RenameOp> {
     let mut mappings = Vec::new();
+    let mut on_conflict = RenameConflict::Error;
 
     for inner_pair in pair.into_inner() {
-        if inner_pair.as_rule() == Rule::rename_mapping_list {
-            for mapping_pair in inner_pair.into_inner() {
-                let mut mapping_inner = mapping_pair.into_inner();
-                let col_ref = parse_column_ref(mapping_inner.next().unwrap())?;
-                let new_name_pair = mapping_inner.next().unwrap();
-
-                let new_name = match new_name_pair.as_rule() {
-                    Rule::identifier => new_name_pair.as_str().to_string(),
-                    Rule::string => parse_string(new_name_pair)?,
-                    _ => return Err(DtransformError::ParseError("Invalid new name in rename".to_string()))
-                };
+        match inner_pair.as_rule() {
+            Rule::rename_mapping_list => {
+                for mapping_pair in inner_pair.into_inner() {
+                    let mut mapping_inner = mapping_pair.into_inner();
+                    if mapping_inner.peek().map(|p| p.as_rule()) == Some(Rule::regex_rename) {
+                        let (pattern, replacement) = parse_regex_rename(mapping_inner.next().unwrap())?;
+                        mappings.push(RenameMapping::Regex { pattern, replacement });
+                        continue;
+                    }
+
+                    let col_ref = parse_column_ref(mapping_inner.next().unwrap())?;
+                    let new_name_pair = mapping_inner.next().unwrap();
+
+                    let new_name = match new_name_pair.as_rule() {
+                        Rule::identifier => new_name_pair.as_str().to_string(),
+                        Rule::string => parse_string(new_name_pair)?,
+                        _ => return Err(DtransformError::ParseError("Invalid new name in rename".to_string()))
+                    };
 
-                mappings.push((col_ref, new_name));
+                    mappings.push(RenameMapping::Column(col_ref, new_name));
+                }
             }
+            Rule::params => on_conflict = parse_rename_params("rename", inner_pair)?,
+            _ => {}
         }
     }
 
-    Ok(RenameOp { mappings })
+    Ok(RenameOp { mappings, on_conflict })
 }
 
 fn parse_rename_all_op(pair: pest::iterators::Pair<Rule>) -> Result<RenameAllOp> {
-    let strategy_pair = pair.into_inner().next().unwrap();
-    let strategy = parse_rename_strategy(strategy_pair)?;
-    Ok(RenameAllOp { strategy })
+    let mut inner = pair.into_inner();
+    let strategy = parse_rename_strategy(inner.next().unwrap())?;
+    let on_conflict = match inner.next() {
+        Some(params) => parse_rename_params("rename_all", params)?,
+        None => RenameConflict::Error,
+    };
+    Ok(RenameAllOp { strategy, on_conflict })
+}
+
+/// The params of rename() / rename_all(): on_conflict='error' | 'suffix'
+fn parse_rename_params(operation: &str, pair: pest::iterators::Pair<Rule>) -> Result<RenameConflict> {
+    let mut on_conflict = RenameConflict::Error;
+    for param in pair.into_inner() {
+        let mut param_inner = param.into_inner();
+        let name = param_inner.next().unwrap().as_str();
+        let value = parse_param_value(param_inner.next().unwrap())?;
+
+        match (name, value.as_str()) {
+            ("on_conflict", "error") => on_conflict = RenameConflict::Error,
+            ("on_conflict", "suffix") => on_conflict = RenameConflict::Suffix,
+            ("on_conflict", other) => {
+                return Err(DtransformError::ParseError(format!(
+                    "Unknown on_conflict '{}'. Supported: error, suffix", other
+                )))
+            }
+            _ => {
+                return Err(DtransformError::ParseError(format!(
+                    "Unknown {}() parameter '{}'. Supported: on_conflict", operation, name
+                )))
+            }
+        }
+    }
+    Ok(on_conflict)
 }
 
 fn parse_rename_strategy(pair: pest::iterators::Pair<Rule>) -> Result<RenameStrategy> {
@@ -430,31 +1024,172 @@ fn parse_rename_strategy(pair: pest::iterators::Pair<Rule>) -> Result<RenameStra
             let end = parse_number_as_usize(inner_pairs.next().unwrap().as_str())?;
             Ok(RenameStrategy::Sequential { prefix, start, end })
         }
+        Rule::regex_rename => {
+            let (pattern, replacement) = parse_regex_rename(inner)?;
+            Ok(RenameStrategy::Regex { pattern, replacement })
+        }
+        Rule::affix_strategy => {
+            let mut inner_pairs = inner.into_inner();
+            let kind = inner_pairs.next().unwrap().as_str();
+            let text = parse_string(inner_pairs.next().unwrap())?;
+            Ok(match kind {
+                "prefix" => RenameStrategy::Prefix(text),
+                "suffix" => RenameStrategy::Suffix(text),
+                "strip_prefix" => RenameStrategy::StripPrefix(text),
+                _ => RenameStrategy::StripSuffix(text),
+            })
+        }
+        Rule::case_strategy => Ok(match inner.as_str() {
+            "lowercase" => RenameStrategy::Lowercase,
+            "uppercase" => RenameStrategy::Uppercase,
+            "snake_case" => RenameStrategy::SnakeCase,
+            "camelCase" => RenameStrategy::CamelCase,
+            _ => RenameStrategy::Trim,
+        }),
         _ => Err(DtransformError::ParseError("Unknown rename strategy".to_string()))
     }
 }
 
+fn parse_regex_rename(pair: pest::iterators::Pair<Rule>) -> Result<(String, String)> {
+    let mut inner_pairs = pair.into_inner();
+    let regex_pair = inner_pairs.next().unwrap();
+    let pattern = parse_string(regex_pair.into_inner().next().unwrap())?;
+    let replacement = parse_string(inner_pairs.next().unwrap())?;
+    Ok((pattern, replacement))
+}
+
 fn parse_sort_op(pair: pest::iterators::Pair<Rule>) -> Result<SortOp> {
     let mut columns = Vec::new();
+    let mut limit = None;
+    let mut stable = true;
+    let mut multithreaded = true;
 
     for inner_pair in pair.into_inner() {
-        if inner_pair.as_rule() == Rule::sort_column_list {
-            for sort_col_pair in inner_pair.into_inner() {
-                let mut sort_col_inner = sort_col_pair.into_inner();
-                let col_ref = parse_column_ref(sort_col_inner.next().unwrap())?;
-
-                let descending = if let Some(order_pair) = sort_col_inner.next() {
-                    order_pair.as_str() == "desc"
-                } else {
-                    false
-                };
+        match inner_pair.as_rule() {
+            Rule::sort_column_list => {
+                for sort_col_pair in inner_pair.into_inner() {
+                    let mut sort_col_inner = sort_col_pair.into_inner();
+                    let column = parse_column_ref(sort_col_inner.next().unwrap())?;
+
+                    let mut descending = false;
+                    let mut nulls_last = false;
+                    for modifier in sort_col_inner {
+                        match modifier.as_rule() {
+                            Rule::order => descending = modifier.as_str() == "desc",
+                            Rule::nulls_order => {
+                                let position = modifier.into_inner().next().unwrap();
+                                nulls_last = position.as_str() == "last";
+                            }
+                            _ => {}
+                        }
+                    }
+
+                    columns.push(SortColumn { column, descending, nulls_last });
+                }
+            }
+            Rule::params => {
+                for param in inner_pair.into_inner() {
+                    let mut param_inner = param.into_inner();
+                    let name = param_inner.next().unwrap().as_str();
+                    let value = parse_param_value(param_inner.next().unwrap())?;
+
+                    match name {
+                        "limit" => limit = Some(parse_number_as_usize(&value)?),
+                        "stable" => stable = parse_bool_param(name, &value)?,
+                        "multithreaded" => multithreaded = parse_bool_param(name, &value)?,
+                        _ => {
+                            return Err(DtransformError::ParseError(format!(
+                                "Unknown sort() parameter '{}'. Supported: limit, stable, multithreaded", name
+                            )))
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(SortOp { columns, limit, stable, multithreaded })
+}
 
-                columns.push((col_ref, descending));
+fn parse_cast_op(pair: pest::iterators::Pair<Rule>) -> Result<CastOp> {
+    let mut casts = Vec::new();
+
+    for mapping_pair in pair.into_inner() {
+        let mut mapping_inner = mapping_pair.into_inner();
+        let selector = parse_selector(mapping_inner.next().unwrap())?;
+
+        let mut type_inner = mapping_inner.next().unwrap().into_inner();
+        let type_name = type_inner.next().unwrap().as_str();
+        let format = match type_inner.next() {
+            Some(format_pair) => Some(parse_string(format_pair)?),
+            None => None,
+        };
+
+        let cast_type = match (type_name, format) {
+            ("date", format) => CastType::Date(format),
+            ("datetime", format) => CastType::Datetime(format),
+            (name, Some(_)) => {
+                return Err(DtransformError::ParseError(format!(
+                    "Type '{}' does not take a format; only date('...') and datetime('...') do",
+                    name
+                )))
             }
+            ("int", None) => CastType::Int,
+            ("float", None) => CastType::Float,
+            ("bool", None) => CastType::Bool,
+            _ => CastType::String,
+        };
+
+        casts.push((selector, cast_type));
+    }
+
+    Ok(CastOp { casts })
+}
+
+fn parse_concat_op(pair: pest::iterators::Pair<Rule>) -> Result<ConcatOp> {
+    let mut sources = Vec::new();
+    let mut mode = ConcatMode::Strict;
+
+    for inner_pair in pair.into_inner() {
+        match inner_pair.as_rule() {
+            Rule::table_source_list => {
+                for source_pair in inner_pair.into_inner() {
+                    let source_inner = source_pair.into_inner().next().unwrap();
+                    let source = match source_inner.as_rule() {
+                        Rule::read_op => Source::Read(Box::new(parse_read_op(source_inner)?)),
+                        _ => Source::Variable(source_inner.as_str().to_string()),
+                    };
+                    sources.push(source);
+                }
+            }
+            Rule::params => {
+                for param in inner_pair.into_inner() {
+                    let mut param_inner = param.into_inner();
+                    let name = param_inner.next().unwrap().as_str();
+                    let value = parse_param_value(param_inner.next().unwrap())?;
+
+                    match (name, value.as_str()) {
+                        ("mode", "strict") => mode = ConcatMode::Strict,
+                        ("mode", "diagonal") => mode = ConcatMode::Diagonal,
+                        ("mode", other) => {
+                            return Err(DtransformError::ParseError(format!(
+                                "Unknown concat mode '{}'. Supported: strict, diagonal", other
+                            )))
+                        }
+                        _ => {
+                            return Err(DtransformError::ParseError(format!(
+                                "Unknown concat() parameter '{}'. Supported: mode", name
+                            )))
+                        }
+                    }
+                }
+            }
+            _ => {}
         }
     }
 
-    Ok(SortOp { columns })
+    Ok(ConcatOp { sources, mode })
 }
 
 fn parse_take_op(pair: pest::iterators::Pair<Rule>) -> Result<TakeOp> {
@@ -467,6 +1202,55 @@ fn parse_skip_op(pair: pest::iterators::Pair<Rule>) -> Result<SkipOp> {
     Ok(SkipOp { n })
 }
 
+fn parse_sample_op(pair: pest::iterators::Pair<Rule>) -> Result<SampleOp> {
+    let mut size = None;
+    let mut seed = None;
+    let mut with_replacement = false;
+
+    // Parameters come either after the row count (inside `params`) or on their own
+    let mut params = Vec::new();
+    for inner_pair in pair.into_inner() {
+        match inner_pair.as_rule() {
+            Rule::number => size = Some(SampleSize::Rows(parse_number_as_usize(inner_pair.as_str())?)),
+            Rule::params => params.extend(inner_pair.into_inner()),
+            Rule::param => params.push(inner_pair),
+            _ => {}
+        }
+    }
+
+    for param in params {
+        let mut param_inner = param.into_inner();
+        let name = param_inner.next().unwrap().as_str();
+        let value = parse_param_value(param_inner.next().unwrap())?;
+
+        match name {
+            "n" => size = Some(SampleSize::Rows(parse_number_as_usize(&value)?)),
+            "frac" | "fraction" => {
+                let frac = parse_number(&value)?;
+                if !(0.0..=1.0).contains(&frac) {
+                    return Err(DtransformError::ParseError(format!(
+                        "sample() frac must be between 0 and 1, got {}", value
+                    )));
+                }
+                size = Some(SampleSize::Fraction(frac));
+            }
+            "seed" => seed = Some(parse_number_as_usize(&value)? as u64),
+            "with_replacement" | "replace" => with_replacement = parse_bool_param(name, &value)?,
+            _ => {
+                return Err(DtransformError::ParseError(format!(
+                    "Unknown sample() parameter '{}'. Supported: n, frac, seed, with_replacement", name
+                )))
+            }
+        }
+    }
+
+    let size = size.ok_or_else(|| DtransformError::ParseError(
+        "sample() needs a row count or a fraction: sample(100) or sample(frac=0.1)".to_string()
+    ))?;
+
+    Ok(SampleOp { size, seed, with_replacement })
+}
+
 fn parse_slice_op(pair: pest::iterators::Pair<Rule>) -> Result<SliceOp> {
     let mut inner_pairs = pair.into_inner();
     let start = parse_number_as_usize(inner_pairs.next().unwrap().as_str())?;
@@ -474,8 +1258,74 @@ fn parse_slice_op(pair: pest::iterators::Pair<Rule>) -> Result<SliceOp> {
     Ok(SliceOp { start, end })
 }
 
+fn parse_relocate_op(pair: pest::iterators::Pair<Rule>) -> Result<RelocateOp> {
+    let mut columns = Vec::new();
+    let mut anchor = RelocateAnchor::First;
+
+    for inner_pair in pair.into_inner() {
+        match inner_pair.as_rule() {
+            Rule::selector_list => {
+                for selector_item_pair in inner_pair.into_inner() {
+                    let (selector, _alias) = parse_selector_item(selector_item_pair)?;
+                    columns.push(selector);
+                }
+            }
+            Rule::relocate_anchor => {
+                let mut anchor_inner = inner_pair.into_inner();
+                let after = anchor_inner.next().unwrap().as_str() == "after";
+                let column = parse_column_ref(anchor_inner.next().unwrap())?;
+                // before=first and after=last are the ends of the table, not columns
+                anchor = match (after, column) {
+                    (false, ColumnRef::Name(name)) if name == "first" => RelocateAnchor::First,
+                    (true, ColumnRef::Name(name)) if name == "last" => RelocateAnchor::Last,
+                    (false, column) => RelocateAnchor::Before(column),
+                    (true, column) => RelocateAnchor::After(column),
+                };
+            }
+            _ => {}
+        }
+    }
+
+    Ok(RelocateOp { columns, anchor })
+}
+
 fn parse_drop_op(pair: pest::iterators::Pair<Rule>) -> Result<DropOp> {
     let mut columns = Vec::new();
+    let mut if_exists = false;
+
+    for inner_pair in pair.into_inner() {
+        match inner_pair.as_rule() {
+            Rule::selector_list => {
+                for selector_item_pair in inner_pair.into_inner() {
+                    let (selector, _alias) = parse_selector_item(selector_item_pair)?;
+                    columns.push(selector);
+                }
+            }
+            Rule::params => {
+                for param in inner_pair.into_inner() {
+                    let mut param_inner = param.into_inner();
+                    let name = param_inner.next().unwrap().as_str();
+                    let value = parse_param_value(param_inner.next().unwrap())?;
+
+                    match name {
+                        "if_exists" => if_exists = parse_bool_param(name, &value)?,
+                        _ => {
+                            return Err(DtransformError::ParseError(format!(
+                                "Unknown drop() parameter '{}'. Supported: if_exists", name
+                            )))
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(DropOp { columns, if_exists })
+}
+
+fn parse_drop_nulls_op(pair: pest::iterators::Pair<Rule>) -> Result<DropNullsOp> {
+    let mut columns = Vec::new();
 
     for inner_pair in pair.into_inner() {
         if inner_pair.as_rule() == Rule::selector_list {
@@ -486,10 +1336,28 @@ fn parse_drop_op(pair: pest::iterators::Pair<Rule>) -> Result<DropOp> {
         }
     }
 
-    Ok(DropOp { columns })
+    Ok(DropNullsOp { columns })
+}
+
+fn parse_fill_nulls_op(pair: pest::iterators::Pair<Rule>) -> Result<FillNullsOp> {
+    let mut fills = Vec::new();
+
+    for item_pair in pair.into_inner() {
+        let mut item_inner = item_pair.into_inner();
+        let selector = parse_selector(item_inner.next().unwrap())?;
+        let value = parse_expression(item_inner.next().unwrap())?;
+        fills.push((selector, value));
+    }
+
+    Ok(FillNullsOp { fills })
 }
 
 fn parse_distinct_op(pair: pest::iterators::Pair<Rule>) -> Result<DistinctOp> {
+    Ok(DistinctOp { columns: parse_optional_selectors(pair)? })
+}
+
+/// Selectors of an operation whose selector list may be left empty: distinct(), describe()
+fn parse_optional_selectors(pair: pest::iterators::Pair<Rule>) -> Result<Option<Vec<ColumnSelector>>> {
     let mut columns = None;
 
     for inner_pair in pair.into_inner() {
@@ -503,7 +1371,7 @@ fn parse_distinct_op(pair: pest::iterators::Pair<Rule>) -> Result<DistinctOp> {
         }
     }
 
-    Ok(DistinctOp { columns })
+    Ok(columns)
 }
 
 fn parse_column_ref(pair: pest::iterators::Pair<Rule>) -> Result<ColumnRef> {
@@ -529,17 +1397,15 @@ fn parse_column_ref(pair: pest::iterators::Pair<Rule>) -> Result<ColumnRef> {
 
 fn parse_expression(pair: pest::iterators::Pair<Rule>) -> Result<Expression> {
     match pair.as_rule() {
-        Rule::expression | Rule::logical_or | Rule::logical_and | Rule::comparison | Rule::term | Rule::factor => {
+        Rule::expression | Rule::logical_or | Rule::logical_and | Rule::comparison | Rule::term | Rule::factor | Rule::power => {
             let mut pairs = pair.into_inner();
             let first = pairs.next().unwrap();
             let mut left = parse_expression(first)?;
 
             while let Some(op_pair) = pairs.next() {
                 let op = match op_pair.as_rule() {
-                    Rule::comparison_op | Rule::add_op | Rule::sub_op | Rule::mul_op | Rule::div_op => {
-                        parse_bin_op(op_pair.as_str())?
-                    }
-                    _ if op_pair.as_str() == "and" || op_pair.as_str() == "or" => {
+                    Rule::comparison_op | Rule::add_op | Rule::sub_op | Rule::mul_op | Rule::div_op
+                    | Rule::mod_op | Rule::pow_op | Rule::and_op | Rule::or_op => {
                         parse_bin_op(op_pair.as_str())?
                     }
                     _ => {
@@ -565,22 +1431,65 @@ fn parse_expression(pair: pest::iterators::Pair<Rule>) -> Result<Expression> {
 
             Ok(left)
         }
+        Rule::unary => {
+            let mut inner_pairs = pair.into_inner();
+            let first = inner_pairs.next().unwrap();
+            if first.as_rule() == Rule::neg_op {
+                match parse_expression(inner_pairs.next().unwrap())? {
+                    // Fold -5 into a literal so functions taking numeric arguments still see one
+                    Expression::Literal(Literal::Number(n)) => Ok(Expression::Literal(Literal::Number(-n))),
+                    operand => Ok(Expression::Negate(Box::new(operand))),
+                }
+            } else {
+                parse_expression(first)
+            }
+        }
         Rule::primary => {
             let inner = pair.into_inner().next().unwrap();
             parse_expression(inner)
         }
-        Rule::invalid_split => {
-            return Err(DtransformError::ParseError(
-                "split() must be followed by [index]. Example: split(text, ':')[0]".to_string()
-            ));
-        }
         Rule::split_call => parse_split_call(pair),
         Rule::lookup_call => parse_lookup_call(pair),
+        Rule::fuzzy_lookup_call => parse_fuzzy_lookup_call(pair),
         Rule::replace_call => parse_replace_call(pair),
         Rule::regex_literal => {
             let pattern = parse_string(pair.into_inner().next().unwrap())?;
             Ok(Expression::Regex(pattern))
         }
+        Rule::date_literal => parse_date_literal(pair),
+        Rule::column_quantifier => {
+            let mut inner_pairs = pair.into_inner();
+            let quantifier = match inner_pairs.next().unwrap().as_str() {
+                "all" => Quantifier::All,
+                _ => Quantifier::Any,
+            };
+            let columns = inner_pairs
+                .next()
+                .unwrap()
+                .into_inner()
+                .map(parse_selector)
+                .collect::<Result<Vec<_>>>()?;
+            Ok(Expression::ColumnQuantifier { quantifier, columns })
+        }
+        Rule::function_call => parse_function_call(pair),
+        Rule::variable_column => {
+            let mut inner_pairs = pair.into_inner();
+            let variable = inner_pairs.next().unwrap().as_str().to_string();
+            let column_pair = inner_pairs.next().unwrap();
+            let column = match column_pair.as_rule() {
+                Rule::positional_column => {
+                    let position = parse_number_as_usize(&column_pair.as_str()[1..])?;
+                    if position == 0 {
+                        return Err(DtransformError::ParseError(
+                            "Positional columns start at $1, not $0".to_string()
+                        ));
+                    }
+                    ColumnRef::Position(position)
+                }
+                _ => ColumnRef::Name(column_pair.as_str().to_string()),
+            };
+            Ok(Expression::VariableColumn { variable, column })
+        }
         Rule::method_call => parse_method_call(pair),
         Rule::positional_column => {
             // $1, $2, etc. - AWK-style (1-based)
@@ -648,9 +1557,16 @@ fn parse_split_call(pair: pest::iterators::Pair<Rule>) -> Result<Expression> {
     // Parse delimiter expression
     let delimiter_expr = parse_expression(inner_pairs.next().unwrap())?;
 
-    // Parse index (0-based)
-    let index_pair = inner_pairs.next().unwrap();
-    let index = parse_number_as_usize(index_pair.as_str())?;
+    // Parse optional index (0-based, negative counts from the end)
+    let index = match inner_pairs.next() {
+        Some(index_pair) => Some(index_pair.as_str().parse::<i64>().map_err(|_| {
+            DtransformError::ParseError(format!(
+                "Invalid split index '{}': expected a whole number such as [0] or [-1]",
+                index_pair.as_str()
+            ))
+        })?),
+        None => None,
+    };
 
     Ok(Expression::Split {
         string: Box::new(string_expr),
@@ -665,23 +1581,71 @@ fn parse_lookup_call(pair: pest::iterators::Pair<Rule>) -> Result<Expression> {
     // Parse table name (identifier)
     let table = inner_pairs.next().unwrap().as_str().to_string();
 
-    // Parse key expression
-    let key_expr = parse_expression(inner_pairs.next().unwrap())?;
-
-    // Parse 'on' field (string or column_ref)
-    let on = parse_lookup_field(inner_pairs.next().unwrap())?;
+    // Parse key expression(s)
+    let key = inner_pairs.next().unwrap()
+        .into_inner()
+        .map(parse_expression)
+        .collect::<Result<Vec<_>>>()?;
+
+    // Parse 'on' field(s) (string or column_ref)
+    let on = inner_pairs.next().unwrap()
+        .into_inner()
+        .map(parse_lookup_field)
+        .collect::<Result<Vec<_>>>()?;
+
+    if key.len() != on.len() {
+        return Err(DtransformError::ParseError(format!(
+            "lookup() has {} key expression(s) but {} on= field(s); they must match",
+            key.len(), on.len()
+        )));
+    }
 
     // Parse 'return' field (string or column_ref)
     let return_field = parse_lookup_field(inner_pairs.next().unwrap())?;
 
+    // Optional default= and strict=
+    let mut default = None;
+    let mut strict = false;
+    for option in inner_pairs {
+        let option = option.into_inner().next().unwrap();
+        match option.as_rule() {
+            Rule::lookup_default => {
+                default = Some(Box::new(parse_expression(option.into_inner().next().unwrap())?));
+            }
+            Rule::lookup_strict => strict = option.into_inner().next().unwrap().as_str() == "true",
+            _ => {}
+        }
+    }
+    if default.is_some() && strict {
+        return Err(DtransformError::ParseError(
+            "lookup() takes default= or strict=true, not both".to_string()
+        ));
+    }
+
     Ok(Expression::Lookup {
         table,
-        key: Box::new(key_expr),
+        key,
         on,
         return_field,
+        default,
+        strict,
     })
 }
 
+fn parse_fuzzy_lookup_call(pair: pest::iterators::Pair<Rule>) -> Result<Expression> {
+    let mut inner_pairs = pair.into_inner();
+    let table = inner_pairs.next().unwrap().as_str().to_string();
+    let key = Box::new(parse_expression(inner_pairs.next().unwrap())?);
+    let on = parse_lookup_field(inner_pairs.next().unwrap())?;
+    let return_field = parse_lookup_field(inner_pairs.next().unwrap())?;
+    let max_dist = match inner_pairs.next() {
+        Some(number) => parse_number_as_usize(number.as_str())?,
+        None => 2,
+    };
+
+    Ok(Expression::FuzzyLookup { table, key, on, return_field, max_dist })
+}
+
 fn parse_replace_call(pair: pest::iterators::Pair<Rule>) -> Result<Expression> {
     let mut inner_pairs = pair.into_inner();
 
@@ -694,10 +1658,41 @@ fn parse_replace_call(pair: pest::iterators::Pair<Rule>) -> Result<Expression> {
     // Parse new expression (replacement text)
     let new_expr = parse_expression(inner_pairs.next().unwrap())?;
 
+    // Optional parameters: n=1, case=false
+    let mut count = None;
+    let mut case_sensitive = true;
+    if let Some(params_pair) = inner_pairs.next() {
+        for param in params_pair.into_inner() {
+            let mut param_inner = param.into_inner();
+            let name = param_inner.next().unwrap().as_str();
+            let value = parse_param_value(param_inner.next().unwrap())?;
+
+            match name {
+                "n" => {
+                    let n = parse_number_as_usize(&value)?;
+                    if n == 0 {
+                        return Err(DtransformError::ParseError(
+                            "replace() n= must be at least 1".to_string()
+                        ));
+                    }
+                    count = Some(n);
+                }
+                "case" => case_sensitive = parse_bool_param(name, &value)?,
+                _ => {
+                    return Err(DtransformError::ParseError(format!(
+                        "Unknown replace() parameter '{}'. Supported: n, case", name
+                    )))
+                }
+            }
+        }
+    }
+
     Ok(Expression::Replace {
         text: Box::new(text_expr),
         old: Box::new(old_expr),
         new: Box::new(new_expr),
+        count,
+        case_sensitive,
     })
 }
 
@@ -747,6 +1742,78 @@ fn parse_lookup_field(pair: pest::iterators::Pair<Rule>) -> Result<crate::parser
     }
 }
 
+fn parse_function_call(pair: pest::iterators::Pair<Rule>) -> Result<Expression> {
+    let mut inner_pairs = pair.into_inner();
+    let name = inner_pairs.next().unwrap().as_str().to_string();
+
+    let mut args = Vec::new();
+    let mut named = Vec::new();
+    if let Some(args_pair) = inner_pairs.next() {
+        for arg_pair in args_pair.into_inner() {
+            if arg_pair.as_rule() == Rule::named_arg {
+                let mut parts = arg_pair.into_inner();
+                let key = parts.next().unwrap().as_str().to_string();
+                named.push((key, parse_expression(parts.next().unwrap())?));
+            } else if named.is_empty() {
+                args.push(parse_expression(arg_pair)?);
+            } else {
+                return Err(DtransformError::ParseError(format!(
+                    "{}(): positional arguments must come before named ones",
+                    name
+                )));
+            }
+        }
+    }
+    if !named.is_empty() {
+        args = place_named_args(&name, args, named)?;
+    }
+
+    Ok(Expression::FunctionCall { name, args })
+}
+
+/// Arguments of the functions that also take them by name, in positional order
+fn named_arg_positions(function: &str) -> Option<&'static [&'static str]> {
+    match function {
+        "rolling_mean" | "rolling_sum" | "rolling_min" | "rolling_max" => Some(&["x", "window", "by"]),
+        _ => None,
+    }
+}
+
+/// Put named arguments in their positions: rolling_mean(x, by=ts, window='7d')
+/// is rolling_mean(x, '7d', ts)
+fn place_named_args(function: &str, args: Vec<Expression>, named: Vec<(String, Expression)>) -> Result<Vec<Expression>> {
+    let positions = named_arg_positions(function).ok_or_else(|| {
+        DtransformError::ParseError(format!("{}() takes no named arguments", function))
+    })?;
+
+    let mut slots: Vec<Option<Expression>> = args.into_iter().map(Some).collect();
+    for (key, value) in named {
+        let index = positions.iter().position(|p| *p == key).ok_or_else(|| {
+            DtransformError::ParseError(format!(
+                "{}() has no argument '{}'; it takes {}",
+                function, key, positions.join(", ")
+            ))
+        })?;
+        if slots.len() <= index {
+            slots.resize(index + 1, None);
+        }
+        if slots[index].is_some() {
+            return Err(DtransformError::ParseError(format!("{}() got '{}' twice", function, key)));
+        }
+        slots[index] = Some(value);
+    }
+
+    slots
+        .into_iter()
+        .enumerate()
+        .map(|(index, slot)| {
+            slot.ok_or_else(|| {
+                DtransformError::ParseError(format!("{}() is missing its '{}' argument", function, positions[index]))
+            })
+        })
+        .collect()
+}
+
 fn parse_method_call(pair: pest::iterators::Pair<Rule>) -> Result<Expression> {
     let mut inner_pairs = pair.into_inner();
     let object_pair = inner_pairs.next().unwrap();
@@ -774,6 +1841,17 @@ fn parse_method_call(pair: pest::iterators::Pair<Rule>) -> Result<Expression> {
                 }
             }
 
+            // var.select(col) picks a single column of a stored variable
+            if let (Expression::Column(ColumnRef::Name(variable)), "select", [Expression::Column(column)]) =
+                (&object, method.as_str(), args.as_slice())
+            {
+                object = Expression::VariableColumn {
+                    variable: variable.clone(),
+                    column: column.clone(),
+                };
+                continue;
+            }
+
             object = Expression::MethodCall {
                 object: Box::new(object),
                 method,
@@ -810,16 +1888,47 @@ fn parse_literal(pair: pest::iterators::Pair<Rule>) -> Result<Literal> {
     }
 }
 
+/// d'2024-01-31' is to_date('2024-01-31'); with a time it is to_datetime(...).
+/// The text is checked here so a typo is a syntax error rather than a null.
+fn parse_date_literal(pair: pest::iterators::Pair<Rule>) -> Result<Expression> {
+    let text = parse_string(pair.into_inner().next().unwrap())?;
+    let (name, format) = if chrono::NaiveDate::parse_from_str(&text, "%Y-%m-%d").is_ok() {
+        ("to_date", "%Y-%m-%d")
+    } else if let Some(format) = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M"]
+        .into_iter()
+        .find(|format| chrono::NaiveDateTime::parse_from_str(&text, format).is_ok())
+    {
+        ("to_datetime", format)
+    } else {
+        return Err(DtransformError::ParseError(format!(
+            "Invalid date literal d'{}': expected d'YYYY-MM-DD' or d'YYYY-MM-DD HH:MM:SS'",
+            text
+        )));
+    };
+    Ok(Expression::FunctionCall {
+        name: name.to_string(),
+        args: vec![
+            Expression::Literal(Literal::String(text)),
+            Expression::Literal(Literal::String(format.to_string())),
+        ],
+    })
+}
+
 fn parse_literal_expression(pair: pest::iterators::Pair<Rule>) -> Result<Expression> {
     parse_literal(pair).map(Expression::Literal)
 }
 
 fn parse_bin_op(op_str: &str) -> Result<BinOp> {
-    match op_str {
+    // Normalize keyword operators spanning whitespace ("is   not" → "is not")
+    let normalized = op_str.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    match normalized.as_str() {
         "+" => Ok(BinOp::Add),
         "-" => Ok(BinOp::Sub),
         "*" => Ok(BinOp::Mul),
         "/" => Ok(BinOp::Div),
+        "%" => Ok(BinOp::Mod),
+        "**" | "^" => Ok(BinOp::Pow),
         ">" => Ok(BinOp::Gt),
         "<" => Ok(BinOp::Lt),
         ">=" => Ok(BinOp::Gte),
@@ -829,6 +1938,11 @@ fn parse_bin_op(op_str: &str) -> Result<BinOp> {
         "and" => Ok(BinOp::And),
         "or" => Ok(BinOp::Or),
         "in" => Ok(BinOp::In),
+        "not in" => Ok(BinOp::NotIn),
+        "is" => Ok(BinOp::Is),
+        "is not" => Ok(BinOp::IsNot),
+        "~" => Ok(BinOp::Matches),
+        "!~" => Ok(BinOp::NotMatches),
         _ => Err(DtransformError::ParseError(format!("Unknown operator: {}", op_str)))
     }
 }
@@ -837,18 +1951,94 @@ fn parse_string(pair: pest::iterators::Pair<Rule>) -> Result<String> {
     let inner = pair.into_inner().next().unwrap();
     let s = inner.as_str();
 
-    // Unescape common escape sequences
-    let unescaped = s
-        .replace("\\n", "\n")
-        .replace("\\r", "\r")
-        .replace("\\t", "\t")
-        .replace("\\\"", "\"")
-        .replace("\\'", "'")
-        .replace("\\\\", "\\");
+    // Unescape common escape sequences in one pass, so an escaped backslash
+    // never starts another escape; unknown ones like `\d` are kept for regexes
+    let mut unescaped = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some('t') => unescaped.push('\t'),
+            Some(c @ ('"' | '\'' | '\\')) => unescaped.push(c),
+            Some(c) => {
+                unescaped.push('\\');
+                unescaped.push(c);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
 
     Ok(unescaped)
 }
 
+fn parse_bool_param(name: &str, value: &str) -> Result<bool> {
+    match value {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(DtransformError::ParseError(format!(
+            "Parameter '{}' must be true or false, got '{}'", name, value
+        ))),
+    }
+}
+
+/// `compression=` of read() and write(), as `gzip`, `zstd`, `bzip2` or `none`
+fn parse_compression(value: &str) -> Result<String> {
+    match value.to_lowercase().as_str() {
+        "gzip" | "gz" => Ok("gzip".to_string()),
+        "zstd" | "zst" => Ok("zstd".to_string()),
+        "bzip2" | "bz2" => Ok("bzip2".to_string()),
+        "none" | "" => Ok("none".to_string()),
+        other => Err(DtransformError::ParseError(format!(
+            "Unsupported compression '{}'. Supported: gzip, zstd, bzip2, none", other
+        ))),
+    }
+}
+
+/// Encodings read() can decode; anything but UTF-8 is transcoded in memory
+pub const SUPPORTED_ENCODINGS: &[&str] = &[
+    "utf8", "utf-8", "utf8-lossy", "latin1", "iso-8859-1", "windows-1252", "cp1252",
+];
+
+/// A `['a', 'b']` parameter; a single string counts as a one-item list
+fn parse_param_list(pair: pest::iterators::Pair<Rule>) -> Result<Vec<String>> {
+    let inner = pair.into_inner().next().unwrap();
+    match inner.as_rule() {
+        Rule::param_list => inner.into_inner().map(parse_string).collect(),
+        Rule::string => Ok(vec![parse_string(inner)?]),
+        _ => Err(DtransformError::ParseError(format!(
+            "Expected a string or a list of strings like ['NA', '-'], got {}", inner.as_str()
+        ))),
+    }
+}
+
+/// A `{column: 'value', ...}` parameter
+fn parse_param_map(name: &str, pair: pest::iterators::Pair<Rule>) -> Result<Vec<(ColumnRef, String)>> {
+    let inner = pair.into_inner().next().unwrap();
+    if inner.as_rule() != Rule::param_map {
+        return Err(DtransformError::ParseError(format!(
+            "Parameter '{}' expects {{column: 'value', ...}}", name
+        )));
+    }
+
+    let mut entries = Vec::new();
+    for entry in inner.into_inner() {
+        let mut entry_inner = entry.into_inner();
+        let key_pair = entry_inner.next().unwrap();
+        let key = match key_pair.as_rule() {
+            Rule::string => ColumnRef::Name(parse_string(key_pair)?),
+            _ => parse_column_ref(key_pair)?,
+        };
+        let value = parse_string(entry_inner.next().unwrap())?;
+        entries.push((key, value));
+    }
+    Ok(entries)
+}
+
 fn parse_param_value(pair: pest::iterators::Pair<Rule>) -> Result<String> {
     match pair.as_rule() {
         Rule::param_value => {
@@ -860,6 +2050,12 @@ fn parse_param_value(pair: pest::iterators::Pair<Rule>) -> Result<String> {
         Rule::number => Ok(pair.as_str().to_string()),
         Rule::boolean => Ok(pair.as_str().to_string()),
         Rule::identifier => Ok(pair.as_str().to_string()),
+        Rule::param_map => Err(DtransformError::ParseError(
+            "A {key: value} map is only allowed for write(..., formats=...) and read(..., headers=...)".to_string()
+        )),
+        Rule::param_list => Err(DtransformError::ParseError(
+            "A [...] list is only allowed for read(..., null_values=...)".to_string()
+        )),
         _ => Err(DtransformError::ParseError(format!("Invalid parameter value: {:?}", pair.as_rule())))
     }
 }
@@ -0,0 +1,296 @@
+use colored::*;
+use rustyline::completion::{Completer, FilenameCompleter, Pair};
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Helper};
+use std::borrow::Cow;
+use std::cell::Cell;
+
+use crate::parser::catalog::{self, FUNCTIONS, KEYWORDS, OPERATIONS};
+
+const COMMANDS: &[&str] = &[
+    ".help", ".exit", ".quit", ".schema", ".undo", ".redo", ".history", ".mark", ".goto", ".explain", ".save", ".load", ".open", ".vars", ".top", ".set", ".clear",
+];
+
+/// Line editor helper for the REPL: completes dot-commands, operation names,
+/// stored variables, column names and functions, and file paths inside
+/// string literals, and colors the line as it's typed. The REPL refreshes
+/// `columns` and `variables` after every input.
+pub struct ReplHelper {
+    pub columns: Vec<String>,
+    pub variables: Vec<String>,
+    filenames: FilenameCompleter,
+    // Off for the final render when Enter is pressed, so no bracket stays marked
+    match_brackets: Cell<bool>,
+}
+
+impl ReplHelper {
+    pub fn new() -> Self {
+        Self {
+            columns: Vec::new(),
+            variables: Vec::new(),
+            filenames: FilenameCompleter::new(),
+            match_brackets: Cell::new(true),
+        }
+    }
+}
+
+impl Default for ReplHelper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Whether `pos` is inside a string literal, and how many parentheses are open there
+fn scan_context(line: &str, pos: usize) -> (bool, usize) {
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    let mut depth: usize = 0;
+
+    for c in line[..pos].chars() {
+        match quote {
+            Some(q) => {
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' {
+                    escaped = true;
+                } else if c == q {
+                    quote = None;
+                }
+            }
+            None => match c {
+                '\'' | '"' => quote = Some(c),
+                '(' | '[' => depth += 1,
+                ')' | ']' => depth = depth.saturating_sub(1),
+                _ => {}
+            },
+        }
+    }
+
+    (quote.is_some(), depth)
+}
+
+/// Byte offsets of the bracket at (or just before) the cursor and its partner
+fn matching_brackets(line: &str, pos: usize) -> Option<(usize, usize)> {
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    let mut brackets: Vec<(usize, char)> = Vec::new();
+
+    for (i, c) in line.char_indices() {
+        match quote {
+            Some(q) => {
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' {
+                    escaped = true;
+                } else if c == q {
+                    quote = None;
+                }
+            }
+            None => match c {
+                '\'' | '"' => quote = Some(c),
+                '(' | ')' | '[' | ']' => brackets.push((i, c)),
+                _ => {}
+            },
+        }
+    }
+
+    let at_cursor = |offset: usize| brackets.iter().position(|(i, _)| *i == offset);
+    let index = at_cursor(pos).or_else(|| pos.checked_sub(1).and_then(at_cursor))?;
+    let (offset, c) = brackets[index];
+
+    let (open, close, forward) = match c {
+        '(' => ('(', ')', true),
+        '[' => ('[', ']', true),
+        ')' => ('(', ')', false),
+        _ => ('[', ']', false),
+    };
+
+    let mut depth = 0;
+    let candidates: Box<dyn Iterator<Item = &(usize, char)>> = if forward {
+        Box::new(brackets[index..].iter())
+    } else {
+        Box::new(brackets[..=index].iter().rev())
+    };
+    for &(other, ch) in candidates {
+        if ch == open {
+            depth += if forward { 1 } else { -1 };
+        } else if ch == close {
+            depth += if forward { -1 } else { 1 };
+        }
+        if depth == 0 {
+            return Some((offset, other));
+        }
+    }
+    None
+}
+
+fn highlight_line(line: &str, brackets: Option<(usize, usize)>) -> String {
+    let chars: Vec<(usize, char)> = line.char_indices().collect();
+    let end_of = |j: usize| chars.get(j).map(|(offset, _)| *offset).unwrap_or(line.len());
+    let mut out = String::with_capacity(line.len() * 2);
+    let mut i = 0;
+
+    while i < chars.len() {
+        let (start, c) = chars[i];
+
+        // String literal, up to the closing quote (or the end of an unfinished one)
+        if c == '\'' || c == '"' {
+            let mut j = i + 1;
+            let mut escaped = false;
+            while j < chars.len() {
+                let ch = chars[j].1;
+                j += 1;
+                if escaped {
+                    escaped = false;
+                } else if ch == '\\' {
+                    escaped = true;
+                } else if ch == c {
+                    break;
+                }
+            }
+            out.push_str(&line[start..end_of(j)].green().to_string());
+            i = j;
+            continue;
+        }
+
+        if c == '#' || c == '/' && chars.get(i + 1).is_some_and(|(_, d)| *d == '/') {
+            out.push_str(&line[start..].bright_black().to_string());
+            break;
+        }
+
+        // $N column reference
+        if c == '$' && chars.get(i + 1).is_some_and(|(_, d)| d.is_ascii_digit()) {
+            let mut j = i + 1;
+            while j < chars.len() && chars[j].1.is_ascii_digit() {
+                j += 1;
+            }
+            out.push_str(&line[start..end_of(j)].cyan().to_string());
+            i = j;
+            continue;
+        }
+
+        // Number, with an optional fraction and k/m/b suffix
+        if c.is_ascii_digit() {
+            let mut j = i;
+            while j < chars.len() && (chars[j].1.is_ascii_digit() || chars[j].1 == '.' && chars.get(j + 1).is_some_and(|(_, d)| d.is_ascii_digit())) {
+                j += 1;
+            }
+            if j < chars.len() && matches!(chars[j].1, 'k' | 'K' | 'm' | 'M' | 'b' | 'B') {
+                j += 1;
+            }
+            out.push_str(&line[start..end_of(j)].yellow().to_string());
+            i = j;
+            continue;
+        }
+
+        if c.is_alphabetic() || c == '_' {
+            let mut j = i;
+            while j < chars.len() && (chars[j].1.is_alphanumeric() || chars[j].1 == '_') {
+                j += 1;
+            }
+            let word = &line[start..end_of(j)];
+            let is_call = line[end_of(j)..].trim_start().starts_with('(');
+
+            // Keywords first: `and (a or b)` is not a call
+            let colored = if KEYWORDS.contains(&word) {
+                word.magenta().to_string()
+            } else if is_call && catalog::names(OPERATIONS).any(|op| op == word) {
+                word.blue().bold().to_string()
+            } else if is_call {
+                word.blue().to_string()
+            } else {
+                word.to_string()
+            };
+            out.push_str(&colored);
+            i = j;
+            continue;
+        }
+
+        match brackets {
+            Some((a, b)) if start == a || start == b => {
+                out.push_str(&c.to_string().bold().reversed().to_string());
+            }
+            _ => out.push(c),
+        }
+        i += 1;
+    }
+
+    out
+}
+
+impl Completer for ReplHelper {
+    type Candidate = Pair;
+
+    fn complete(&self, line: &str, pos: usize, ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let (in_string, depth) = scan_context(line, pos);
+        if in_string {
+            return self.filenames.complete(line, pos, ctx);
+        }
+
+        let start = line[..pos]
+            .char_indices()
+            .rev()
+            .take_while(|(_, c)| c.is_alphanumeric() || *c == '_')
+            .last()
+            .map(|(i, _)| i)
+            .unwrap_or(pos);
+        let word = &line[start..pos];
+
+        // Dot-commands only at the very start of the line
+        if line[..start].trim_start() == "." {
+            let typed = format!(".{}", word);
+            let candidates = COMMANDS
+                .iter()
+                .filter(|cmd| cmd.starts_with(&typed))
+                .map(|cmd| Pair { display: cmd.to_string(), replacement: cmd.to_string() })
+                .collect();
+            return Ok((start - 1, candidates));
+        }
+
+        // Outside parentheses we're naming an operation or a variable source,
+        // inside them a column or function (or a variable, for lookup() and `in`)
+        let mut names: Vec<&str> = if depth == 0 {
+            catalog::names(OPERATIONS).collect()
+        } else {
+            let mut names: Vec<&str> = self.columns.iter().map(|s| s.as_str()).collect();
+            names.extend(catalog::names(FUNCTIONS));
+            names
+        };
+        names.extend(self.variables.iter().map(|s| s.as_str()));
+
+        let mut candidates: Vec<Pair> = Vec::new();
+        for name in names {
+            if name.starts_with(word) && !candidates.iter().any(|c| c.replacement == name) {
+                candidates.push(Pair { display: name.to_string(), replacement: name.to_string() });
+            }
+        }
+
+        Ok((start, candidates))
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {
+    fn highlight<'l>(&self, line: &'l str, pos: usize) -> Cow<'l, str> {
+        let brackets = if self.match_brackets.get() {
+            matching_brackets(line, pos)
+        } else {
+            None
+        };
+        Cow::Owned(highlight_line(line, brackets))
+    }
+
+    fn highlight_char(&self, _line: &str, _pos: usize, forced: bool) -> bool {
+        self.match_brackets.set(!forced);
+        true
+    }
+}
+
+impl Validator for ReplHelper {}
+
+impl Helper for ReplHelper {}
@@ -0,0 +1,103 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use polars::prelude::*;
+
+/// Rows looked at per column
+const SAMPLE_ROWS: usize = 1000;
+
+/// How a suggestion refers to a column: its name, or `$N` when the name can't be typed as is
+fn column_label(name: &str, position: usize) -> String {
+    let identifier = name.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if identifier {
+        name.to_string()
+    } else {
+        format!("${}", position + 1)
+    }
+}
+
+/// The cast() type every value of a text column converts to, if there is one.
+/// Numbers with leading zeros (zip codes, ids) are left as text.
+fn text_type(values: &[&str]) -> Option<&'static str> {
+    if values.is_empty() {
+        return None;
+    }
+    let all = |check: fn(&str) -> bool| values.iter().all(|value| check(value.trim()));
+    let leading_zero = |v: &str| v.len() > 1 && v.starts_with('0') && !v.starts_with("0.");
+
+    if all(|v| v.parse::<f64>().is_ok()) {
+        if values.iter().any(|v| leading_zero(v.trim())) {
+            None
+        } else if all(|v| v.parse::<i64>().is_ok()) {
+            Some("int")
+        } else {
+            Some("float")
+        }
+    } else if all(|v| NaiveDate::parse_from_str(v, "%Y-%m-%d").is_ok()) {
+        Some("date")
+    } else if all(|v| {
+        NaiveDateTime::parse_from_str(v, "%Y-%m-%d %H:%M:%S").is_ok()
+            || NaiveDateTime::parse_from_str(v, "%Y-%m-%dT%H:%M:%S").is_ok()
+    }) {
+        Some("datetime")
+    } else if all(|v| matches!(v.to_lowercase().as_str(), "true" | "false" | "yes" | "no")) {
+        Some("bool")
+    } else {
+        None
+    }
+}
+
+/// Next steps for a table just read, from a look at its first rows: text columns
+/// holding numbers, dates or booleans, text with surrounding spaces, and columns
+/// with no values at all. cast() doesn't trim, so a padded column that would
+/// cast is suggested a trim first.
+pub fn suggest(df: &DataFrame) -> Vec<String> {
+    let sample = df.head(Some(SAMPLE_ROWS));
+    let mut casts = Vec::new();
+    let mut trim_first = Vec::new();
+    let mut padded = Vec::new();
+    let mut empty = Vec::new();
+
+    for (position, column) in sample.get_columns().iter().enumerate() {
+        let label = column_label(column.name(), position);
+        if df.height() > 0 && column.null_count() == column.len() {
+            empty.push(label);
+            continue;
+        }
+        if let Ok(text) = column.as_materialized_series().str() {
+            let values: Vec<&str> = text.into_iter().flatten().filter(|v| !v.trim().is_empty()).collect();
+            let is_padded = values.iter().any(|v| v.trim() != *v);
+            if let Some(cast_type) = text_type(&values) {
+                casts.push(format!("{} -> {}", label, cast_type));
+                if is_padded {
+                    trim_first.push(label.clone());
+                }
+            }
+            if is_padded {
+                padded.push(label);
+            }
+        }
+    }
+
+    let mut hints = Vec::new();
+    if !casts.is_empty() {
+        let after_trim = if trim_first.is_empty() {
+            String::new()
+        } else {
+            format!(" after trimming {} (below)", trim_first.join(", "))
+        };
+        hints.push(format!(
+            "{} text column(s) look like numbers, dates or booleans: cast({}){}",
+            casts.len(),
+            casts.join(", "),
+            after_trim
+        ));
+    }
+    if !padded.is_empty() {
+        let trims: Vec<String> = padded.iter().map(|c| format!("{} = trim({})", c, c)).collect();
+        hints.push(format!("Values with surrounding spaces in {}: mutate({})", padded.join(", "), trims.join(", ")));
+    }
+    if !empty.is_empty() {
+        hints.push(format!("No values in {} (first rows): drop({})", empty.join(", "), empty.join(", ")));
+    }
+    hints
+}
@@ -1,15 +1,24 @@
 use colored::*;
 use polars::prelude::*;
 use rustyline::error::ReadlineError;
-use rustyline::DefaultEditor;
+use rustyline::history::DefaultHistory;
+use rustyline::Editor;
 use std::collections::HashMap;
 
+mod helper;
+mod hints;
+use helper::ReplHelper;
+
 use crate::error::Result;
-use crate::executor::Executor;
-use crate::parser::{parse, ast::Statement};
+use crate::executor::{format_bytes, Executor};
+use crate::parser::{parse, ast::{Program, Statement}};
+
+/// An earlier value of a variable (None: it didn't exist) and the script line
+/// that assigned it
+type VariableVersion = (Option<DataFrame>, Option<String>);
 
 pub struct Repl {
-    editor: DefaultEditor,
+    editor: Editor<ReplHelper, DefaultHistory>,
     executor: Executor,
 
     // Current state
@@ -23,22 +32,51 @@ pub struct Repl {
     // Operation history (for .history command)
     operation_log: Vec<String>,
 
+    // Successful statements as runnable script lines with the variable each one
+    // leaves as the current table (for .save). Kept in step with `history`:
+    // undone statements sit at the end until a new statement replaces them.
+    script: Vec<(String, String)>,
+
+    // Set while .load runs a script, to skip the preview after each statement
+    loading: bool,
+
     // Variable snapshots: stores complete variable state at each history point
     variable_snapshots: Vec<std::collections::HashMap<String, DataFrame>>,
+
+    // Earlier values of each variable, for `.undo <name>`
+    variable_history: HashMap<String, Vec<VariableVersion>>,
+
+    // Named history positions for .goto, in the order they were set
+    marks: Vec<(String, usize)>,
+
+    // Show column types, null counts and min/max under previews (.set preview_stats on)
+    preview_stats: bool,
+
+    // Suggest next steps (casts, trims, drops) after a read (.set hints on)
+    hints: bool,
 }
 
 impl Repl {
     pub fn new() -> Result<Self> {
+        let mut editor = Editor::new()
+            .map_err(|e| crate::error::DtransformError::ReadlineError(e.to_string()))?;
+        editor.set_helper(Some(ReplHelper::new()));
+
         Ok(Self {
-            editor: DefaultEditor::new()
-                .map_err(|e| crate::error::DtransformError::ReadlineError(e.to_string()))?,
+            editor,
             executor: Executor::new(),
             current: None,
             history: Vec::new(),
             history_position: 0,
             max_history: 10,
             operation_log: Vec::new(),
+            script: Vec::new(),
+            loading: false,
             variable_snapshots: Vec::new(),
+            variable_history: HashMap::new(),
+            marks: Vec::new(),
+            preview_stats: false,
+            hints: false,
         })
     }
 
@@ -79,15 +117,14 @@ impl Repl {
                         continue;
                     }
 
-                    // We have a complete statement, process it
+                    // We have a complete statement, process it. Line breaks are
+                    // whitespace to the parser, and end any # comments
                     let _ = self.editor.add_history_entry(accumulated_input.as_str());
 
-                    // Normalize multi-line input: replace newlines with spaces
-                    let normalized = accumulated_input.replace('\n', " ");
-
-                    if let Err(e) = self.handle_input(&normalized) {
+                    if let Err(e) = self.handle_input(accumulated_input.trim()) {
                         eprintln!("{}: {}", "Error".red().bold(), e.display_friendly());
                     }
+                    self.refresh_completions();
 
                     // Reset for next statement
                     accumulated_input.clear();
@@ -120,16 +157,46 @@ impl Repl {
         let statement = parse(input)?;
         let operation_desc = self.describe_statement(&statement);
 
+        // How this statement reads in a saved script: results that later statements
+        // build on are stored in `_`, and pipelines without a source name what they
+        // were applied to
+        let script_entry = match &statement {
+            Statement::Assignment { name, .. } => (input.trim().to_string(), name.clone()),
+            Statement::Pipeline(pipeline) if pipeline.source.is_some() => {
+                (format!("_ = {}", input.trim()), "_".to_string())
+            }
+            Statement::Pipeline(_) => {
+                let current = self.active_script().last().map(|(_, name)| name.as_str()).unwrap_or("_");
+                (format!("_ = {} | {}", current, input.trim()), "_".to_string())
+            }
+        };
+
+        let reads_file = match &statement {
+            Statement::Assignment { pipeline, .. } | Statement::Pipeline(pipeline) => {
+                matches!(pipeline.source, Some(crate::parser::ast::Source::Read(_)))
+            }
+        };
+
         match statement {
             Statement::Assignment { name, pipeline } => {
                 // Execute pipeline
                 let result = self.executor.execute_pipeline(pipeline)?;
 
+                // Keep the value being replaced for .undo <name>
+                let previous = self.executor.get_variable(&name).cloned();
+                let previous_line = self.assigning_line(&name);
+                let versions = self.variable_history.entry(name.clone()).or_default();
+                versions.push((previous, previous_line));
+                if versions.len() > self.max_history {
+                    versions.remove(0);
+                }
+
                 // Store in executor's variable map
                 self.executor.set_variable(name.clone(), result.clone());
 
                 // Also set as current for _
                 self.current = Some(result.clone());
+                self.record_script(script_entry);
                 self.save_to_history(Some(name.clone()));
 
                 self.operation_log.push(format!("{} = ...", name));
@@ -142,43 +209,54 @@ impl Repl {
                     result.width()
                 );
                 self.preview_result(&result);
+                if reads_file {
+                    self.show_hints(&result);
+                }
             }
             Statement::Pipeline(pipeline) => {
-                // If pipeline has no source, use current table
-                let has_source = pipeline.source.is_some();
-                let pipeline_to_execute = if !has_source {
-                    // Use current table as source
-                    if let Some(ref current_df) = self.current {
-                        // Create a temporary variable for the current table
-                        self.executor.set_variable("_".to_string(), current_df.clone());
-
-                        let mut modified_pipeline = pipeline;
-                        modified_pipeline.source = Some(crate::parser::ast::Source::Variable("_".to_string()));
-                        modified_pipeline
-                    } else {
-                        pipeline
-                    }
-                } else {
-                    pipeline
+                // A pipeline without a source applies to the current table. It is
+                // handed over directly: the clone shares the table's column buffers,
+                // and no `_` variable keeps the old table alive in undo snapshots.
+                let result = match self.current.as_ref().filter(|_| pipeline.source.is_none()) {
+                    Some(current_df) => self.executor.execute_operations(current_df.clone(), pipeline.operations)?,
+                    None => self.executor.execute_pipeline(pipeline)?,
                 };
 
-                // Execute pipeline
-                let result = self.executor.execute_pipeline(pipeline_to_execute)?;
-
                 // Save to history for undo
                 self.current = Some(result.clone());
+                self.record_script(script_entry);
                 self.save_to_history(None);
 
                 self.operation_log.push(operation_desc);
 
                 // Preview
                 self.preview_result(&result);
+                if reads_file {
+                    self.show_hints(&result);
+                }
             }
         }
 
         Ok(())
     }
 
+    /// Point tab completion at the current table's columns and the stored variables
+    fn refresh_completions(&mut self) {
+        let columns = self
+            .current
+            .as_ref()
+            .map(|df| df.get_column_names().iter().map(|s| s.to_string()).collect())
+            .unwrap_or_default();
+        let mut variables = self.executor.list_variables();
+        variables.retain(|name| name != "_");
+        variables.sort();
+
+        if let Some(helper) = self.editor.helper_mut() {
+            helper.columns = columns;
+            helper.variables = variables;
+        }
+    }
+
     fn describe_statement(&self, statement: &Statement) -> String {
         match statement {
             Statement::Assignment { name, .. } => format!("{} = ...", name),
@@ -192,11 +270,102 @@ impl Repl {
         }
     }
 
+    /// Statements that are in effect, i.e. not undone
+    fn active_script(&self) -> &[(String, String)] {
+        let undone = self.history.len() - self.history_position;
+        &self.script[..self.script.len() - undone]
+    }
+
+    /// The script line that gave a variable its current value
+    fn assigning_line(&self, name: &str) -> Option<String> {
+        self.active_script()
+            .iter()
+            .rev()
+            .map(|(line, _)| line)
+            .find(|line| line.split_once('=').is_some_and(|(target, _)| target.trim() == name))
+            .cloned()
+    }
+
+    /// Must run before save_to_history, which discards the undone states
+    fn record_script(&mut self, entry: (String, String)) {
+        let active = self.active_script().len();
+        self.script.truncate(active);
+        self.script.push(entry);
+    }
+
+    fn save_script(&self, path: Option<&str>) -> Result<()> {
+        let path = path.ok_or_else(|| crate::error::DtransformError::InvalidOperation(
+            "Usage: .save <file.dt>".to_string(),
+        ))?;
+
+        let active = self.active_script();
+        if active.is_empty() {
+            return Err(crate::error::DtransformError::InvalidOperation(
+                "Nothing to save: no statements have been run".to_string(),
+            ));
+        }
+
+        let mut lines: Vec<&str> = active.iter().map(|(line, _)| line.as_str()).collect();
+        // The last result is what `dt -f` should print, so don't store it away
+        if let Some(last) = lines.last_mut() {
+            if let Some(pipeline) = last.strip_prefix("_ = ") {
+                *last = pipeline;
+            }
+        }
+
+        std::fs::write(path, format!("{}\n", lines.join("\n")))?;
+        println!(
+            "{} {} statement(s) to {}. Run with: dt -f {}",
+            "Saved".green(),
+            lines.len(),
+            path,
+            path
+        );
+        Ok(())
+    }
+
+    /// Run a script file statement by statement, as if typed in, so it ends up
+    /// in the session's variables, history and .save output
+    fn load_script(&mut self, path: Option<&str>) -> Result<()> {
+        let path = path.ok_or_else(|| crate::error::DtransformError::InvalidOperation(
+            "Usage: .load <file.dt>".to_string(),
+        ))?;
+        let source = std::fs::read_to_string(path)?;
+
+        // Don't run half a script because of a typo further down
+        let statements = crate::parser::split_statements(&source)?;
+
+        self.loading = true;
+        let mut count = 0;
+        let mut result = Ok(());
+        for (line, statement) in statements {
+            if let Err(e) = self.handle_input(&statement) {
+                result = Err(crate::error::DtransformError::InvalidOperation(format!(
+                    "{} line {}: {}",
+                    path,
+                    line,
+                    e.display_friendly()
+                )));
+                break;
+            }
+            count += 1;
+        }
+        self.loading = false;
+
+        println!("{} {} statement(s) from {}", "Loaded".green(), count, path);
+        if let Some(ref df) = self.current {
+            self.preview_result(df);
+        }
+        result
+    }
+
     fn save_to_history(&mut self, _variable_name: Option<String>) {
         if let Some(ref current) = self.current {
             // Truncate future if we're in the middle of history
             self.history.truncate(self.history_position);
             self.variable_snapshots.truncate(self.history_position);
+            let position = self.history_position;
+            self.marks.retain(|(_, mark)| *mark <= position);
 
             // Save current dataframe state
             self.history.push(current.clone());
@@ -205,12 +374,22 @@ impl Repl {
             let snapshot = self.executor.get_all_variables();
             self.variable_snapshots.push(snapshot);
 
-            // Limit history size
-            if self.history.len() > self.max_history {
-                self.history.remove(0);
-                self.variable_snapshots.remove(0);
-            } else {
-                self.history_position += 1;
+            // Limit history size: marked states don't count and are never dropped,
+            // so .goto works however long ago the mark was set
+            let marked = |index: usize| self.marks.iter().any(|(_, mark)| *mark == index + 1);
+            let unmarked = (0..self.history.len()).filter(|&index| !marked(index)).count();
+            let oldest_unmarked = (0..self.history.len() - 1).find(|&index| !marked(index));
+            match oldest_unmarked {
+                Some(index) if unmarked > self.max_history => {
+                    self.history.remove(index);
+                    self.variable_snapshots.remove(index);
+                    for (_, mark) in &mut self.marks {
+                        if *mark > index + 1 {
+                            *mark -= 1;
+                        }
+                    }
+                }
+                _ => self.history_position += 1,
             }
         }
     }
@@ -221,23 +400,63 @@ impl Repl {
         match parts[0] {
             ".help" => self.show_help(),
             ".exit" | ".quit" => std::process::exit(0),
-            ".schema" => self.show_schema()?,
-            ".undo" => {
-                let n = parts.get(1).and_then(|s| s.parse().ok()).unwrap_or(1);
-                self.undo(n)?;
+            ".schema" => {
+                let target = cmd[parts[0].len()..].trim().trim_matches(|c: char| c == '\'' || c == '"');
+                self.show_schema((!target.is_empty()).then_some(target))?
             }
+            ".undo" => match parts.get(1) {
+                Some(arg) if arg.parse::<usize>().is_err() => self.undo_variable(arg)?,
+                arg => self.undo(arg.and_then(|s| s.parse().ok()).unwrap_or(1))?,
+            },
             ".redo" => {
                 let n = parts.get(1).and_then(|s| s.parse().ok()).unwrap_or(1);
                 self.redo(n)?;
             }
             ".history" => self.show_history(),
-            ".vars" | ".variables" => self.show_variables(),
+            ".mark" => self.mark(parts.get(1).copied()),
+            ".goto" => self.goto(parts.get(1).copied())?,
+            ".explain" => self.explain(cmd[parts[0].len()..].trim())?,
+            ".save" => self.save_script(parts.get(1).copied())?,
+            ".load" | ".open" => self.load_script(parts.get(1).copied())?,
+            ".vars" | ".variables" => self.show_variables(cmd[parts[0].len()..].trim())?,
+            ".top" => self.show_top(cmd[parts[0].len()..].trim())?,
+            ".set" => self.set_option(parts.get(1).copied(), parts.get(2).copied())?,
             ".clear" => self.clear(),
             _ => println!("Unknown command: {}. Type .help for help.", parts[0]),
         }
         Ok(())
     }
 
+    /// Show the stages a statement would run, without running it
+    fn explain(&mut self, input: &str) -> Result<()> {
+        if input.is_empty() {
+            return Err(crate::error::DtransformError::InvalidOperation(
+                "Usage: .explain <statement>".to_string(),
+            ));
+        }
+
+        let mut statement = parse(input)?;
+        // Operations without a source apply to the current table, as when run; it is
+        // lent as `_` for the plan only, so a `_` of the user's own is put back after
+        let saved = self.executor.get_variable("_").cloned();
+        if let Statement::Pipeline(pipeline) = &mut statement {
+            if pipeline.source.is_none() {
+                if let Some(ref current_df) = self.current {
+                    self.executor.set_variable("_".to_string(), current_df.clone());
+                    pipeline.source = Some(crate::parser::ast::Source::Variable("_".to_string()));
+                }
+            }
+        }
+
+        let plan = self.executor.explain_program(&Program { statements: vec![statement] });
+        match saved {
+            Some(df) => self.executor.set_variable("_".to_string(), df),
+            None => self.executor.remove_variable("_"),
+        }
+        print!("{}", plan?);
+        Ok(())
+    }
+
     fn undo(&mut self, n: usize) -> Result<()> {
         if self.history_position == 0 {
             return Err(crate::error::DtransformError::InvalidOperation(
@@ -266,6 +485,9 @@ impl Repl {
             self.executor.restore_variables(HashMap::new());
         }
 
+        // Per-variable history describes the states just left behind
+        self.variable_history.clear();
+
         println!("{} {} step(s)", "Undid".yellow(), steps);
 
         if let Some(ref df) = self.current {
@@ -275,6 +497,98 @@ impl Repl {
         Ok(())
     }
 
+    /// Give one variable back its value from before its last assignment, leaving
+    /// the current table and every other variable alone. This is a step of its
+    /// own: a whole-session .undo reverts it, and .save runs the statement that
+    /// assigned the earlier value again.
+    fn undo_variable(&mut self, name: &str) -> Result<()> {
+        let (previous, line) = self
+            .variable_history
+            .get_mut(name)
+            .and_then(|versions| versions.pop())
+            .ok_or_else(|| crate::error::DtransformError::InvalidOperation(format!(
+                "No earlier value of '{}' to restore",
+                name
+            )))?;
+
+        match &previous {
+            Some(df) => self.executor.set_variable(name.to_string(), df.clone()),
+            None => self.executor.remove_variable(name),
+        }
+
+        // Scripts can't remove a variable, so that only leaves a comment
+        let line = match (&previous, line) {
+            (Some(_), Some(line)) => line,
+            _ => format!("// .undo {}", name),
+        };
+        let current = self
+            .active_script()
+            .last()
+            .map(|(_, current)| current.clone())
+            .unwrap_or_else(|| "_".to_string());
+        if self.current.is_some() {
+            self.record_script((line, current));
+            self.save_to_history(None);
+            self.operation_log.push(format!(".undo {}", name));
+        }
+
+        match previous {
+            Some(df) => {
+                println!("{} {} ({} rows × {} cols)", "Restored".yellow(), name, df.height(), df.width());
+                self.preview_result(&df);
+            }
+            None => println!("{} {} (it had no earlier value)", "Removed".yellow(), name),
+        }
+        Ok(())
+    }
+
+    /// Name the current state for .goto; without a name, list the marks
+    fn mark(&mut self, name: Option<&str>) {
+        let name = match name {
+            Some(name) => name,
+            None => {
+                println!("{}", "Marks:".bright_blue());
+                for (name, position) in &self.marks {
+                    let marker = if *position == self.history_position { " ← current" } else { "" };
+                    println!("  {} (step {}){}", name, position, marker.green());
+                }
+                if self.marks.is_empty() {
+                    println!("  (no marks set)");
+                }
+                return;
+            }
+        };
+
+        self.marks.retain(|(existing, _)| existing != name);
+        self.marks.push((name.to_string(), self.history_position));
+        println!("{} {} at step {}", "Marked".green(), name, self.history_position);
+    }
+
+    /// Undo or redo to the state saved by .mark
+    fn goto(&mut self, name: Option<&str>) -> Result<()> {
+        let name = name.ok_or_else(|| crate::error::DtransformError::InvalidOperation(
+            "Usage: .goto <mark>".to_string(),
+        ))?;
+        let position = self
+            .marks
+            .iter()
+            .find(|(existing, _)| existing == name)
+            .map(|(_, position)| *position)
+            .ok_or_else(|| crate::error::DtransformError::InvalidOperation(format!(
+                "No mark named '{}'. Use .mark to list marks.",
+                name
+            )))?;
+
+        if position < self.history_position {
+            self.undo(self.history_position - position)
+        } else if position > self.history_position {
+            self.redo(position - self.history_position)
+        } else {
+            println!("Already at {}", name);
+            Ok(())
+        }
+    }
+
     fn redo(&mut self, n: usize) -> Result<()> {
         if self.history_position >= self.history.len() {
             return Err(crate::error::DtransformError::InvalidOperation(
@@ -292,6 +606,8 @@ impl Repl {
         let snapshot = self.variable_snapshots[self.history_position - 1].clone();
         self.executor.restore_variables(snapshot);
 
+        self.variable_history.clear();
+
         println!("{} {} step(s)", "Redid".yellow(), steps);
 
         if let Some(ref df) = self.current {
@@ -317,24 +633,145 @@ impl Repl {
         }
     }
 
-    fn show_variables(&self) {
-        println!("{}", "Stored Variables:".bright_blue());
-        let vars = self.executor.list_variables();
+    /// `.vars`: each variable's size; `.vars --schema` adds its columns, types and
+    /// estimated memory, and `.vars name` shows that for one variable
+    fn show_variables(&self, args: &str) -> Result<()> {
+        let (schema, names) = match args {
+            "" => (false, self.executor.list_variables()),
+            "--schema" | "-s" => (true, self.executor.list_variables()),
+            name => {
+                let name = name.trim_matches(|c: char| c == '\'' || c == '"');
+                if self.executor.get_variable(name).is_none() {
+                    return Err(crate::error::DtransformError::VariableNotFound(name.to_string()));
+                }
+                (true, vec![name.to_string()])
+            }
+        };
 
-        if vars.is_empty() {
+        println!("{}", "Stored Variables:".bright_blue());
+        if names.is_empty() {
             println!("  (no variables stored)");
-        } else {
-            for name in vars {
-                if let Some(df) = self.executor.get_variable(&name) {
-                    println!(
-                        "  {} → {} rows × {} cols",
-                        name,
-                        df.height(),
-                        df.width()
-                    );
-                }
+        }
+        for name in names {
+            let Some(df) = self.executor.get_variable(&name) else { continue };
+            if !schema {
+                println!("  {} → {} rows × {} cols", name, df.height(), df.width());
+                continue;
             }
+
+            println!(
+                "  {} → {} rows × {} cols, ~{}",
+                name,
+                df.height(),
+                df.width(),
+                format_bytes(df.estimated_size() as u64)
+            );
+            let width = df.get_column_names().iter().map(|c| c.chars().count()).max().unwrap_or(0);
+            let types: Vec<String> = df.get_columns().iter().map(|c| c.dtype().to_string()).collect();
+            let type_width = types.iter().map(|t| t.chars().count()).max().unwrap_or(0);
+            for (column, dtype) in df.get_columns().iter().zip(&types) {
+                println!(
+                    "      {:<width$}  {:<type_width$}  {:>10}",
+                    column.name().as_str(),
+                    dtype,
+                    format_bytes(column.as_materialized_series().estimated_size() as u64),
+                    width = width,
+                    type_width = type_width
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// `.top column [n]`: the n most frequent values of a column of the current
+    /// table, with counts and shares of all rows; nulls count as a value
+    fn show_top(&self, args: &str) -> Result<()> {
+        let usage = || crate::error::DtransformError::InvalidOperation("Usage: .top <column> [n]".to_string());
+        let df = match self.current {
+            Some(ref df) => df,
+            None => {
+                println!("No table loaded. Use read() to load data or a variable name.");
+                return Ok(());
+            }
+        };
+
+        // The column may be quoted and contain spaces; a trailing number is n
+        let (column, n) = match args.rsplit_once(char::is_whitespace) {
+            Some((column, n)) if n.parse::<usize>().is_ok() => (column.trim(), n.parse().unwrap()),
+            _ => (args, 10),
+        };
+        let column = column.trim_matches(|c: char| c == '\'' || c == '"');
+        if column.is_empty() {
+            return Err(usage());
+        }
+        let name = match column.strip_prefix('$').and_then(|pos| pos.parse::<usize>().ok()) {
+            Some(pos) => df
+                .get_column_names()
+                .get(pos.wrapping_sub(1))
+                .map(|name| name.to_string())
+                .ok_or_else(|| crate::error::DtransformError::ColumnNotFound(column.to_string()))?,
+            None => column.to_string(),
+        };
+        if df.column(&name).is_err() {
+            return Err(crate::error::DtransformError::ColumnNotFound(name));
+        }
+
+        let counts = df
+            .clone()
+            .lazy()
+            .select([col(name.as_str()).alias("value")])
+            .group_by([col("value")])
+            .agg([len().cast(DataType::UInt64).alias("count")])
+            .sort(
+                ["count", "value"],
+                SortMultipleOptions::default()
+                    .with_order_descending_multi([true, false])
+                    .with_nulls_last(true),
+            )
+            .collect()?;
+        let values = counts.column("value")?.as_materialized_series().cast(&DataType::String)?;
+        let values = values.str()?;
+        let frequencies = counts.column("count")?.as_materialized_series().u64()?;
+        // Quoted, so empty and padded text stand out
+        let quoted = df.column(&name)?.dtype() == &DataType::String;
+
+        let rows = df.height();
+        let distinct = counts.height();
+        let shown = distinct.min(n);
+        let percent = |count: u64| if rows == 0 { 0.0 } else { count as f64 * 100.0 / rows as f64 };
+
+        let mut lines: Vec<(String, u64)> = (0..shown)
+            .map(|i| {
+                let value = match values.get(i) {
+                    Some(value) if quoted => format!("{:?}", value),
+                    Some(value) => value.to_string(),
+                    None => "null".to_string(),
+                };
+                (value, frequencies.get(i).unwrap_or(0))
+            })
+            .collect();
+        let rest: u64 = (shown..distinct).filter_map(|i| frequencies.get(i)).sum();
+        if distinct > shown {
+            lines.push((format!("({} other values)", distinct - shown), rest));
         }
+
+        println!(
+            "{}",
+            format!("Top {} of {} distinct values in '{}' ({} rows):", shown, distinct, name, rows).bright_blue()
+        );
+        let width = lines.iter().map(|(value, _)| value.chars().count()).max().unwrap_or(0);
+        let count_width = lines.iter().map(|(_, count)| count.to_string().len()).max().unwrap_or(0);
+        for (value, count) in &lines {
+            println!(
+                "  {:<width$}  {:>count_width$}  {:>5.1}%",
+                value,
+                count,
+                percent(*count),
+                width = width,
+                count_width = count_width
+            );
+        }
+        Ok(())
     }
 
     fn clear(&mut self) {
@@ -342,19 +779,80 @@ impl Repl {
         self.history.clear();
         self.history_position = 0;
         self.operation_log.clear();
+        self.script.clear();
         self.variable_snapshots.clear();
+        self.variable_history.clear();
+        self.marks.clear();
         println!("{}", "Cleared current table and history".yellow());
     }
 
+    /// `.set <option> on|off` or `.set float_fmt '%.3f'`; without arguments, show the options
+    fn set_option(&mut self, option: Option<&str>, value: Option<&str>) -> Result<()> {
+        let on_off = |on: bool| if on { "on" } else { "off" };
+        let (option, value) = match (option, value) {
+            (None, _) => {
+                println!("{}", "Settings:".bright_blue());
+                println!("  preview_stats  {}", on_off(self.preview_stats));
+                println!("  hints          {}", on_off(self.hints));
+                println!("  float_fmt      {}", crate::executor::float_format());
+                return Ok(());
+            }
+            (Some(option), Some(value)) => (option, value),
+            (Some(_), None) => {
+                return Err(crate::error::DtransformError::InvalidOperation(
+                    "Usage: .set <option> on|off, or .set float_fmt '%.3f' | full | auto".to_string(),
+                ))
+            }
+        };
+
+        if option == "float_fmt" {
+            crate::executor::set_float_format(value.trim_matches(|c: char| c == '\'' || c == '"'))?;
+            println!("{} float_fmt {}", "Set".green(), crate::executor::float_format());
+            return Ok(());
+        }
+
+        let enabled = match value {
+            "on" | "true" => true,
+            "off" | "false" => false,
+            other => {
+                return Err(crate::error::DtransformError::InvalidOperation(format!(
+                    "Expected on or off, got '{}'",
+                    other
+                )))
+            }
+        };
+        match option {
+            "preview_stats" => self.preview_stats = enabled,
+            "hints" => self.hints = enabled,
+            other => {
+                return Err(crate::error::DtransformError::InvalidOperation(format!(
+                    "Unknown setting '{}'. Settings: preview_stats, hints, float_fmt",
+                    other
+                )))
+            }
+        }
+        println!("{} {} {}", "Set".green(), option, on_off(enabled));
+        Ok(())
+    }
+
     fn show_help(&self) {
         println!("{}", "Available commands:".bright_blue());
         println!("  .help          - Show this help");
         println!("  .exit          - Exit REPL");
-        println!("  .schema        - Show current table schema");
+        println!("  .schema [name] - Show the schema of the current table, a variable or a file");
         println!("  .undo [n]      - Undo last n operations (default: 1)");
+        println!("  .undo <name>   - Restore a variable's value from before its last assignment");
         println!("  .redo [n]      - Redo last n operations (default: 1)");
         println!("  .history       - Show operation history");
+        println!("  .mark [name]   - Name the current state (no name: list marks)");
+        println!("  .goto <name>   - Go back or forward to a marked state");
+        println!("  .explain <stmt> - Show the stages a statement would run, without running it");
+        println!("  .save <file>   - Save the session's statements as a script (run with dt -f)");
+        println!("  .load <file>   - Run a script in this session (also .open)");
         println!("  .vars          - Show stored variables");
+        println!("  .vars --schema - Also show columns, types and memory (.vars <name>: one variable)");
+        println!("  .top col [n]   - The n most frequent values of a column, with counts (default: 10)");
+        println!("  .set [opt value] - Change a setting (preview_stats, hints, float_fmt); no arguments lists them");
         println!("  .clear         - Clear current table and history");
         println!("\n{}", "Multi-line statements:".bright_blue());
         println!("  Lines ending with | continue to the next line");
@@ -376,27 +874,43 @@ impl Repl {
         println!("  String operations:      mutate(email = email.lower())");
     }
 
-    fn show_schema(&self) -> Result<()> {
-        if let Some(ref df) = self.current {
-            println!("{}", "Schema:".bright_blue());
-            let schema = df.schema();
+    /// Schema of the current table, a stored variable, or a file (scanned, not loaded)
+    fn show_schema(&self, target: Option<&str>) -> Result<()> {
+        let (schema, rows) = match target {
+            Some(name) => match self.executor.get_variable(name) {
+                Some(df) => (df.schema(), Some(df.height())),
+                None if std::path::Path::new(name).exists() => {
+                    let (schema, rows) = self.executor.peek_schema(name)?;
+                    ((*schema).clone(), rows)
+                }
+                None => return Err(crate::error::DtransformError::VariableNotFound(name.to_string())),
+            },
+            None => match self.current {
+                Some(ref df) => (df.schema(), Some(df.height())),
+                None => {
+                    println!("No table loaded. Use read() to load data or a variable name.");
+                    return Ok(());
+                }
+            },
+        };
 
-            for (i, (name, field)) in schema.iter().enumerate() {
-                println!("  {}. {} ({})", i + 1, name, field);
-            }
+        println!("{}", "Schema:".bright_blue());
+        for (i, (name, field)) in schema.iter().enumerate() {
+            println!("  {}. {} ({})", i + 1, name, field);
+        }
 
-            println!(
-                "\n{} rows × {} columns",
-                df.height(),
-                df.width()
-            );
-        } else {
-            println!("No table loaded. Use read() to load data or a variable name.");
+        match rows {
+            Some(rows) => println!("\n{} rows × {} columns", rows, schema.len()),
+            None => println!("\n{} columns (rows not counted)", schema.len()),
         }
         Ok(())
     }
 
     fn preview_result(&self, df: &DataFrame) {
+        if self.loading {
+            return;
+        }
+
         let rows = df.height();
         let cols = df.width();
 
@@ -412,6 +926,27 @@ impl Repl {
         if rows > 5 {
             println!("... {} more rows", rows - 5);
         }
+
+        if self.preview_stats && cols > 0 {
+            match self.executor.column_stats(df) {
+                Ok(stats) => println!("{}", stats),
+                Err(e) => eprintln!("{}: {}", "Column stats unavailable".yellow(), e),
+            }
+        }
         println!();
     }
+
+    /// Suggested next steps for a table just read (.set hints on)
+    fn show_hints(&self, df: &DataFrame) {
+        if !self.hints || self.loading {
+            return;
+        }
+        let hints = hints::suggest(df);
+        for hint in &hints {
+            println!("{} {}", "Hint:".cyan(), hint);
+        }
+        if !hints.is_empty() {
+            println!();
+        }
+    }
 }
@@ -0,0 +1,38 @@
+use data_transform::{parse_program, Executor};
+use polars::prelude::*;
+
+fn run(program: &str) -> DataFrame {
+    let program = parse_program(program).expect("program should parse");
+    Executor::new()
+        .execute_program(program)
+        .expect("program should run")
+        .expect("program should produce a table")
+}
+
+fn names(df: &DataFrame) -> Vec<String> {
+    df.column("name")
+        .unwrap()
+        .str()
+        .unwrap()
+        .into_iter()
+        .map(|name| name.unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn and_keeps_rows_matching_both_conditions() {
+    let df = run("read('tests/fixtures/sample.csv') | filter(department == 'Engineering' and age > 31)");
+    assert_eq!(names(&df), ["Charlie", "Eve", "Jack"]);
+}
+
+#[test]
+fn or_keeps_rows_matching_either_condition() {
+    let df = run("read('tests/fixtures/sample.csv') | filter(city == 'Boston' or salary < 65000)");
+    assert_eq!(names(&df), ["Frank", "Ivy", "Jack"]);
+}
+
+#[test]
+fn and_binds_tighter_than_or() {
+    let df = run("read('tests/fixtures/sample.csv') | filter(city == 'LA' and age < 26 or name == 'Jack')");
+    assert_eq!(names(&df), ["Bob", "Jack"]);
+}
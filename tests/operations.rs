@@ -0,0 +1,74 @@
+use data_transform::{parse_program, Executor};
+use polars::prelude::*;
+
+fn run(program: &str) -> Result<DataFrame, data_transform::DtransformError> {
+    let program = parse_program(program)?;
+    Ok(Executor::new()
+        .execute_program(program)?
+        .expect("program should produce a table"))
+}
+
+fn u64s(df: &DataFrame, column: &str) -> Vec<Option<u64>> {
+    df.column(column).unwrap().u64().unwrap().into_iter().collect()
+}
+
+#[test]
+fn resample_counts_rows_per_bucket() {
+    let df = run("read('tests/fixtures/events.csv') | resample(ts, every='1h', agg(count = count(), avg = mean(value)))")
+        .unwrap();
+    assert_eq!(u64s(&df, "count"), [Some(2), Some(1)]);
+    let avg: Vec<Option<f64>> = df.column("avg").unwrap().f64().unwrap().into_iter().collect();
+    assert_eq!(avg, [Some(2.0), Some(4.0)]);
+}
+
+#[test]
+fn resample_rejects_text_that_isnt_dates() {
+    let error = run("read('tests/fixtures/events.csv') | resample(status, every='1h', agg(n = count()))").unwrap_err();
+    assert!(error.to_string().contains("cast(status -> datetime)"), "{}", error);
+}
+
+#[test]
+fn file_name_is_kept_out_of_other_steps() {
+    let df = run("read('tests/fixtures/parts/*.csv') | expect(columns=2) | rename_all(uppercase) | select(A) \
+                  | mutate(file = file_name())")
+        .unwrap();
+    assert_eq!(df.get_column_names_str(), ["A", "file"]);
+    let files: Vec<Option<&str>> = df.column("file").unwrap().str().unwrap().into_iter().collect();
+    assert_eq!(
+        files,
+        [Some("tests/fixtures/parts/part1.csv"), Some("tests/fixtures/parts/part1.csv"), Some("tests/fixtures/parts/part2.csv")]
+    );
+}
+
+#[test]
+fn cast_to_int_keeps_large_whole_numbers_exact() {
+    let df = run("read('tests/fixtures/numbers.csv') | cast(n -> int)").unwrap();
+    let values: Vec<Option<i64>> = df.column("n").unwrap().i64().unwrap().into_iter().collect();
+    assert_eq!(values, [Some(9_007_199_254_740_993), Some(3), None]);
+}
+
+#[test]
+fn resample_flags_buckets_with_any_and_all() {
+    let df = run("read('tests/fixtures/events.csv') \
+                  | resample(ts, every='1h', agg(has_error = any(status == 'ERR'), all_ok = all(status == 'OK')))")
+        .unwrap();
+    let flags = |column: &str| -> Vec<Option<bool>> { df.column(column).unwrap().bool().unwrap().into_iter().collect() };
+    assert_eq!(flags("has_error"), [Some(true), Some(false)]);
+    assert_eq!(flags("all_ok"), [Some(false), Some(true)]);
+}
+
+#[test]
+fn summarize_accepts_any() {
+    let df = run("read('tests/fixtures/events.csv') | summarize(has_error = any(status == 'ERR'))").unwrap();
+    assert_eq!(df.column("has_error").unwrap().bool().unwrap().get(0), Some(true));
+}
+
+#[test]
+fn fill_nulls_keeps_the_column_type() {
+    let df = run("read('tests/fixtures/numbers.csv') | cast(n -> int) | fill_nulls(n = '0')").unwrap();
+    let values: Vec<Option<i64>> = df.column("n").unwrap().i64().unwrap().into_iter().collect();
+    assert_eq!(values, [Some(9_007_199_254_740_993), Some(3), Some(0)]);
+
+    let error = run("read('tests/fixtures/numbers.csv') | cast(n -> int) | fill_nulls(n = 'none')").unwrap_err();
+    assert!(error.to_string().contains("fill_nulls() can't fill column 'n' (i64)"), "{}", error);
+}
@@ -0,0 +1,42 @@
+use std::collections::HashMap;
+
+use data_transform::substitute_variables;
+
+fn substitute(source: &str, params: &[(&str, &str)]) -> String {
+    let params: HashMap<String, String> =
+        params.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect();
+    substitute_variables(source, &params).expect("placeholders should be filled")
+}
+
+#[test]
+fn values_in_string_literals_are_escaped() {
+    let source = substitute("mutate(n = '${param:name}', m = \"${param:name}\")", &[("name", r#"it's "x" \ y"#)]);
+    assert_eq!(source, r#"mutate(n = 'it\'s "x" \\ y', m = "it's \"x\" \\ y")"#);
+}
+
+#[test]
+fn values_outside_literals_cannot_add_code() {
+    assert_eq!(substitute("filter(amount > ${param:min})", &[("min", "-1.5")]), "filter(amount > -1.5)");
+    assert_eq!(substitute("select(${param:col})", &[("col", "region")]), "select(region)");
+    assert_eq!(
+        substitute("filter(amount > ${param:min})", &[("min", "0) | drop(id")]),
+        "filter(amount > '0) | drop(id')"
+    );
+}
+
+#[test]
+fn parameter_names_match_regardless_of_case() {
+    assert_eq!(substitute("head(${param:ROWS})", &[("rows", "5")]), "head(5)");
+}
+
+#[test]
+fn unset_upper_case_names_are_left_for_regex_groups() {
+    let source = "replace(code, re('(?P<YEAR>\\d+)'), '${YEAR}_${1}')";
+    assert_eq!(substitute(source, &[]), source);
+}
+
+#[test]
+fn comments_are_not_filled_in() {
+    let source = "read('a.csv') # ${param:missing}\n| head(1)";
+    assert_eq!(substitute(source, &[]), source);
+}